@@ -0,0 +1,63 @@
+//! Telling applications apart from the session by their cgroup.
+//!
+//! COSMIC and Flatpak start applications in their own `app-*.scope` units of the user manager,
+//! while the compositor, the panel, and the other session services are in the session scope or
+//! in services. Only applications are safe to terminate on behalf of the user.
+
+use std::fs;
+
+/// The `app-*.scope` unit in the contents of `/proc/<pid>/cgroup`, such as
+/// `app-cosmic-org.gnome.Nautilus-1234.scope`, or `None` if the process is not in one.
+pub fn app_scope(cgroup: &str) -> Option<&str> {
+    // The unified hierarchy of cgroup v2 is `0::<path>`, which is the only one systemd uses.
+    let path = cgroup.lines().find_map(|line| line.strip_prefix("0::"))?;
+    path.trim()
+        .rsplit('/')
+        .find(|unit| unit.starts_with("app-") && unit.ends_with(".scope"))
+}
+
+/// Whether a process is an application started in its own scope.
+pub fn is_app(pid: u32) -> bool {
+    fs::read_to_string(format!("/proc/{pid}/cgroup"))
+        .is_ok_and(|cgroup| app_scope(&cgroup).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER: &str = "0::/user.slice/user-1000.slice/user@1000.service";
+
+    #[test]
+    fn applications() {
+        let cosmic = format!("{USER}/app.slice/app-cosmic-org.gnome.Nautilus-1234.scope\n");
+        assert_eq!(
+            app_scope(&cosmic),
+            Some("app-cosmic-org.gnome.Nautilus-1234.scope")
+        );
+
+        let flatpak = format!("{USER}/app.slice/app-flatpak-org.mozilla.firefox-5678.scope\n");
+        assert_eq!(
+            app_scope(&flatpak),
+            Some("app-flatpak-org.mozilla.firefox-5678.scope")
+        );
+    }
+
+    #[test]
+    fn session() {
+        assert_eq!(
+            app_scope("0::/user.slice/user-1000.slice/session-2.scope\n"),
+            None
+        );
+        let service = format!("{USER}/session.slice/cosmic-settings-daemon.service\n");
+        assert_eq!(app_scope(&service), None);
+        assert_eq!(app_scope(&format!("{USER}/init.scope\n")), None);
+    }
+
+    #[test]
+    fn only_the_unified_hierarchy() {
+        let v1 = "12:pids:/user.slice/app-cosmic-foo-1.scope\n0::/user.slice/session-2.scope\n";
+        assert_eq!(app_scope(v1), None);
+        assert_eq!(app_scope(""), None);
+    }
+}
@@ -0,0 +1,32 @@
+use chrono::{DateTime, Local};
+
+/// The source of the current time.
+pub trait Clock {
+    fn now(&self) -> DateTime<Local>;
+}
+
+/// The realtime clock of the system.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// A clock which is set by hand, for simulating the passing of time.
+#[derive(Clone, Copy, Debug)]
+pub struct ManualClock(pub DateTime<Local>);
+
+impl ManualClock {
+    pub fn set(&mut self, now: DateTime<Local>) {
+        self.0 = now;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Local> {
+        self.0
+    }
+}
@@ -0,0 +1,85 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The source of the stored values of config keys, in RON.
+pub trait ConfigProvider {
+    /// The value of `key` of version `version` of the config `id`, or `None` if it is not set.
+    fn read_key(&self, id: &str, version: u64, key: &str) -> Option<String>;
+
+    /// Set `key` to `value`, or unset it if `None`.
+    fn write_key(&self, id: &str, version: u64, key: &str, value: Option<&str>) -> io::Result<()>;
+}
+
+/// A directory of configs as cosmic-config stores them, with a file for each key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigDir(pub PathBuf);
+
+impl ConfigDir {
+    /// The configs of the user, in `$XDG_CONFIG_HOME/cosmic`.
+    pub fn user() -> Option<Self> {
+        dirs::config_dir()
+            .map(|dir| dir.join("cosmic"))
+            .or_else(|| dirs::home_dir().map(|dir| dir.join(".config/cosmic")))
+            .map(Self)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    fn key_path(&self, id: &str, version: u64, key: &str) -> PathBuf {
+        self.0.join(id).join(format!("v{version}")).join(key)
+    }
+}
+
+impl ConfigProvider for ConfigDir {
+    fn read_key(&self, id: &str, version: u64, key: &str) -> Option<String> {
+        fs::read_to_string(self.key_path(id, version, key)).ok()
+    }
+
+    fn write_key(&self, id: &str, version: u64, key: &str, value: Option<&str>) -> io::Result<()> {
+        let path = self.key_path(id, version, key);
+        match value {
+            Some(value) => crate::files::write_atomic(&path, value),
+            None => match fs::remove_file(&path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_read_and_unset() {
+        let dir = std::env::temp_dir().join(format!("csd-config-{}", std::process::id()));
+        let config = ConfigDir(dir.clone());
+
+        assert_eq!(config.read_key("com.example.Test", 1, "enabled"), None);
+        config
+            .write_key("com.example.Test", 1, "enabled", Some("true"))
+            .unwrap();
+        assert_eq!(
+            config.read_key("com.example.Test", 1, "enabled").as_deref(),
+            Some("true")
+        );
+        assert!(dir.join("com.example.Test/v1/enabled").is_file());
+        assert_eq!(config.read_key("com.example.Test", 2, "enabled"), None);
+
+        config
+            .write_key("com.example.Test", 1, "enabled", None)
+            .unwrap();
+        assert_eq!(config.read_key("com.example.Test", 1, "enabled"), None);
+        // Unsetting a key which is not set is not an error.
+        config
+            .write_key("com.example.Test", 1, "enabled", None)
+            .unwrap();
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+static WRITES: AtomicU64 = AtomicU64::new(0);
+
+/// How many files `write_atomic` replaced, for finding exports which rewrite files too often.
+pub fn writes() -> u64 {
+    WRITES.load(Ordering::Relaxed)
+}
+
+/// Replace the file at `path` with `contents` by renaming, so that readers never see a partial
+/// file. Nothing is written if the contents are unchanged.
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    if fs::read_to_string(path).is_ok_and(|current| current == contents) {
+        return Ok(());
+    }
+
+    let dir = path
+        .parent()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no parent"))?;
+    fs::create_dir_all(dir)?;
+
+    // Hidden, so that it is not taken for a config key when written to a config directory.
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(path.file_name().unwrap_or_default());
+    tmp_name.push(".tmp");
+    let tmp = dir.join(tmp_name);
+
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    WRITES.fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Set `key` in `section` of an INI file such as GTK's `settings.ini`, keeping everything else.
+pub fn set_ini_value(path: &Path, section: &str, key: &str, value: &str) -> io::Result<()> {
+    set_ini_values(path, &[(section, key, value)])
+}
+
+/// Set several `(section, key, value)` entries of an INI file at once, so that it is only
+/// replaced once.
+pub fn set_ini_values(path: &Path, values: &[(&str, &str, &str)]) -> io::Result<()> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+
+    let mut lines: Vec<String> = contents.lines().map(str::to_owned).collect();
+    for (section, key, value) in values {
+        set_line(&mut lines, section, key, value);
+    }
+
+    write_atomic(path, &(lines.join("\n") + "\n"))
+}
+
+/// Remove several `(section, key)` entries of an INI file, keeping everything else. A missing
+/// file is left missing.
+pub fn remove_ini_values(path: &Path, keys: &[(&str, &str)]) -> io::Result<()> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    let mut lines: Vec<String> = contents.lines().map(str::to_owned).collect();
+    for (section, key) in keys {
+        remove_line(&mut lines, section, key);
+    }
+
+    write_atomic(path, &(lines.join("\n") + "\n"))
+}
+
+/// The range of the lines of `section` after its header, or `None` if it is missing.
+fn section_lines(lines: &[String], section: &str) -> Option<std::ops::Range<usize>> {
+    let header = format!("[{section}]");
+    let start = lines.iter().position(|line| line.trim() == header)? + 1;
+    let end = lines[start..]
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .map_or(lines.len(), |i| start + i);
+    Some(start..end)
+}
+
+/// The index of the entry of `key` among `lines`.
+fn key_line(lines: &[String], key: &str) -> Option<usize> {
+    lines.iter().position(|line| {
+        line.split_once('=')
+            .is_some_and(|(name, _)| name.trim() == key)
+    })
+}
+
+fn remove_line(lines: &mut Vec<String>, section: &str, key: &str) {
+    let Some(range) = section_lines(lines, section) else {
+        return;
+    };
+
+    if let Some(i) = key_line(&lines[range.clone()], key) {
+        lines.remove(range.start + i);
+    }
+}
+
+fn set_line(lines: &mut Vec<String>, section: &str, key: &str, value: &str) {
+    let entry = format!("{key}={value}");
+
+    let Some(range) = section_lines(lines, section) else {
+        if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+            lines.push(String::new());
+        }
+        lines.push(format!("[{section}]"));
+        lines.push(entry);
+        return;
+    };
+
+    match key_line(&lines[range.clone()], key) {
+        Some(i) => lines[range.start + i] = entry,
+        None => {
+            // After the last entry of the section, rather than after trailing blank lines.
+            let last = lines[range.clone()]
+                .iter()
+                .rposition(|line| !line.trim().is_empty())
+                .map_or(range.start, |i| range.start + i + 1);
+            lines.insert(last, entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn temp_file(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("csd-files-{}-{name}", std::process::id()));
+        _ = fs::remove_dir_all(&dir);
+        dir.join("nested").join(name)
+    }
+
+    #[test]
+    fn write_atomic_creates_and_replaces() {
+        let path = temp_file("replace");
+        write_atomic(&path, "one").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one");
+
+        let before = writes();
+        write_atomic(&path, "one").unwrap();
+        write_atomic(&path, "two").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "two");
+        // Tests run in parallel, so other tests may write too.
+        assert!(writes() > before);
+
+        let dir = path.parent().unwrap();
+        let names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["replace"]);
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn write_atomic_skips_unchanged() {
+        let path = temp_file("unchanged");
+        write_atomic(&path, "same").unwrap();
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        write_atomic(&path, "same").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+        fs::remove_dir_all(path.parent().unwrap().parent().unwrap()).unwrap();
+    }
+
+    fn set(contents: &str, values: &[(&str, &str, &str)]) -> String {
+        let mut lines: Vec<String> = contents.lines().map(str::to_owned).collect();
+        for (section, key, value) in values {
+            set_line(&mut lines, section, key, value);
+        }
+        lines.join("\n") + "\n"
+    }
+
+    #[test]
+    fn ini_replaces_existing_key() {
+        let ini = "[Settings]\ngtk-theme-name=Adwaita\ngtk-font-name=Sans 10\n";
+        assert_eq!(
+            set(ini, &[("Settings", "gtk-theme-name", "adw-gtk3-dark")]),
+            "[Settings]\ngtk-theme-name=adw-gtk3-dark\ngtk-font-name=Sans 10\n"
+        );
+    }
+
+    #[test]
+    fn ini_adds_key_after_last_entry_of_section() {
+        let ini = "[Settings]\na=1\n\n[Other]\nb=2\n";
+        assert_eq!(
+            set(ini, &[("Settings", "c", "3")]),
+            "[Settings]\na=1\nc=3\n\n[Other]\nb=2\n"
+        );
+    }
+
+    #[test]
+    fn ini_keeps_same_key_of_other_sections() {
+        let ini = "[Other]\na=1\n[Settings]\na=2\n";
+        assert_eq!(
+            set(ini, &[("Settings", "a", "3")]),
+            "[Other]\na=1\n[Settings]\na=3\n"
+        );
+    }
+
+    #[test]
+    fn ini_adds_section() {
+        assert_eq!(set("", &[("Settings", "a", "1")]), "[Settings]\na=1\n");
+        assert_eq!(
+            set(
+                "[Other]\nb=2",
+                &[("Settings", "a", "1"), ("Settings", "c", "3")]
+            ),
+            "[Other]\nb=2\n\n[Settings]\na=1\nc=3\n"
+        );
+    }
+
+    #[test]
+    fn ini_removes_key_of_section() {
+        let mut lines: Vec<String> = "[Other]\na=1\n[Settings]\na=2\nb=3\n"
+            .lines()
+            .map(str::to_owned)
+            .collect();
+        remove_line(&mut lines, "Settings", "a");
+        remove_line(&mut lines, "Settings", "c");
+        remove_line(&mut lines, "Missing", "a");
+        assert_eq!(lines, ["[Other]", "a=1", "[Settings]", "b=3"]);
+    }
+
+    #[test]
+    fn remove_ini_values_keeps_missing_file_missing() {
+        let path = temp_file("missing.ini");
+        remove_ini_values(&path, &[("Settings", "a")]).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn set_ini_values_writes_file() {
+        let path = temp_file("settings.ini");
+        set_ini_values(&path, &[("Settings", "a", "1"), ("Settings", "b", "2")]).unwrap();
+        set_ini_value(&path, "Settings", "a", "3").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[Settings]\na=3\nb=2\n");
+        fs::remove_dir_all(path.parent().unwrap().parent().unwrap()).unwrap();
+    }
+}
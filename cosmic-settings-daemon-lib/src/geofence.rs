@@ -0,0 +1,163 @@
+//! Circular regions, and which of them a location is in.
+
+use serde::{Deserialize, Serialize};
+
+use crate::schedule::Location;
+
+/// Smaller regions are widened to this, so that they stay coarse.
+const MIN_RADIUS_METERS: f64 = 100.0;
+
+/// A region is only left this far outside of it, so that inaccurate locations near its border do
+/// not switch the profile back and forth.
+const LEAVE_MARGIN_METERS: f64 = 100.0;
+
+const EARTH_RADIUS_METERS: f64 = 6_371_000.0;
+
+/// A circular region, such as "home" or "office".
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Region {
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub radius_meters: f64,
+    /// The profile which is active inside the region.
+    pub profile: String,
+}
+
+impl Region {
+    fn radius(&self) -> f64 {
+        self.radius_meters.max(MIN_RADIUS_METERS)
+    }
+
+    /// The great-circle distance of the center from `location`, in meters.
+    pub fn distance(&self, location: Location) -> f64 {
+        let (lat1, lat2) = (location.latitude.to_radians(), self.latitude.to_radians());
+        let dlat = lat2 - lat1;
+        let dlong = (self.longitude - location.longitude).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlong / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_METERS * a.sqrt().asin()
+    }
+}
+
+/// The region the device is in: the `current` region until it is left, and otherwise the
+/// region with the nearest center.
+pub fn region<'a>(
+    regions: &'a [Region],
+    location: Location,
+    current: Option<&str>,
+) -> Option<&'a Region> {
+    let current = current.and_then(|name| regions.iter().find(|r| r.name == name));
+    if let Some(region) = current {
+        if region.distance(location) <= region.radius() + LEAVE_MARGIN_METERS {
+            return Some(region);
+        }
+    }
+
+    regions
+        .iter()
+        .map(|region| (region.distance(location), region))
+        .filter(|(distance, region)| *distance <= region.radius())
+        .min_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, region)| region)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Meters per degree of latitude.
+    const DEGREE_METERS: f64 = EARTH_RADIUS_METERS * std::f64::consts::PI / 180.0;
+
+    fn circle(name: &str, latitude: f64, longitude: f64, radius_meters: f64) -> Region {
+        Region {
+            name: name.to_owned(),
+            latitude,
+            longitude,
+            radius_meters,
+            profile: name.to_owned(),
+        }
+    }
+
+    /// The location `meters` north of the center of `region`.
+    fn north_of(region: &Region, meters: f64) -> Location {
+        Location {
+            latitude: region.latitude + meters / DEGREE_METERS,
+            longitude: region.longitude,
+        }
+    }
+
+    fn name(region: Option<&Region>) -> Option<&str> {
+        region.map(|region| region.name.as_str())
+    }
+
+    #[test]
+    fn haversine_distance() {
+        let home = circle("home", 0.0, 0.0, 500.0);
+        let degree = Location {
+            latitude: 0.0,
+            longitude: 1.0,
+        };
+        assert!((home.distance(degree) - DEGREE_METERS).abs() < 1e-6);
+
+        // Paris to London is about 344 km.
+        let paris = circle("paris", 48.8566, 2.3522, 500.0);
+        let london = Location {
+            latitude: 51.5074,
+            longitude: -0.1278,
+        };
+        assert!((paris.distance(london) / 1000.0 - 344.0).abs() < 1.0);
+        assert_eq!(paris.distance(north_of(&paris, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn entered_inside_radius() {
+        let regions = [circle("home", 52.0, 13.0, 500.0)];
+        assert_eq!(
+            name(region(&regions, north_of(&regions[0], 499.0), None)),
+            Some("home")
+        );
+        assert_eq!(
+            name(region(&regions, north_of(&regions[0], 501.0), None)),
+            None
+        );
+    }
+
+    #[test]
+    fn left_outside_margin() {
+        let regions = [circle("home", 52.0, 13.0, 500.0)];
+        let current = Some("home");
+        assert_eq!(
+            name(region(&regions, north_of(&regions[0], 599.0), current)),
+            Some("home")
+        );
+        assert_eq!(
+            name(region(&regions, north_of(&regions[0], 601.0), current)),
+            None
+        );
+    }
+
+    #[test]
+    fn small_regions_are_widened() {
+        let regions = [circle("desk", 52.0, 13.0, 1.0)];
+        assert_eq!(
+            name(region(&regions, north_of(&regions[0], 99.0), None)),
+            Some("desk")
+        );
+    }
+
+    #[test]
+    fn nearest_center_wins() {
+        let regions = [
+            circle("office", 52.0, 13.0, 1000.0),
+            circle("cafe", 52.0 + 400.0 / DEGREE_METERS, 13.0, 1000.0),
+        ];
+        let location = north_of(&regions[0], 300.0);
+        assert_eq!(name(region(&regions, location, None)), Some("cafe"));
+        // The current region is kept until it is left, even where another one is nearer.
+        assert_eq!(
+            name(region(&regions, location, Some("office"))),
+            Some("office")
+        );
+        assert_eq!(name(region(&regions, location, Some("gone"))), Some("cafe"));
+    }
+}
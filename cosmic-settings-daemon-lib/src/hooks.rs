@@ -0,0 +1,72 @@
+use std::{
+    ffi::OsStr,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::OnceLock,
+};
+
+static REPORT: OnceLock<fn(&Path, Option<String>)> = OnceLock::new();
+
+/// The directory holding the scripts of a hook, `$XDG_DATA_HOME/cosmic-settings-daemon/<hook>.d/`.
+pub fn hook_dir(hook: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("cosmic-settings-daemon").join(format!("{hook}.d")))
+}
+
+/// Report the outcome of every script once it exits to `report`: its error, or `None` if it
+/// succeeded.
+pub fn report_to(report: fn(&Path, Option<String>)) {
+    _ = REPORT.set(report);
+}
+
+/// Run the executables in a hook directory in lexical order, passing `env` to each.
+///
+/// Scripts run one at a time in the background. A failing script is logged and
+/// does not prevent the following scripts from running.
+pub fn run<K: AsRef<OsStr> + Send + 'static>(hook: &'static str, env: Vec<(K, String)>) {
+    let Some(dir) = hook_dir(hook) else {
+        return;
+    };
+
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut scripts: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.metadata()
+                .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        })
+        .collect();
+
+    if scripts.is_empty() {
+        return;
+    }
+
+    scripts.sort();
+
+    tokio::spawn(async move {
+        for script in scripts {
+            let status = tokio::process::Command::new(&script)
+                .envs(env.iter().map(|(k, v)| (k, v)))
+                .stdin(Stdio::null())
+                .status()
+                .await;
+
+            let err = match status {
+                Ok(status) if status.success() => None,
+                Ok(status) => Some(format!("Hook {} exited with {status}", script.display())),
+                Err(err) => Some(format!("Failed to run hook {}: {err}", script.display())),
+            };
+
+            if let Some(err) = &err {
+                eprintln!("{err}");
+            }
+            if let Some(report) = REPORT.get() {
+                report(&script, err);
+            }
+        }
+    });
+}
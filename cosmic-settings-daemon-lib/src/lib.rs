@@ -0,0 +1,14 @@
+//! The logic of cosmic-settings-daemon which does not depend on the session, with the clock
+//! and location provided by the caller so that it can be driven by tests.
+
+pub mod cgroup;
+pub mod clock;
+pub mod config;
+pub mod files;
+pub mod geofence;
+pub mod hooks;
+pub mod nmea;
+pub mod palette;
+pub mod schedule;
+pub mod shortcuts;
+pub mod sun;
@@ -0,0 +1,151 @@
+//! Positions from the NMEA 0183 sentences of GPS receivers.
+
+use crate::schedule::Location;
+
+/// A position from a `GGA` or `RMC` sentence.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    pub location: Location,
+    /// The horizontal dilution of precision, which only `GGA` sentences have.
+    pub hdop: Option<f64>,
+}
+
+/// The position in a sentence such as `$GPGGA,...*47`, or `None` if it has no valid fix, its
+/// checksum differs, or it is another kind of sentence.
+///
+/// Sentences of every talker are accepted, so that GLONASS, Galileo, and combined receivers work
+/// like GPS ones.
+pub fn parse(sentence: &str) -> Option<Position> {
+    let (body, checksum) = sentence.trim().strip_prefix('$')?.split_once('*')?;
+    let checksum = u8::from_str_radix(checksum, 16).ok()?;
+    if body.bytes().fold(0, |sum, byte| sum ^ byte) != checksum {
+        return None;
+    }
+
+    let fields: Vec<&str> = body.split(',').collect();
+    // The kind follows the two letters of the talker, such as `GP` or `GN`.
+    match fields.first()?.get(2..)? {
+        // GGA: time, latitude, N/S, longitude, E/W, fix quality, satellites, HDOP, ...
+        "GGA" if *fields.get(6)? != "0" => Some(Position {
+            location: location(fields.get(2..6)?)?,
+            hdop: fields.get(8).and_then(|hdop| hdop.parse().ok()),
+        }),
+        // RMC: time, status, latitude, N/S, longitude, E/W, ...
+        "RMC" if *fields.get(2)? == "A" => Some(Position {
+            location: location(fields.get(3..7)?)?,
+            hdop: None,
+        }),
+        _ => None,
+    }
+}
+
+/// The location in the fields `latitude, N/S, longitude, E/W`.
+fn location(fields: &[&str]) -> Option<Location> {
+    let [latitude, north_south, longitude, east_west] = fields else {
+        return None;
+    };
+
+    Some(Location {
+        latitude: coordinate(latitude, 2, north_south, "N", "S")?,
+        longitude: coordinate(longitude, 3, east_west, "E", "W")?,
+    })
+}
+
+/// Degrees from a coordinate such as `4807.038`, which is degrees followed by minutes.
+fn coordinate(
+    value: &str,
+    degree_digits: usize,
+    hemisphere: &str,
+    positive: &str,
+    negative: &str,
+) -> Option<f64> {
+    let degrees: f64 = value.get(..degree_digits)?.parse().ok()?;
+    let minutes: f64 = value.get(degree_digits..)?.parse().ok()?;
+    let coordinate = degrees + minutes / 60.0;
+
+    if hemisphere == positive {
+        Some(coordinate)
+    } else if hemisphere == negative {
+        Some(-coordinate)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GGA: &str = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
+    const RMC: &str = "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A";
+
+    /// A sentence with its checksum, which is the XOR of the bytes between `$` and `*`.
+    fn sentence(body: &str) -> String {
+        let checksum = body.bytes().fold(0, |sum, byte| sum ^ byte);
+        format!("${body}*{checksum:02X}")
+    }
+
+    fn assert_near(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6, "{a} != {b}");
+    }
+
+    #[test]
+    fn gga() {
+        let position = parse(GGA).unwrap();
+        assert_near(position.location.latitude, 48.0 + 7.038 / 60.0);
+        assert_near(position.location.longitude, 11.0 + 31.0 / 60.0);
+        assert_eq!(position.hdop, Some(0.9));
+    }
+
+    #[test]
+    fn rmc() {
+        let position = parse(RMC).unwrap();
+        assert_near(position.location.latitude, 48.0 + 7.038 / 60.0);
+        assert_near(position.location.longitude, 11.0 + 31.0 / 60.0);
+        assert_eq!(position.hdop, None);
+    }
+
+    #[test]
+    fn line_ending_and_other_talkers() {
+        assert!(parse(&format!("{GGA}\r\n")).is_some());
+
+        let gn = sentence("GNGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,");
+        assert!(parse(&gn).is_some());
+    }
+
+    #[test]
+    fn southern_and_western_hemispheres() {
+        let body = "GPRMC,123519,A,3352.128,S,15112.558,W,022.4,084.4,230394,003.1,W";
+        let position = parse(&sentence(body)).unwrap();
+        assert_near(position.location.latitude, -(33.0 + 52.128 / 60.0));
+        assert_near(position.location.longitude, -(151.0 + 12.558 / 60.0));
+    }
+
+    #[test]
+    fn wrong_checksum() {
+        assert_eq!(parse(&GGA.replace("*47", "*48")), None);
+        assert_eq!(parse(GGA.trim_end_matches("*47")), None);
+    }
+
+    #[test]
+    fn no_fix() {
+        let gga = sentence("GPGGA,123519,,,,,0,00,,,M,,M,,");
+        assert_eq!(parse(&gga), None);
+        let rmc = sentence("GPRMC,123519,V,4807.038,N,01131.000,E,,,230394,,");
+        assert_eq!(parse(&rmc), None);
+    }
+
+    #[test]
+    fn other_sentences_and_garbage() {
+        assert_eq!(
+            parse(&sentence("GPGSA,A,3,04,05,,09,12,,,24,,,,,2.5,1.3,2.1")),
+            None
+        );
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("$*00"), None);
+        let invalid = sentence("GPGGA,123519,48x7.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,");
+        assert_eq!(parse(&invalid), None);
+        let hemisphere = sentence("GPGGA,123519,4807.038,E,01131.000,E,1,08,0.9,545.4,M,,M,,");
+        assert_eq!(parse(&hemisphere), None);
+    }
+}
@@ -0,0 +1,219 @@
+//! Palettes of 16 colors, like those of terminals, generated from the dominant colors of an
+//! image.
+
+/// The colors found in an image: its darkest, its lightest, and six between them.
+pub const DOMINANT_COLORS: usize = 8;
+
+const BLACK: Rgb = [0, 0, 0];
+const WHITE: Rgb = [255, 255, 255];
+
+pub type Rgb = [u8; 3];
+
+pub fn hex([r, g, b]: Rgb) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn luminance([r, g, b]: Rgb) -> f32 {
+    (0.2126 * f32::from(r) + 0.7152 * f32::from(g) + 0.0722 * f32::from(b)) / 255.0
+}
+
+fn saturation(color: Rgb) -> f32 {
+    let max = color.into_iter().max().unwrap_or(0);
+    let min = color.into_iter().min().unwrap_or(0);
+    if max == 0 {
+        0.0
+    } else {
+        f32::from(max - min) / f32::from(max)
+    }
+}
+
+/// `a` moved towards `b` by `t`, from 0 to 1.
+fn mix(a: Rgb, b: Rgb, t: f32) -> Rgb {
+    std::array::from_fn(|i| (f32::from(a[i]) + (f32::from(b[i]) - f32::from(a[i])) * t) as u8)
+}
+
+fn average(pixels: &[Rgb]) -> Rgb {
+    let mut sums = [0u64; 3];
+    for pixel in pixels {
+        for (sum, channel) in sums.iter_mut().zip(pixel) {
+            *sum += u64::from(*channel);
+        }
+    }
+    sums.map(|sum| (sum / pixels.len().max(1) as u64) as u8)
+}
+
+/// The channel the pixels differ most in, and by how much.
+fn widest_channel(pixels: &[Rgb]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let max = pixels.iter().map(|p| p[channel]).max().unwrap_or(0);
+            let min = pixels.iter().map(|p| p[channel]).min().unwrap_or(0);
+            (channel, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap_or((0, 0))
+}
+
+/// The dominant colors of the pixels by median cut, which splits the most varied group of pixels
+/// in half along its widest channel until there are `count` groups.
+pub fn dominant_colors(pixels: Vec<Rgb>, count: usize) -> Vec<Rgb> {
+    let mut groups = vec![pixels];
+    while groups.len() < count {
+        let widest = groups
+            .iter()
+            .enumerate()
+            .filter(|(_, group)| group.len() > 1)
+            .map(|(i, group)| (i, widest_channel(group)))
+            .max_by_key(|(_, (_, range))| *range);
+        let Some((i, (channel, _))) = widest else {
+            break;
+        };
+
+        let mut group = groups.swap_remove(i);
+        group.sort_unstable_by_key(|pixel| pixel[channel]);
+        let upper = group.split_off(group.len() / 2);
+        groups.push(group);
+        groups.push(upper);
+    }
+
+    groups
+        .iter()
+        .filter(|group| !group.is_empty())
+        .map(|group| average(group))
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub colors: [Rgb; 16],
+    /// The most vivid color of the image.
+    pub accent: Rgb,
+}
+
+impl Palette {
+    /// The palette of the pixels of an image, or `None` if it has none.
+    pub fn from_pixels(pixels: Vec<Rgb>, is_dark: bool) -> Option<Self> {
+        Self::new(dominant_colors(pixels, DOMINANT_COLORS), is_dark)
+    }
+
+    /// Like the palette of a terminal, `color0` is the background and `color15` the foreground,
+    /// which are dark and light in the dark mode, and the other way around in the light mode.
+    pub fn new(mut dominant: Vec<Rgb>, is_dark: bool) -> Option<Self> {
+        let last = *dominant.last()?;
+        dominant.resize(DOMINANT_COLORS, last);
+        dominant.sort_by(|a, b| luminance(*a).total_cmp(&luminance(*b)));
+
+        let (darkest, lightest) = (dominant[0], dominant[DOMINANT_COLORS - 1]);
+        let middle = &dominant[1..DOMINANT_COLORS - 1];
+        let (background, foreground, towards_fg) = if is_dark {
+            (mix(darkest, BLACK, 0.7), mix(lightest, WHITE, 0.85), WHITE)
+        } else {
+            (mix(lightest, WHITE, 0.85), mix(darkest, BLACK, 0.8), BLACK)
+        };
+
+        let mut colors = [BLACK; 16];
+        colors[0] = background;
+        colors[7] = mix(foreground, background, 0.25);
+        colors[8] = mix(background, foreground, 0.3);
+        colors[15] = foreground;
+        for (i, color) in middle.iter().enumerate() {
+            colors[1 + i] = mix(*color, towards_fg, 0.15);
+            colors[9 + i] = mix(*color, towards_fg, 0.35);
+        }
+
+        let accent = middle
+            .iter()
+            .copied()
+            .max_by(|a, b| saturation(*a).total_cmp(&saturation(*b)))?;
+
+        Some(Self { colors, accent })
+    }
+
+    pub fn background(&self) -> Rgb {
+        self.colors[0]
+    }
+
+    pub fn foreground(&self) -> Rgb {
+        self.colors[15]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgb = [200, 30, 30];
+    const BLUE: Rgb = [20, 40, 220];
+
+    fn sorted(mut colors: Vec<Rgb>) -> Vec<Rgb> {
+        colors.sort_unstable();
+        colors
+    }
+
+    #[test]
+    fn median_cut_separates_distinct_colors() {
+        let pixels = [vec![RED; 50], vec![BLUE; 50]].concat();
+        assert_eq!(sorted(dominant_colors(pixels, 2)), sorted(vec![RED, BLUE]));
+    }
+
+    #[test]
+    fn median_cut_averages_groups() {
+        let pixels = vec![[0, 0, 0], [10, 0, 0], [250, 0, 0], [240, 0, 0]];
+        assert_eq!(
+            sorted(dominant_colors(pixels, 2)),
+            vec![[5, 0, 0], [245, 0, 0]]
+        );
+    }
+
+    #[test]
+    fn median_cut_stops_when_groups_cannot_split() {
+        assert_eq!(dominant_colors(vec![RED], DOMINANT_COLORS), vec![RED]);
+        assert_eq!(
+            dominant_colors(Vec::new(), DOMINANT_COLORS),
+            Vec::<Rgb>::new()
+        );
+    }
+
+    #[test]
+    fn widest_channel_of_pixels() {
+        assert_eq!(widest_channel(&[RED, BLUE]), (2, 190));
+        assert_eq!(widest_channel(&[RED]).1, 0);
+    }
+
+    #[test]
+    fn palette_of_no_pixels() {
+        assert_eq!(Palette::from_pixels(Vec::new(), true), None);
+    }
+
+    #[test]
+    fn palette_modes() {
+        let pixels: Vec<Rgb> = (0..=255).map(|i| [i, i / 2, 255 - i]).collect();
+        let dark = Palette::from_pixels(pixels.clone(), true).unwrap();
+        let light = Palette::from_pixels(pixels, false).unwrap();
+
+        assert!(luminance(dark.background()) < luminance(dark.foreground()));
+        assert!(luminance(light.background()) > luminance(light.foreground()));
+        assert!(dark.colors[1..7]
+            .iter()
+            .all(|color| luminance(*color) > luminance(dark.background())));
+    }
+
+    #[test]
+    fn accent_is_most_saturated() {
+        let gray = [128, 128, 128];
+        let pixels = [
+            vec![[0; 3]; 10],
+            vec![gray; 10],
+            vec![RED; 10],
+            vec![[255; 3]; 10],
+        ]
+        .concat();
+        let palette = Palette::from_pixels(pixels, true).unwrap();
+        assert_eq!(palette.accent, RED);
+    }
+
+    #[test]
+    fn hex_colors() {
+        assert_eq!(hex([0, 128, 255]), "#0080ff");
+    }
+}
@@ -0,0 +1,442 @@
+use std::{
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use tokio::io::unix::AsyncFd;
+
+use crate::clock::Clock;
+
+/// Makes a timer end early when the clock is set, which the libc crate does not define.
+const TFD_TIMER_CANCEL_ON_SET: libc::c_int = 1 << 1;
+
+/// Coordinates used for sunrise and sunset times.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// The latest known location, which is `None` until a location is known.
+pub type LocationReceiver = tokio::sync::watch::Receiver<Option<Location>>;
+
+/// The source of the location of schedules from sunset to sunrise.
+pub trait LocationProvider {
+    /// The latest known location, or `None` until a location is known.
+    fn location(&self) -> Option<Location>;
+}
+
+impl LocationProvider for Option<Location> {
+    fn location(&self) -> Option<Location> {
+        *self
+    }
+}
+
+impl LocationProvider for LocationReceiver {
+    fn location(&self) -> Option<Location> {
+        *self.borrow()
+    }
+}
+
+impl<P: LocationProvider + ?Sized> LocationProvider for &P {
+    fn location(&self) -> Option<Location> {
+        (**self).location()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct TimeOfDay {
+    pub hour: u32,
+    pub minute: u32,
+}
+
+/// A daily period of time.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub enum Schedule {
+    /// From `start` until `end`, which is on the following day if it is before `start`.
+    Fixed { start: TimeOfDay, end: TimeOfDay },
+    /// From sunset until sunrise.
+    SunsetToSunrise,
+}
+
+fn local_time(date: NaiveDate, time: TimeOfDay) -> Option<DateTime<Local>> {
+    let naive = date.and_hms_opt(time.hour, time.minute, 0)?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+impl TimeOfDay {
+    /// The time on `date`, or `None` if a daylight saving time change skips it.
+    pub fn on(self, date: NaiveDate) -> Option<DateTime<Local>> {
+        local_time(date, self)
+    }
+}
+
+/// Sunrise and sunset of a day.
+pub fn sunrise_sunset(
+    date: NaiveDate,
+    location: Location,
+) -> Option<(DateTime<Local>, DateTime<Local>)> {
+    let (sunrise, sunset) = sunrise::sunrise_sunset(
+        location.latitude,
+        location.longitude,
+        date.year(),
+        date.month(),
+        date.day(),
+    );
+
+    Some((
+        Local.timestamp_opt(sunrise, 0).single()?,
+        Local.timestamp_opt(sunset, 0).single()?,
+    ))
+}
+
+impl Schedule {
+    /// The period which starts on `date`.
+    fn window(
+        &self,
+        date: NaiveDate,
+        location: Option<Location>,
+    ) -> Option<(DateTime<Local>, DateTime<Local>)> {
+        match *self {
+            Schedule::Fixed { start, end } => {
+                let end_date = if (end.hour, end.minute) <= (start.hour, start.minute) {
+                    date.checked_add_days(Days::new(1))?
+                } else {
+                    date
+                };
+
+                Some((local_time(date, start)?, local_time(end_date, end)?))
+            }
+
+            Schedule::SunsetToSunrise => {
+                let location = location?;
+                let (_, sunset) = sunrise_sunset(date, location)?;
+                let (sunrise, _) = sunrise_sunset(date.checked_add_days(Days::new(1))?, location)?;
+                Some((sunset, sunrise))
+            }
+        }
+    }
+
+    /// Periods starting from yesterday until tomorrow, which cover `now`.
+    fn windows(
+        &self,
+        now: DateTime<Local>,
+        location: Option<Location>,
+    ) -> impl Iterator<Item = (DateTime<Local>, DateTime<Local>)> + '_ {
+        let today = now.date_naive();
+        [
+            today.checked_sub_days(Days::new(1)),
+            Some(today),
+            today.checked_add_days(Days::new(1)),
+        ]
+        .into_iter()
+        .flatten()
+        .filter_map(move |date| self.window(date, location))
+    }
+
+    pub fn is_active(&self, now: DateTime<Local>, location: impl LocationProvider) -> bool {
+        self.windows(now, location.location())
+            .any(|(start, end)| start <= now && now < end)
+    }
+
+    /// The next time the schedule becomes active or inactive.
+    ///
+    /// Returns `None` if the schedule depends on a location which is not known yet.
+    pub fn next_transition(
+        &self,
+        now: DateTime<Local>,
+        location: impl LocationProvider,
+    ) -> Option<DateTime<Local>> {
+        self.windows(now, location.location())
+            .flat_map(|(start, end)| [start, end])
+            .filter(|t| *t > now)
+            .min()
+    }
+
+    /// Whether the schedule is active at the time of `clock`, and when that changes next.
+    pub fn state(
+        &self,
+        clock: &impl Clock,
+        location: impl LocationProvider,
+    ) -> (bool, Option<DateTime<Local>>) {
+        let now = clock.now();
+        let location = location.location();
+        (
+            self.is_active(now, location),
+            self.next_transition(now, location),
+        )
+    }
+}
+
+/// A day of every year.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct MonthDay {
+    pub month: u32,
+    pub day: u32,
+}
+
+impl MonthDay {
+    fn of(date: NaiveDate) -> Self {
+        Self {
+            month: date.month(),
+            day: date.day(),
+        }
+    }
+}
+
+/// A yearly period of days, such as December or the summer months.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct DateRange {
+    /// The first day of the period.
+    pub start: MonthDay,
+    /// The last day of the period, which is in the following year if it is before `start`.
+    pub end: MonthDay,
+}
+
+impl DateRange {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        let day = MonthDay::of(date);
+        if self.start <= self.end {
+            self.start <= day && day <= self.end
+        } else {
+            day >= self.start || day <= self.end
+        }
+    }
+}
+
+/// The start of the day after `now`, when date ranges are checked again.
+pub fn next_day(now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let date = now.date_naive().checked_add_days(Days::new(1))?;
+    // Where a daylight saving time change skips midnight, the day starts an hour later.
+    local_time(date, TimeOfDay { hour: 0, minute: 0 })
+        .or_else(|| local_time(date, TimeOfDay { hour: 1, minute: 0 }))
+}
+
+/// A timer on the realtime clock which becomes readable at `t`, or when the clock is set.
+///
+/// Unlike tokio's timers on the monotonic clock, it keeps time while the system is suspended.
+fn realtime_timer(t: DateTime<Local>) -> io::Result<AsyncFd<OwnedFd>> {
+    // SAFETY: timerfd_create has no memory safety requirements.
+    let fd = unsafe {
+        libc::timerfd_create(libc::CLOCK_REALTIME, libc::TFD_NONBLOCK | libc::TFD_CLOEXEC)
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: The file descriptor was just opened, and nothing else owns it.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let spec = libc::itimerspec {
+        it_interval: libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        },
+        it_value: libc::timespec {
+            tv_sec: t.timestamp() as libc::time_t,
+            tv_nsec: t.timestamp_subsec_nanos() as libc::c_long,
+        },
+    };
+
+    // SAFETY: `spec` is a valid itimerspec, and the previous setting is not requested.
+    let res = unsafe {
+        libc::timerfd_settime(
+            fd.as_raw_fd(),
+            libc::TFD_TIMER_ABSTIME | TFD_TIMER_CANCEL_ON_SET,
+            &spec,
+            std::ptr::null_mut(),
+        )
+    };
+    if res != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    AsyncFd::new(fd)
+}
+
+/// Sleep until a time from a schedule, or forever if there is none.
+///
+/// Returns early if the clock is set, so that the caller can check the schedule again.
+pub async fn sleep_until(t: Option<DateTime<Local>>) {
+    let Some(t) = t else {
+        return std::future::pending().await;
+    };
+
+    match realtime_timer(t) {
+        Ok(timer) => {
+            let _res = timer.readable().await;
+        }
+        Err(err) => {
+            eprintln!("Failed to create a realtime timer: {err}");
+            let duration = (t - Local::now()).to_std().unwrap_or_default();
+            tokio::time::sleep(duration).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    const NIGHT: Schedule = Schedule::Fixed {
+        start: TimeOfDay {
+            hour: 22,
+            minute: 0,
+        },
+        end: TimeOfDay { hour: 6, minute: 0 },
+    };
+
+    const BERLIN: Location = Location {
+        latitude: 52.52,
+        longitude: 13.405,
+    };
+
+    /// A time in January, when no time zone changes to or from daylight saving time.
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        let date = NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        TimeOfDay { hour, minute }.on(date).unwrap()
+    }
+
+    #[test]
+    fn fixed_across_midnight() {
+        assert!(!NIGHT.is_active(at(15, 21, 59), None::<Location>));
+        assert!(NIGHT.is_active(at(15, 22, 0), None::<Location>));
+        assert!(NIGHT.is_active(at(16, 5, 59), None::<Location>));
+        assert!(!NIGHT.is_active(at(16, 6, 0), None::<Location>));
+
+        assert_eq!(
+            NIGHT.next_transition(at(15, 12, 0), None::<Location>),
+            Some(at(15, 22, 0))
+        );
+        assert_eq!(
+            NIGHT.next_transition(at(15, 23, 0), None::<Location>),
+            Some(at(16, 6, 0))
+        );
+    }
+
+    #[test]
+    fn fixed_within_day() {
+        let work = Schedule::Fixed {
+            start: TimeOfDay { hour: 9, minute: 0 },
+            end: TimeOfDay {
+                hour: 17,
+                minute: 30,
+            },
+        };
+        assert!(!work.is_active(at(15, 8, 59), None::<Location>));
+        assert!(work.is_active(at(15, 12, 0), None::<Location>));
+        assert!(!work.is_active(at(15, 17, 30), None::<Location>));
+        assert_eq!(
+            work.next_transition(at(15, 18, 0), None::<Location>),
+            Some(at(16, 9, 0))
+        );
+    }
+
+    #[test]
+    fn sunset_to_sunrise_needs_location() {
+        let schedule = Schedule::SunsetToSunrise;
+        assert!(!schedule.is_active(at(15, 23, 0), None::<Location>));
+        assert_eq!(
+            schedule.next_transition(at(15, 23, 0), None::<Location>),
+            None
+        );
+    }
+
+    #[test]
+    fn sunset_to_sunrise() {
+        let schedule = Schedule::SunsetToSunrise;
+        let date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let (sunrise, sunset) = sunrise_sunset(date, BERLIN).unwrap();
+        let minute = chrono::Duration::minutes(1);
+
+        assert!(schedule.is_active(sunrise - minute, Some(BERLIN)));
+        assert!(!schedule.is_active(sunrise + minute, Some(BERLIN)));
+        assert!(!schedule.is_active(sunset - minute, Some(BERLIN)));
+        assert!(schedule.is_active(sunset + minute, Some(BERLIN)));
+        assert_eq!(
+            schedule.next_transition(sunrise + minute, Some(BERLIN)),
+            Some(sunset)
+        );
+    }
+
+    #[test]
+    fn state_from_clock_and_receiver() {
+        let (tx, rx) = tokio::sync::watch::channel(None);
+        let mut clock = ManualClock(at(15, 23, 0));
+        let schedule = Schedule::SunsetToSunrise;
+        assert_eq!(schedule.state(&clock, &rx), (false, None));
+
+        tx.send_replace(Some(BERLIN));
+        let (active, next) = schedule.state(&clock, &rx);
+        assert!(active);
+        assert!(next.is_some_and(|next| next > clock.0 && next < at(16, 12, 0)));
+
+        clock.set(at(15, 12, 0));
+        assert_eq!(NIGHT.state(&clock, &rx), (false, Some(at(15, 22, 0))));
+    }
+
+    const DECEMBER: DateRange = DateRange {
+        start: MonthDay { month: 12, day: 1 },
+        end: MonthDay { month: 12, day: 31 },
+    };
+
+    const WINTER: DateRange = DateRange {
+        start: MonthDay { month: 12, day: 21 },
+        end: MonthDay { month: 3, day: 19 },
+    };
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn month_days_are_ordered_by_month_first() {
+        assert!(MonthDay { month: 1, day: 31 } < MonthDay { month: 2, day: 1 });
+        assert_eq!(
+            MonthDay::of(date(2024, 2, 29)),
+            MonthDay { month: 2, day: 29 }
+        );
+    }
+
+    #[test]
+    fn date_range_within_year() {
+        assert!(!DECEMBER.contains(date(2024, 11, 30)));
+        assert!(DECEMBER.contains(date(2024, 12, 1)));
+        assert!(DECEMBER.contains(date(2024, 12, 31)));
+        assert!(!DECEMBER.contains(date(2025, 1, 1)));
+    }
+
+    #[test]
+    fn date_range_across_new_year() {
+        assert!(!WINTER.contains(date(2024, 12, 20)));
+        assert!(WINTER.contains(date(2024, 12, 21)));
+        assert!(WINTER.contains(date(2025, 1, 1)));
+        assert!(WINTER.contains(date(2025, 3, 19)));
+        assert!(!WINTER.contains(date(2025, 3, 20)));
+        assert!(!WINTER.contains(date(2025, 7, 1)));
+    }
+
+    #[test]
+    fn single_day_range() {
+        let day = MonthDay { month: 2, day: 29 };
+        let leap_day = DateRange {
+            start: day,
+            end: day,
+        };
+        assert!(leap_day.contains(date(2024, 2, 29)));
+        assert!(!leap_day.contains(date(2024, 3, 1)));
+    }
+
+    #[test]
+    fn next_day_is_midnight() {
+        assert_eq!(next_day(at(15, 12, 0)), Some(at(16, 0, 0)));
+        assert_eq!(next_day(at(15, 0, 0)), Some(at(16, 0, 0)));
+        assert_eq!(
+            next_day(at(31, 23, 59)),
+            TimeOfDay { hour: 0, minute: 0 }.on(date(2024, 2, 1))
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Adding shortcuts to the custom shortcuts of COSMIC, which the compositor binds.
+//!
+//! The shortcuts are a RON map from bindings to actions, such as
+//! `{ (modifiers: [Super], key: "t"): Spawn("cosmic-term") }`. Only the bindings are read, and
+//! new entries are added to the end of the map, so that the actions of the other shortcuts are
+//! kept as they are written.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum Modifier {
+    Super,
+    Ctrl,
+    Alt,
+    Shift,
+}
+
+/// A key with modifiers, such as Super+Alt+S, where the key is the name of its keysym.
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct Binding {
+    #[serde(default)]
+    pub modifiers: Vec<Modifier>,
+    /// Bindings of only modifiers, such as Super on its own, have no key.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub key: String,
+}
+
+impl Binding {
+    pub fn new(modifiers: &[Modifier], key: &str) -> Self {
+        let mut modifiers = modifiers.to_vec();
+        modifiers.sort();
+        modifiers.dedup();
+        Self {
+            modifiers,
+            key: key.to_owned(),
+        }
+    }
+
+    /// The binding with its modifiers in order, for comparing ones written in another order.
+    fn normalized(&self) -> Self {
+        Self::new(&self.modifiers, &self.key)
+    }
+}
+
+#[derive(Serialize)]
+enum Action<'a> {
+    Spawn(&'a str),
+}
+
+/// The bindings of a shortcuts map.
+pub fn bindings(shortcuts: &str) -> Result<BTreeSet<Binding>, ron::error::SpannedError> {
+    if shortcuts.trim().is_empty() {
+        return Ok(BTreeSet::new());
+    }
+
+    let map: BTreeMap<Binding, IgnoredAny> = ron::from_str(shortcuts)?;
+    Ok(map.keys().map(Binding::normalized).collect())
+}
+
+/// The shortcuts map with shortcuts running the commands added to its end, or `None` if it is
+/// not a map.
+pub fn add(shortcuts: &str, added: &[(Binding, &str)]) -> Option<String> {
+    let mut entries = String::new();
+    for (binding, command) in added {
+        let entry = BTreeMap::from([(binding, Action::Spawn(command))]);
+        let map = ron::ser::to_string_pretty(&entry, ron::ser::PrettyConfig::new()).ok()?;
+        // The entry without the braces of its map, indented like the entries of the map.
+        let entry = map.trim().strip_prefix('{')?.strip_suffix('}')?;
+        entries.push_str(entry.trim_start_matches('\n'));
+    }
+
+    let shortcuts = shortcuts.trim();
+    if shortcuts.is_empty() {
+        return Some(format!("{{\n{entries}}}"));
+    }
+
+    let body = shortcuts.strip_prefix('{')?.strip_suffix('}')?.trim_end();
+    let separator = if body.trim().is_empty() || body.ends_with(',') {
+        ""
+    } else {
+        ","
+    };
+    Some(format!("{{{body}{separator}\n{entries}}}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUSTOM: &str = r#"{
+    (
+        modifiers: [
+            Super,
+        ],
+        key: "t",
+        description: Some("Terminal"),
+    ): Spawn("cosmic-term"),
+    (modifiers: [Shift, Super], key: "Left"): Focus(Left),
+    (modifiers: [Super]): System(Launcher),
+}"#;
+
+    #[test]
+    fn reads_the_bindings_of_any_action() {
+        let bindings = bindings(CUSTOM).unwrap();
+        assert!(bindings.contains(&Binding::new(&[Modifier::Super], "t")));
+        assert!(bindings.contains(&Binding::new(&[Modifier::Super, Modifier::Shift], "Left")));
+        assert!(bindings.contains(&Binding::new(&[Modifier::Super], "")));
+        assert_eq!(bindings.len(), 3);
+
+        assert!(super::bindings("").unwrap().is_empty());
+        assert!(super::bindings("{}").unwrap().is_empty());
+        assert!(super::bindings("[").is_err());
+    }
+
+    #[test]
+    fn adds_to_the_end() {
+        let binding = Binding::new(&[Modifier::Alt, Modifier::Super], "s");
+        let shortcuts = add(CUSTOM, &[(binding.clone(), "busctl call")]).unwrap();
+
+        // The other shortcuts are kept as they were written.
+        assert!(shortcuts.starts_with(CUSTOM.strip_suffix('}').unwrap()));
+        assert!(shortcuts.contains("Spawn(\"busctl call\")"));
+        let bindings = bindings(&shortcuts).unwrap();
+        assert!(bindings.contains(&binding));
+        assert_eq!(bindings.len(), 4);
+    }
+
+    #[test]
+    fn adds_to_empty_maps() {
+        let ctrl = Binding::new(&[Modifier::Ctrl], "Print");
+        let shift = Binding::new(&[Modifier::Shift], "Print");
+        for shortcuts in [
+            "",
+            "{}",
+            "{\n}\n",
+            "{ (modifiers: [Super], key: \"t\"): Disable }",
+        ] {
+            let added = add(shortcuts, &[(ctrl.clone(), "a"), (shift.clone(), "b")]).unwrap();
+            let bindings = bindings(&added).unwrap();
+            assert!(
+                bindings.contains(&ctrl) && bindings.contains(&shift),
+                "{added}"
+            );
+        }
+
+        assert_eq!(add("[]", &[(ctrl, "a")]), None);
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::bail;
+use chrono::{DateTime, Days, Local, NaiveDate};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    schedule::{sunrise_sunset, Location},
+};
+
+/// The times of the day when the theme switches, cached for the current date.
+#[derive(Debug)]
+pub struct SunriseSunset<C: Clock = SystemClock> {
+    clock: C,
+    date: NaiveDate,
+    sunrise: DateTime<Local>,
+    sunset: DateTime<Local>,
+    /// Sunrise on the following day, which is next after sunset.
+    next_sunrise: DateTime<Local>,
+    location: Location,
+    /// accuracy in meters
+    pub accuracy: f64,
+}
+
+impl SunriseSunset {
+    pub fn new(lat: f64, long: f64, accuracy: f64) -> anyhow::Result<Self> {
+        let location = Location {
+            latitude: lat,
+            longitude: long,
+        };
+        Self::with_clock(SystemClock, location, accuracy)
+    }
+}
+
+impl<C: Clock> SunriseSunset<C> {
+    pub fn with_clock(clock: C, location: Location, accuracy: f64) -> anyhow::Result<Self> {
+        let date = clock.now().date_naive();
+        let (sunrise, sunset, next_sunrise) = Self::transitions(date, location)?;
+
+        Ok(Self {
+            clock,
+            date,
+            sunrise,
+            sunset,
+            next_sunrise,
+            location,
+            accuracy,
+        })
+    }
+
+    pub fn clock_mut(&mut self) -> &mut C {
+        &mut self.clock
+    }
+
+    fn transitions(
+        date: NaiveDate,
+        location: Location,
+    ) -> anyhow::Result<(DateTime<Local>, DateTime<Local>, DateTime<Local>)> {
+        let Some((sunrise, sunset)) = sunrise_sunset(date, location) else {
+            bail!("Failed to calculate sunrise and sunset times");
+        };
+
+        let Some((next_sunrise, _)) = date
+            .checked_add_days(Days::new(1))
+            .and_then(|tomorrow| sunrise_sunset(tomorrow, location))
+        else {
+            bail!("Failed to calculate the next sunrise time");
+        };
+
+        Ok((sunrise, sunset, next_sunrise))
+    }
+
+    /// Recalculate the times once the date changes.
+    fn refresh(&mut self, now: DateTime<Local>) -> anyhow::Result<()> {
+        let date = now.date_naive();
+        if date != self.date {
+            (self.sunrise, self.sunset, self.next_sunrise) =
+                Self::transitions(date, self.location)?;
+            self.date = date;
+        }
+        Ok(())
+    }
+
+    pub fn is_dark(&mut self) -> anyhow::Result<bool> {
+        let now = self.clock.now();
+        self.refresh(now)?;
+        Ok(now < self.sunrise || now >= self.sunset)
+    }
+
+    /// The next sunrise or sunset.
+    pub fn next_transition(&mut self) -> anyhow::Result<DateTime<Local>> {
+        let now = self.clock.now();
+        self.refresh(now)?;
+        Ok(if now < self.sunrise {
+            self.sunrise
+        } else if now < self.sunset {
+            self.sunset
+        } else {
+            self.next_sunrise
+        })
+    }
+}
@@ -0,0 +1,524 @@
+use std::sync::Mutex;
+
+use cosmic::theme::CosmicTheme;
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, ConfigGet, CosmicConfigEntry};
+use cosmic_settings_daemon_lib::config::{ConfigDir, ConfigProvider};
+use cosmic_theme::Theme;
+use serde::{Deserialize, Serialize};
+use tokio::{process::Child, sync::mpsc::Receiver};
+use zbus::{Connection, InterfaceRef};
+
+use crate::{atspi::StatusProxy, DBUS_PATH};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.A11y";
+
+const MAX_ZOOM_LEVEL: f64 = 20.0;
+
+/// The config of cosmic-comp, which zooms the screen as its `accessibility_zoom` key says.
+const COMP_ID: &str = "com.system76.CosmicComp";
+
+static CONFIG: Mutex<Option<A11yConfig>> = Mutex::new(None);
+
+/// What the visual bell flashes when the bell rings.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub enum VisualBell {
+    #[default]
+    Off,
+    /// Flash the whole screen.
+    Screen,
+    /// Flash the output of the focused window.
+    FocusedOutput,
+}
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct A11yConfig {
+    /// Use the high contrast variants of the light and dark themes.
+    pub high_contrast: bool,
+    /// Run the screen reader, starting it with the session.
+    pub screen_reader: bool,
+    pub screen_reader_command: String,
+    /// Magnify the screen, which cosmic-comp does with the settings passed on to its
+    /// `accessibility_zoom`.
+    pub magnifier: bool,
+    pub zoom_level: f64,
+    /// Step of the zoom in and zoom out shortcuts.
+    pub zoom_increment: f64,
+    /// Keep the focused window or text cursor in view while zoomed.
+    pub zoom_follow_focus: bool,
+    /// Ask applications to turn off or tone down animations.
+    pub reduce_motion: bool,
+    pub visual_bell: VisualBell,
+    /// Play the bell sound along with the visual bell.
+    pub visual_bell_sound: bool,
+}
+
+impl Default for A11yConfig {
+    fn default() -> Self {
+        Self {
+            high_contrast: false,
+            screen_reader: false,
+            screen_reader_command: "orca".to_owned(),
+            magnifier: false,
+            zoom_level: 2.0,
+            zoom_increment: 0.5,
+            zoom_follow_focus: true,
+            reduce_motion: false,
+            visual_bell: VisualBell::Off,
+            visual_bell_sound: false,
+        }
+    }
+}
+
+/// How the zoomed view follows the pointer and the focus, as cosmic-comp names it.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+enum ZoomMovement {
+    OnEdge,
+    Centered,
+    Continuously,
+}
+
+/// The `accessibility_zoom` key of cosmic-comp.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct CompZoomConfig {
+    start_on_login: bool,
+    show_overlay: bool,
+    /// The step of the compositor's zoom shortcuts, in percent.
+    increment: u32,
+    view_moves: ZoomMovement,
+}
+
+impl A11yConfig {
+    /// The zoom level, which is 1 while the magnifier is off.
+    fn effective_zoom_level(&self) -> f64 {
+        if self.magnifier {
+            self.zoom_level.clamp(1.0, MAX_ZOOM_LEVEL)
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Flash the visual bell if it is enabled, returning whether the bell sound should play.
+pub fn ring_visual_bell() -> bool {
+    let config = CONFIG
+        .lock()
+        .ok()
+        .and_then(|config| config.clone())
+        .unwrap_or_default();
+
+    if config.visual_bell == VisualBell::Off {
+        return true;
+    }
+
+    crate::visual_bell::flash(config.visual_bell);
+
+    config.visual_bell_sound
+}
+
+/// Whether applications are asked to reduce motion, read from the config, so that it is current
+/// even before this module sees a change.
+pub fn reduce_motion() -> bool {
+    cosmic_config::Config::new(ID, A11yConfig::VERSION)
+        .ok()
+        .and_then(|helper| helper.get::<bool>("reduce_motion").ok())
+        .unwrap_or(false)
+}
+
+fn set_config(config: A11yConfig) {
+    if let Ok(mut global) = CONFIG.lock() {
+        *global = Some(config);
+    }
+}
+
+fn load_config() -> zbus::fdo::Result<(cosmic_config::Config, A11yConfig)> {
+    crate::config::load::<A11yConfig>(ID).map_err(config_error)
+}
+
+fn config_error(err: cosmic_config::Error) -> zbus::fdo::Error {
+    zbus::fdo::Error::Failed(format!("{err:?}"))
+}
+
+/// Accessibility controls which work without the settings application, for shortcuts.
+#[derive(Default)]
+pub struct A11y {
+    screen_reader: bool,
+    zoom_level: f64,
+    zoom_follow_focus: bool,
+    reduce_motion: bool,
+}
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.A11y")]
+impl A11y {
+    /// Whether the screen reader is running.
+    #[dbus_interface(property)]
+    async fn screen_reader(&self) -> bool {
+        self.screen_reader
+    }
+
+    /// The level the compositor zooms the screen to, which is 1 while the magnifier is off.
+    #[dbus_interface(property)]
+    async fn zoom_level(&self) -> f64 {
+        self.zoom_level
+    }
+
+    /// Whether the compositor keeps the focus in view while zoomed.
+    #[dbus_interface(property)]
+    async fn zoom_follow_focus(&self) -> bool {
+        self.zoom_follow_focus
+    }
+
+    /// Whether applications should turn off or tone down animations.
+    #[dbus_interface(property)]
+    async fn reduce_motion(&self) -> bool {
+        self.reduce_motion
+    }
+
+    async fn set_screen_reader(&self, enabled: bool) -> zbus::fdo::Result<()> {
+        let (helper, config) = load_config()?;
+        config
+            .set_screen_reader(&helper, enabled)
+            .map_err(config_error)
+    }
+
+    /// Start or stop the screen reader, for the Super+Alt+S shortcut.
+    async fn toggle_screen_reader(&self) -> zbus::fdo::Result<()> {
+        self.set_screen_reader(!self.screen_reader).await
+    }
+
+    /// Turn the magnifier on or off, for its shortcut.
+    async fn toggle_magnifier(&self) -> zbus::fdo::Result<()> {
+        let (helper, config) = load_config()?;
+        config
+            .set_magnifier(&helper, !config.magnifier)
+            .map_err(config_error)
+    }
+
+    /// Zoom in by one step, turning the magnifier on if it is off.
+    async fn zoom_in(&self) -> zbus::fdo::Result<()> {
+        let (helper, config) = load_config()?;
+        if !config.magnifier {
+            return config.set_magnifier(&helper, true).map_err(config_error);
+        }
+
+        let level = (config.zoom_level + config.zoom_increment).min(MAX_ZOOM_LEVEL);
+        config.set_zoom_level(&helper, level).map_err(config_error)
+    }
+
+    /// Zoom out by one step, turning the magnifier off when the screen is no longer zoomed.
+    async fn zoom_out(&self) -> zbus::fdo::Result<()> {
+        let (helper, config) = load_config()?;
+        if !config.magnifier {
+            return Ok(());
+        }
+
+        let level = config.zoom_level - config.zoom_increment;
+        if level <= 1.0 {
+            return config.set_magnifier(&helper, false).map_err(config_error);
+        }
+
+        config.set_zoom_level(&helper, level).map_err(config_error)
+    }
+}
+
+async fn publish_zoom(interface: &InterfaceRef<A11y>, config: &A11yConfig) {
+    let zoom_level = config.effective_zoom_level();
+
+    let mut a11y = interface.get_mut().await;
+    let ctxt = interface.signal_context();
+    if a11y.zoom_level != zoom_level {
+        a11y.zoom_level = zoom_level;
+        _ = a11y.zoom_level_changed(ctxt).await;
+    }
+
+    if a11y.zoom_follow_focus != config.zoom_follow_focus {
+        a11y.zoom_follow_focus = config.zoom_follow_focus;
+        _ = a11y.zoom_follow_focus_changed(ctxt).await;
+    }
+}
+
+/// Pass the magnifier settings on to cosmic-comp, which zooms the screen. The overlay of the
+/// compositor is kept as it is set.
+fn export_zoom(config: &A11yConfig) {
+    let Some(config_dir) = ConfigDir::user() else {
+        return;
+    };
+
+    let current = config_dir
+        .read_key(COMP_ID, 1, "accessibility_zoom")
+        .and_then(|zoom| ron::from_str::<CompZoomConfig>(&zoom).ok());
+    let view_moves = match current.as_ref().map(|zoom| zoom.view_moves) {
+        _ if !config.zoom_follow_focus => ZoomMovement::OnEdge,
+        Some(ZoomMovement::Centered) => ZoomMovement::Centered,
+        _ => ZoomMovement::Continuously,
+    };
+    let zoom = CompZoomConfig {
+        start_on_login: config.magnifier,
+        show_overlay: current.as_ref().map_or(true, |zoom| zoom.show_overlay),
+        increment: (config.zoom_increment * 100.0).round().clamp(1.0, 1000.0) as u32,
+        view_moves,
+    };
+    if current.as_ref() == Some(&zoom) {
+        return;
+    }
+
+    let res = ron::to_string(&zoom)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+        .and_then(|zoom| config_dir.write_key(COMP_ID, 1, "accessibility_zoom", Some(&zoom)));
+    if let Err(err) = res {
+        eprintln!("Failed to pass the magnifier settings on to the compositor: {err}");
+    }
+}
+
+/// Switch both themes to or from their high contrast variant.
+///
+/// The theme watcher exports the changed themes to GTK, the exporters override the colors of GTK
+/// and Qt with the high contrast palette of `crate::contrast`, and the settings portal reports
+/// the contrast of the theme to Flatpak and Qt applications.
+fn set_theme_high_contrast(high_contrast: bool) {
+    for helper in [CosmicTheme::dark_config(), CosmicTheme::light_config()] {
+        let helper = match helper {
+            Ok(helper) => helper,
+            Err(err) => {
+                eprintln!("Failed to open the theme config {err:?}");
+                continue;
+            }
+        };
+
+        let theme = match Theme::get_entry(&helper) {
+            Ok(theme) => theme,
+            Err((errs, theme)) => {
+                for why in errs {
+                    eprintln!("{why}");
+                }
+                theme
+            }
+        };
+
+        if theme.is_high_contrast == high_contrast {
+            continue;
+        }
+
+        if let Err(err) = theme.set_is_high_contrast(&helper, high_contrast) {
+            eprintln!("Failed to set the high contrast theme {err:?}");
+        }
+    }
+}
+
+/// Mirror high contrast to GNOME, which libadwaita applications follow outside of Flatpak.
+fn set_gnome_high_contrast(high_contrast: bool) {
+    crate::gsettings::set(vec![(
+        "org.gnome.desktop.a11y.interface",
+        "high-contrast",
+        high_contrast.to_string(),
+    )]);
+}
+
+/// Turn animations off or on in GNOME, GTK, and KDE applications.
+fn apply_reduce_motion(reduce_motion: bool) {
+    let animations = if reduce_motion { "false" } else { "true" };
+
+    crate::gsettings::set(vec![(
+        "org.gnome.desktop.interface",
+        "enable-animations",
+        animations.to_owned(),
+    )]);
+
+    let Some(config_dir) = dirs::config_dir() else {
+        return;
+    };
+
+    for gtk in ["gtk-3.0", "gtk-4.0"] {
+        let path = config_dir.join(gtk).join("settings.ini");
+        if let Err(err) =
+            crate::files::set_ini_value(&path, "Settings", "gtk-enable-animations", animations)
+        {
+            eprintln!("Failed to write {}: {err}", path.display());
+        }
+    }
+
+    let path = config_dir.join("kdeglobals");
+    let factor = if reduce_motion { "0" } else { "1" };
+    if let Err(err) = crate::files::set_ini_value(&path, "KDE", "AnimationDurationFactor", factor) {
+        eprintln!("Failed to write {}: {err}", path.display());
+    }
+}
+
+async fn publish_reduce_motion(interface: &InterfaceRef<A11y>, reduce_motion: bool) {
+    let mut a11y = interface.get_mut().await;
+    if a11y.reduce_motion != reduce_motion {
+        a11y.reduce_motion = reduce_motion;
+        _ = a11y.reduce_motion_changed(interface.signal_context()).await;
+    }
+}
+
+fn apply_high_contrast(high_contrast: bool) {
+    set_theme_high_contrast(high_contrast);
+    set_gnome_high_contrast(high_contrast);
+}
+
+/// Variables which make toolkits load their accessibility support even before they see that
+/// a screen reader runs.
+fn screen_reader_env(enabled: bool) -> crate::environment::Variables {
+    let value = |value: &str| enabled.then(|| value.to_owned());
+    vec![
+        ("ACCESSIBILITY_ENABLED", value("1")),
+        ("GTK_MODULES", value("gail:atk-bridge")),
+        ("QT_ACCESSIBILITY", value("1")),
+        ("QT_LINUX_ACCESSIBILITY_ALWAYS_ON", value("1")),
+    ]
+}
+
+async fn set_atspi_screen_reader(conn: &Connection, enabled: bool) -> zbus::Result<()> {
+    StatusProxy::new(conn)
+        .await?
+        .set_screen_reader_enabled(enabled)
+        .await
+}
+
+fn start_screen_reader(command: &str) -> Option<Child> {
+    match tokio::process::Command::new(command)
+        .arg("--replace")
+        .spawn()
+    {
+        Ok(child) => Some(child),
+        Err(err) => {
+            eprintln!("Failed to start the screen reader {command}: {err}");
+            None
+        }
+    }
+}
+
+/// Stop a screen reader, which is waited for in the background, so that its exit is not taken
+/// for the one started after it quitting.
+fn stop_screen_reader(mut child: Child) {
+    if let Some(pid) = child.id() {
+        // SAFETY: kill has no memory safety requirements.
+        if unsafe { libc::kill(pid as i32, libc::SIGTERM) } != 0 {
+            eprintln!(
+                "Failed to stop the screen reader: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+
+    tokio::spawn(async move {
+        _ = child.wait().await;
+    });
+}
+
+async fn apply_screen_reader(
+    conn: &Connection,
+    interface: &InterfaceRef<A11y>,
+    config: &A11yConfig,
+    reader: &mut Option<Child>,
+) {
+    let enabled = config.screen_reader;
+
+    crate::environment::set("a11y", screen_reader_env(enabled));
+    if let Err(err) = set_atspi_screen_reader(conn, enabled).await {
+        eprintln!("Failed to set the screen reader status of AT-SPI: {err}");
+    }
+
+    if !enabled {
+        if let Some(child) = reader.take() {
+            stop_screen_reader(child);
+        }
+    } else if reader.is_none() {
+        *reader = start_screen_reader(&config.screen_reader_command);
+    }
+
+    let mut a11y = interface.get_mut().await;
+    if a11y.screen_reader != enabled {
+        a11y.screen_reader = enabled;
+        _ = a11y.screen_reader_changed(interface.signal_context()).await;
+    }
+}
+
+/// Apply the accessibility settings which affect the whole session.
+pub async fn run(conn: Connection, mut rx: Receiver<String>) {
+    let Ok(interface) = conn.object_server().interface::<_, A11y>(DBUS_PATH).await else {
+        return;
+    };
+
+    let (helper, mut config) = match crate::config::load_async::<A11yConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load accessibility config {err:?}");
+            return;
+        }
+    };
+
+    set_config(config.clone());
+
+    // A high contrast theme chosen by other means is left alone unless the setting changes.
+    if config.high_contrast {
+        apply_high_contrast(true);
+    }
+
+    if config.reduce_motion {
+        apply_reduce_motion(true);
+    }
+
+    publish_zoom(&interface, &config).await;
+    export_zoom(&config);
+    publish_reduce_motion(&interface, config.reduce_motion).await;
+
+    let mut reader: Option<Child> = None;
+    if config.screen_reader {
+        apply_screen_reader(&conn, &interface, &config, &mut reader).await;
+    }
+
+    loop {
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+
+                let changes = crate::config::update(&mut config, &helper, &key);
+                set_config(config.clone());
+
+                if changes.contains(&"high_contrast") {
+                    apply_high_contrast(config.high_contrast);
+                }
+
+                if changes.contains(&"screen_reader") {
+                    apply_screen_reader(&conn, &interface, &config, &mut reader).await;
+                    crate::osd::show(
+                        "screen-reader",
+                        i32::from(config.screen_reader),
+                        "preferences-desktop-accessibility-symbolic",
+                    );
+                }
+
+                if changes.contains(&"reduce_motion") {
+                    apply_reduce_motion(config.reduce_motion);
+                    publish_reduce_motion(&interface, config.reduce_motion).await;
+                }
+
+                let zoom_keys = ["magnifier", "zoom_level", "zoom_increment", "zoom_follow_focus"];
+                if changes.iter().any(|change| zoom_keys.contains(change)) {
+                    publish_zoom(&interface, &config).await;
+                    export_zoom(&config);
+                    crate::osd::show(
+                        "zoom",
+                        (config.effective_zoom_level() * 100.0).round() as i32,
+                        "zoom-in-symbolic",
+                    );
+                }
+            }
+
+            _ = async { reader.as_mut()?.wait().await.ok() }, if reader.is_some() => {
+                reader = None;
+
+                // Quitting the screen reader turns it off, rather than starting it again.
+                if config.screen_reader {
+                    if let Err(err) = config.set_screen_reader(&helper, false) {
+                        eprintln!("Failed to disable the screen reader {err:?}");
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::{fs, io, path::Path, sync::Mutex};
+
+use anyhow::Context;
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use cosmic_theme::Theme;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Accent";
+
+static CONFIG: Mutex<Option<AccentConfig>> = Mutex::new(None);
+
+/// Written next to GTK's `gtk.css`, which imports it.
+pub const GTK_ACCENT_FILE: &str = "cosmic-accent.css";
+
+/// The entries of kdeglobals set to the accent color.
+const KDE_ACCENT_ENTRIES: &[(&str, &str)] = &[
+    ("General", "AccentColor"),
+    ("Colors:Selection", "BackgroundNormal"),
+    ("Colors:View", "DecorationFocus"),
+    ("Colors:View", "DecorationHover"),
+];
+
+/// The entries of kdeglobals set to the color of text on the accent color.
+const KDE_ON_ACCENT_ENTRIES: &[(&str, &str)] = &[("Colors:Selection", "ForegroundNormal")];
+
+/// The exports of the accent color, which are off by default because they override the colors
+/// that applications and their themes choose.
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct AccentConfig {
+    /// Define the accent colors of GTK and libadwaita in `gtk.css`.
+    pub gtk: bool,
+    /// Set the accent and selection colors of Qt and KDE applications in `kdeglobals`.
+    pub kde: bool,
+}
+
+type Rgb = [u8; 3];
+
+fn rgb(color: cosmic_theme::palette::Srgba) -> Rgb {
+    [color.red, color.green, color.blue].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+fn hex([r, g, b]: Rgb) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn kde_color([r, g, b]: Rgb) -> String {
+    format!("{r},{g},{b}")
+}
+
+/// The accent of the theme, and the color of text on it, of the high contrast palette if the
+/// theme is high contrast.
+fn accent(theme: &Theme) -> (Rgb, Rgb) {
+    let colors = crate::contrast::Colors::new(theme);
+    (rgb(colors.accent), rgb(colors.accent_text))
+}
+
+/// The accent color of the theme, as chosen rather than as exported for high contrast.
+pub fn theme_accent(theme: &Theme) -> Rgb {
+    rgb(theme.accent_color())
+}
+
+/// Remove the accent colors from the GTK config directory `dir`.
+fn remove_gtk(dir: &Path) -> io::Result<()> {
+    crate::gtk::remove_import(&dir.join("gtk.css"), GTK_ACCENT_FILE).and_then(|()| {
+        match fs::remove_file(dir.join(GTK_ACCENT_FILE)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    })
+}
+
+fn export_gtk(config_dir: &Path, enabled: bool, (accent, on_accent): (Rgb, Rgb)) {
+    let css = format!(
+        "/* Generated by cosmic-settings-daemon from the COSMIC accent color. */\n\
+         @define-color accent_color {accent};\n\
+         @define-color accent_bg_color {accent};\n\
+         @define-color accent_fg_color {on_accent};\n\
+         @define-color theme_selected_bg_color {accent};\n\
+         @define-color theme_selected_fg_color {on_accent};\n",
+        accent = hex(accent),
+        on_accent = hex(on_accent),
+    );
+
+    for gtk in ["gtk-3.0", "gtk-4.0"] {
+        let dir = config_dir.join(gtk);
+        let gtk_css = dir.join("gtk.css");
+        let accent_css = dir.join(GTK_ACCENT_FILE);
+
+        let res = if enabled {
+            crate::files::write_atomic(&accent_css, &css)
+                .and_then(|()| crate::gtk::add_import(&gtk_css, GTK_ACCENT_FILE))
+        } else {
+            remove_gtk(&dir)
+        };
+
+        if let Err(err) = res {
+            crate::export::failed(format!("Failed to export the accent color to {gtk}: {err}"));
+        }
+    }
+}
+
+fn export_kde(config_dir: &Path, (accent, on_accent): (Rgb, Rgb)) {
+    let path = config_dir.join("kdeglobals");
+    let accent = kde_color(accent);
+    let on_accent = kde_color(on_accent);
+
+    let values: Vec<(&str, &str, &str)> = KDE_ACCENT_ENTRIES
+        .iter()
+        .map(|(section, key)| (*section, *key, accent.as_str()))
+        .chain(
+            KDE_ON_ACCENT_ENTRIES
+                .iter()
+                .map(|(section, key)| (*section, *key, on_accent.as_str())),
+        )
+        .collect();
+
+    if let Err(err) = crate::files::set_ini_values(&path, &values) {
+        crate::export::failed(format!("Failed to write {}: {err}", path.display()));
+    }
+}
+
+fn export(config: &AccentConfig, theme: &Theme) {
+    let Some(config_dir) = dirs::config_dir() else {
+        return;
+    };
+
+    let colors = accent(theme);
+    export_gtk(&config_dir, config.gtk, colors);
+
+    // The previous colors of KDE are unknown, so disabling this leaves them as they are.
+    if config.kde {
+        export_kde(&config_dir, colors);
+    }
+}
+
+/// Stop exporting the accent color to GTK and KDE, and remove it from both.
+///
+/// Without the entries of the export, KDE applications take the colors of their color scheme.
+pub fn reset() -> anyhow::Result<()> {
+    let (helper, config) = crate::config::load::<AccentConfig>(ID)?;
+    config.set_gtk(&helper, false)?;
+    config.set_kde(&helper, false)?;
+
+    let config_dir = dirs::config_dir().context("no config directory")?;
+    for gtk in ["gtk-3.0", "gtk-4.0"] {
+        remove_gtk(&config_dir.join(gtk)).with_context(|| format!("failed to clean up {gtk}"))?;
+    }
+
+    let kdeglobals = config_dir.join("kdeglobals");
+    let entries: Vec<(&str, &str)> = KDE_ACCENT_ENTRIES
+        .iter()
+        .chain(KDE_ON_ACCENT_ENTRIES)
+        .copied()
+        .collect();
+    crate::files::remove_ini_values(&kdeglobals, &entries)
+        .with_context(|| format!("failed to clean up {}", kdeglobals.display()))?;
+    Ok(())
+}
+
+/// Export the accent color of the theme after it changed.
+pub fn theme_changed(theme: &Theme) {
+    let config = CONFIG.lock().ok().and_then(|config| config.clone());
+    if let Some(config) = config {
+        export(&config, theme);
+    }
+}
+
+/// Export the accent color of the theme to GTK and KDE, and again whenever the config changes.
+pub async fn run(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<AccentConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load accent config {err:?}");
+            return;
+        }
+    };
+
+    if let Ok(mut global) = CONFIG.lock() {
+        *global = Some(config.clone());
+    }
+
+    export(&config, &crate::theme::current().1);
+
+    while let Some(key) = rx.recv().await {
+        if crate::config::update(&mut config, &helper, &key).is_empty() {
+            continue;
+        }
+
+        if let Ok(mut global) = CONFIG.lock() {
+            *global = Some(config.clone());
+        }
+
+        export(&config, &crate::theme::current().1);
+    }
+}
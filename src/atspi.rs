@@ -0,0 +1,10 @@
+#[zbus::dbus_proxy(
+    default_service = "org.a11y.Bus",
+    interface = "org.a11y.Status",
+    default_path = "/org/a11y/bus"
+)]
+trait Status {
+    /// Toolkits enable their accessibility support while this is set.
+    #[dbus_proxy(property)]
+    fn set_screen_reader_enabled(&self, enabled: bool) -> zbus::Result<()>;
+}
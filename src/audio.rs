@@ -0,0 +1,1153 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+use zbus::{Connection, SignalContext};
+
+use crate::{
+    clock::SystemClock,
+    pactl::{self, DeviceKind, EventKind, Facility},
+    schedule::{LocationReceiver, Schedule},
+    LogindSessionProxy, DBUS_PATH,
+};
+
+/// Bluetooth profiles with a microphone, in order of preference.
+const HEADSET_PROFILES: [&str; 4] = [
+    "headset-head-unit-msbc",
+    "headset-head-unit",
+    "headset_head_unit",
+    "handsfree_head_unit",
+];
+
+/// Delay before saving device volumes, so that dragging a slider only writes once.
+const VOLUME_SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// Changes of the default device this soon after a device was added or removed
+/// are made by the audio server rather than the user.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Audio";
+
+/// The sink which downmixes to mono for the mono audio option.
+const MONO_SINK: &str = "cosmic-mono";
+
+static QUIET_HOURS: AtomicBool = AtomicBool::new(false);
+
+/// Whether the quiet hours schedule is active, which limits the volume and silences event sounds.
+pub fn quiet_hours_active() -> bool {
+    QUIET_HOURS.load(Ordering::Relaxed)
+}
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct AudioConfig {
+    /// Percentage the volume keys raise or lower the volume by.
+    pub volume_step: u32,
+    /// Allow the volume keys to raise the volume above 100%, up to 150%.
+    pub over_amplification: bool,
+    /// Play a sound after the volume is changed with the volume keys.
+    pub feedback_sound: bool,
+    /// Maximum volume percentage of a sink, by name, such as a safety limit for headphones.
+    ///
+    /// Applies to every volume change, including changes made by applications.
+    pub volume_limits: BTreeMap<String, u32>,
+    /// Switch to headphones and USB or Bluetooth audio devices when they are connected.
+    pub switch_on_connect: bool,
+    /// Also switch to the microphone of USB and Bluetooth devices when they are connected.
+    pub switch_source_on_connect: bool,
+    /// Switch Bluetooth headsets to a headset profile while an application is recording.
+    pub bluetooth_autoswitch: bool,
+    /// Addresses of Bluetooth devices which are never switched to a headset profile.
+    pub bluetooth_never_switch: Vec<String>,
+    /// A daily period during which the volume is limited and event sounds are muted.
+    pub quiet_hours: Option<Schedule>,
+    /// Maximum volume percentage of every sink during quiet hours.
+    pub quiet_hours_volume_limit: u32,
+    /// Play the same mix on every channel, for listening with a single ear.
+    pub mono: bool,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            volume_step: 5,
+            over_amplification: false,
+            feedback_sound: true,
+            volume_limits: BTreeMap::new(),
+            switch_on_connect: true,
+            switch_source_on_connect: false,
+            bluetooth_autoswitch: true,
+            bluetooth_never_switch: Vec::new(),
+            quiet_hours: None,
+            quiet_hours_volume_limit: 30,
+            mono: false,
+        }
+    }
+}
+
+impl AudioConfig {
+    pub fn max_volume(&self) -> u32 {
+        if self.over_amplification {
+            150
+        } else {
+            100
+        }
+    }
+
+    /// The highest volume allowed for a sink.
+    pub fn volume_limit(&self, sink: &str) -> u32 {
+        let mut max = self.max_volume();
+        if quiet_hours_active() {
+            max = max.min(self.quiet_hours_volume_limit);
+        }
+
+        self.volume_limits
+            .get(sink)
+            .map_or(max, |limit| (*limit).min(max))
+    }
+}
+
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct AudioState {
+    /// The sink chosen by the user for each set of available sinks.
+    pub preferred_sinks: BTreeMap<String, String>,
+    /// The source chosen by the user for each set of available sources.
+    pub preferred_sources: BTreeMap<String, String>,
+    /// The last volume of each sink and source, by name.
+    pub device_volumes: BTreeMap<String, DeviceVolume>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct DeviceVolume {
+    pub volume: u32,
+    pub muted: bool,
+}
+
+pub struct Audio {
+    helper: Option<cosmic_config::Config>,
+    config: AudioConfig,
+    volume: i32,
+    muted: bool,
+    microphone_muted: bool,
+    logind_session: Option<LogindSessionProxy<'static>>,
+    /// An LED showing the microphone mute state which the kernel does not drive itself.
+    mic_mute_led: Option<String>,
+    /// The module of the mono sink, and the sink it plays to.
+    mono_sink: Option<(u32, String)>,
+}
+
+/// Find a microphone mute LED, such as `platform::micmute`.
+fn mic_mute_led() -> Option<String> {
+    std::fs::read_dir("/sys/class/leds")
+        .ok()?
+        .filter_map(Result::ok)
+        .find_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.ends_with("::micmute") {
+                return None;
+            }
+
+            // LEDs using the audio-micmute trigger follow the mute state of the sound card.
+            let trigger = std::fs::read_to_string(entry.path().join("trigger")).unwrap_or_default();
+            (!trigger.contains("[audio-micmute]")).then_some(name)
+        })
+}
+
+impl Audio {
+    pub async fn new() -> Self {
+        let (helper, config) = match crate::config::load_async::<AudioConfig>(ID).await {
+            Ok((helper, config)) => (Some(helper), config),
+            Err(err) => {
+                eprintln!("Failed to load audio config {err:?}");
+                (None, AudioConfig::default())
+            }
+        };
+
+        let logind_session = async {
+            let connection = crate::bus::system().await?;
+            LogindSessionProxy::builder(&connection).build().await
+        }
+        .await
+        .ok();
+
+        let mut audio = Self {
+            helper,
+            config,
+            volume: -1,
+            muted: false,
+            microphone_muted: false,
+            logind_session,
+            mic_mute_led: mic_mute_led(),
+            mono_sink: None,
+        };
+        audio.refresh_sink().await;
+        audio.refresh_source().await;
+        audio.update_mic_mute_led().await;
+        audio
+    }
+
+    /// Play the default sink through a sink which downmixes to mono while the option is
+    /// enabled, following changes of the default sink.
+    async fn apply_mono(&mut self) {
+        if !self.config.mono {
+            let Some((module, master)) = self.mono_sink.take() else {
+                return;
+            };
+
+            let default = pactl::default_device_name(DeviceKind::Sink).await.ok();
+            if default.as_deref() == Some(MONO_SINK) {
+                let _res = pactl::set_default(DeviceKind::Sink, &master).await;
+            }
+
+            if let Err(err) = pactl::unload_module(module).await {
+                eprintln!("Failed to remove the mono sink: {err}");
+            }
+            return;
+        }
+
+        let default = pactl::default_device_name(DeviceKind::Sink).await.ok();
+        let Some(master) = default.filter(|name| name != MONO_SINK && !name.is_empty()) else {
+            return;
+        };
+
+        // Also removed by the audio server along with the sink it played to.
+        if let Some((module, _)) = self.mono_sink.take() {
+            let _res = pactl::unload_module(module).await;
+        }
+
+        let args = [
+            format!("sink_name={MONO_SINK}"),
+            format!("master={master}"),
+            "channels=2".to_owned(),
+            "channel_map=mono,mono".to_owned(),
+            "sink_properties=device.description=Mono".to_owned(),
+        ];
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+        match pactl::load_module("module-remap-sink", &args).await {
+            Ok(module) => {
+                if let Err(err) = pactl::set_default(DeviceKind::Sink, MONO_SINK).await {
+                    eprintln!("Failed to switch to the mono sink: {err}");
+                }
+                self.mono_sink = Some((module, master));
+            }
+            Err(err) => eprintln!("Failed to create the mono sink: {err}"),
+        }
+    }
+
+    async fn refresh_source(&mut self) {
+        let source = DeviceKind::Source.default_device();
+        self.microphone_muted = pactl::mute(DeviceKind::Source, source)
+            .await
+            .unwrap_or(false);
+    }
+
+    async fn update_mic_mute_led(&self) {
+        let (Some(session), Some(led)) = (self.logind_session.as_ref(), self.mic_mute_led.as_ref())
+        else {
+            return;
+        };
+
+        if let Err(err) = session
+            .set_brightness("leds", led, u32::from(self.microphone_muted))
+            .await
+        {
+            eprintln!("Failed to set the microphone mute LED: {err}");
+        }
+    }
+
+    async fn set_microphone_muted(&mut self, mute: bool, ctxt: &SignalContext<'_>) {
+        let source = DeviceKind::Source.default_device();
+        if let Err(err) = pactl::set_mute(DeviceKind::Source, source, mute).await {
+            eprintln!("Failed to set mute of the default source: {err}");
+        }
+
+        self.refresh_source().await;
+        self.update_mic_mute_led().await;
+        _ = self.microphone_muted_changed(ctxt).await;
+
+        let icon = if self.microphone_muted {
+            "microphone-sensitivity-muted-symbolic"
+        } else {
+            "audio-input-microphone-symbolic"
+        };
+        crate::osd::show("microphone-mute", i32::from(self.microphone_muted), icon);
+    }
+
+    /// Read the volume and mute state of the default sink.
+    async fn refresh_sink(&mut self) {
+        let sink = DeviceKind::Sink.default_device();
+        self.volume = pactl::volume(DeviceKind::Sink, sink)
+            .await
+            .map(|v| v as i32)
+            .unwrap_or(-1);
+        self.muted = pactl::mute(DeviceKind::Sink, sink).await.unwrap_or(false);
+    }
+
+    /// Update the properties after the default devices changed outside of the daemon.
+    async fn sync(&mut self, ctxt: &SignalContext<'_>) {
+        let (volume, muted, microphone_muted) = (self.volume, self.muted, self.microphone_muted);
+        self.refresh_sink().await;
+        self.refresh_source().await;
+
+        if volume != self.volume {
+            _ = self.volume_changed(ctxt).await;
+        }
+
+        if muted != self.muted {
+            _ = self.muted_changed(ctxt).await;
+        }
+
+        if microphone_muted != self.microphone_muted {
+            self.update_mic_mute_led().await;
+            _ = self.microphone_muted_changed(ctxt).await;
+        }
+    }
+
+    async fn change_volume(&mut self, delta: i32, ctxt: &SignalContext<'_>) {
+        let sink = DeviceKind::Sink.default_device();
+        let Ok(current) = pactl::volume(DeviceKind::Sink, sink).await else {
+            return;
+        };
+
+        let max = match pactl::default_device_name(DeviceKind::Sink).await {
+            Ok(name) => self.config.volume_limit(&name),
+            Err(_) => self.config.max_volume(),
+        };
+        let step = self.config.volume_step.max(1) as i32;
+        let target = if delta > 0 {
+            // Never lower a volume which was raised above the limit by something else.
+            (current as i32 + step).min(max.max(current) as i32)
+        } else {
+            (current as i32 - step).max(0)
+        };
+
+        if delta > 0 && self.muted {
+            if let Err(err) = pactl::set_mute(DeviceKind::Sink, sink, false).await {
+                eprintln!("Failed to unmute the default sink: {err}");
+            }
+        }
+
+        if let Err(err) = pactl::set_volume(DeviceKind::Sink, sink, target as u32).await {
+            eprintln!("Failed to set the volume of the default sink: {err}");
+        }
+
+        self.changed(ctxt).await;
+    }
+
+    async fn changed(&mut self, ctxt: &SignalContext<'_>) {
+        self.refresh_sink().await;
+        _ = self.volume_changed(ctxt).await;
+        _ = self.muted_changed(ctxt).await;
+
+        crate::osd::show(
+            "volume",
+            self.volume,
+            crate::osd::volume_icon("audio", self.volume, self.muted),
+        );
+
+        if self.config.feedback_sound && !self.muted {
+            crate::sounds::play(crate::sounds::Event::VolumeChange);
+        }
+    }
+}
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.Audio")]
+impl Audio {
+    /// Volume of the default sink as a percentage, or -1 if unknown.
+    #[dbus_interface(property)]
+    async fn volume(&self) -> i32 {
+        self.volume
+    }
+
+    #[dbus_interface(property)]
+    async fn muted(&self) -> bool {
+        self.muted
+    }
+
+    async fn increase_volume(&mut self, #[zbus(signal_context)] ctxt: SignalContext<'_>) {
+        if crate::seat::is_active() {
+            self.change_volume(1, &ctxt).await;
+        }
+    }
+
+    async fn decrease_volume(&mut self, #[zbus(signal_context)] ctxt: SignalContext<'_>) {
+        if crate::seat::is_active() {
+            self.change_volume(-1, &ctxt).await;
+        }
+    }
+
+    async fn toggle_mute(&mut self, #[zbus(signal_context)] ctxt: SignalContext<'_>) {
+        if !crate::seat::is_active() {
+            return;
+        }
+
+        let sink = DeviceKind::Sink.default_device();
+        if let Err(err) = pactl::set_mute(DeviceKind::Sink, sink, !self.muted).await {
+            eprintln!("Failed to toggle mute of the default sink: {err}");
+        }
+
+        self.changed(&ctxt).await;
+    }
+
+    #[dbus_interface(property)]
+    async fn microphone_muted(&self) -> bool {
+        self.microphone_muted
+    }
+
+    #[dbus_interface(property)]
+    async fn quiet_hours(&self) -> bool {
+        quiet_hours_active()
+    }
+
+    /// Toggle the microphone mute from the microphone mute key.
+    ///
+    /// There is no push to talk mode, as the shortcuts of COSMIC only run when their keys are
+    /// pressed, so the release of a held key never arrives.
+    async fn toggle_microphone_mute(&mut self, #[zbus(signal_context)] ctxt: SignalContext<'_>) {
+        if !crate::seat::is_active() {
+            return;
+        }
+
+        self.set_microphone_muted(!self.microphone_muted, &ctxt)
+            .await;
+    }
+}
+
+/// Apply changes to the audio config.
+///
+/// Quiet hours are started and ended from here, as they depend on the config.
+pub async fn watch_config(
+    conn: Connection,
+    mut rx: Receiver<String>,
+    mut location_rx: LocationReceiver,
+) {
+    let Ok(interface) = conn.object_server().interface::<_, Audio>(DBUS_PATH).await else {
+        return;
+    };
+
+    let mut location_open = true;
+
+    loop {
+        let quiet_hours = interface.get().await.config.quiet_hours;
+        crate::location::want("audio", quiet_hours == Some(Schedule::SunsetToSunrise));
+        let (active, next_transition) =
+            quiet_hours.map_or((false, None), |s| s.state(&SystemClock, &location_rx));
+        if QUIET_HOURS.swap(active, Ordering::Relaxed) != active {
+            let audio = interface.get().await;
+            _ = audio.quiet_hours_changed(interface.signal_context()).await;
+
+            if active {
+                let sink = DeviceKind::Sink.default_device();
+                let limit = match pactl::default_device_name(DeviceKind::Sink).await {
+                    Ok(name) => audio.config.volume_limit(&name),
+                    Err(_) => audio.config.quiet_hours_volume_limit,
+                };
+
+                if pactl::volume(DeviceKind::Sink, sink)
+                    .await
+                    .is_ok_and(|v| v > limit)
+                {
+                    if let Err(err) = pactl::set_volume(DeviceKind::Sink, sink, limit).await {
+                        eprintln!("Failed to lower the volume for quiet hours: {err}");
+                    }
+                }
+            }
+        }
+
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+
+                let mut audio = interface.get_mut().await;
+                let audio = &mut *audio;
+                let Some(helper) = audio.helper.as_ref() else {
+                    continue;
+                };
+
+                let changes = crate::config::update(&mut audio.config, helper, &key);
+                if changes.contains(&"mono") {
+                    audio.apply_mono().await;
+                }
+            }
+
+            res = location_rx.changed(), if location_open => {
+                location_open = res.is_ok();
+            }
+
+            _ = crate::schedule::sleep_until(next_transition) => (),
+        }
+    }
+}
+
+/// Identifies a set of devices by their sorted names.
+fn device_set_key(devices: &[pactl::Device]) -> String {
+    let mut names: Vec<&str> = devices
+        .iter()
+        .filter(|device| !device.is_monitor() && device.name != MONO_SINK)
+        .map(|device| device.name.as_str())
+        .collect();
+    names.sort_unstable();
+    names.join(";")
+}
+
+/// A switch to a newly connected device, which is undone when it is removed.
+struct Switch {
+    kind: DeviceKind,
+    index: u32,
+    previous: String,
+}
+
+/// Reacts to changes of the audio server's devices.
+struct Policy {
+    helper: Option<cosmic_config::Config>,
+    state: AudioState,
+    /// When a device was last added or removed.
+    devices_changed: Option<Instant>,
+    switches: Vec<Switch>,
+    /// Availability of the ports of each sink, used to detect jacks being plugged in.
+    port_availability: BTreeMap<u32, BTreeMap<String, bool>>,
+    /// Whether an application is recording from a source.
+    recording: bool,
+    /// Bluetooth cards switched to a headset profile for recording, with the profile to restore.
+    headset_profiles: BTreeMap<String, String>,
+    /// Device volumes which have not been saved yet.
+    device_volumes: BTreeMap<String, DeviceVolume>,
+    volumes_dirty: bool,
+    /// Devices which were added, to describe them to hooks after they are removed.
+    added_devices: BTreeMap<(DeviceKind, u32), pactl::Device>,
+}
+
+impl Policy {
+    fn new() -> Self {
+        let (helper, state) = match crate::config::load_state::<AudioState>(ID) {
+            Ok((helper, state)) => (Some(helper), state),
+            Err(err) => {
+                eprintln!("Failed to load audio state {err:?}");
+                (None, AudioState::default())
+            }
+        };
+
+        Self {
+            helper,
+            devices_changed: None,
+            switches: Vec::new(),
+            port_availability: BTreeMap::new(),
+            recording: false,
+            headset_profiles: BTreeMap::new(),
+            device_volumes: state.device_volumes.clone(),
+            volumes_dirty: false,
+            added_devices: BTreeMap::new(),
+            state,
+        }
+    }
+
+    fn preferred(&self, kind: DeviceKind) -> &BTreeMap<String, String> {
+        match kind {
+            DeviceKind::Sink => &self.state.preferred_sinks,
+            DeviceKind::Source => &self.state.preferred_sources,
+        }
+    }
+
+    /// Select the device the user chose the last time these devices were available.
+    ///
+    /// Returns `false` if the user never chose a device for this set of devices.
+    async fn restore_default(&self, kind: DeviceKind) -> bool {
+        let Ok(devices) = pactl::devices(kind).await else {
+            return false;
+        };
+
+        let Some(preferred) = self.preferred(kind).get(&device_set_key(&devices)) else {
+            return false;
+        };
+
+        if !devices.iter().any(|device| &device.name == preferred) {
+            return false;
+        }
+
+        if pactl::default_device_name(kind).await.ok().as_ref() == Some(preferred) {
+            return true;
+        }
+
+        if let Err(err) = pactl::set_default(kind, preferred).await {
+            eprintln!("Failed to restore default {kind:?} {preferred}: {err}");
+        }
+
+        true
+    }
+
+    /// Switch to a USB or Bluetooth device which was just connected.
+    async fn switch_to_new_device(&mut self, config: &AudioConfig, kind: DeviceKind, index: u32) {
+        let enabled = match kind {
+            DeviceKind::Sink => config.switch_on_connect,
+            DeviceKind::Source => config.switch_source_on_connect,
+        };
+
+        if !enabled {
+            return;
+        }
+
+        let Ok(devices) = pactl::devices(kind).await else {
+            return;
+        };
+
+        let Some(device) = devices.iter().find(|device| device.index == index) else {
+            return;
+        };
+
+        if device.is_monitor() || !device.is_external() {
+            return;
+        }
+
+        let Ok(previous) = pactl::default_device_name(kind).await else {
+            return;
+        };
+
+        if previous == device.name {
+            return;
+        }
+
+        if let Err(err) = pactl::set_default(kind, &device.name).await {
+            eprintln!("Failed to switch to {}: {err}", device.name);
+            return;
+        }
+
+        self.switches.retain(|switch| switch.kind != kind);
+        self.switches.push(Switch {
+            kind,
+            index,
+            previous: previous.clone(),
+        });
+
+        notify_switch(device.description.clone(), async move {
+            if let Err(err) = pactl::set_default(kind, &previous).await {
+                eprintln!("Failed to switch back to {previous}: {err}");
+            }
+        });
+    }
+
+    /// Return to the previous default device when a device which was switched to is removed.
+    async fn switch_back(&mut self, kind: DeviceKind, index: u32) {
+        let Some(pos) = self
+            .switches
+            .iter()
+            .position(|switch| switch.kind == kind && switch.index == index)
+        else {
+            return;
+        };
+
+        let switch = self.switches.remove(pos);
+        let Ok(devices) = pactl::devices(kind).await else {
+            return;
+        };
+
+        if devices.iter().any(|device| device.name == switch.previous) {
+            if let Err(err) = pactl::set_default(kind, &switch.previous).await {
+                eprintln!("Failed to switch back to {}: {err}", switch.previous);
+            }
+        }
+    }
+
+    /// Switch to the headphones port of a sink when a jack is plugged in.
+    async fn check_ports(&mut self, config: &AudioConfig, index: u32) {
+        let Ok(devices) = pactl::devices(DeviceKind::Sink).await else {
+            return;
+        };
+
+        let Some(sink) = devices.iter().find(|device| device.index == index) else {
+            return;
+        };
+
+        let availability: BTreeMap<String, bool> = sink
+            .ports
+            .iter()
+            .map(|port| (port.name.clone(), port.is_available()))
+            .collect();
+
+        let Some(previous) = self.port_availability.insert(index, availability) else {
+            return;
+        };
+
+        if !config.switch_on_connect {
+            return;
+        }
+
+        let Some(port) = sink.ports.iter().find(|port| {
+            port.is_headphones() && port.is_available() && previous.get(&port.name) == Some(&false)
+        }) else {
+            return;
+        };
+
+        let previous_port = sink.active_port.clone();
+        if previous_port.as_ref() != Some(&port.name) {
+            if let Err(err) = pactl::set_port(DeviceKind::Sink, &sink.name, &port.name).await {
+                eprintln!("Failed to switch to {}: {err}", port.name);
+                return;
+            }
+        }
+
+        let previous_default = pactl::default_device_name(DeviceKind::Sink).await.ok();
+        if previous_default.as_ref() != Some(&sink.name) {
+            if let Err(err) = pactl::set_default(DeviceKind::Sink, &sink.name).await {
+                eprintln!("Failed to switch to {}: {err}", sink.name);
+            }
+        }
+
+        let sink_name = sink.name.clone();
+        notify_switch(port.description.clone(), async move {
+            if let Some(port) = previous_port {
+                let _res = pactl::set_port(DeviceKind::Sink, &sink_name, &port).await;
+            }
+            if let Some(previous) = previous_default {
+                let _res = pactl::set_default(DeviceKind::Sink, &previous).await;
+            }
+        });
+    }
+
+    /// Remember the current default device for the set of available devices.
+    async fn record_default(&mut self, kind: DeviceKind) {
+        let (Ok(devices), Ok(default)) = (
+            pactl::devices(kind).await,
+            pactl::default_device_name(kind).await,
+        ) else {
+            return;
+        };
+
+        if default.is_empty() || default.ends_with(".monitor") || default == MONO_SINK {
+            return;
+        }
+
+        let key = device_set_key(&devices);
+        if self.preferred(kind).get(&key) == Some(&default) {
+            return;
+        }
+
+        let mut preferred = self.preferred(kind).clone();
+        preferred.insert(key, default);
+
+        let Some(helper) = self.helper.as_ref() else {
+            return;
+        };
+
+        let res = match kind {
+            DeviceKind::Sink => self.state.set_preferred_sinks(helper, preferred),
+            DeviceKind::Source => self.state.set_preferred_sources(helper, preferred),
+        };
+
+        if let Err(err) = res {
+            eprintln!("Failed to save the default {kind:?} {err:?}");
+        }
+    }
+
+    /// Lower the volume of a sink which was raised above its limit.
+    async fn enforce_volume_limit(&self, config: &AudioConfig, index: u32) {
+        let Ok(devices) = pactl::devices(DeviceKind::Sink).await else {
+            return;
+        };
+
+        let Some(sink) = devices.iter().find(|device| device.index == index) else {
+            return;
+        };
+
+        let limit = config.volume_limit(&sink.name);
+        let Ok(volume) = pactl::volume(DeviceKind::Sink, &sink.name).await else {
+            return;
+        };
+
+        if volume > limit {
+            if let Err(err) = pactl::set_volume(DeviceKind::Sink, &sink.name, limit).await {
+                eprintln!("Failed to limit the volume of {}: {err}", sink.name);
+            }
+        }
+    }
+
+    /// Switch Bluetooth headsets to a profile with a microphone while applications are recording.
+    async fn check_recording(&mut self, config: &AudioConfig) {
+        let (Ok(outputs), Ok(sources)) = (
+            pactl::source_outputs().await,
+            pactl::devices(DeviceKind::Source).await,
+        ) else {
+            return;
+        };
+
+        let recording = outputs.iter().any(|output| {
+            sources
+                .iter()
+                .find(|source| source.index == output.source)
+                .map_or(true, |source| !source.is_monitor())
+        });
+
+        if recording == self.recording {
+            return;
+        }
+
+        self.recording = recording;
+
+        if !recording {
+            for (card, profile) in std::mem::take(&mut self.headset_profiles) {
+                if let Err(err) = pactl::set_card_profile(&card, &profile).await {
+                    eprintln!("Failed to restore the {profile} profile of {card}: {err}");
+                }
+            }
+            return;
+        }
+
+        if !config.bluetooth_autoswitch {
+            return;
+        }
+
+        let (Ok(default_sink), Ok(sinks), Ok(cards)) = (
+            pactl::default_device_name(DeviceKind::Sink).await,
+            pactl::devices(DeviceKind::Sink).await,
+            pactl::cards().await,
+        ) else {
+            return;
+        };
+
+        let Some(card) = sinks
+            .iter()
+            .find(|sink| sink.name == default_sink)
+            .and_then(|sink| sink.card_name())
+            .and_then(|name| cards.iter().find(|card| card.name == name))
+            .filter(|card| card.name.starts_with("bluez_card."))
+        else {
+            return;
+        };
+
+        let address = card
+            .property("api.bluez5.address")
+            .or_else(|| card.property("device.string"));
+        if address.is_some_and(|address| {
+            config
+                .bluetooth_never_switch
+                .iter()
+                .any(|never| never.eq_ignore_ascii_case(address))
+        }) {
+            return;
+        }
+
+        if !card.active_profile.starts_with("a2dp") {
+            return;
+        }
+
+        let Some(profile) = HEADSET_PROFILES
+            .iter()
+            .find(|profile| card.profiles.get(**profile).is_some_and(|p| p.available))
+        else {
+            return;
+        };
+
+        if let Err(err) = pactl::set_card_profile(&card.name, profile).await {
+            eprintln!(
+                "Failed to switch {} to the {profile} profile: {err}",
+                card.name
+            );
+            return;
+        }
+
+        self.headset_profiles
+            .insert(card.name.clone(), card.active_profile.clone());
+    }
+
+    /// Record from the microphone of a headset which was switched to a headset profile.
+    async fn select_headset_source(&self, index: u32) {
+        if self.headset_profiles.is_empty() {
+            return;
+        }
+
+        let Ok(sources) = pactl::devices(DeviceKind::Source).await else {
+            return;
+        };
+
+        let Some(source) = sources.iter().find(|source| {
+            source.index == index
+                && source
+                    .card_name()
+                    .is_some_and(|card| self.headset_profiles.contains_key(card))
+        }) else {
+            return;
+        };
+
+        if let Err(err) = pactl::set_default(DeviceKind::Source, &source.name).await {
+            eprintln!("Failed to switch to {}: {err}", source.name);
+        }
+    }
+
+    /// Remember the volume of a device, to restore it when the device is connected again.
+    async fn remember_volume(&mut self, kind: DeviceKind, index: u32) {
+        let Ok(devices) = pactl::devices(kind).await else {
+            return;
+        };
+
+        let Some(device) = devices
+            .iter()
+            .find(|device| device.index == index && !device.is_monitor())
+        else {
+            return;
+        };
+
+        let Ok(volume) = pactl::volume(kind, &device.name).await else {
+            return;
+        };
+
+        let volume = DeviceVolume {
+            volume,
+            muted: device.mute,
+        };
+
+        if self.device_volumes.get(&device.name) != Some(&volume) {
+            self.device_volumes.insert(device.name.clone(), volume);
+            self.volumes_dirty = true;
+        }
+    }
+
+    /// Apply the remembered volume of a device which was connected.
+    async fn restore_volume(&self, kind: DeviceKind, index: u32) {
+        let Ok(devices) = pactl::devices(kind).await else {
+            return;
+        };
+
+        let Some((device, volume)) = devices
+            .iter()
+            .find(|device| device.index == index)
+            .and_then(|device| Some((device, self.device_volumes.get(&device.name)?)))
+        else {
+            return;
+        };
+
+        if let Err(err) = pactl::set_volume(kind, &device.name, volume.volume).await {
+            eprintln!("Failed to restore the volume of {}: {err}", device.name);
+        }
+
+        if device.mute != volume.muted {
+            if let Err(err) = pactl::set_mute(kind, &device.name, volume.muted).await {
+                eprintln!("Failed to restore the mute state of {}: {err}", device.name);
+            }
+        }
+    }
+
+    fn save_volumes(&mut self) {
+        self.volumes_dirty = false;
+        let Some(helper) = self.helper.as_ref() else {
+            return;
+        };
+
+        if let Err(err) = self
+            .state
+            .set_device_volumes(helper, self.device_volumes.clone())
+        {
+            eprintln!("Failed to save device volumes {err:?}");
+        }
+    }
+
+    /// Run the `audio-device-added` hooks for a device.
+    async fn device_added(&mut self, kind: DeviceKind, index: u32) {
+        let Ok(devices) = pactl::devices(kind).await else {
+            return;
+        };
+
+        let Some(device) = devices
+            .into_iter()
+            .find(|device| device.index == index && !device.is_monitor())
+        else {
+            return;
+        };
+
+        crate::hooks::run("audio-device-added", hook_env(kind, &device));
+        self.added_devices.insert((kind, index), device);
+    }
+
+    /// Run the `audio-device-removed` hooks for a device.
+    fn device_removed(&mut self, kind: DeviceKind, index: u32) {
+        if let Some(device) = self.added_devices.remove(&(kind, index)) {
+            crate::hooks::run("audio-device-removed", hook_env(kind, &device));
+        }
+    }
+
+    async fn handle(&mut self, event: pactl::Event, config: &AudioConfig) {
+        let kind = match event.facility {
+            Facility::Sink => DeviceKind::Sink,
+            Facility::Source => DeviceKind::Source,
+            Facility::Server => {
+                if self
+                    .devices_changed
+                    .is_some_and(|changed| changed.elapsed() < SETTLE_TIME)
+                {
+                    self.restore_default(DeviceKind::Sink).await;
+                    self.restore_default(DeviceKind::Source).await;
+                } else {
+                    self.record_default(DeviceKind::Sink).await;
+                    self.record_default(DeviceKind::Source).await;
+                }
+                return;
+            }
+            Facility::SourceOutput => {
+                if event.kind != EventKind::Change {
+                    self.check_recording(config).await;
+                }
+                return;
+            }
+            _ => return,
+        };
+
+        match (event.kind, event.index) {
+            (EventKind::New, Some(index)) => {
+                self.devices_changed = Some(Instant::now());
+                self.device_added(kind, index).await;
+                self.restore_volume(kind, index).await;
+                if !self.restore_default(kind).await {
+                    self.switch_to_new_device(config, kind, index).await;
+                }
+                if kind == DeviceKind::Source {
+                    self.select_headset_source(index).await;
+                }
+            }
+
+            (EventKind::Remove, Some(index)) => {
+                self.devices_changed = Some(Instant::now());
+                self.device_removed(kind, index);
+                self.switch_back(kind, index).await;
+                self.restore_default(kind).await;
+                if kind == DeviceKind::Sink {
+                    self.port_availability.remove(&index);
+                }
+            }
+
+            (EventKind::Change, Some(index)) => self.remember_volume(kind, index).await,
+
+            _ => (),
+        }
+
+        if kind == DeviceKind::Sink && event.kind != EventKind::Remove {
+            if let Some(index) = event.index {
+                self.enforce_volume_limit(config, index).await;
+                self.check_ports(config, index).await;
+            }
+        }
+    }
+}
+
+/// Environment describing a device to audio device hooks.
+fn hook_env(kind: DeviceKind, device: &pactl::Device) -> Vec<(&'static str, String)> {
+    let property = |key| device.property(key).unwrap_or_default().to_owned();
+
+    vec![
+        ("COSMIC_AUDIO_DEVICE_CLASS", kind.as_str().to_owned()),
+        ("COSMIC_AUDIO_DEVICE_NAME", device.name.clone()),
+        (
+            "COSMIC_AUDIO_DEVICE_DESCRIPTION",
+            device.description.clone(),
+        ),
+        ("COSMIC_AUDIO_DEVICE_BUS", property("device.bus")),
+        (
+            "COSMIC_AUDIO_DEVICE_FORM_FACTOR",
+            property("device.form_factor"),
+        ),
+        (
+            "COSMIC_AUDIO_CARD",
+            device.card_name().unwrap_or_default().to_owned(),
+        ),
+    ]
+}
+
+/// Tell the user about an automatic switch, offering to undo it.
+fn notify_switch(description: String, undo: impl Future<Output = ()> + 'static) {
+    tokio::task::spawn_local(async move {
+        let Ok(handle) = Notification::new()
+            .appname("")
+            .summary("Audio Device Connected")
+            .body(&format!("Switched to {description}."))
+            .icon("audio-headphones-symbolic")
+            .action("undo", "Undo")
+            .timeout(Duration::from_secs(10))
+            .show_async()
+            .await
+        else {
+            return;
+        };
+
+        if crate::notifications::wait_for_action(handle)
+            .await
+            .as_deref()
+            == Some("undo")
+        {
+            undo.await;
+        }
+    });
+}
+
+/// Apply the audio policy as devices come and go, and keep the properties of
+/// the audio interface in sync with the audio server.
+pub async fn policy(conn: Connection) {
+    let Ok(interface) = conn.object_server().interface::<_, Audio>(DBUS_PATH).await else {
+        return;
+    };
+
+    let mut policy = Policy::new();
+
+    loop {
+        match pactl::subscribe() {
+            Ok(mut events) => {
+                let mut save_at: Option<tokio::time::Instant> = None;
+
+                // The mono sink is gone if the audio server was restarted.
+                interface.get_mut().await.apply_mono().await;
+
+                loop {
+                    tokio::select! {
+                        event = events.recv() => {
+                            let Some(event) = event else {
+                                break;
+                            };
+
+                            let config = interface.get().await.config.clone();
+                            policy.handle(event, &config).await;
+
+                            if policy.volumes_dirty && save_at.is_none() {
+                                save_at = Some(tokio::time::Instant::now() + VOLUME_SAVE_DELAY);
+                            }
+
+                            if matches!(event.facility, Facility::Sink | Facility::Server) {
+                                interface.get_mut().await.apply_mono().await;
+                            }
+
+                            if matches!(
+                                event.facility,
+                                Facility::Sink | Facility::Source | Facility::Server
+                            ) {
+                                interface
+                                    .get_mut()
+                                    .await
+                                    .sync(interface.signal_context())
+                                    .await;
+                            }
+                        }
+
+                        _ = tokio::time::sleep_until(save_at.unwrap_or_else(tokio::time::Instant::now)), if save_at.is_some() => {
+                            save_at = None;
+                            policy.save_volumes();
+                        }
+                    }
+                }
+
+                if policy.volumes_dirty {
+                    policy.save_volumes();
+                }
+            }
+            Err(err) => eprintln!("Failed to subscribe to audio server events: {err}"),
+        }
+
+        // The audio server was restarted, or is not running yet.
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+}
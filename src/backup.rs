@@ -0,0 +1,274 @@
+use std::{
+    collections::BTreeSet,
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use walkdir::WalkDir;
+
+/// Configs in a bundle, by ID or by prefix of the ID ending in `.`.
+const TREES: &[&str] = &[
+    // Theme
+    "com.system76.CosmicTheme.",
+    "com.system76.CosmicTk",
+    // Input, among the rest of the compositor config
+    "com.system76.CosmicComp",
+    // Power and idle
+    "com.system76.CosmicIdle",
+    "com.system76.CosmicSettingsDaemon.",
+    // Shortcuts
+    "com.system76.CosmicSettings.Shortcuts",
+];
+
+/// Pre-import backups older than the newest few are removed.
+const KEEP_BACKUPS: usize = 5;
+
+fn is_bundled(id: &str) -> bool {
+    TREES.iter().any(|tree| match tree.strip_suffix('.') {
+        Some(prefix) => id
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('.')),
+        None => id == *tree,
+    })
+}
+
+fn cosmic_config_dir() -> io::Result<PathBuf> {
+    dirs::config_dir()
+        .map(|dir| dir.join("cosmic"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))
+}
+
+fn backup_dir() -> Option<PathBuf> {
+    dirs::state_dir().map(|dir| dir.join("cosmic-settings-daemon/backups"))
+}
+
+fn failed(err: impl std::fmt::Display) -> zbus::fdo::Error {
+    zbus::fdo::Error::Failed(err.to_string())
+}
+
+async fn tar(args: &[&OsStr]) -> io::Result<()> {
+    let output = tokio::process::Command::new("tar")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .await?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ))
+    }
+}
+
+/// Walking and writing the config trees runs on a blocking thread.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> io::Result<T> + Send + 'static,
+) -> io::Result<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .unwrap_or_else(|err| Err(io::Error::new(io::ErrorKind::Other, err)))
+}
+
+/// Write the bundled configs to a compressed tar archive at `path`.
+async fn export(path: &Path) -> io::Result<()> {
+    let config_dir = cosmic_config_dir()?;
+    let ids: BTreeSet<String> = fs::read_dir(&config_dir)?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|id| is_bundled(id))
+        .collect();
+
+    if ids.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no settings to export",
+        ));
+    }
+
+    let mut args: Vec<&OsStr> = vec![
+        "-czf".as_ref(),
+        path.as_os_str(),
+        "-C".as_ref(),
+        config_dir.as_os_str(),
+        "--".as_ref(),
+    ];
+    args.extend(ids.iter().map(|id| id.as_ref()));
+    tar(&args).await
+}
+
+/// Check that an extracted bundle only has keys of the bundled configs, as
+/// `<id>/v<version>/<key>`, and return the relative paths of the keys.
+fn validate(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let mut keys = Vec::new();
+
+    for entry in WalkDir::new(dir).min_depth(1) {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+        let components: Vec<&str> = relative.iter().filter_map(|c| c.to_str()).collect();
+
+        if let Some(id) = components.first() {
+            if !is_bundled(id) {
+                return Err(invalid(format!("{id} is not a settings config")));
+            }
+        }
+
+        if let Some(version) = components.get(1) {
+            if version
+                .strip_prefix('v')
+                .and_then(|v| v.parse::<u64>().ok())
+                .is_none()
+            {
+                return Err(invalid(format!(
+                    "{} is not a config version",
+                    relative.display()
+                )));
+            }
+        }
+
+        let file_type = entry.file_type();
+        match components.len() {
+            1 | 2 if file_type.is_dir() => (),
+            3 if file_type.is_file() => {
+                if fs::read_to_string(entry.path()).is_err() {
+                    return Err(invalid(format!("{} is not text", relative.display())));
+                }
+                keys.push(relative.to_owned());
+            }
+            _ => return Err(invalid(format!("unexpected entry {}", relative.display()))),
+        }
+    }
+
+    if keys.is_empty() {
+        return Err(invalid("the bundle has no settings".to_owned()));
+    }
+
+    Ok(keys)
+}
+
+/// Replace the configs of the bundle extracted to `from` with its keys.
+///
+/// Keys are written one at a time, so that the config watchers of running applications see the
+/// changes. Keys missing from the bundle are removed from the versions it has.
+fn restore(from: &Path, keys: &[PathBuf], config_dir: &Path) -> io::Result<()> {
+    let versions: BTreeSet<&Path> = keys.iter().filter_map(|key| key.parent()).collect();
+
+    for version in versions {
+        let Ok(entries) = fs::read_dir(config_dir.join(version)) else {
+            continue;
+        };
+
+        for entry in entries.filter_map(Result::ok) {
+            let key = version.join(entry.file_name());
+            if entry.file_type().is_ok_and(|t| t.is_file()) && !keys.contains(&key) {
+                fs::remove_file(entry.path())?;
+            }
+        }
+    }
+
+    // Unchanged keys are not written, so that their watchers are not woken.
+    for key in keys {
+        let contents = fs::read_to_string(from.join(key))?;
+        crate::files::write_atomic(&config_dir.join(key), &contents)?;
+    }
+
+    Ok(())
+}
+
+/// Back up the current settings, keeping only the newest backups.
+async fn backup() -> io::Result<PathBuf> {
+    let dir = backup_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no state directory"))?;
+    fs::create_dir_all(&dir)?;
+
+    let name = format!(
+        "settings-{}.tar.gz",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    );
+    let path = dir.join(name);
+    export(&path).await?;
+
+    let mut backups: Vec<PathBuf> = fs::read_dir(&dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "gz"))
+        .collect();
+    backups.sort();
+
+    let old = backups.len().saturating_sub(KEEP_BACKUPS);
+    for path in &backups[..old] {
+        let _res = fs::remove_file(path);
+    }
+
+    Ok(path)
+}
+
+/// Validate the bundle at `path` and restore it, after backing up the current settings.
+async fn import(path: &Path) -> io::Result<PathBuf> {
+    let config_dir = cosmic_config_dir()?;
+    let staging = config_dir.with_file_name(format!(".cosmic-import-{}", std::process::id()));
+    let _res = fs::remove_dir_all(&staging);
+    fs::create_dir_all(&staging)?;
+
+    let res = async {
+        tar(&[
+            "-xzf".as_ref(),
+            path.as_os_str(),
+            "-C".as_ref(),
+            staging.as_os_str(),
+            "--no-same-owner".as_ref(),
+            "--no-same-permissions".as_ref(),
+        ])
+        .await?;
+
+        let keys = blocking({
+            let staging = staging.clone();
+            move || validate(&staging)
+        })
+        .await?;
+
+        // Nothing is lost if there are no settings yet.
+        let backup = match backup().await {
+            Ok(backup) => backup,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => PathBuf::new(),
+            Err(err) => return Err(err),
+        };
+
+        blocking({
+            let staging = staging.clone();
+            let config_dir = config_dir.clone();
+            move || restore(&staging, &keys, &config_dir)
+        })
+        .await?;
+        Ok(backup)
+    }
+    .await;
+
+    let _res = fs::remove_dir_all(&staging);
+    res
+}
+
+/// Bundles of the theme, input, power, and shortcut settings, for moving them to another
+/// machine or returning to a known state.
+pub struct Backup;
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.Backup")]
+impl Backup {
+    /// Write the settings to a compressed tar archive at `path`.
+    async fn export_settings(&self, path: &str) -> zbus::fdo::Result<()> {
+        export(Path::new(path)).await.map_err(failed)
+    }
+
+    /// Restore the settings of an archive written by `ExportSettings`, returning the path of
+    /// the backup of the previous settings, or an empty path if there were none.
+    async fn import_settings(&self, path: &str) -> zbus::fdo::Result<String> {
+        let backup = import(Path::new(path)).await.map_err(failed)?;
+        Ok(backup.display().to_string())
+    }
+}
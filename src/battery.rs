@@ -1,16 +1,51 @@
 use acpid_plug::AcPlugEvents;
 use notify_rust::Notification;
+use std::collections::BTreeMap;
+use std::time::Duration;
 use std::time::Instant;
-use std::{path::Path, time::Duration};
 use tokio::sync::mpsc::Sender;
 use tokio::sync::mpsc::{error::TryRecvError, Receiver};
+use tokio::task::JoinHandle;
 use tokio_stream::StreamExt;
-use upower_dbus::BatteryLevel;
-use zbus::Connection;
+use upower_dbus::{BatteryLevel, BatteryState, BatteryType, DeviceProxy};
+use zbus::{zvariant::ObjectPath, Connection};
+
+use crate::sounds::{self, Event};
+
+/// The `WarningLevel` of a UPower device at which the low battery warning is shown, which UPower
+/// reaches at the `PercentageLow` of its config.
+const WARNING_LEVEL_LOW: u32 = 3;
+
+/// The `WarningLevel` from which the battery is critical, which UPower reaches at the
+/// `PercentageCritical` of its config. The next level, at `PercentageAction`, is when UPower
+/// takes its critical power action.
+const WARNING_LEVEL_CRITICAL: u32 = 4;
+
+#[zbus::dbus_proxy(
+    default_service = "org.freedesktop.UPower",
+    default_path = "/org/freedesktop/UPower",
+    interface = "org.freedesktop.UPower"
+)]
+trait UPowerDevices {
+    /// What UPower does once the batteries run out, such as `PowerOff` or `Hibernate`.
+    fn get_critical_action(&self) -> zbus::Result<String>;
+
+    #[dbus_proxy(signal)]
+    fn device_removed(&self, device: ObjectPath<'_>) -> zbus::Result<()>;
+}
 
-// TODO: Add config parameter for changing the preferred sound theme.
+#[zbus::dbus_proxy(
+    default_service = "org.freedesktop.UPower",
+    interface = "org.freedesktop.UPower.Device"
+)]
+trait WarningLevel {
+    #[dbus_proxy(property)]
+    fn warning_level(&self) -> zbus::Result<u32>;
+}
 
 pub async fn monitor() {
+    tokio::task::spawn_local(ups_monitor());
+
     let Ok(ac_plug_events) = acpid_plug::connect().await else {
         return;
     };
@@ -51,7 +86,7 @@ pub async fn ac_plug_monitor(
 }
 
 pub async fn low_power_monitor(mut ac_plugged: bool, mut ac_plug_rx: Receiver<acpid_plug::Event>) {
-    let Ok(conn) = Connection::system().await else {
+    let Ok(conn) = crate::bus::system().await else {
         return;
     };
 
@@ -125,7 +160,7 @@ pub async fn low_power_monitor(mut ac_plugged: bool, mut ac_plug_rx: Receiver<ac
                             }
 
                             current_battery = BatteryLevel::Low;
-                            crate::pipewire::play_sound("Pop", "battery-caution");
+                            sounds::play(Event::BatteryCaution);
 
                             let now = Instant::now();
                             if now.duration_since(last_low_notification) > Duration::from_secs(5) {
@@ -144,7 +179,7 @@ pub async fn low_power_monitor(mut ac_plugged: bool, mut ac_plug_rx: Receiver<ac
 
                         percent if percent == 100.0 => {
                             current_battery = BatteryLevel::Full;
-                            crate::pipewire::play_sound("Pop", "battery-full");
+                            sounds::play(Event::BatteryFull);
                         }
 
                         _ => current_battery = BatteryLevel::Normal,
@@ -167,7 +202,7 @@ async fn critical_battery_nag(mut watch: Receiver<bool>) {
                     _ => break,
                 }
 
-                crate::pipewire::play_sound("Pop", "battery-low");
+                sounds::play(Event::BatteryLow);
             },
             Some(false) => (),
             None => break,
@@ -177,15 +212,273 @@ async fn critical_battery_nag(mut watch: Receiver<bool>) {
 
 /// Play a power plug sound on an AC plug event.
 fn on_ac_plug(event: acpid_plug::Event, battery_level: BatteryLevel) {
-    let (theme, sound) = if matches!(event, acpid_plug::Event::Plugged) {
-        ("freedesktop", "power-plug")
-    } else if Path::new("/usr/share/sounds/Pop/").exists()
-        && matches!(battery_level, BatteryLevel::Low | BatteryLevel::Critical)
-    {
-        ("Pop", "power-unplug-battery-low")
+    sounds::play(if matches!(event, acpid_plug::Event::Plugged) {
+        Event::PowerPlug
+    } else if matches!(battery_level, BatteryLevel::Low | BatteryLevel::Critical) {
+        Event::PowerUnplugBatteryLow
     } else {
-        ("freedesktop", "power-unplug")
+        Event::PowerUnplug
+    });
+}
+
+/// Watch UPower for UPS devices, and warn when the system is running on UPS power.
+pub async fn ups_monitor() {
+    let Ok(conn) = crate::bus::system().await else {
+        return;
+    };
+
+    let (Ok(upower), Ok(devices)) = (
+        upower_dbus::UPowerProxy::new(&conn).await,
+        UPowerDevicesProxy::new(&conn).await,
+    ) else {
+        return;
+    };
+
+    let (Ok(mut device_added_stream), Ok(mut device_removed_stream)) = (
+        upower.receive_device_added().await,
+        devices.receive_device_removed().await,
+    ) else {
+        return;
     };
 
-    crate::pipewire::play_sound(theme, sound);
+    // The monitor of each UPS, by the path of its device, which is stopped when it is removed.
+    let mut monitors: BTreeMap<String, JoinHandle<()>> = BTreeMap::new();
+
+    if let Ok(paths) = upower.enumerate_devices().await {
+        for path in paths {
+            let path = path.into_inner();
+            if let Some(monitor) = ups_device(&conn, &devices, path.clone()).await {
+                monitors.insert(path.to_string(), monitor);
+            }
+        }
+    }
+
+    loop {
+        tokio::select! {
+            signal = device_added_stream.next() => {
+                let Some(signal) = signal else {
+                    break;
+                };
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+
+                let path = args.device().to_owned();
+                if let Some(monitor) = ups_device(&conn, &devices, path.clone()).await {
+                    if let Some(previous) = monitors.insert(path.to_string(), monitor) {
+                        previous.abort();
+                    }
+                }
+            }
+
+            signal = device_removed_stream.next() => {
+                let Some(signal) = signal else {
+                    break;
+                };
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+
+                if let Some(monitor) = monitors.remove(args.device().as_str()) {
+                    monitor.abort();
+                }
+            }
+        }
+    }
+
+    for monitor in monitors.into_values() {
+        monitor.abort();
+    }
+}
+
+/// Start monitoring the device at the given path if it is a UPS.
+async fn ups_device(
+    conn: &Connection,
+    devices: &UPowerDevicesProxy<'static>,
+    path: ObjectPath<'static>,
+) -> Option<JoinHandle<()>> {
+    let device = DeviceProxy::builder(conn)
+        .path(path.clone())
+        .ok()?
+        .build()
+        .await
+        .ok()?;
+
+    if device.type_().await.ok()? != BatteryType::Ups {
+        return None;
+    }
+
+    let warning_level = WarningLevelProxy::builder(conn)
+        .path(path)
+        .ok()?
+        .build()
+        .await
+        .ok()?;
+
+    Some(tokio::task::spawn_local(ups_device_monitor(
+        device,
+        warning_level,
+        devices.clone(),
+    )))
+}
+
+/// Notify when mains power is lost, and warn at the low and critical levels of
+/// UPower's config as the UPS runs down while the system is running on it.
+async fn ups_device_monitor(
+    device: DeviceProxy<'static>,
+    warning_level: WarningLevelProxy<'static>,
+    devices: UPowerDevicesProxy<'static>,
+) {
+    let mut state_changed_stream = device.receive_state_changed().await;
+    let mut level_changed_stream = warning_level.receive_warning_level_changed().await;
+
+    let (nag_tx, nag_rx) = tokio::sync::mpsc::channel(1);
+    tokio::task::spawn_local(critical_battery_nag(nag_rx));
+
+    let mut on_ups = false;
+    let mut current_battery = BatteryLevel::Normal;
+
+    if let Ok(state) = device.state().await {
+        on_ups = is_discharging(state);
+        if on_ups {
+            on_ups_power_lost().await;
+            if let Ok(level) = warning_level.warning_level().await {
+                current_battery =
+                    on_ups_warning_level(level, current_battery, &nag_tx, &devices).await;
+            }
+        }
+    }
+
+    loop {
+        tokio::select! {
+            result = state_changed_stream.next() => {
+                let Some(message) = result else {
+                    break
+                };
+
+                let Ok(state) = message.get().await else {
+                    continue
+                };
+
+                if is_discharging(state) == on_ups {
+                    continue
+                }
+
+                on_ups = !on_ups;
+
+                if on_ups {
+                    on_ups_power_lost().await;
+                    if let Ok(level) = warning_level.warning_level().await {
+                        current_battery =
+                            on_ups_warning_level(level, current_battery, &nag_tx, &devices).await;
+                    }
+                } else {
+                    current_battery = BatteryLevel::Normal;
+                    let _res = nag_tx.send(false).await;
+                    sounds::play(Event::PowerPlug);
+
+                    let _res = Notification::new()
+                        .appname("")
+                        .summary("Mains Power Restored")
+                        .body("The system is no longer running on UPS power.")
+                        .icon("ac-adapter-symbolic")
+                        .urgency(notify_rust::Urgency::Normal)
+                        .timeout(Duration::from_secs(5))
+                        .show_async()
+                        .await;
+                }
+            },
+
+            result = level_changed_stream.next() => {
+                let Some(message) = result else {
+                    break
+                };
+
+                if !on_ups {
+                    continue
+                }
+
+                if let Ok(level) = message.get().await {
+                    current_battery =
+                        on_ups_warning_level(level, current_battery, &nag_tx, &devices).await;
+                }
+            }
+        }
+    }
+}
+
+fn is_discharging(state: BatteryState) -> bool {
+    matches!(state, BatteryState::Discharging | BatteryState::Empty)
+}
+
+async fn on_ups_power_lost() {
+    sounds::play(Event::PowerUnplug);
+
+    let _res = Notification::new()
+        .appname("")
+        .summary("On UPS Power")
+        .body("Mains power was lost. Save your work in case the outage continues.")
+        .icon("battery-caution-symbolic")
+        .urgency(notify_rust::Urgency::Normal)
+        .timeout(Duration::from_secs(10))
+        .show_async()
+        .await;
+}
+
+/// What the system does once the UPS runs out, for the critical warning.
+fn critical_action_body(action: &str) -> &'static str {
+    match action {
+        "Hibernate" => "The system will hibernate soon unless mains power is restored.",
+        "HybridSleep" | "Suspend" => "The system will suspend soon unless mains power is restored.",
+        "Ignore" => "The system will lose power soon unless mains power is restored.",
+        _ => "The system will shut down soon unless mains power is restored.",
+    }
+}
+
+/// Escalate warnings as the UPS warning level rises, returning the new battery level.
+async fn on_ups_warning_level(
+    level: u32,
+    current_battery: BatteryLevel,
+    nag_tx: &Sender<bool>,
+    devices: &UPowerDevicesProxy<'_>,
+) -> BatteryLevel {
+    if level >= WARNING_LEVEL_CRITICAL {
+        if current_battery != BatteryLevel::Critical {
+            let _res = nag_tx.send(true).await;
+            let action = devices.get_critical_action().await.unwrap_or_default();
+            let _res = Notification::new()
+                .appname("")
+                .summary("UPS Battery Critical")
+                .body(critical_action_body(&action))
+                .icon("dialog-warning-symbolic")
+                .urgency(notify_rust::Urgency::Critical)
+                .timeout(Duration::from_secs(30))
+                .show_async()
+                .await;
+        }
+
+        BatteryLevel::Critical
+    } else if level == WARNING_LEVEL_LOW {
+        if current_battery == BatteryLevel::Critical {
+            let _res = nag_tx.send(false).await;
+        } else if current_battery != BatteryLevel::Low {
+            sounds::play(Event::BatteryCaution);
+            let _res = Notification::new()
+                .appname("")
+                .summary("UPS Battery Low")
+                .icon("dialog-warning-symbolic")
+                .urgency(notify_rust::Urgency::Normal)
+                .timeout(Duration::from_secs(5))
+                .show_async()
+                .await;
+        }
+
+        BatteryLevel::Low
+    } else {
+        if current_battery == BatteryLevel::Critical {
+            let _res = nag_tx.send(false).await;
+        }
+
+        BatteryLevel::Normal
+    }
 }
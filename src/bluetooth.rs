@@ -0,0 +1,249 @@
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+use tokio_stream::StreamExt;
+use zbus::{fdo::ObjectManagerProxy, zvariant::OwnedObjectPath, Connection};
+
+use crate::{
+    bluez::{AdapterProxy, DeviceProxy},
+    power_profiles::PowerProfilesProxy,
+};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Bluetooth";
+
+const ADAPTER_INTERFACE: &str = "org.bluez.Adapter1";
+const DEVICE_INTERFACE: &str = "org.bluez.Device1";
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct BluetoothConfig {
+    /// Power Bluetooth on or off at login, as it was at the end of the last session.
+    pub restore_at_login: bool,
+    /// Power Bluetooth off in the power saver profile, unless a device is connected.
+    pub off_in_power_saver: bool,
+}
+
+impl Default for BluetoothConfig {
+    fn default() -> Self {
+        Self {
+            restore_at_login: true,
+            off_in_power_saver: false,
+        }
+    }
+}
+
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct BluetoothState {
+    /// Whether Bluetooth was powered at the end of the last session, if it was saved.
+    pub powered: Option<bool>,
+}
+
+async fn bluez_manager(conn: &Connection) -> zbus::Result<ObjectManagerProxy<'static>> {
+    ObjectManagerProxy::builder(conn)
+        .destination("org.bluez")?
+        .path("/")?
+        .build()
+        .await
+}
+
+async fn objects_with(conn: &Connection, interface: &str) -> zbus::Result<Vec<OwnedObjectPath>> {
+    Ok(bluez_manager(conn)
+        .await?
+        .get_managed_objects()
+        .await?
+        .into_iter()
+        .filter(|(_, interfaces)| interfaces.keys().any(|name| name.as_str() == interface))
+        .map(|(path, _)| path)
+        .collect())
+}
+
+async fn adapter(conn: &Connection, path: OwnedObjectPath) -> zbus::Result<AdapterProxy<'static>> {
+    AdapterProxy::builder(conn).path(path)?.build().await
+}
+
+async fn adapters(conn: &Connection) -> zbus::Result<Vec<AdapterProxy<'static>>> {
+    let mut adapters = Vec::new();
+    for path in objects_with(conn, ADAPTER_INTERFACE).await? {
+        adapters.push(adapter(conn, path).await?);
+    }
+    Ok(adapters)
+}
+
+async fn any_powered(conn: &Connection) -> zbus::Result<bool> {
+    for adapter in adapters(conn).await? {
+        if adapter.powered().await? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+async fn any_connected(conn: &Connection) -> zbus::Result<bool> {
+    for path in objects_with(conn, DEVICE_INTERFACE).await? {
+        let device = DeviceProxy::builder(conn).path(path)?.build().await?;
+        if device.connected().await? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+async fn set_powered(adapter: &AdapterProxy<'_>, powered: bool) {
+    if adapter.powered().await.is_ok_and(|p| p == powered) {
+        return;
+    }
+
+    if let Err(err) = adapter.set_powered(powered).await {
+        eprintln!(
+            "Failed to power Bluetooth {}: {err}",
+            if powered { "on" } else { "off" }
+        );
+    }
+}
+
+/// Power all adapters on or off, returning how many there are.
+async fn set_all_powered(conn: &Connection, powered: bool) -> usize {
+    match adapters(conn).await {
+        Ok(adapters) => {
+            for adapter in &adapters {
+                set_powered(adapter, powered).await;
+            }
+            adapters.len()
+        }
+        Err(err) => {
+            eprintln!("Failed to list Bluetooth adapters: {err}");
+            0
+        }
+    }
+}
+
+/// Remember whether Bluetooth is powered, to restore it at the next login.
+pub async fn logout() {
+    let Ok(conn) = crate::bus::system().await else {
+        return;
+    };
+
+    let Ok(powered) = any_powered(&conn).await else {
+        return;
+    };
+
+    match crate::config::load_state::<BluetoothState>(ID) {
+        Ok((helper, state)) => {
+            if let Err(err) = state.set_powered(&helper, Some(powered)) {
+                eprintln!("Failed to save Bluetooth state {err:?}");
+            }
+        }
+        Err(err) => eprintln!("Failed to load Bluetooth state {err:?}"),
+    }
+}
+
+/// Restore the Bluetooth power state of the last session, and power it off in the power
+/// saver profile if enabled.
+pub async fn run(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<BluetoothConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load Bluetooth config {err:?}");
+            return;
+        }
+    };
+
+    let restore = match crate::config::load_state_async::<BluetoothState>(ID).await {
+        Ok((_, state)) => state.powered.filter(|_| config.restore_at_login),
+        Err(err) => {
+            eprintln!("Failed to load Bluetooth state {err:?}");
+            None
+        }
+    };
+
+    let Ok(conn) = crate::bus::system().await else {
+        return;
+    };
+
+    let Ok(manager) = bluez_manager(&conn).await else {
+        return;
+    };
+
+    let Ok(mut adapters_added) = manager.receive_interfaces_added().await else {
+        return;
+    };
+
+    // Kept until an adapter is found, since it may only appear after the session started.
+    let mut restore = restore;
+    if let Some(powered) = restore {
+        if set_all_powered(&conn, powered).await > 0 {
+            restore = None;
+        }
+    }
+
+    let power_profiles = PowerProfilesProxy::new(&conn).await.ok();
+    let mut profile_changes = match power_profiles.as_ref() {
+        Some(proxy) => Some(proxy.receive_active_profile_changed().await),
+        None => None,
+    };
+
+    // Set when Bluetooth was powered off for the power saver profile, to power it back on after.
+    let mut powered_off = false;
+
+    loop {
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                crate::config::update(&mut config, &helper, &key);
+            }
+
+            signal = adapters_added.next() => {
+                let Some(signal) = signal else {
+                    break;
+                };
+
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+
+                let is_adapter = args
+                    .interfaces_and_properties()
+                    .keys()
+                    .any(|name| name.as_str() == ADAPTER_INTERFACE);
+
+                if !is_adapter {
+                    continue;
+                }
+
+                let powered = if powered_off { Some(false) } else { restore.take() };
+                if let Some(powered) = powered {
+                    let path = args.object_path().to_owned().into();
+                    if let Ok(adapter) = adapter(&conn, path).await {
+                        set_powered(&adapter, powered).await;
+                    }
+                }
+            }
+
+            change = async { profile_changes.as_mut()?.next().await }, if profile_changes.is_some() => {
+                let Some(change) = change else {
+                    profile_changes = None;
+                    continue;
+                };
+
+                let Ok(profile) = change.get().await else {
+                    continue;
+                };
+
+                if profile == "power-saver" {
+                    if config.off_in_power_saver
+                        && !any_connected(&conn).await.unwrap_or(true)
+                        && any_powered(&conn).await.unwrap_or(false)
+                    {
+                        set_all_powered(&conn, false).await;
+                        powered_off = true;
+                    }
+                } else if std::mem::take(&mut powered_off) {
+                    set_all_powered(&conn, true).await;
+                }
+            }
+        }
+    }
+}
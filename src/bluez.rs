@@ -0,0 +1,19 @@
+use zbus::zvariant::OwnedObjectPath;
+
+#[zbus::dbus_proxy(default_service = "org.bluez", interface = "org.bluez.Adapter1")]
+trait Adapter {
+    #[dbus_proxy(property)]
+    fn powered(&self) -> zbus::Result<bool>;
+
+    #[dbus_proxy(property)]
+    fn set_powered(&self, powered: bool) -> zbus::Result<()>;
+}
+
+#[zbus::dbus_proxy(default_service = "org.bluez", interface = "org.bluez.Device1")]
+trait Device {
+    #[dbus_proxy(property)]
+    fn adapter(&self) -> zbus::Result<OwnedObjectPath>;
+
+    #[dbus_proxy(property)]
+    fn connected(&self) -> zbus::Result<bool>;
+}
@@ -0,0 +1,214 @@
+use std::time::Duration;
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::mpsc::Receiver, time::Instant};
+use tokio_stream::StreamExt;
+use zbus::{Connection, PropertyStream};
+
+use crate::{LogindSessionProxy, SettingsDaemon, DBUS_PATH};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Breaks";
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct BreaksConfig {
+    /// Remind the user to take breaks.
+    pub enabled: bool,
+    /// Minutes of activity between breaks.
+    pub interval_minutes: u64,
+    /// Length of a break in seconds. Being idle for this long counts as a break.
+    pub duration_seconds: u64,
+    /// Dim the display during a break.
+    pub dim_screen: bool,
+}
+
+impl Default for BreaksConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_minutes: 50,
+            duration_seconds: 20,
+            dim_screen: false,
+        }
+    }
+}
+
+impl crate::demand::Feature for BreaksConfig {
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// Halve the display brightness for the length of a break, unless it was changed in the meantime.
+async fn dim(conn: &Connection, duration: Duration) {
+    let Ok(interface) = conn
+        .object_server()
+        .interface::<_, SettingsDaemon>(DBUS_PATH)
+        .await
+    else {
+        return;
+    };
+
+    let brightness = interface.get().await.display_brightness().await;
+    if brightness <= 0 {
+        return;
+    }
+
+    let dimmed = (brightness / 2).max(1);
+    interface.get().await.set_display_brightness(dimmed).await;
+    tokio::time::sleep(duration).await;
+
+    let daemon = interface.get().await;
+    if daemon.display_brightness().await == dimmed {
+        daemon.set_display_brightness(brightness).await;
+    }
+}
+
+async fn remind(conn: Connection, config: &BreaksConfig) {
+    let duration = Duration::from_secs(config.duration_seconds);
+
+    let _res = Notification::new()
+        .appname("")
+        .summary("Time for a Break")
+        .body(&format!(
+            "Look away from the screen for {} seconds.",
+            config.duration_seconds
+        ))
+        .icon("preferences-desktop-screensaver-symbolic")
+        .urgency(notify_rust::Urgency::Normal)
+        .timeout(duration)
+        .show_async()
+        .await;
+
+    if config.dim_screen {
+        tokio::task::spawn_local(async move { dim(&conn, duration).await });
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Activity {
+    Active,
+    /// Idle or locked.
+    Idle,
+    /// Do not disturb is enabled, or a window is fullscreen.
+    Paused,
+}
+
+async fn activity(logind_session: Option<&LogindSessionProxy<'_>>) -> Activity {
+    // The lock hint is cached, and updated by its change signal.
+    let is_locked = match logind_session {
+        Some(session) => session.locked_hint().await.unwrap_or(false),
+        None => false,
+    };
+    let is_idle = is_locked || crate::idle::session_idle_since().is_some();
+
+    if is_idle {
+        Activity::Idle
+    } else if crate::dnd::is_active() || crate::dnd::fullscreen() {
+        Activity::Paused
+    } else {
+        Activity::Active
+    }
+}
+
+/// Wait for the next change of a property, or forever without the property.
+async fn changed(stream: &mut Option<PropertyStream<'_, bool>>) {
+    if let Some(changes) = stream {
+        if changes.next().await.is_some() {
+            return;
+        }
+    }
+
+    *stream = None;
+    std::future::pending().await
+}
+
+/// Remind the user to take a break after a period of activity.
+///
+/// Time spent idle, with do not disturb enabled, or in a fullscreen window does not count,
+/// and a long enough idle period resets the timer. Only the idle notifications of the
+/// compositor and the changes of the lock hint of logind and of do not disturb wake the reminder,
+/// besides the reminder itself.
+pub async fn run(conn: Connection, mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<BreaksConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load break reminder config {err:?}");
+            return;
+        }
+    };
+
+    let logind_session = match crate::bus::system().await {
+        Ok(conn) => LogindSessionProxy::builder(&conn).build().await.ok(),
+        Err(_) => None,
+    };
+
+    let mut locked_changes = match logind_session.as_ref() {
+        Some(session) => Some(session.receive_locked_hint_changed().await),
+        None => None,
+    };
+    let mut idle_changes = crate::idle::subscribe();
+    let mut dnd_changes = crate::dnd::subscribe();
+
+    // Activity is counted up to the start of the current period.
+    let mut active = Duration::ZERO;
+    let mut current = activity(logind_session.as_ref()).await;
+    let mut since = Instant::now();
+
+    loop {
+        let next = activity(logind_session.as_ref()).await;
+        if next != current {
+            let now = Instant::now();
+            match current {
+                Activity::Active => active += now - since,
+                Activity::Idle if now - since >= Duration::from_secs(config.duration_seconds) => {
+                    active = Duration::ZERO;
+                }
+                Activity::Idle | Activity::Paused => (),
+            }
+            current = next;
+            since = now;
+        }
+
+        let interval = Duration::from_secs(config.interval_minutes * 60);
+        let due = (config.enabled && current == Activity::Active)
+            .then(|| since + interval.saturating_sub(active));
+        let reminder = async {
+            match due {
+                Some(due) => tokio::time::sleep_until(due).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+
+                let changes = crate::config::update(&mut config, &helper, &key);
+                if changes.contains(&"enabled") || changes.contains(&"interval_minutes") {
+                    active = Duration::ZERO;
+                    since = Instant::now();
+                }
+            }
+
+            Ok(()) = idle_changes.changed() => (),
+            _ = changed(&mut locked_changes) => (),
+
+            res = dnd_changes.changed() => {
+                if res.is_err() {
+                    break;
+                }
+            }
+
+            _ = reminder => {
+                active = Duration::ZERO;
+                since = Instant::now();
+                remind(conn.clone(), &config).await;
+            }
+        }
+    }
+}
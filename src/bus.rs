@@ -0,0 +1,17 @@
+use tokio::sync::OnceCell;
+use zbus::Connection;
+
+static SESSION: OnceCell<Connection> = OnceCell::const_new();
+
+/// The connection to the session bus of the modules which only call or follow other services,
+/// opened on first use and shared, rather than one for each call.
+pub async fn session() -> zbus::Result<Connection> {
+    SESSION.get_or_try_init(Connection::session).await.cloned()
+}
+
+static SYSTEM: OnceCell<Connection> = OnceCell::const_new();
+
+/// The connection to the system bus, shared by all modules like the session one.
+pub async fn system() -> zbus::Result<Connection> {
+    SYSTEM.get_or_try_init(Connection::system).await.cloned()
+}
@@ -0,0 +1,196 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+use zbus::{Connection, InterfaceRef};
+
+use crate::{pipewire::Captures, DBUS_PATH};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Capture";
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct CaptureConfig {
+    /// Notify when an application starts using the microphone or the camera.
+    pub notify: bool,
+    /// Show the OSD when an application starts using the microphone or the camera.
+    pub osd: bool,
+    /// Applications which are not notified of, such as a video call application which is
+    /// expected to use the camera. They are still listed on D-Bus, for the privacy indicator.
+    ///
+    /// Sandboxed applications are named by their app ID, and those which open the camera
+    /// directly by their process name.
+    pub suppressed: Vec<String>,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            notify: true,
+            osd: true,
+            suppressed: Vec::new(),
+        }
+    }
+}
+
+/// The applications using the microphone and the camera, for the privacy indicator of the panel.
+#[derive(Default)]
+pub struct Capture {
+    microphone: Vec<String>,
+    camera: Vec<String>,
+}
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.Capture")]
+impl Capture {
+    /// The applications recording from the microphone.
+    #[dbus_interface(property)]
+    async fn microphone(&self) -> Vec<String> {
+        self.microphone.clone()
+    }
+
+    /// The applications using the camera.
+    #[dbus_interface(property)]
+    async fn camera(&self) -> Vec<String> {
+        self.camera.clone()
+    }
+}
+
+async fn publish(interface: &InterfaceRef<Capture>, captures: &Captures) {
+    let mut capture = interface.get_mut().await;
+    let ctxt = interface.signal_context();
+
+    let microphone: Vec<String> = captures.microphone.iter().cloned().collect();
+    if capture.microphone != microphone {
+        capture.microphone = microphone;
+        _ = capture.microphone_changed(ctxt).await;
+    }
+
+    let camera: Vec<String> = captures.camera.iter().cloned().collect();
+    if capture.camera != camera {
+        capture.camera = camera;
+        _ = capture.camera_changed(ctxt).await;
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Device {
+    Microphone,
+    Camera,
+}
+
+impl Device {
+    fn name(self) -> &'static str {
+        match self {
+            Device::Microphone => "microphone",
+            Device::Camera => "camera",
+        }
+    }
+
+    fn summary(self) -> &'static str {
+        match self {
+            Device::Microphone => "Microphone in Use",
+            Device::Camera => "Camera in Use",
+        }
+    }
+
+    fn osd_kind(self) -> &'static str {
+        match self {
+            Device::Microphone => "microphone-in-use",
+            Device::Camera => "camera-in-use",
+        }
+    }
+
+    fn icon(self) -> &'static str {
+        match self {
+            Device::Microphone => "audio-input-microphone-symbolic",
+            Device::Camera => "camera-web-symbolic",
+        }
+    }
+}
+
+/// Notify of the applications in `now` which were not in `before`.
+async fn announce(
+    config: &CaptureConfig,
+    device: Device,
+    before: &BTreeSet<String>,
+    now: &BTreeSet<String>,
+) {
+    let started: Vec<&String> = now
+        .difference(before)
+        .filter(|application| !config.suppressed.contains(application))
+        .collect();
+    if started.is_empty() {
+        return;
+    }
+
+    if config.osd {
+        crate::osd::show(device.osd_kind(), 1, device.icon());
+    }
+
+    if config.notify {
+        for application in started {
+            let _res = Notification::new()
+                .appname("")
+                .summary(device.summary())
+                .body(&format!("{application} is using the {}.", device.name()))
+                .icon(device.icon())
+                .timeout(Duration::from_secs(5))
+                .show_async()
+                .await;
+        }
+    }
+}
+
+/// Publish and announce the applications which use the microphone and the camera.
+pub async fn run(conn: Connection, mut rx: Receiver<String>) {
+    let Ok(interface) = conn
+        .object_server()
+        .interface::<_, Capture>(DBUS_PATH)
+        .await
+    else {
+        return;
+    };
+
+    let (helper, mut config) = match crate::config::load_async::<CaptureConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load capture config {err:?}");
+            return;
+        }
+    };
+
+    let mut pipewire = crate::pipewire::captures();
+    let mut v4l2 = crate::v4l2::subscribe();
+    let mut current = Captures::default();
+
+    loop {
+        // Applications which open the camera directly with V4L2 are merged with those which use
+        // it through PipeWire.
+        let mut captures = pipewire.borrow_and_update().clone();
+        captures
+            .camera
+            .extend(v4l2.borrow_and_update().iter().cloned());
+
+        if captures != current {
+            publish(&interface, &captures).await;
+            let (mic, camera) = (Device::Microphone, Device::Camera);
+            announce(&config, mic, &current.microphone, &captures.microphone).await;
+            announce(&config, camera, &current.camera, &captures.camera).await;
+            current = captures;
+        }
+
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                crate::config::update(&mut config, &helper, &key);
+            }
+
+            Ok(()) = pipewire.changed() => (),
+            Ok(()) = v4l2.changed() => (),
+        }
+    }
+}
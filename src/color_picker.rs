@@ -0,0 +1,163 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio_stream::StreamExt;
+use zbus::{
+    zvariant::{OwnedValue, Value},
+    Connection,
+};
+
+use crate::screenshot_portal::{PortalRequestProxy, ScreenshotPortalProxy};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.ColorPicker";
+
+static REQUESTS: AtomicU64 = AtomicU64::new(0);
+
+/// How a picked color is copied to the clipboard.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub enum ColorFormat {
+    /// Such as `#3daee9`.
+    #[default]
+    Hex,
+    /// Such as `rgb(61, 174, 233)`.
+    Rgb,
+}
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct ColorPickerConfig {
+    pub format: ColorFormat,
+    /// Show the picked color on the OSD.
+    pub show_osd: bool,
+}
+
+impl Default for ColorPickerConfig {
+    fn default() -> Self {
+        Self {
+            format: ColorFormat::Hex,
+            show_osd: true,
+        }
+    }
+}
+
+fn format_color(format: ColorFormat, (r, g, b): (u8, u8, u8)) -> String {
+    match format {
+        ColorFormat::Hex => format!("#{r:02x}{g:02x}{b:02x}"),
+        ColorFormat::Rgb => format!("rgb({r}, {g}, {b})"),
+    }
+}
+
+fn channel(value: &Value<'_>) -> Option<u8> {
+    match value {
+        Value::F64(value) => Some((value.clamp(0.0, 1.0) * 255.0).round() as u8),
+        _ => None,
+    }
+}
+
+/// The color in the results of a `PickColor` request, which has channels from 0 to 1.
+fn picked_color(results: &HashMap<String, OwnedValue>) -> Option<(u8, u8, u8)> {
+    let Value::Structure(color) = &**results.get("color")? else {
+        return None;
+    };
+
+    match color.fields() {
+        [r, g, b] => Some((channel(r)?, channel(g)?, channel(b)?)),
+        _ => None,
+    }
+}
+
+/// Let the user pick a color with the screenshot portal, or `None` if it was cancelled.
+async fn pick(conn: &Connection) -> zbus::Result<Option<(u8, u8, u8)>> {
+    let portal = ScreenshotPortalProxy::new(conn).await?;
+
+    // The response is subscribed to before the request is made, so that it cannot be missed.
+    let sender = conn
+        .unique_name()
+        .ok_or_else(|| zbus::Error::Failure("no unique name on the session bus".to_owned()))?
+        .trim_start_matches(':')
+        .replace('.', "_");
+    let token = format!(
+        "cosmic_settings_daemon_{}",
+        REQUESTS.fetch_add(1, Ordering::Relaxed)
+    );
+    let request = PortalRequestProxy::builder(conn)
+        .path(format!(
+            "/org/freedesktop/portal/desktop/request/{sender}/{token}"
+        ))?
+        .build()
+        .await?;
+    let mut responses = request.receive_response().await?;
+
+    let options = HashMap::from([("handle_token", Value::from(token.as_str()))]);
+    portal.pick_color("", options).await?;
+
+    let Some(response) = responses.next().await else {
+        return Ok(None);
+    };
+    let args = response.args()?;
+    if *args.response() != 0 {
+        return Ok(None);
+    }
+
+    Ok(picked_color(args.results()))
+}
+
+async fn copy_to_clipboard(text: &str) -> std::io::Result<()> {
+    let status = tokio::process::Command::new("wl-copy")
+        .arg(text)
+        .status()
+        .await?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!(
+            "wl-copy failed with {status}"
+        )))
+    }
+}
+
+/// Pick a color, and copy it to the clipboard.
+async fn pick_to_clipboard(conn: Connection) {
+    let config = crate::config::load::<ColorPickerConfig>(ID)
+        .map(|(_, config)| config)
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to load color picker config {err:?}");
+            ColorPickerConfig::default()
+        });
+
+    let color = match pick(&conn).await {
+        Ok(Some(color)) => color,
+        Ok(None) => return,
+        Err(err) => {
+            eprintln!("Failed to pick a color: {err}");
+            return;
+        }
+    };
+
+    if let Err(err) = copy_to_clipboard(&format_color(config.format, color)).await {
+        eprintln!("Failed to copy the picked color: {err}");
+        return;
+    }
+
+    if config.show_osd {
+        let (r, g, b) = color;
+        let rgb = (i32::from(r) << 16) | (i32::from(g) << 8) | i32::from(b);
+        crate::osd::show("color-picked", rgb, "color-select-symbolic");
+    }
+}
+
+/// The color picker, for its Super+Shift+C shortcut.
+pub struct ColorPicker;
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.ColorPicker")]
+impl ColorPicker {
+    /// Pick a color from the screen, and copy it to the clipboard in the configured format.
+    async fn pick(&self, #[zbus(connection)] conn: &Connection) {
+        tokio::spawn(pick_to_clipboard(conn.clone()));
+    }
+}
@@ -0,0 +1,63 @@
+use cosmic_config::{Config, CosmicConfigEntry};
+
+/// Open the config for `id` and read its entry, logging any keys that failed to load.
+pub fn load<T: CosmicConfigEntry>(id: &str) -> Result<(Config, T), cosmic_config::Error> {
+    Ok(read(Config::new(id, T::VERSION)?))
+}
+
+/// Open the state for `id` and read its entry, logging any keys that failed to load.
+pub fn load_state<T: CosmicConfigEntry>(id: &str) -> Result<(Config, T), cosmic_config::Error> {
+    Ok(read(Config::new_state(id, T::VERSION)?))
+}
+
+/// Read the entry of a config or state, logging any keys that failed to load.
+fn read<T: CosmicConfigEntry>(helper: Config) -> (Config, T) {
+    let entry = match T::get_entry(&helper) {
+        Ok(t) => t,
+        Err((errs, t)) => {
+            for why in errs {
+                eprintln!("{why}");
+            }
+            t
+        }
+    };
+
+    (helper, entry)
+}
+
+/// Reload a changed key of an entry, returning the names of the fields that changed.
+pub fn update<T: CosmicConfigEntry>(
+    entry: &mut T,
+    helper: &Config,
+    key: &str,
+) -> Vec<&'static str> {
+    let (errs, changes) = entry.update_keys(helper, &[key]);
+    for err in errs {
+        eprintln!("Error updating config {err:?}");
+    }
+    changes
+}
+
+/// Like `load`, but read on a blocking thread, so that the modules read their configs in
+/// parallel at startup instead of one after another.
+pub async fn load_async<T>(id: &'static str) -> Result<(Config, T), cosmic_config::Error>
+where
+    T: CosmicConfigEntry + Send + 'static,
+{
+    match tokio::task::spawn_blocking(move || load::<T>(id)).await {
+        Ok(res) => res,
+        // The blocking threads are only gone while the runtime shuts down.
+        Err(_) => load::<T>(id),
+    }
+}
+
+/// Like `load_state`, but read on a blocking thread.
+pub async fn load_state_async<T>(id: &'static str) -> Result<(Config, T), cosmic_config::Error>
+where
+    T: CosmicConfigEntry + Send + 'static,
+{
+    match tokio::task::spawn_blocking(move || load_state::<T>(id)).await {
+        Ok(res) => res,
+        Err(_) => load_state::<T>(id),
+    }
+}
@@ -0,0 +1,205 @@
+use std::{fs, io, path::Path};
+
+use anyhow::Context;
+use cosmic_theme::{palette::Srgba, Theme};
+
+/// Written next to GTK's `gtk.css`, which imports it, while the theme is high contrast.
+pub const GTK_CONTRAST_FILE: &str = "cosmic-contrast.css";
+
+/// The contrast of text with its background in the high contrast palette, the enhanced level of
+/// WCAG.
+const MIN_CONTRAST: f32 = 7.0;
+
+/// The colors of the theme which the GTK and Qt exports are made of.
+#[derive(Clone, Copy, Debug)]
+pub struct Colors {
+    pub window: Srgba,
+    pub window_text: Srgba,
+    pub view: Srgba,
+    pub view_text: Srgba,
+    pub button: Srgba,
+    pub button_text: Srgba,
+    pub accent: Srgba,
+    pub accent_text: Srgba,
+    pub divider: Srgba,
+}
+
+impl Colors {
+    /// The colors of the theme, or its high contrast palette if the theme is high contrast.
+    pub fn new(theme: &Theme) -> Self {
+        let colors = Self {
+            window: theme.background.base,
+            window_text: theme.background.on,
+            view: theme.primary.base,
+            view_text: theme.primary.on,
+            button: theme.button.base,
+            button_text: theme.button.on,
+            accent: theme.accent_color(),
+            accent_text: theme.accent.on,
+            divider: theme.background.divider,
+        };
+
+        if theme.is_high_contrast {
+            colors.high_contrast(theme.is_dark)
+        } else {
+            colors
+        }
+    }
+
+    /// Black and white backgrounds and text, dividers and borders in the color of text, and the
+    /// accent darkened or lightened until its text stands out as much.
+    fn high_contrast(self, is_dark: bool) -> Self {
+        let (black, white) = (
+            Srgba::new(0.0, 0.0, 0.0, 1.0),
+            Srgba::new(1.0, 1.0, 1.0, 1.0),
+        );
+        let (background, text) = if is_dark {
+            (black, white)
+        } else {
+            (white, black)
+        };
+
+        let (accent_text, towards) = if contrast(self.accent, white) >= contrast(self.accent, black)
+        {
+            (white, black)
+        } else {
+            (black, white)
+        };
+        let accent = (0..=10)
+            .map(|step| mix(self.accent, towards, step as f32 / 10.0))
+            .find(|accent| contrast(*accent, accent_text) >= MIN_CONTRAST)
+            .unwrap_or(towards);
+
+        Self {
+            window: background,
+            window_text: text,
+            view: background,
+            view_text: text,
+            button: background,
+            button_text: text,
+            accent,
+            accent_text,
+            divider: text,
+        }
+    }
+}
+
+/// The relative luminance of a color, as WCAG defines it.
+fn luminance(color: Srgba) -> f32 {
+    let linear = |c: f32| {
+        if c <= 0.040_45 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(color.red) + 0.7152 * linear(color.green) + 0.0722 * linear(color.blue)
+}
+
+/// The contrast ratio of two colors, from 1 to 21.
+fn contrast(a: Srgba, b: Srgba) -> f32 {
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// `a` moved towards `b` by `t`, from 0 to 1.
+fn mix(a: Srgba, b: Srgba, t: f32) -> Srgba {
+    let mix = |a: f32, b: f32| a + (b - a) * t;
+    Srgba::new(
+        mix(a.red, b.red),
+        mix(a.green, b.green),
+        mix(a.blue, b.blue),
+        1.0,
+    )
+}
+
+fn hex(color: Srgba) -> String {
+    let [r, g, b] =
+        [color.red, color.green, color.blue].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// The named colors of libadwaita and of GTK 3 themes, set to the high contrast palette.
+fn css(colors: &Colors) -> String {
+    let background = hex(colors.window);
+    let text = hex(colors.window_text);
+    let accent = hex(colors.accent);
+    let accent_text = hex(colors.accent_text);
+    // The accent as a color of text, such as of links, on the background.
+    let accent_fg = if contrast(colors.accent, colors.window) >= MIN_CONTRAST {
+        accent.clone()
+    } else {
+        text.clone()
+    };
+
+    let mut css = "/* Generated by cosmic-settings-daemon for high contrast. */\n".to_owned();
+    for surface in [
+        "window",
+        "view",
+        "headerbar",
+        "sidebar",
+        "card",
+        "dialog",
+        "popover",
+    ] {
+        css.push_str(&format!("@define-color {surface}_bg_color {background};\n"));
+        css.push_str(&format!("@define-color {surface}_fg_color {text};\n"));
+    }
+    for (name, color) in [
+        ("accent_color", &accent_fg),
+        ("accent_bg_color", &accent),
+        ("accent_fg_color", &accent_text),
+        ("headerbar_border_color", &text),
+        ("borders", &text),
+        ("theme_bg_color", &background),
+        ("theme_fg_color", &text),
+        ("theme_base_color", &background),
+        ("theme_text_color", &text),
+        ("theme_selected_bg_color", &accent),
+        ("theme_selected_fg_color", &accent_text),
+    ] {
+        css.push_str(&format!("@define-color {name} {color};\n"));
+    }
+    css
+}
+
+/// Remove the high contrast colors from the GTK config directory `dir`.
+fn remove_gtk(dir: &Path) -> io::Result<()> {
+    crate::gtk::remove_import(&dir.join("gtk.css"), GTK_CONTRAST_FILE).and_then(|()| {
+        match fs::remove_file(dir.join(GTK_CONTRAST_FILE)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    })
+}
+
+/// Override the colors of GTK applications with the high contrast palette while the theme is
+/// high contrast, as adw-gtk3 and most GTK 3 themes have no high contrast variant.
+pub fn theme_changed(theme: &Theme) {
+    let Some(config_dir) = dirs::config_dir() else {
+        return;
+    };
+
+    let css = theme.is_high_contrast.then(|| css(&Colors::new(theme)));
+    for gtk in ["gtk-3.0", "gtk-4.0"] {
+        let dir = config_dir.join(gtk);
+        let res = match &css {
+            Some(css) => crate::files::write_atomic(&dir.join(GTK_CONTRAST_FILE), css)
+                .and_then(|()| crate::gtk::add_import(&dir.join("gtk.css"), GTK_CONTRAST_FILE)),
+            None => remove_gtk(&dir),
+        };
+
+        if let Err(err) = res {
+            crate::export::failed(format!("Failed to export high contrast to {gtk}: {err}"));
+        }
+    }
+}
+
+/// Remove the high contrast colors from GTK.
+pub fn reset() -> anyhow::Result<()> {
+    let config_dir = dirs::config_dir().context("no config directory")?;
+    for gtk in ["gtk-3.0", "gtk-4.0"] {
+        remove_gtk(&config_dir.join(gtk)).with_context(|| format!("failed to clean up {gtk}"))?;
+    }
+    Ok(())
+}
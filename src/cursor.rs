@@ -0,0 +1,94 @@
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Cursor";
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct CursorConfig {
+    /// Name of the cursor theme, or the theme of the session if unset.
+    pub theme: Option<String>,
+    /// Size of the cursor in logical pixels.
+    pub size: u32,
+}
+
+impl Default for CursorConfig {
+    fn default() -> Self {
+        Self {
+            theme: None,
+            size: 24,
+        }
+    }
+}
+
+impl CursorConfig {
+    fn size(&self) -> u32 {
+        self.size.clamp(16, 256)
+    }
+}
+
+/// Export the cursor theme to the session environment, GNOME, GTK's `settings.ini`, and KDE,
+/// whose applications use the Breeze cursors otherwise.
+fn export(config: &CursorConfig) {
+    let size = config.size().to_string();
+
+    crate::environment::set(
+        "cursor",
+        vec![
+            ("XCURSOR_THEME", config.theme.clone()),
+            ("XCURSOR_SIZE", Some(size.clone())),
+        ],
+    );
+
+    let mut settings = vec![("org.gnome.desktop.interface", "cursor-size", size.clone())];
+    if let Some(theme) = config.theme.clone() {
+        settings.push(("org.gnome.desktop.interface", "cursor-theme", theme));
+    }
+    crate::gsettings::set(settings);
+
+    let Some(config_dir) = dirs::config_dir() else {
+        return;
+    };
+
+    let mut gtk = vec![("Settings", "gtk-cursor-theme-size", size.as_str())];
+    let mut kde = vec![("Mouse", "cursorSize", size.as_str())];
+    if let Some(theme) = config.theme.as_deref() {
+        gtk.push(("Settings", "gtk-cursor-theme-name", theme));
+        kde.push(("Mouse", "cursorTheme", theme));
+    }
+
+    let files = [
+        (config_dir.join("gtk-3.0/settings.ini"), &gtk),
+        (config_dir.join("gtk-4.0/settings.ini"), &gtk),
+        (config_dir.join("kcminputrc"), &kde),
+    ];
+
+    for (path, values) in files {
+        if let Err(err) = crate::files::set_ini_values(&path, values) {
+            eprintln!("Failed to write {}: {err}", path.display());
+        }
+    }
+}
+
+/// Apply the cursor theme and size.
+pub async fn run(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<CursorConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load cursor config {err:?}");
+            return;
+        }
+    };
+
+    // The session already has its cursor theme unless it was overridden.
+    if config != CursorConfig::default() {
+        export(&config);
+    }
+
+    while let Some(key) = rx.recv().await {
+        if !crate::config::update(&mut config, &helper, &key).is_empty() {
+            export(&config);
+        }
+    }
+}
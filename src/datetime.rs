@@ -0,0 +1,222 @@
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+use zbus::{Connection, InterfaceRef};
+
+use crate::{
+    schedule::{Location, LocationReceiver},
+    timedate::TimedateProxy,
+    DBUS_PATH,
+};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.DateTime";
+
+const ZONE_TAB: &str = "/usr/share/zoneinfo/zone1970.tab";
+
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct DateTimeConfig {
+    /// Set the time zone from the location used for theme switching.
+    pub automatic_timezone: bool,
+    /// Synchronize the clock over the network, or leave the system setting alone if unset.
+    pub automatic_time: Option<bool>,
+}
+
+/// The state of the date and time preferences applied by the daemon.
+#[derive(Default)]
+pub struct DateTime {
+    ntp_error: String,
+}
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.DateTime")]
+impl DateTime {
+    /// Empty unless applying the automatic time preference failed, such as when polkit did not
+    /// authorize it.
+    #[dbus_interface(property)]
+    async fn ntp_error(&self) -> String {
+        self.ntp_error.clone()
+    }
+}
+
+async fn publish_ntp_error(interface: &InterfaceRef<DateTime>, error: String) {
+    let mut datetime = interface.get_mut().await;
+    if datetime.ntp_error != error {
+        datetime.ntp_error = error;
+        _ = datetime.ntp_error_changed(interface.signal_context()).await;
+    }
+}
+
+/// Enable or disable network time synchronization, unless it is already in that state.
+async fn update_ntp(timedate: &TimedateProxy<'_>, use_ntp: bool) -> zbus::Result<()> {
+    if timedate.ntp().await? == use_ntp {
+        return Ok(());
+    }
+
+    timedate.set_ntp(use_ntp, false).await
+}
+
+/// Parse a `zone1970.tab` coordinate, `±DDMM` or `±DDMMSS` with `degree_digits` digits of degrees.
+fn parse_coordinate(s: &str, degree_digits: usize) -> Option<f64> {
+    let sign = match s.get(..1)? {
+        "+" => 1.0,
+        "-" => -1.0,
+        _ => return None,
+    };
+    let digits = s.get(1..)?;
+    let degrees: f64 = digits.get(..degree_digits)?.parse().ok()?;
+    let minutes: f64 = digits.get(degree_digits..degree_digits + 2)?.parse().ok()?;
+    let seconds: f64 = match digits.get(degree_digits + 2..) {
+        Some("") | None => 0.0,
+        Some(seconds) => seconds.parse().ok()?,
+    };
+    Some(sign * (degrees + minutes / 60.0 + seconds / 3600.0))
+}
+
+fn parse_location(coordinates: &str) -> Option<Location> {
+    let split = coordinates.get(1..)?.find(['+', '-'])? + 1;
+    let (latitude, longitude) = coordinates.split_at(split);
+    Some(Location {
+        latitude: parse_coordinate(latitude, 2)?,
+        longitude: parse_coordinate(longitude, 3)?,
+    })
+}
+
+/// Great-circle distance in radians.
+fn distance(a: Location, b: Location) -> f64 {
+    let (lat_a, lat_b) = (a.latitude.to_radians(), b.latitude.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b.longitude - a.longitude).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * h.sqrt().asin()
+}
+
+/// The time zone whose principal location in the tz database is nearest to `location`.
+///
+/// Like GNOME, this is only an approximation near borders, but needs no network service.
+fn nearest_timezone(location: Location) -> Option<String> {
+    let zones = std::fs::read_to_string(ZONE_TAB).ok()?;
+    zones
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let _countries = fields.next()?;
+            let zone_location = parse_location(fields.next()?)?;
+            let zone = fields.next()?;
+            Some((distance(location, zone_location), zone))
+        })
+        .min_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, zone)| zone.to_owned())
+}
+
+fn notify_timezone(timezone: String) {
+    tokio::task::spawn_local(async move {
+        let _res = Notification::new()
+            .appname("")
+            .summary("Time Zone Changed")
+            .body(&format!("The time zone was changed to {timezone}."))
+            .icon("preferences-system-time-symbolic")
+            .show_async()
+            .await;
+    });
+}
+
+/// Set the time zone for `location` if it differs from the current one.
+async fn update_timezone(timedate: &TimedateProxy<'_>, location: Location) -> zbus::Result<()> {
+    let Some(timezone) = nearest_timezone(location) else {
+        return Ok(());
+    };
+
+    if timedate.timezone().await? == timezone {
+        return Ok(());
+    }
+
+    // Not interactive, since there is nobody to answer a prompt in the background. Setting
+    // the time zone requires a polkit rule allowing it for the session, like GNOME's.
+    timedate.set_timezone(&timezone, false).await?;
+    notify_timezone(timezone);
+    Ok(())
+}
+
+/// Apply the network time preference, and keep the system time zone in sync with the
+/// location if enabled.
+pub async fn run(conn: Connection, mut rx: Receiver<String>, mut location_rx: LocationReceiver) {
+    let Ok(interface) = conn
+        .object_server()
+        .interface::<_, DateTime>(DBUS_PATH)
+        .await
+    else {
+        return;
+    };
+
+    let (helper, mut config) = match crate::config::load_async::<DateTimeConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load date and time config {err:?}");
+            return;
+        }
+    };
+
+    let Ok(system_conn) = crate::bus::system().await else {
+        return;
+    };
+
+    // timedated is only started while something is automatic.
+    let mut timedate: Option<TimedateProxy> = None;
+    let mut location_open = true;
+    let mut apply_ntp = true;
+
+    loop {
+        crate::location::want("datetime", config.automatic_timezone);
+
+        if config.automatic_time.is_none() && !config.automatic_timezone {
+            timedate = None;
+        } else if timedate.is_none() {
+            match TimedateProxy::new(&system_conn).await {
+                Ok(proxy) => timedate = Some(proxy),
+                Err(err) => eprintln!("Failed to connect to timedated: {err}"),
+            }
+        }
+
+        if std::mem::take(&mut apply_ntp) {
+            let res = match (config.automatic_time, timedate.as_ref()) {
+                (Some(use_ntp), Some(timedate)) => update_ntp(timedate, use_ntp).await,
+                _ => Ok(()),
+            };
+
+            let error = match res {
+                Ok(()) => String::new(),
+                Err(err) => {
+                    eprintln!("Failed to set network time synchronization: {err}");
+                    err.to_string()
+                }
+            };
+            publish_ntp_error(&interface, error).await;
+        }
+
+        let location = *location_rx.borrow_and_update();
+        if let (Some(location), Some(timedate)) = (
+            location.filter(|_| config.automatic_timezone),
+            timedate.as_ref(),
+        ) {
+            if let Err(err) = update_timezone(timedate, location).await {
+                eprintln!("Failed to set time zone: {err}");
+            }
+        }
+
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                let changed = crate::config::update(&mut config, &helper, &key);
+                apply_ntp = changed.contains(&"automatic_time");
+            }
+
+            res = location_rx.changed(), if location_open => {
+                location_open = res.is_ok();
+            }
+        }
+    }
+}
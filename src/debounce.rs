@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+    time::Instant,
+};
+
+use crate::Change;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Debounce";
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct DebounceConfig {
+    /// How long a key must stay unchanged before its change is applied, in milliseconds, or 0
+    /// to apply every change.
+    pub window_ms: u64,
+}
+
+impl Default for DebounceConfig {
+    fn default() -> Self {
+        Self { window_ms: 150 }
+    }
+}
+
+impl DebounceConfig {
+    fn window(&self) -> Duration {
+        Duration::from_millis(self.window_ms.min(5000))
+    }
+}
+
+/// Coalesce bursts of changes of the same key, such as while a slider is dragged, forwarding
+/// each key once it settles.
+pub async fn run(mut rx: UnboundedReceiver<Vec<Change>>, tx: UnboundedSender<Vec<Change>>) {
+    let (helper, mut config) = match crate::config::load_async::<DebounceConfig>(ID).await {
+        Ok((helper, config)) => (Some(helper), config),
+        Err(err) => {
+            eprintln!("Failed to load debounce config {err:?}");
+            (None, DebounceConfig::default())
+        }
+    };
+
+    // Changes waiting to settle, in the order they first arrived.
+    let mut pending: Vec<(Change, Instant)> = Vec::new();
+
+    loop {
+        let next = pending.iter().map(|(_, deadline)| *deadline).min();
+        let settle = async {
+            match next {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            changes = rx.recv() => {
+                let Some(changes) = changes else {
+                    break;
+                };
+
+                let mut immediate = Vec::new();
+                for change in changes {
+                    if let (Change::Config(id, key, _), Some(helper)) = (&change, &helper) {
+                        if id == ID {
+                            crate::config::update(&mut config, helper, key);
+                        }
+                    }
+
+                    let window = config.window();
+                    if window.is_zero() || matches!(change, Change::Ping(..)) {
+                        immediate.push(change);
+                        continue;
+                    }
+
+                    let deadline = Instant::now() + window;
+                    match pending.iter_mut().find(|(pending, _)| *pending == change) {
+                        Some((_, pending_deadline)) => *pending_deadline = deadline,
+                        None => pending.push((change, deadline)),
+                    }
+                }
+
+                if !immediate.is_empty() && tx.send(immediate).is_err() {
+                    break;
+                }
+            }
+
+            _ = settle => {
+                let now = Instant::now();
+                let (settled, waiting): (Vec<_>, Vec<_>) =
+                    pending.drain(..).partition(|(_, deadline)| *deadline <= now);
+                pending = waiting;
+
+                let settled: Vec<Change> = settled.into_iter().map(|(change, _)| change).collect();
+                if tx.send(settled).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use std::future::Future;
+
+use cosmic_config::CosmicConfigEntry;
+use tokio::{
+    sync::mpsc::{self, Receiver, Sender},
+    task::{self, JoinHandle},
+};
+
+/// The config of a module which only needs to run while it enables a feature.
+pub trait Feature: CosmicConfigEntry + Send + 'static {
+    fn enabled(&self) -> bool;
+}
+
+/// Run a module only while its config `id` enables its feature, so that a disabled feature holds
+/// no connections, proxies, or timers.
+///
+/// `rx` receives the changed keys of the config, which are forwarded to the running module. The
+/// module is stopped by closing its channel once it was sent the key which disabled it, so that
+/// it can release what it holds first, as it does when its channel closes at exit.
+pub async fn run<T, F, Fut>(id: &'static str, mut rx: Receiver<String>, start: F)
+where
+    T: Feature,
+    F: Fn(Receiver<String>) -> Fut,
+    Fut: Future<Output = ()> + 'static,
+{
+    let (helper, mut config) = match crate::config::load_async::<T>(id).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load config {id} {err:?}");
+            return;
+        }
+    };
+
+    let mut running: Option<(Sender<String>, JoinHandle<()>)> = None;
+    loop {
+        if config.enabled() && running.is_none() {
+            let (tx, rx) = mpsc::channel(10);
+            running = Some((tx, task::spawn_local(start(rx))));
+        }
+
+        let Some(key) = rx.recv().await else {
+            break;
+        };
+        crate::config::update(&mut config, &helper, &key);
+
+        if let Some((tx, _)) = &running {
+            _ = tx.send(key).await;
+        }
+
+        if !config.enabled() {
+            if let Some((tx, handle)) = running.take() {
+                drop(tx);
+                _ = handle.await;
+            }
+        }
+    }
+}
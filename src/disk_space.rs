@@ -0,0 +1,241 @@
+use std::{
+    collections::BTreeMap,
+    ffi::CString,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.DiskSpace";
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct DiskSpaceConfig {
+    /// Mount points to check, where `~` is the home directory.
+    pub mounts: Vec<String>,
+    /// Warn when less than this percentage of a mount is free.
+    pub warning_percent: u32,
+    /// Warn urgently when less than this percentage of a mount is free.
+    pub critical_percent: u32,
+    /// Never warn while at least this many gigabytes are free, which matters for large disks.
+    pub min_free_gb: u64,
+    /// Mount points which the user asked not to be warned about.
+    pub ignore: Vec<String>,
+    /// Application opened by the notification to see what is using the space.
+    pub disk_usage_tool: String,
+}
+
+impl Default for DiskSpaceConfig {
+    fn default() -> Self {
+        Self {
+            mounts: vec!["~".to_owned(), "/".to_owned()],
+            warning_percent: 10,
+            critical_percent: 5,
+            min_free_gb: 2,
+            ignore: Vec::new(),
+            disk_usage_tool: "baobab".to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Level {
+    Normal,
+    Low,
+    Critical,
+}
+
+struct Usage {
+    /// Identifies the filesystem, so that mounts sharing one are only checked once.
+    fsid: u64,
+    free: u64,
+    total: u64,
+}
+
+// The field types of `statvfs` differ between architectures.
+#[allow(clippy::unnecessary_cast)]
+fn usage(path: &Path) -> Option<Usage> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+
+    // SAFETY: `path` is a valid C string and `stat` is only read if the call succeeded.
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+
+    Some(Usage {
+        fsid: stat.f_fsid as u64,
+        free: stat.f_bavail as u64 * stat.f_frsize as u64,
+        total: stat.f_blocks as u64 * stat.f_frsize as u64,
+    })
+}
+
+fn expand(mount: &str) -> Option<PathBuf> {
+    match mount.strip_prefix('~') {
+        Some(rest) => Some(dirs::home_dir()?.join(rest.trim_start_matches('/'))),
+        None => Some(PathBuf::from(mount)),
+    }
+}
+
+impl DiskSpaceConfig {
+    fn level(&self, usage: &Usage) -> Level {
+        if usage.total == 0 || usage.free >= self.min_free_gb * 1_000_000_000 {
+            return Level::Normal;
+        }
+
+        let percent = usage.free * 100 / usage.total;
+        if percent < u64::from(self.critical_percent) {
+            Level::Critical
+        } else if percent < u64::from(self.warning_percent) {
+            Level::Low
+        } else {
+            Level::Normal
+        }
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    let gb = bytes as f64 / 1e9;
+    if gb >= 1.0 {
+        format!("{gb:.1} GB")
+    } else {
+        format!("{:.0} MB", bytes as f64 / 1e6)
+    }
+}
+
+/// Notify about a mount running out of space, offering to examine or ignore it.
+fn notify(mount: String, free: u64, level: Level, tool: String, ignore_tx: Sender<String>) {
+    tokio::task::spawn_local(async move {
+        let (summary, urgency) = if level == Level::Critical {
+            ("Disk Space Critically Low", notify_rust::Urgency::Critical)
+        } else {
+            ("Low Disk Space", notify_rust::Urgency::Normal)
+        };
+
+        let name = if mount == "~" {
+            "your home folder"
+        } else {
+            mount.as_str()
+        };
+
+        let mut notification = Notification::new();
+        notification
+            .appname("")
+            .summary(summary)
+            .body(&format!("Only {} is left on {name}.", format_size(free)))
+            .icon("drive-harddisk-symbolic")
+            .urgency(urgency)
+            .action("ignore", "Don't Warn Again");
+
+        if !tool.is_empty() {
+            notification.action("examine", "Examine");
+        }
+
+        let Ok(handle) = notification.show_async().await else {
+            return;
+        };
+
+        match crate::notifications::wait_for_action(handle)
+            .await
+            .as_deref()
+        {
+            Some("examine") => {
+                let mut command = tokio::process::Command::new(&tool);
+                if let Some(path) = expand(&mount) {
+                    command.arg(path);
+                }
+
+                if let Err(err) = command.spawn() {
+                    eprintln!("Failed to open {tool}: {err}");
+                }
+            }
+
+            Some("ignore") => {
+                let _res = ignore_tx.send(mount).await;
+            }
+
+            _ => (),
+        }
+    });
+}
+
+/// Periodically check the free space of the configured mounts.
+pub async fn monitor(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<DiskSpaceConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load disk space config {err:?}");
+            return;
+        }
+    };
+
+    let (ignore_tx, mut ignore_rx) = mpsc::channel(1);
+    // The level each mount was last notified at, so that the user is only notified again when it gets worse.
+    let mut levels: BTreeMap<String, Level> = BTreeMap::new();
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                crate::config::update(&mut config, &helper, &key);
+            }
+
+            mount = ignore_rx.recv() => {
+                let Some(mount) = mount else {
+                    break;
+                };
+
+                if !config.ignore.contains(&mount) {
+                    let mut ignore = config.ignore.clone();
+                    ignore.push(mount);
+                    if let Err(err) = config.set_ignore(&helper, ignore) {
+                        eprintln!("Failed to save ignored mounts {err:?}");
+                    }
+                }
+            }
+
+            _ = interval.tick() => {
+                let mut checked = Vec::new();
+                for mount in &config.mounts {
+                    if config.ignore.contains(mount) {
+                        continue;
+                    }
+
+                    let Some(usage) = expand(mount).and_then(|path| usage(&path)) else {
+                        continue;
+                    };
+
+                    if checked.contains(&usage.fsid) {
+                        continue;
+                    }
+                    checked.push(usage.fsid);
+
+                    let level = config.level(&usage);
+                    let prev = levels.insert(mount.clone(), level).unwrap_or(Level::Normal);
+                    if level > prev {
+                        notify(
+                            mount.clone(),
+                            usage.free,
+                            level,
+                            config.disk_usage_tool.clone(),
+                            ignore_tx.clone(),
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
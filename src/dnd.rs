@@ -0,0 +1,288 @@
+use std::{
+    collections::BTreeSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+};
+
+use cosmic_config::{
+    cosmic_config_derive::CosmicConfigEntry, ConfigGet, ConfigSet, CosmicConfigEntry,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{
+    mpsc::{unbounded_channel, Receiver, UnboundedSender},
+    watch,
+};
+use zbus::{Connection, InterfaceRef};
+
+use crate::{
+    clock::SystemClock,
+    schedule::{LocationReceiver, Schedule},
+    DBUS_PATH,
+};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.DoNotDisturb";
+
+/// The config of cosmic-notifications, which owns the do not disturb setting.
+pub const NOTIFICATIONS_ID: &str = "com.system76.CosmicNotifications";
+const NOTIFICATIONS_VERSION: u64 = 1;
+const DO_NOT_DISTURB: &str = "do_not_disturb";
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static FULLSCREEN: AtomicBool = AtomicBool::new(false);
+static HOLD_TX: OnceLock<UnboundedSender<(&'static str, bool)>> = OnceLock::new();
+static CHANGES: OnceLock<watch::Sender<()>> = OnceLock::new();
+
+fn changes() -> &'static watch::Sender<()> {
+    CHANGES.get_or_init(|| watch::channel(()).0)
+}
+
+/// Notified whenever `is_active` or `fullscreen` changes.
+pub fn subscribe() -> watch::Receiver<()> {
+    changes().subscribe()
+}
+
+/// Whether notifications are silenced.
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Whether the active window is fullscreen, as the toplevel info of the compositor reports.
+pub fn fullscreen() -> bool {
+    FULLSCREEN.load(Ordering::Relaxed)
+}
+
+/// Called by the toplevels thread when the active window enters or leaves fullscreen.
+pub fn set_fullscreen(fullscreen: bool) {
+    if FULLSCREEN.swap(fullscreen, Ordering::Relaxed) != fullscreen {
+        changes().send_replace(());
+        hold("fullscreen", fullscreen);
+    }
+}
+
+/// Enable do not disturb while `held`, restoring the previous state when no reason is left.
+fn hold(reason: &'static str, held: bool) {
+    if let Some(tx) = HOLD_TX.get() {
+        let _res = tx.send((reason, held));
+    }
+}
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct DndConfig {
+    /// A daily period during which do not disturb is enabled.
+    pub schedule: Option<Schedule>,
+    /// Enable do not disturb while the screen is shared, so that notifications are not leaked.
+    pub during_screencast: bool,
+    /// Enable do not disturb while a window is fullscreen.
+    pub during_fullscreen: bool,
+}
+
+impl Default for DndConfig {
+    fn default() -> Self {
+        Self {
+            schedule: None,
+            during_screencast: true,
+            during_fullscreen: false,
+        }
+    }
+}
+
+impl DndConfig {
+    fn allows(&self, reason: &str) -> bool {
+        match reason {
+            "screencast" => self.during_screencast,
+            "fullscreen" => self.during_fullscreen,
+            _ => true,
+        }
+    }
+}
+
+/// The do not disturb state of notifications.
+#[derive(Default)]
+pub struct DoNotDisturb {
+    active: bool,
+    reasons: Vec<String>,
+}
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.DoNotDisturb")]
+impl DoNotDisturb {
+    /// Whether notifications are silenced, for any reason.
+    #[dbus_interface(property)]
+    async fn active(&self) -> bool {
+        self.active
+    }
+
+    /// Why the daemon is holding do not disturb on: `schedule`, `screencast`, or `fullscreen`.
+    #[dbus_interface(property)]
+    async fn reasons(&self) -> Vec<String> {
+        self.reasons.clone()
+    }
+}
+
+async fn publish(interface: &InterfaceRef<DoNotDisturb>, active: bool, reasons: Vec<String>) {
+    if ACTIVE.swap(active, Ordering::Relaxed) != active {
+        changes().send_replace(());
+    }
+
+    let mut dnd = interface.get_mut().await;
+    let ctxt = interface.signal_context();
+    if dnd.active != active {
+        dnd.active = active;
+        _ = dnd.active_changed(ctxt).await;
+    }
+
+    if dnd.reasons != reasons {
+        dnd.reasons = reasons;
+        _ = dnd.reasons_changed(ctxt).await;
+    }
+}
+
+/// Hold do not disturb on while the screen is being captured.
+async fn watch_screencasts() {
+    let mut screencast = crate::pipewire::screencast();
+    loop {
+        let capturing = *screencast.borrow_and_update();
+        hold("screencast", capturing);
+        if screencast.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Enable do not disturb during the scheduled period, and while anything else holds it on,
+/// following changes to it by the user.
+///
+/// `notifications_rx` receives changed keys of the cosmic-notifications config.
+pub async fn run(
+    conn: Connection,
+    mut rx: Receiver<String>,
+    mut notifications_rx: Receiver<String>,
+    mut location_rx: LocationReceiver,
+) {
+    let Ok(interface) = conn
+        .object_server()
+        .interface::<_, DoNotDisturb>(DBUS_PATH)
+        .await
+    else {
+        return;
+    };
+
+    let (helper, mut config) = match crate::config::load_async::<DndConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load do not disturb config {err:?}");
+            return;
+        }
+    };
+
+    let notifications = match cosmic_config::Config::new(NOTIFICATIONS_ID, NOTIFICATIONS_VERSION) {
+        Ok(notifications) => notifications,
+        Err(err) => {
+            eprintln!("Failed to open the notifications config {err:?}");
+            return;
+        }
+    };
+
+    let (hold_tx, mut hold_rx) = unbounded_channel();
+    if HOLD_TX.set(hold_tx).is_err() {
+        return;
+    }
+
+    // PipeWire is only monitored while screencasts are set to enable do not disturb.
+    let mut screencasts: Option<tokio::task::JoinHandle<()>> = None;
+
+    let mut active = notifications.get::<bool>(DO_NOT_DISTURB).unwrap_or(false);
+    let mut holds: BTreeSet<&'static str> = BTreeSet::new();
+    let mut held = false;
+    // Do not disturb is only disabled when nothing holds it anymore if it was enabled by a hold.
+    let mut enabled_by_hold = false;
+    let mut location_open = true;
+
+    loop {
+        crate::location::want("dnd", config.schedule == Some(Schedule::SunsetToSunrise));
+        if !config.during_screencast {
+            if let Some(handle) = screencasts.take() {
+                handle.abort();
+                holds.remove("screencast");
+            }
+        } else if screencasts.is_none() {
+            screencasts = Some(tokio::task::spawn_local(watch_screencasts()));
+        }
+
+        let (scheduled, next_transition) = config
+            .schedule
+            .map_or((false, None), |s| s.state(&SystemClock, &location_rx));
+
+        if scheduled {
+            holds.insert("schedule");
+        } else {
+            holds.remove("schedule");
+        }
+
+        let reasons: Vec<String> = holds
+            .iter()
+            .filter(|reason| config.allows(reason))
+            .map(|reason| (*reason).to_owned())
+            .collect();
+
+        let should_hold = !reasons.is_empty();
+        if should_hold != held {
+            held = should_hold;
+
+            let enable = if held {
+                enabled_by_hold = !active;
+                true
+            } else {
+                !std::mem::take(&mut enabled_by_hold) && active
+            };
+
+            if enable != active {
+                match notifications.set(DO_NOT_DISTURB, enable) {
+                    Ok(()) => active = enable,
+                    Err(err) => eprintln!("Failed to set do not disturb {err:?}"),
+                }
+            }
+        }
+
+        publish(&interface, active, reasons).await;
+
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                crate::config::update(&mut config, &helper, &key);
+            }
+
+            key = notifications_rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+
+                if key == DO_NOT_DISTURB {
+                    active = notifications.get::<bool>(DO_NOT_DISTURB).unwrap_or(false);
+                    // The user turned it off early, so there is nothing left to restore.
+                    if !active {
+                        enabled_by_hold = false;
+                    }
+                }
+            }
+
+            Some((reason, on)) = hold_rx.recv() => {
+                if on {
+                    holds.insert(reason);
+                } else {
+                    holds.remove(reason);
+                }
+            }
+
+            res = location_rx.changed(), if location_open => {
+                location_open = res.is_ok();
+            }
+
+            _ = crate::schedule::sleep_until(next_transition) => (),
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use std::{collections::BTreeSet, ffi::OsStr, io, path::PathBuf, time::Duration};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::unix::AsyncFd,
+    sync::mpsc::Receiver,
+    time::{sleep_until, Instant},
+};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Dock";
+
+/// Plugging into a dock connects its displays, keyboard, and power one after another, so the
+/// docked state is only checked once the devices stop changing.
+const SETTLE: Duration = Duration::from_secs(2);
+
+/// Connectors of built-in panels.
+const INTERNAL_CONNECTORS: &[&str] = &["eDP", "LVDS", "DSI"];
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct DockConfig {
+    /// The profile which is active while docked, such as one with a lower scale, the speakers
+    /// of the dock as the default audio device, and longer idle timeouts.
+    pub docked_profile: Option<String>,
+    /// The profile which is active while undocked. Without one, the settings the docked profile
+    /// replaced are restored.
+    pub undocked_profile: Option<String>,
+    /// Only docked with an external display connected.
+    pub require_external_display: bool,
+    /// Only docked while on AC power.
+    pub require_ac: bool,
+    /// Only docked with a USB or Bluetooth keyboard connected.
+    pub require_external_keyboard: bool,
+}
+
+impl Default for DockConfig {
+    fn default() -> Self {
+        Self {
+            docked_profile: None,
+            undocked_profile: None,
+            require_external_display: true,
+            require_ac: true,
+            require_external_keyboard: true,
+        }
+    }
+}
+
+/// The devices of a subsystem on the seat of the session.
+fn scan(subsystem: &str) -> io::Result<Vec<udev::Device>> {
+    let mut enumerator = udev::Enumerator::new()?;
+    enumerator.match_subsystem(subsystem)?;
+    Ok(enumerator
+        .scan_devices()?
+        .filter(|device| crate::seat::owns(device))
+        .collect())
+}
+
+fn attribute_is(device: &udev::Device, name: &str, value: &str) -> bool {
+    device.attribute_value(name) == Some(OsStr::new(value))
+}
+
+/// Whether a display is connected to a connector other than that of a built-in panel.
+fn external_display() -> io::Result<bool> {
+    Ok(scan("drm")?.iter().any(|device| {
+        // Connectors are named like `card1-HDMI-A-1`.
+        let name = device.sysname().to_string_lossy();
+        let Some((_, connector)) = name.split_once('-') else {
+            return false;
+        };
+
+        !INTERNAL_CONNECTORS
+            .iter()
+            .any(|internal| connector.starts_with(internal))
+            && attribute_is(device, "status", "connected")
+    }))
+}
+
+/// Whether the device is powered by AC, which it always is without a battery.
+pub fn on_ac() -> io::Result<bool> {
+    let supplies = scan("power_supply")?;
+    let plugged = supplies.iter().any(|device| {
+        (attribute_is(device, "type", "Mains") || attribute_is(device, "type", "USB"))
+            && attribute_is(device, "online", "1")
+    });
+    let battery = supplies.iter().any(|device| {
+        attribute_is(device, "type", "Battery") && !attribute_is(device, "scope", "Device")
+    });
+    Ok(plugged || !battery)
+}
+
+fn property_is_set(device: &udev::Device, name: &str) -> bool {
+    device.property_value(name) == Some(OsStr::new("1"))
+}
+
+/// The USB device which an input or hidraw device is an interface of.
+fn usb_device(device: &udev::Device) -> Option<PathBuf> {
+    let usb = device
+        .parent_with_subsystem_devtype("usb", "usb_device")
+        .ok()??;
+    Some(usb.syspath().to_owned())
+}
+
+/// Whether a USB or Bluetooth keyboard is connected.
+///
+/// Security keys type one-time passwords as a keyboard, and the receivers of wireless keyboards
+/// and mice, which stay plugged into laptops, have a keyboard next to their mouse, so USB
+/// devices which are also a security key or a mouse do not count.
+fn external_keyboard() -> io::Result<bool> {
+    let inputs = scan("input")?;
+    let tokens: BTreeSet<PathBuf> = scan("hidraw")?
+        .iter()
+        .filter(|device| {
+            property_is_set(device, "ID_FIDO_TOKEN") || property_is_set(device, "ID_SECURITY_TOKEN")
+        })
+        .filter_map(usb_device)
+        .collect();
+    let mice: BTreeSet<PathBuf> = inputs
+        .iter()
+        .filter(|device| property_is_set(device, "ID_INPUT_MOUSE"))
+        .filter_map(usb_device)
+        .collect();
+
+    Ok(inputs.iter().any(|device| {
+        if !property_is_set(device, "ID_INPUT_KEYBOARD") {
+            return false;
+        }
+
+        match device.property_value("ID_BUS").and_then(OsStr::to_str) {
+            Some("bluetooth") => true,
+            Some("usb") => {
+                usb_device(device).is_some_and(|usb| !tokens.contains(&usb) && !mice.contains(&usb))
+            }
+            _ => false,
+        }
+    }))
+}
+
+fn is_docked(config: &DockConfig) -> io::Result<bool> {
+    Ok((!config.require_external_display || external_display()?)
+        && (!config.require_ac || on_ac()?)
+        && (!config.require_external_keyboard || external_keyboard()?))
+}
+
+fn monitor_socket() -> io::Result<AsyncFd<udev::MonitorSocket>> {
+    let socket = udev::MonitorBuilder::new()?
+        .match_subsystem("drm")?
+        .match_subsystem("power_supply")?
+        .match_subsystem("input")?
+        .listen()?;
+    AsyncFd::new(socket)
+}
+
+/// Activate the docked profile while docked, and the undocked profile otherwise.
+pub async fn run(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<DockConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load dock config {err:?}");
+            return;
+        }
+    };
+
+    let mut socket = match monitor_socket() {
+        Ok(socket) => Some(socket),
+        Err(err) => {
+            eprintln!("Error creating udev dock monitor: {err}");
+            None
+        }
+    };
+
+    let mut settle: Option<Instant> = None;
+
+    loop {
+        if settle.is_none() {
+            match is_docked(&config) {
+                Ok(true) => crate::profiles::activate("dock", config.docked_profile.clone()),
+                Ok(false) => crate::profiles::activate("dock", config.undocked_profile.clone()),
+                Err(err) => eprintln!("Failed to check whether docked: {err}"),
+            }
+        }
+
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                crate::config::update(&mut config, &helper, &key);
+            }
+
+            guard = async { socket.as_ref()?.readable().await.ok() }, if socket.is_some() => {
+                let Some(mut guard) = guard else {
+                    eprintln!("Error reading udev dock monitor");
+                    socket = None;
+                    continue;
+                };
+
+                // The devices are scanned once they settle.
+                for _event in guard.get_inner().iter() {}
+                guard.clear_ready();
+                settle = Some(Instant::now() + SETTLE);
+            }
+
+            () = sleep_until(settle.unwrap_or_else(Instant::now)), if settle.is_some() => {
+                settle = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, OnceLock},
+};
+
+use tokio::sync::{
+    mpsc::{unbounded_channel, UnboundedSender},
+    oneshot,
+};
+use zbus::Connection;
+
+use crate::{systemd::SystemdManagerProxy, DBUS_PATH};
+
+/// Variables set by a module, where `None` unsets the variable.
+pub type Variables = Vec<(&'static str, Option<String>)>;
+
+type Reply = oneshot::Sender<Result<(), String>>;
+
+enum Request {
+    Set(&'static str, Variables, Option<Reply>),
+    Resync(Reply),
+}
+
+static TX: OnceLock<UnboundedSender<Request>> = OnceLock::new();
+
+/// Replace the session environment variables owned by `owner`.
+///
+/// Updates arriving together are exported in one pass, along with the variables of every
+/// other owner.
+pub fn set(owner: &'static str, vars: Variables) {
+    if let Some(tx) = TX.get() {
+        let _res = tx.send(Request::Set(owner, vars, None));
+    }
+}
+
+/// Like `set`, but wait until the variables were exported, with the error if that failed.
+pub async fn set_exported(owner: &'static str, vars: Variables) -> Result<(), String> {
+    let tx = TX.get().ok_or("environment is not ready")?;
+
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if tx.send(Request::Set(owner, vars, Some(reply_tx))).is_err() {
+        return Err("environment is not running".to_owned());
+    }
+    reply_rx
+        .await
+        .unwrap_or_else(|_| Err("environment is not running".to_owned()))
+}
+
+/// Set or unset variables in the environment of services started by systemd --user and
+/// D-Bus activation, which applications launched after this inherit.
+async fn export(conn: &Connection, vars: &BTreeMap<&str, Option<&str>>) -> zbus::Result<()> {
+    let systemd = SystemdManagerProxy::new(conn).await?;
+
+    let assignments: Vec<String> = vars
+        .iter()
+        .filter_map(|(name, value)| Some(format!("{name}={}", (*value)?)))
+        .collect();
+    if !assignments.is_empty() {
+        let assignments: Vec<&str> = assignments.iter().map(String::as_str).collect();
+        systemd.set_environment(&assignments).await?;
+    }
+
+    let unset: Vec<&str> = vars
+        .iter()
+        .filter(|(_, value)| value.is_none())
+        .map(|(name, _)| *name)
+        .collect();
+    if !unset.is_empty() {
+        systemd.unset_environment(&unset).await?;
+    }
+
+    // The activation environment cannot unset variables, so those are set empty, which most
+    // programs treat the same.
+    let activation: HashMap<&str, &str> = vars
+        .iter()
+        .map(|(name, value)| (*name, value.unwrap_or("")))
+        .collect();
+    zbus::fdo::DBusProxy::new(conn)
+        .await?
+        .update_activation_environment(activation)
+        .await?;
+
+    Ok(())
+}
+
+/// The session environment variables owned by the daemon.
+///
+/// The variables are behind a mutex rather than updated through the interface, since waiting to
+/// get the interface mutably would wait for callers of `Resync`, which wait for the export.
+#[derive(Default)]
+pub struct Environment {
+    variables: Mutex<HashMap<String, String>>,
+}
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.Environment")]
+impl Environment {
+    /// The variables exported to the session, without the ones which are unset.
+    #[dbus_interface(property)]
+    async fn variables(&self) -> HashMap<String, String> {
+        self.variables
+            .lock()
+            .map(|variables| variables.clone())
+            .unwrap_or_default()
+    }
+
+    /// Export all variables again, such as after a service manager restarted.
+    async fn resync(&self) -> zbus::fdo::Result<()> {
+        let tx = TX
+            .get()
+            .ok_or_else(|| zbus::fdo::Error::Failed("environment is not ready".to_owned()))?;
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        if tx.send(Request::Resync(reply_tx)).is_err() {
+            return Err(zbus::fdo::Error::Failed(
+                "environment is not running".to_owned(),
+            ));
+        }
+
+        match reply_rx.await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(err)) => Err(zbus::fdo::Error::Failed(err)),
+            Err(_) => Err(zbus::fdo::Error::Failed(
+                "environment is not running".to_owned(),
+            )),
+        }
+    }
+}
+
+/// Export the variables of all modules to the session whenever any of them change.
+pub async fn run(conn: Connection) {
+    let (tx, mut rx) = unbounded_channel();
+    if TX.set(tx).is_err() {
+        return;
+    }
+
+    let Ok(interface) = conn
+        .object_server()
+        .interface::<_, Environment>(DBUS_PATH)
+        .await
+    else {
+        return;
+    };
+
+    let mut owners: BTreeMap<&'static str, Variables> = BTreeMap::new();
+
+    while let Some(request) = rx.recv().await {
+        let mut changed = false;
+        let mut replies = Vec::new();
+
+        let mut request = Some(request);
+        while let Some(next) = request.take().or_else(|| rx.try_recv().ok()) {
+            match next {
+                Request::Set(owner, vars, reply) => {
+                    changed |= owners.get(owner) != Some(&vars);
+                    owners.insert(owner, vars);
+                    replies.extend(reply);
+                }
+                Request::Resync(reply) => replies.push(reply),
+            }
+        }
+
+        if !changed && replies.is_empty() {
+            continue;
+        }
+
+        let vars: BTreeMap<&str, Option<&str>> = owners
+            .values()
+            .flatten()
+            .map(|(name, value)| (*name, value.as_deref()))
+            .collect();
+
+        let res = export(&conn, &vars).await.map_err(|err| err.to_string());
+        if let Err(err) = &res {
+            eprintln!("Failed to export the session environment: {err}");
+        }
+
+        for reply in replies {
+            let _res = reply.send(res.clone());
+        }
+
+        let variables: HashMap<String, String> = vars
+            .into_iter()
+            .filter_map(|(name, value)| Some((name.to_owned(), value?.to_owned())))
+            .collect();
+
+        let environment = interface.get().await;
+        let changed = match environment.variables.lock() {
+            Ok(mut published) if *published != variables => {
+                *published = variables;
+                true
+            }
+            _ => false,
+        };
+        if changed {
+            _ = environment
+                .variables_changed(interface.signal_context())
+                .await;
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    path::Path,
+    sync::OnceLock,
+};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, ConfigSet, CosmicConfigEntry};
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc::Receiver, watch};
+use zbus::{Connection, InterfaceRef};
+
+use crate::DBUS_PATH;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Errors";
+
+/// Failures in a row before a failure is persistent, so that a failure which goes away when
+/// retried is only logged.
+const PERSISTENT_FAILURES: u32 = 3;
+
+static FAILURES: OnceLock<watch::Sender<BTreeMap<String, Failure>>> = OnceLock::new();
+
+/// Notices of persistent failures, which are otherwise only logged.
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct ErrorsConfig {
+    /// Notify when a feature keeps failing.
+    pub notify: bool,
+    /// The sources of the failures not to notify of again, which "Don't Show Again" adds to.
+    pub dismissed: BTreeSet<String>,
+}
+
+impl Default for ErrorsConfig {
+    fn default() -> Self {
+        Self {
+            notify: true,
+            dismissed: BTreeSet::new(),
+        }
+    }
+}
+
+/// The failures of a source since it last succeeded.
+#[derive(Clone, Debug, PartialEq)]
+struct Failure {
+    /// What does not work because of the failure, such as "Automatic Dark Mode Is Unavailable".
+    summary: String,
+    /// The latest error.
+    message: String,
+    count: u32,
+}
+
+fn failures() -> &'static watch::Sender<BTreeMap<String, Failure>> {
+    FAILURES.get_or_init(|| watch::channel(BTreeMap::new()).0)
+}
+
+/// Record a failure of `source`, such as `geoclue`, `export`, or `hook:<script>`. After a few in
+/// a row, it is published, and the user is notified with `summary` and `message`.
+pub fn fail(source: &str, summary: &str, message: impl Display) {
+    let message = message.to_string();
+    failures().send_modify(|failures| {
+        let failure = failures
+            .entry(source.to_owned())
+            .or_insert_with(|| Failure {
+                summary: String::new(),
+                message: String::new(),
+                count: 0,
+            });
+        failure.summary = summary.to_owned();
+        failure.message = message;
+        failure.count += 1;
+    });
+}
+
+/// Forget the failures of `source`, once it works again.
+pub fn succeed(source: &str) {
+    failures().send_if_modified(|failures| failures.remove(source).is_some());
+}
+
+/// The failures of `source` since it last succeeded.
+pub fn count(source: &str) -> u32 {
+    failures()
+        .borrow()
+        .get(source)
+        .map_or(0, |failure| failure.count)
+}
+
+/// Record the outcome of a hook script, which the hooks report once each script exits.
+pub fn hook_outcome(script: &Path, err: Option<String>) {
+    let source = format!("hook:{}", script.display());
+    match err {
+        Some(err) => fail(&source, "A Hook Script Keeps Failing", err),
+        None => succeed(&source),
+    }
+}
+
+/// Stop notifying of the failures of `source`.
+fn dismiss(source: String) {
+    let (helper, mut config) = match crate::config::load::<ErrorsConfig>(ID) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load errors config {err:?}");
+            return;
+        }
+    };
+
+    if config.dismissed.insert(source) {
+        if let Err(err) = helper.set("dismissed", &config.dismissed) {
+            eprintln!("Failed to save the dismissed errors {err:?}");
+        }
+    }
+}
+
+/// Notify of a persistent failure, offering to not show it again.
+fn notify(source: String, failure: &Failure) {
+    let notification = Notification::new()
+        .appname("")
+        .summary(&failure.summary)
+        .body(&failure.message)
+        .icon("dialog-warning-symbolic")
+        .action("dismiss", "Don't Show Again")
+        .finalize();
+
+    tokio::task::spawn_local(async move {
+        let Ok(handle) = notification.show_async().await else {
+            return;
+        };
+
+        if crate::notifications::wait_for_action(handle)
+            .await
+            .as_deref()
+            == Some("dismiss")
+        {
+            dismiss(source);
+        }
+    });
+}
+
+/// The persistent failures of the daemon, so that settings can explain why a feature does not
+/// work.
+#[derive(Default)]
+pub struct Errors {
+    errors: Vec<(String, String, String, u32)>,
+}
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.Errors")]
+impl Errors {
+    /// The persistent failures, as the source, the summary, the latest error, and the number of
+    /// failures in a row.
+    #[dbus_interface(property)]
+    async fn errors(&self) -> Vec<(String, String, String, u32)> {
+        self.errors.clone()
+    }
+
+    /// Stop notifying of the failures of a source.
+    async fn dismiss(&self, source: String) {
+        dismiss(source);
+    }
+}
+
+async fn publish(interface: &InterfaceRef<Errors>, persistent: &[(String, Failure)]) {
+    let errors: Vec<_> = persistent
+        .iter()
+        .map(|(source, failure)| {
+            let Failure {
+                summary,
+                message,
+                count,
+            } = failure.clone();
+            (source.clone(), summary, message, count)
+        })
+        .collect();
+
+    let mut status = interface.get_mut().await;
+    if status.errors != errors {
+        status.errors = errors;
+        _ = status.errors_changed(interface.signal_context()).await;
+    }
+}
+
+/// Publish the persistent failures, and notify of each one once until it recovers.
+pub async fn run(conn: Connection, mut rx: Receiver<String>) {
+    let Ok(interface) = conn.object_server().interface::<_, Errors>(DBUS_PATH).await else {
+        return;
+    };
+
+    let (helper, mut config) = match crate::config::load_async::<ErrorsConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load errors config {err:?}");
+            return;
+        }
+    };
+
+    let mut failures_rx = failures().subscribe();
+    let mut notified: BTreeSet<String> = BTreeSet::new();
+
+    loop {
+        let persistent: Vec<(String, Failure)> = failures_rx
+            .borrow_and_update()
+            .iter()
+            .filter(|(_, failure)| failure.count >= PERSISTENT_FAILURES)
+            .map(|(source, failure)| (source.clone(), failure.clone()))
+            .collect();
+        publish(&interface, &persistent).await;
+
+        // A failure is notified of again once it recovered and keeps failing again.
+        notified.retain(|source| {
+            persistent
+                .iter()
+                .any(|(persistent, _)| persistent == source)
+        });
+        for (source, failure) in &persistent {
+            if config.notify
+                && !config.dismissed.contains(source)
+                && notified.insert(source.clone())
+            {
+                notify(source.clone(), failure);
+            }
+        }
+
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                crate::config::update(&mut config, &helper, &key);
+            }
+
+            res = failures_rx.changed() => {
+                if res.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
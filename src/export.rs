@@ -0,0 +1,67 @@
+use std::{fmt::Display, sync::Mutex, time::Duration};
+
+use tokio::sync::mpsc::Receiver;
+
+/// Changes of the theme are exported once they stop for this long, so that dragging a color
+/// slider does not rewrite the files for every step.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// The failures of the current pass.
+static FAILURES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Log a failure of an exporter, which is reported once passes keep failing.
+pub fn failed(err: impl Display) {
+    let err = err.to_string();
+    eprintln!("{err}");
+    if let Ok(mut failures) = FAILURES.lock() {
+        failures.push(err);
+    }
+}
+
+/// Export the theme to the toolkits whenever it changes.
+///
+/// Every exporter runs in one pass with the same snapshot of the theme, and the Flatpak
+/// applications receive the files last, once they are all written.
+///
+/// `theme_rx` receives a message whenever the theme mode, either theme, the toolkit config, or
+/// the wallpaper changes.
+pub async fn run(mut theme_rx: Receiver<()>) {
+    while theme_rx.recv().await.is_some() {
+        loop {
+            tokio::select! {
+                changed = theme_rx.recv() => {
+                    if changed.is_none() {
+                        return;
+                    }
+                }
+                _ = tokio::time::sleep(DEBOUNCE) => break,
+            }
+        }
+
+        crate::stats::count("exports", "passes");
+        // Exporters also run on their own when their config changes, and only the failures of
+        // passes are reported.
+        if let Ok(mut failures) = FAILURES.lock() {
+            failures.clear();
+        }
+        let (is_dark, theme) = crate::theme::current();
+        crate::accent::theme_changed(&theme);
+        crate::contrast::theme_changed(&theme);
+        crate::qtct::theme_changed(&theme).await;
+        crate::palette::theme_changed(is_dark).await;
+        crate::greeter::theme_changed(is_dark, &theme).await;
+        crate::flatpak::theme_changed();
+
+        let failures = FAILURES
+            .lock()
+            .map(|mut failures| std::mem::take(&mut *failures));
+        match failures {
+            Ok(failures) if !failures.is_empty() => crate::errors::fail(
+                "export",
+                "The Theme Is Not Applied to Applications",
+                failures.join("\n"),
+            ),
+            _ => crate::errors::succeed("export"),
+        }
+    }
+}
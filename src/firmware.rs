@@ -0,0 +1,186 @@
+use std::{collections::BTreeSet, process::Stdio, time::Duration};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::mpsc::Receiver, time::Instant};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Firmware";
+
+/// Delay before the first check, to stay out of the way while the session starts.
+const STARTUP_DELAY: Duration = Duration::from_secs(10 * 60);
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Delay before trying again when a check was skipped because the system is on battery or the
+/// connection is metered.
+const RETRY_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct FirmwareConfig {
+    /// Check for firmware updates once a day.
+    pub check_updates: bool,
+    /// Only check while the system is on AC power.
+    pub ac_only: bool,
+    /// Application opened by the notification to install the updates.
+    pub updater_command: String,
+}
+
+impl Default for FirmwareConfig {
+    fn default() -> Self {
+        Self {
+            check_updates: true,
+            ac_only: true,
+            updater_command: "cosmic-store".to_owned(),
+        }
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Updates {
+    #[serde(default)]
+    devices: Vec<Device>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Device {
+    name: String,
+    #[serde(default)]
+    releases: Vec<Release>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Release {
+    version: String,
+}
+
+async fn fwupdmgr(args: &[&str]) -> std::io::Result<std::process::Output> {
+    tokio::process::Command::new("fwupdmgr")
+        .args(args)
+        .env("LC_ALL", "C")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await
+}
+
+/// Refresh the metadata and list the devices with an update, with the newest version of each.
+async fn available_updates() -> std::io::Result<Vec<(String, String)>> {
+    // Fails if the metadata is already up to date, which is fine.
+    let _res = fwupdmgr(&["refresh"]).await?;
+
+    let output = fwupdmgr(&["get-updates", "--json", "--no-unreported-check"]).await?;
+    // Nothing is printed if there are no updates.
+    let updates: Updates = serde_json::from_slice(&output.stdout).unwrap_or_default();
+
+    Ok(updates
+        .devices
+        .into_iter()
+        .filter_map(|device| {
+            let release = device.releases.into_iter().next()?;
+            Some((device.name, release.version))
+        })
+        .collect())
+}
+
+async fn on_battery() -> bool {
+    let Ok(conn) = crate::bus::system().await else {
+        return false;
+    };
+
+    let Ok(upower) = upower_dbus::UPowerProxy::new(&conn).await else {
+        return false;
+    };
+
+    upower.on_battery().await.unwrap_or(false)
+}
+
+fn notify(updates: Vec<(String, String)>, command: String) {
+    tokio::task::spawn_local(async move {
+        let body = match updates.as_slice() {
+            [(name, version)] => format!("Version {version} is available for {name}."),
+            _ => format!("Updates are available for {} devices.", updates.len()),
+        };
+
+        let Ok(handle) = Notification::new()
+            .appname("")
+            .summary("Firmware Updates Available")
+            .body(&body)
+            .icon("software-update-available-symbolic")
+            .action("update", "Update")
+            .show_async()
+            .await
+        else {
+            return;
+        };
+
+        if crate::notifications::wait_for_action(handle)
+            .await
+            .as_deref()
+            == Some("update")
+        {
+            if let Err(err) = tokio::process::Command::new(&command).spawn() {
+                eprintln!("Failed to open {command}: {err}");
+            }
+        }
+    });
+}
+
+/// Check for firmware updates through fwupd once a day, notifying about each update once.
+pub async fn monitor(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<FirmwareConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load firmware config {err:?}");
+            return;
+        }
+    };
+
+    let mut notified: BTreeSet<(String, String)> = BTreeSet::new();
+    let mut next_check = Instant::now() + STARTUP_DELAY;
+
+    loop {
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                crate::config::update(&mut config, &helper, &key);
+            }
+
+            _ = tokio::time::sleep_until(next_check) => {
+                next_check = Instant::now() + CHECK_INTERVAL;
+
+                if !config.check_updates {
+                    continue;
+                }
+
+                if crate::network::is_metered() || (config.ac_only && on_battery().await) {
+                    next_check = Instant::now() + RETRY_INTERVAL;
+                    continue;
+                }
+
+                let updates = match available_updates().await {
+                    Ok(updates) => updates,
+                    Err(err) => {
+                        eprintln!("Failed to check for firmware updates: {err}");
+                        continue;
+                    }
+                };
+
+                let new: Vec<_> = updates
+                    .into_iter()
+                    .filter(|update| notified.insert(update.clone()))
+                    .collect();
+
+                if !new.is_empty() {
+                    notify(new, config.updater_command.clone());
+                }
+            }
+        }
+    }
+}
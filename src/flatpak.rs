@@ -0,0 +1,213 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Flatpak";
+
+static CONFIG: Mutex<Option<FlatpakConfig>> = Mutex::new(None);
+
+/// Files and directories of the config directory with theme settings, relative to it.
+const THEME_FILES: &[&str] = &[
+    "gtk-3.0/gtk.css",
+    "gtk-3.0/settings.ini",
+    "gtk-3.0/cosmic-accent.css",
+    "gtk-3.0/cosmic-contrast.css",
+    "gtk-3.0/cosmic",
+    "gtk-4.0/gtk.css",
+    "gtk-4.0/settings.ini",
+    "gtk-4.0/cosmic-accent.css",
+    "gtk-4.0/cosmic-contrast.css",
+    "gtk-4.0/cosmic",
+    "kdeglobals",
+    "qt5ct",
+    "qt6ct",
+];
+
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct FlatpakConfig {
+    /// Copy the theme files into the config directory of the allowed applications.
+    pub export_theme: bool,
+    /// IDs of the Flatpak applications which receive the theme files.
+    pub apps: Vec<String>,
+}
+
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct FlatpakState {
+    /// Applications which the theme files were copied to, to remove them from when disallowed.
+    pub exported: Vec<String>,
+}
+
+/// The config directory of a Flatpak application, `~/.var/app/<id>/config`.
+fn app_config_dir(app: &str) -> Option<PathBuf> {
+    // An ID with a separator would reach outside of `~/.var/app`.
+    if app.is_empty() || app.contains('/') || app.starts_with('.') {
+        return None;
+    }
+
+    dirs::home_dir().map(|home| home.join(".var/app").join(app).join("config"))
+}
+
+/// Copy a file, or the files of a directory, leaving files with the same contents untouched.
+fn copy(from: &Path, to: &Path) -> io::Result<()> {
+    if from.is_dir() {
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        return Ok(());
+    }
+
+    match fs::read_to_string(from) {
+        Ok(contents) => crate::files::write_atomic(to, &contents),
+        // Stale copies of files that are no longer exported are removed.
+        Err(err) if err.kind() == io::ErrorKind::NotFound => remove(to),
+        Err(err) => Err(err),
+    }
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    let res = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+
+    match res {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+fn export_app(config_dir: &Path, app: &str) {
+    let Some(app_dir) = app_config_dir(app) else {
+        eprintln!("Invalid Flatpak application ID {app:?}");
+        return;
+    };
+
+    // The application has not been run yet, or was uninstalled.
+    if !app_dir.parent().is_some_and(Path::is_dir) {
+        return;
+    }
+
+    for file in THEME_FILES {
+        if let Err(err) = copy(&config_dir.join(file), &app_dir.join(file)) {
+            crate::export::failed(format!(
+                "Failed to copy {file} to Flatpak application {app}: {err}"
+            ));
+        }
+    }
+}
+
+fn clean_app(app: &str) {
+    let Some(app_dir) = app_config_dir(app) else {
+        return;
+    };
+
+    for file in THEME_FILES {
+        if let Err(err) = remove(&app_dir.join(file)) {
+            eprintln!("Failed to remove {file} from Flatpak application {app}: {err}");
+        }
+    }
+}
+
+/// Copy the theme files to the allowed applications, and remove them from applications which
+/// are no longer allowed.
+fn export(config: &FlatpakConfig, state_helper: &cosmic_config::Config, state: &mut FlatpakState) {
+    let allowed: &[String] = if config.export_theme {
+        &config.apps
+    } else {
+        &[]
+    };
+
+    for app in state.exported.iter().filter(|app| !allowed.contains(app)) {
+        clean_app(app);
+    }
+
+    if let Some(config_dir) = dirs::config_dir() {
+        for app in allowed {
+            export_app(&config_dir, app);
+        }
+    }
+
+    if state.exported != allowed {
+        if let Err(err) = state.set_exported(state_helper, allowed.to_vec()) {
+            eprintln!("Failed to save the Flatpak applications with the theme {err:?}");
+        }
+        state.exported = allowed.to_vec();
+    }
+}
+
+/// Stop copying the theme files to Flatpak applications, and remove them from every application
+/// which received them.
+pub fn reset() -> anyhow::Result<()> {
+    let (helper, config) = crate::config::load::<FlatpakConfig>(ID)?;
+    config.set_export_theme(&helper, false)?;
+
+    let (state_helper, state) = crate::config::load_state::<FlatpakState>(ID)?;
+    for app in &state.exported {
+        clean_app(app);
+    }
+    state.set_exported(&state_helper, Vec::new())?;
+    Ok(())
+}
+
+/// Copy the theme files to the allowed applications after they were exported.
+pub fn theme_changed() {
+    let config = CONFIG.lock().ok().and_then(|config| config.clone());
+    let Some(config) = config.filter(|config| config.export_theme) else {
+        return;
+    };
+
+    if let Some(config_dir) = dirs::config_dir() {
+        for app in &config.apps {
+            export_app(&config_dir, app);
+        }
+    }
+}
+
+/// Copy the exported theme into the sandboxes of the allowed Flatpak applications, which cannot
+/// read the config directory of the host.
+pub async fn run(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<FlatpakConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load Flatpak config {err:?}");
+            return;
+        }
+    };
+
+    let (state_helper, mut state) = match crate::config::load_state_async::<FlatpakState>(ID).await
+    {
+        Ok(state) => state,
+        Err(err) => {
+            eprintln!("Failed to load Flatpak state {err:?}");
+            return;
+        }
+    };
+
+    if let Ok(mut global) = CONFIG.lock() {
+        *global = Some(config.clone());
+    }
+
+    export(&config, &state_helper, &mut state);
+
+    while let Some(key) = rx.recv().await {
+        if crate::config::update(&mut config, &helper, &key).is_empty() {
+            continue;
+        }
+
+        if let Ok(mut global) = CONFIG.lock() {
+            *global = Some(config.clone());
+        }
+
+        export(&config, &state_helper, &mut state);
+    }
+}
@@ -0,0 +1,353 @@
+use std::{io, path::PathBuf, process::Stdio};
+
+use anyhow::{bail, Context};
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::{io::AsyncWriteExt, sync::mpsc::Receiver};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Fonts";
+
+const FONTCONFIG_FILE: &str = "50-cosmic.conf";
+
+/// Text scales outside of this range make the desktop unusable.
+const MIN_TEXT_SCALE: f64 = 0.5;
+const MAX_TEXT_SCALE: f64 = 3.0;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub enum Antialiasing {
+    None,
+    Grayscale,
+    Subpixel,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub enum Hinting {
+    None,
+    Slight,
+    Medium,
+    Full,
+}
+
+/// Order of the subpixels of the display, used by subpixel antialiasing.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub enum SubpixelOrder {
+    Rgb,
+    Bgr,
+    Vrgb,
+    Vbgr,
+}
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct FontsConfig {
+    pub antialiasing: Antialiasing,
+    pub hinting: Hinting,
+    pub subpixel_order: SubpixelOrder,
+    /// Scale of text relative to 96 DPI, from 0.5 to 3.
+    pub text_scale: f64,
+}
+
+impl Default for FontsConfig {
+    fn default() -> Self {
+        Self {
+            antialiasing: Antialiasing::Grayscale,
+            hinting: Hinting::Slight,
+            subpixel_order: SubpixelOrder::Rgb,
+            text_scale: 1.0,
+        }
+    }
+}
+
+impl FontsConfig {
+    fn text_scale(&self) -> f64 {
+        if self.text_scale.is_finite() {
+            self.text_scale.clamp(MIN_TEXT_SCALE, MAX_TEXT_SCALE)
+        } else {
+            1.0
+        }
+    }
+
+    fn dpi(&self) -> u32 {
+        (96.0 * self.text_scale()).round() as u32
+    }
+
+    fn hint_style(&self) -> &'static str {
+        match self.hinting {
+            Hinting::None => "hintnone",
+            Hinting::Slight => "hintslight",
+            Hinting::Medium => "hintmedium",
+            Hinting::Full => "hintfull",
+        }
+    }
+
+    /// The subpixel order, or `none` unless subpixel antialiasing is used.
+    fn rgba(&self) -> &'static str {
+        if self.antialiasing != Antialiasing::Subpixel {
+            return "none";
+        }
+
+        match self.subpixel_order {
+            SubpixelOrder::Rgb => "rgb",
+            SubpixelOrder::Bgr => "bgr",
+            SubpixelOrder::Vrgb => "vrgb",
+            SubpixelOrder::Vbgr => "vbgr",
+        }
+    }
+
+    fn fontconfig(&self) -> String {
+        let antialias = self.antialiasing != Antialiasing::None;
+        let hinting = self.hinting != Hinting::None;
+        let lcd_filter = if self.rgba() == "none" {
+            "lcdnone"
+        } else {
+            "lcddefault"
+        };
+
+        format!(
+            r#"<?xml version="1.0"?>
+<!DOCTYPE fontconfig SYSTEM "urn:fontconfig:fonts.dtd">
+<!-- Generated by cosmic-settings-daemon from the font settings, changes will be overwritten. -->
+<fontconfig>
+  <match target="pattern">
+    <edit name="dpi" mode="assign"><double>{dpi}</double></edit>
+  </match>
+  <match target="font">
+    <edit name="antialias" mode="assign"><bool>{antialias}</bool></edit>
+    <edit name="hinting" mode="assign"><bool>{hinting}</bool></edit>
+    <edit name="hintstyle" mode="assign"><const>{hint_style}</const></edit>
+    <edit name="rgba" mode="assign"><const>{rgba}</const></edit>
+    <edit name="lcdfilter" mode="assign"><const>{lcd_filter}</const></edit>
+  </match>
+</fontconfig>
+"#,
+            dpi = self.dpi(),
+            hint_style = self.hint_style(),
+            rgba = self.rgba(),
+        )
+    }
+
+    fn xresources(&self) -> String {
+        format!(
+            "Xft.dpi: {}\nXft.antialias: {}\nXft.hinting: {}\nXft.hintstyle: {}\nXft.rgba: {}\n",
+            self.dpi(),
+            u8::from(self.antialiasing != Antialiasing::None),
+            u8::from(self.hinting != Hinting::None),
+            self.hint_style(),
+            self.rgba(),
+        )
+    }
+}
+
+fn fontconfig_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("fontconfig").join("conf.d").join(FONTCONFIG_FILE))
+}
+
+/// Merge the Xft resources into the resource database of XWayland, which X11 applications
+/// read when they start.
+async fn merge_xresources(resources: String) -> io::Result<()> {
+    if std::env::var_os("DISPLAY").is_none() {
+        return Ok(());
+    }
+
+    let mut child = tokio::process::Command::new("xrdb")
+        .args(["-merge", "-nocpp"])
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(resources.as_bytes()).await?;
+    }
+
+    child.wait().await?;
+    Ok(())
+}
+
+/// The font rendering settings and text scale, as GNOME's interface settings name them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GnomeFontRendering {
+    pub antialiasing: &'static str,
+    pub hinting: &'static str,
+    pub rgba_order: &'static str,
+    pub text_scaling_factor: f64,
+}
+
+fn gnome_font_rendering(config: &FontsConfig) -> GnomeFontRendering {
+    let antialiasing = match config.antialiasing {
+        Antialiasing::None => "none",
+        Antialiasing::Grayscale => "grayscale",
+        Antialiasing::Subpixel => "rgba",
+    };
+    let hinting = match config.hinting {
+        Hinting::None => "none",
+        Hinting::Slight => "slight",
+        Hinting::Medium => "medium",
+        Hinting::Full => "full",
+    };
+    let rgba_order = match config.subpixel_order {
+        SubpixelOrder::Rgb => "rgb",
+        SubpixelOrder::Bgr => "bgr",
+        SubpixelOrder::Vrgb => "vrgb",
+        SubpixelOrder::Vbgr => "vbgr",
+    };
+
+    GnomeFontRendering {
+        antialiasing,
+        hinting,
+        rgba_order,
+        text_scaling_factor: config.text_scale(),
+    }
+}
+
+/// The current font rendering settings and text scale for GNOME, read from the config, so that
+/// they are current even before this module sees a change.
+pub fn current_gnome_font_rendering() -> GnomeFontRendering {
+    let config = cosmic_config::Config::new(ID, FontsConfig::VERSION)
+        .ok()
+        .map(|helper| match FontsConfig::get_entry(&helper) {
+            Ok(config) => config,
+            Err((_, config)) => config,
+        })
+        .unwrap_or_default();
+    gnome_font_rendering(&config)
+}
+
+/// Mirror the settings to GNOME, whose XSettings providers serve them to GTK applications.
+fn set_gnome_font_rendering(config: &FontsConfig) {
+    let rendering = gnome_font_rendering(config);
+    let schema = "org.gnome.desktop.interface";
+    crate::gsettings::set(vec![
+        (
+            schema,
+            "font-antialiasing",
+            rendering.antialiasing.to_owned(),
+        ),
+        (schema, "font-hinting", rendering.hinting.to_owned()),
+        (schema, "font-rgba-order", rendering.rgba_order.to_owned()),
+        (
+            schema,
+            "text-scaling-factor",
+            rendering.text_scaling_factor.to_string(),
+        ),
+    ]);
+}
+
+/// Write the settings to GTK's `settings.ini`, for GTK applications without an XSettings
+/// provider, such as on Wayland without GNOME settings.
+fn set_gtk_font_rendering(config: &FontsConfig) {
+    let Some(config_dir) = dirs::config_dir() else {
+        return;
+    };
+
+    let settings = [
+        // In 1024ths of a dot per inch.
+        ("gtk-xft-dpi", (config.dpi() * 1024).to_string()),
+        (
+            "gtk-xft-antialias",
+            i32::from(config.antialiasing != Antialiasing::None).to_string(),
+        ),
+        (
+            "gtk-xft-hinting",
+            i32::from(config.hinting != Hinting::None).to_string(),
+        ),
+        ("gtk-xft-hintstyle", config.hint_style().to_owned()),
+        ("gtk-xft-rgba", config.rgba().to_owned()),
+    ];
+
+    let values: Vec<(&str, &str, &str)> = settings
+        .iter()
+        .map(|(key, value)| ("Settings", *key, value.as_str()))
+        .collect();
+
+    for gtk in ["gtk-3.0", "gtk-4.0"] {
+        let path = config_dir.join(gtk).join("settings.ini");
+        if let Err(err) = crate::files::set_ini_values(&path, &values) {
+            eprintln!("Failed to write {}: {err}", path.display());
+        }
+    }
+}
+
+/// Restore the default font rendering and text scale, and remove their overrides from
+/// fontconfig, GTK's `settings.ini`, and GNOME's settings.
+///
+/// A running daemon exports the defaults again once it sees the config change.
+pub fn reset() -> anyhow::Result<()> {
+    let (helper, _) = crate::config::load::<FontsConfig>(ID)?;
+    FontsConfig::default().write_entry(&helper)?;
+
+    if let Some(path) = fontconfig_path() {
+        match std::fs::remove_file(&path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                return Err(err).with_context(|| format!("failed to remove {}", path.display()));
+            }
+            _ => (),
+        }
+    }
+
+    let config_dir = dirs::config_dir().context("no config directory")?;
+    let keys = [
+        "gtk-xft-dpi",
+        "gtk-xft-antialias",
+        "gtk-xft-hinting",
+        "gtk-xft-hintstyle",
+        "gtk-xft-rgba",
+    ]
+    .map(|key| ("Settings", key));
+    for gtk in ["gtk-3.0", "gtk-4.0"] {
+        let path = config_dir.join(gtk).join("settings.ini");
+        crate::files::remove_ini_values(&path, &keys)
+            .with_context(|| format!("failed to clean up {}", path.display()))?;
+    }
+
+    // Blocking, as the reset also runs from the command line without a runtime.
+    for key in [
+        "font-antialiasing",
+        "font-hinting",
+        "font-rgba-order",
+        "text-scaling-factor",
+    ] {
+        let status = std::process::Command::new("gsettings")
+            .args(["reset", "org.gnome.desktop.interface", key])
+            .status()?;
+        if !status.success() {
+            bail!("failed to reset {key} of GNOME: gsettings {status}");
+        }
+    }
+    Ok(())
+}
+
+async fn apply(config: &FontsConfig) {
+    // Renaming the drop-in into place also changes the mtime of its directory, which running
+    // fontconfig clients check to notice that their configuration is out of date.
+    if let Some(path) = fontconfig_path() {
+        if let Err(err) = crate::files::write_atomic(&path, &config.fontconfig()) {
+            eprintln!("Failed to write {}: {err}", path.display());
+        }
+    }
+
+    if let Err(err) = merge_xresources(config.xresources()).await {
+        eprintln!("Failed to merge Xft resources: {err}");
+    }
+
+    set_gnome_font_rendering(config);
+    set_gtk_font_rendering(config);
+}
+
+/// Export the font rendering settings and text scale to fontconfig, X resources, GTK, and
+/// GNOME settings, so that Wayland and X11 applications render text the same way.
+pub async fn run(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<FontsConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load fonts config {err:?}");
+            return;
+        }
+    };
+
+    apply(&config).await;
+
+    while let Some(key) = rx.recv().await {
+        if !crate::config::update(&mut config, &helper, &key).is_empty() {
+            apply(&config).await;
+        }
+    }
+}
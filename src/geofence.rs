@@ -0,0 +1,85 @@
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use cosmic_settings_daemon_lib::geofence::{region, Region};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+
+use crate::schedule::LocationReceiver;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Geofence";
+
+/// Settings profiles for regions.
+///
+/// The location is only compared with the regions, and is never logged, stored, or published.
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct GeofenceConfig {
+    /// Activate the profile of the region the device is in. This also needs location services.
+    pub enabled: bool,
+    pub regions: Vec<Region>,
+}
+
+impl crate::demand::Feature for GeofenceConfig {
+    fn enabled(&self) -> bool {
+        self.enabled && !self.regions.is_empty()
+    }
+}
+
+/// Activate the profile of the region the device is in, while enabled.
+pub async fn run(mut rx: Receiver<String>, mut location_rx: LocationReceiver) {
+    let (helper, mut config) = match crate::config::load_async::<GeofenceConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load geofence config {err:?}");
+            return;
+        }
+    };
+
+    let mut location_state = crate::location::subscribe();
+    let mut location_open = true;
+    let mut current: Option<String> = None;
+    let mut reported = false;
+
+    loop {
+        let wanted = config.enabled && !config.regions.is_empty();
+        crate::location::want("geofence", wanted);
+
+        let (loaded, enabled) = {
+            let state = location_state.borrow_and_update();
+            (state.loaded(), state.enabled())
+        };
+
+        // Until location services are known to be on or off, the profile from before the daemon
+        // restarted stays active.
+        if !wanted || (loaded && !enabled) {
+            current = None;
+            crate::profiles::activate("geofence", None);
+        } else if let Some(location) = *location_rx.borrow_and_update() {
+            let region = region(&config.regions, location, current.as_deref());
+            let name = region.map(|region| region.name.clone());
+            if name != current || !reported {
+                current = name;
+                reported = true;
+                crate::profiles::activate("geofence", region.map(|region| region.profile.clone()));
+            }
+        }
+
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                crate::config::update(&mut config, &helper, &key);
+            }
+
+            res = location_rx.changed(), if location_open => {
+                location_open = res.is_ok();
+            }
+
+            res = location_state.changed() => {
+                if res.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
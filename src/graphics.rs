@@ -0,0 +1,162 @@
+use notify_rust::Notification;
+use zbus::{Connection, SignalContext};
+
+use crate::{system76_power::PowerDaemonProxy, DBUS_PATH};
+
+const MODES: [&str; 4] = ["integrated", "hybrid", "nvidia", "compute"];
+
+/// Coordinates graphics mode switches with system76-power.
+pub struct Graphics {
+    power_daemon: Option<PowerDaemonProxy<'static>>,
+    switchable: bool,
+    /// The mode the system is currently running in.
+    mode: String,
+    /// The mode which will be active after the next restart.
+    pending_mode: String,
+    /// The mode to switch to when the session ends.
+    scheduled_mode: String,
+}
+
+impl Graphics {
+    pub async fn new() -> Self {
+        let power_daemon = match crate::bus::system().await {
+            Ok(conn) => PowerDaemonProxy::new(&conn).await.ok(),
+            Err(_) => None,
+        };
+
+        let (switchable, mode) = match power_daemon.as_ref() {
+            Some(proxy) => (
+                proxy.get_switchable().await.unwrap_or(false),
+                proxy.get_graphics().await.unwrap_or_default(),
+            ),
+            None => (false, String::new()),
+        };
+
+        Self {
+            power_daemon,
+            switchable,
+            mode,
+            pending_mode: String::new(),
+            scheduled_mode: String::new(),
+        }
+    }
+
+    async fn switch(&mut self, mode: &str) -> zbus::fdo::Result<()> {
+        let Some(proxy) = self.power_daemon.as_ref() else {
+            return Err(zbus::fdo::Error::NotSupported(
+                "system76-power is not available".to_owned(),
+            ));
+        };
+
+        proxy
+            .set_graphics(mode)
+            .await
+            .map_err(|err| zbus::fdo::Error::Failed(err.to_string()))?;
+
+        self.pending_mode = if mode == self.mode {
+            String::new()
+        } else {
+            mode.to_owned()
+        };
+
+        Ok(())
+    }
+}
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.Graphics")]
+impl Graphics {
+    #[dbus_interface(property)]
+    async fn switchable(&self) -> bool {
+        self.switchable
+    }
+
+    #[dbus_interface(property)]
+    async fn mode(&self) -> String {
+        self.mode.clone()
+    }
+
+    /// Empty unless a switch is waiting for a restart.
+    #[dbus_interface(property)]
+    async fn pending_mode(&self) -> String {
+        self.pending_mode.clone()
+    }
+
+    /// Empty unless a switch is waiting for the session to end.
+    #[dbus_interface(property)]
+    async fn scheduled_mode(&self) -> String {
+        self.scheduled_mode.clone()
+    }
+
+    /// Switch to `mode` now, or when the session ends if `at_logout` is set.
+    async fn set_mode(
+        &mut self,
+        mode: String,
+        at_logout: bool,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> zbus::fdo::Result<()> {
+        if !MODES.contains(&mode.as_str()) {
+            return Err(zbus::fdo::Error::InvalidArgs(format!(
+                "unknown graphics mode {mode}"
+            )));
+        }
+
+        if at_logout {
+            self.scheduled_mode = mode.clone();
+            _ = self.scheduled_mode_changed(&ctxt).await;
+
+            let _res = Notification::new()
+                .appname("")
+                .summary("Graphics Switch Scheduled")
+                .body(&format!(
+                    "The system will switch to {mode} graphics when you log out."
+                ))
+                .icon("video-display-symbolic")
+                .show_async()
+                .await;
+
+            return Ok(());
+        }
+
+        self.switch(&mode).await?;
+        if !self.scheduled_mode.is_empty() {
+            self.scheduled_mode.clear();
+            _ = self.scheduled_mode_changed(&ctxt).await;
+        }
+        _ = self.pending_mode_changed(&ctxt).await;
+
+        if !self.pending_mode.is_empty() {
+            let _res = Notification::new()
+                .appname("")
+                .summary("Restart Required")
+                .body(&format!(
+                    "Restart the computer to finish switching to {mode} graphics."
+                ))
+                .icon("video-display-symbolic")
+                .show_async()
+                .await;
+        }
+
+        Ok(())
+    }
+}
+
+/// Apply a graphics switch which was scheduled for the end of the session.
+pub async fn logout(conn: &Connection) {
+    let Ok(interface) = conn
+        .object_server()
+        .interface::<_, Graphics>(DBUS_PATH)
+        .await
+    else {
+        return;
+    };
+
+    let mut graphics = interface.get_mut().await;
+    let mode = std::mem::take(&mut graphics.scheduled_mode);
+    if mode.is_empty() {
+        return;
+    }
+
+    if let Err(err) = graphics.switch(&mode).await {
+        eprintln!("Failed to switch to {mode} graphics at logout: {err}");
+    }
+}
@@ -0,0 +1,272 @@
+use std::{
+    ffi::{CStr, CString, OsStr},
+    io::{self, BufRead, Read},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    process::{ExitCode, Stdio},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use anyhow::{bail, Context};
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, ConfigSet, CosmicConfigEntry};
+use cosmic_theme::{Theme, ThemeMode};
+use serde::{Deserialize, Serialize};
+use tokio::{io::AsyncWriteExt, sync::mpsc::Receiver};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Greeter";
+
+/// The user cosmic-greeter runs as, whose own config is the appearance of the login screen.
+const GREETER_USER: &str = "cosmic-greeter";
+
+/// Wallpapers larger than this are not shared, so that the helper does not read without bound.
+const MAX_WALLPAPER_SIZE: u64 = 32 * 1024 * 1024;
+
+/// The copy of the wallpaper in the data directory of the greeter, which cannot read the home
+/// directory of the user.
+const WALLPAPER_FILE: &str = "cosmic-settings-daemon/wallpaper";
+
+static CONFIG: Mutex<Option<GreeterConfig>> = Mutex::new(None);
+
+/// The appearance last shared, so that the helper only runs again once it has changed.
+static SHARED: Mutex<Option<Appearance>> = Mutex::new(None);
+
+/// Sharing the appearance of the session with the login screen, so that it matches the last
+/// session.
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct GreeterConfig {
+    /// Share the theme mode, the accent color, and the wallpaper of all outputs with
+    /// cosmic-greeter whenever they change, including on each automatic switch of the theme
+    /// mode. The `greeter-sync` helper writes them to the config of the greeter user as root,
+    /// which polkit allows the active session to run.
+    pub enabled: bool,
+}
+
+/// The appearance shared with the greeter, which the helper reads from the first line of its
+/// input, followed by the image of the wallpaper.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct Appearance {
+    is_dark: bool,
+    accent: [u8; 3],
+    /// The size of the image after the first line, or 0 to keep the wallpaper of the greeter,
+    /// such as while the wallpaper is a color.
+    wallpaper_size: u64,
+    /// The wallpaper and when it was modified, which is only compared.
+    #[serde(skip)]
+    wallpaper: Option<(PathBuf, Option<SystemTime>)>,
+}
+
+/// Run the helper as root through pkexec, and send it the appearance and the wallpaper.
+async fn share(appearance: &Appearance, image: &[u8]) -> io::Result<()> {
+    let mut input = serde_json::to_vec(appearance)?;
+    input.push(b'\n');
+    input.extend_from_slice(image);
+
+    let mut child = tokio::process::Command::new("pkexec")
+        .arg(std::env::current_exe()?)
+        .arg("greeter-sync")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(&input).await?;
+    }
+
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "greeter-sync failed with {status}"
+        )));
+    }
+    Ok(())
+}
+
+/// Share the appearance with the greeter after the theme or the wallpaper changed, if enabled
+/// and the appearance is not shared already.
+pub async fn theme_changed(is_dark: bool, theme: &Theme) {
+    let config = CONFIG.lock().ok().and_then(|config| config.clone());
+    if !config.is_some_and(|config| config.enabled) {
+        return;
+    }
+
+    let wallpaper = crate::palette::wallpaper().and_then(|path| {
+        let metadata = std::fs::metadata(&path).ok()?;
+        (metadata.len() <= MAX_WALLPAPER_SIZE).then(|| (path, metadata.modified().ok()))
+    });
+    let mut appearance = Appearance {
+        is_dark,
+        accent: crate::accent::theme_accent(theme),
+        wallpaper_size: 0,
+        wallpaper,
+    };
+
+    if SHARED
+        .lock()
+        .is_ok_and(|shared| shared.as_ref() == Some(&appearance))
+    {
+        return;
+    }
+
+    let image = match &appearance.wallpaper {
+        Some((path, _)) => tokio::fs::read(path).await.unwrap_or_default(),
+        None => Vec::new(),
+    };
+    appearance.wallpaper_size = image.len() as u64;
+
+    match share(&appearance, &image).await {
+        Ok(()) => {
+            appearance.wallpaper_size = 0;
+            if let Ok(mut shared) = SHARED.lock() {
+                *shared = Some(appearance);
+            }
+        }
+        Err(err) => crate::export::failed(format!(
+            "Failed to share the appearance with the greeter: {err}"
+        )),
+    }
+}
+
+/// Share the appearance with the greeter once enabled, and again whenever the config changes.
+pub async fn run(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<GreeterConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load greeter config {err:?}");
+            return;
+        }
+    };
+
+    loop {
+        if let Ok(mut global) = CONFIG.lock() {
+            *global = Some(config.clone());
+        }
+        // The appearance is shared again once sharing is enabled again.
+        if let Ok(mut shared) = SHARED.lock() {
+            *shared = None;
+        }
+
+        let (is_dark, theme) = crate::theme::current();
+        theme_changed(is_dark, &theme).await;
+
+        loop {
+            let Some(key) = rx.recv().await else {
+                return;
+            };
+
+            if !crate::config::update(&mut config, &helper, &key).is_empty() {
+                break;
+            }
+        }
+    }
+}
+
+/// The user ID, group ID, and home directory of the greeter user.
+fn greeter_user() -> anyhow::Result<(libc::uid_t, libc::gid_t, PathBuf)> {
+    let name = CString::new(GREETER_USER)?;
+    // SAFETY: The name is a C string, and the entry is copied before the user database is read
+    // again.
+    let passwd = unsafe { libc::getpwnam(name.as_ptr()) };
+    if passwd.is_null() {
+        bail!("there is no {GREETER_USER} user");
+    }
+
+    // SAFETY: getpwnam returned an entry, whose home directory is a C string.
+    let (uid, gid, home) = unsafe {
+        let passwd = &*passwd;
+        (passwd.pw_uid, passwd.pw_gid, CStr::from_ptr(passwd.pw_dir))
+    };
+    Ok((uid, gid, PathBuf::from(OsStr::from_bytes(home.to_bytes()))))
+}
+
+/// Become the greeter user, so that the files of the greeter are written with its permissions,
+/// and links it placed in its home directory lead nowhere it could not write to itself.
+fn become_user(uid: libc::uid_t, gid: libc::gid_t, home: &Path) -> io::Result<()> {
+    // SAFETY: The group list is empty, and no other pointers are passed.
+    let failed = unsafe {
+        libc::setgroups(0, std::ptr::null()) != 0
+            || libc::setgid(gid) != 0
+            || libc::setuid(uid) != 0
+    };
+    if failed {
+        return Err(io::Error::last_os_error());
+    }
+
+    // The configs are found through the environment, which is only read from this thread.
+    std::env::set_var("HOME", home);
+    std::env::set_var("XDG_CONFIG_HOME", home.join(".config"));
+    std::env::set_var("XDG_DATA_HOME", home.join(".local/share"));
+    Ok(())
+}
+
+/// Write the appearance to the config of the greeter, which reads it like a session does.
+fn write(home: &Path, appearance: &Appearance, image: &[u8]) -> anyhow::Result<()> {
+    ThemeMode::config()?.set("is_dark", appearance.is_dark)?;
+    crate::palette::set_accent(appearance.is_dark, appearance.accent)
+        .context("failed to set the accent color")?;
+
+    if !image.is_empty() {
+        let path = home.join(".local/share").join(WALLPAPER_FILE);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, image)?;
+
+        let entry = crate::wallpaper::Entry::new("all".to_owned(), path);
+        cosmic_config::Config::new(crate::wallpaper::BACKGROUND_ID, 1)?.set("all", entry)?;
+    }
+    Ok(())
+}
+
+fn sync() -> anyhow::Result<()> {
+    // SAFETY: geteuid cannot fail.
+    let euid = unsafe { libc::geteuid() };
+    if euid != 0 || std::env::var_os("PKEXEC_UID").is_none() {
+        bail!("greeter-sync is run as root through pkexec");
+    }
+
+    let mut stdin = io::stdin().lock();
+    let mut header = String::new();
+    (&mut stdin).take(4096).read_line(&mut header)?;
+    let appearance: Appearance =
+        serde_json::from_str(&header).context("failed to read the appearance")?;
+
+    if appearance.wallpaper_size > MAX_WALLPAPER_SIZE {
+        bail!("the wallpaper is larger than {MAX_WALLPAPER_SIZE} bytes");
+    }
+    let mut image = Vec::new();
+    stdin
+        .take(appearance.wallpaper_size)
+        .read_to_end(&mut image)?;
+    if image.len() as u64 != appearance.wallpaper_size {
+        bail!("the wallpaper was cut off");
+    }
+
+    // Only images which the greeter can show are written for it.
+    if !image.is_empty() {
+        match image::guess_format(&image) {
+            Ok(image::ImageFormat::Jpeg | image::ImageFormat::Png | image::ImageFormat::WebP) => (),
+            _ => bail!("the wallpaper is not a JPEG, PNG, or WebP image"),
+        }
+    }
+
+    let (uid, gid, home) = greeter_user()?;
+    become_user(uid, gid, &home).context("failed to become the greeter user")?;
+    write(&home, &appearance, &image)
+}
+
+/// The `greeter-sync` helper, which pkexec runs as root for the session sharing its appearance.
+///
+/// The helper trusts nothing of its input but its layout: the theme mode and accent color are
+/// plain values, and the wallpaper is passed as the image itself, rather than as a path which
+/// root would otherwise read on behalf of the user.
+pub fn cli() -> ExitCode {
+    match sync() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Failed to share the appearance with the greeter: {err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
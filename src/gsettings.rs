@@ -0,0 +1,38 @@
+use std::sync::OnceLock;
+
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+/// Runs every `gsettings set` of the arguments, which are in threes of a schema, a key, and a
+/// value.
+const SET_ALL: &str = r#"while [ $# -ge 3 ]; do gsettings set "$1" "$2" "$3"; shift 3; done"#;
+
+/// A `(schema, key, value)` to set.
+pub type Setting = (&'static str, &'static str, String);
+
+static WRITER: OnceLock<UnboundedSender<Vec<Setting>>> = OnceLock::new();
+
+/// Set GNOME settings in order, with one process for all of them.
+///
+/// The settings of each call are written after those of the previous calls, so that the values
+/// of an older change never replace those of a newer one.
+pub fn set(settings: Vec<Setting>) {
+    let writer = WRITER.get_or_init(|| {
+        let (tx, mut rx) = unbounded_channel::<Vec<Setting>>();
+        tokio::spawn(async move {
+            while let Some(settings) = rx.recv().await {
+                let mut command = tokio::process::Command::new("sh");
+                command.args(["-c", SET_ALL, "sh"]);
+                for (schema, key, value) in &settings {
+                    command.args([schema, key, value.as_str()]);
+                }
+
+                if let Err(err) = command.status().await {
+                    eprintln!("Failed to run gsettings: {err}");
+                }
+            }
+        });
+        tx
+    });
+
+    _ = writer.send(settings);
+}
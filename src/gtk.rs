@@ -0,0 +1,150 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use cosmic_theme::Theme;
+
+/// The first line of the `gtk.css` written from the theme, which tells it apart from one written
+/// by the user.
+const GENERATED: &str = "/* Generated by cosmic-settings-daemon from the COSMIC theme. */";
+
+/// A `gtk.css` of the user is moved here before the theme replaces it, and back once the theme
+/// is no longer applied.
+const BACKUP: &str = "gtk.css.backup";
+
+const GTK_DIRS: [&str; 2] = ["gtk-3.0", "gtk-4.0"];
+
+/// Files next to `gtk.css` which it imports at its end, so that they override the colors of the
+/// theme. High contrast comes last, so that it also overrides the accent colors.
+const IMPORTS: [&str; 2] = [
+    crate::accent::GTK_ACCENT_FILE,
+    crate::contrast::GTK_CONTRAST_FILE,
+];
+
+fn gtk_dirs() -> io::Result<impl Iterator<Item = PathBuf>> {
+    let config_dir = dirs::config_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+    Ok(GTK_DIRS.into_iter().map(move |gtk| config_dir.join(gtk)))
+}
+
+fn css_name(is_dark: bool) -> &'static str {
+    if is_dark {
+        "dark.css"
+    } else {
+        "light.css"
+    }
+}
+
+/// Whether `gtk.css` is the theme, as written by this daemon or as the link to it which
+/// libcosmic creates.
+fn is_generated(gtk_css: &Path) -> bool {
+    gtk_css.is_symlink()
+        || fs::read_to_string(gtk_css).is_ok_and(|contents| contents.starts_with(GENERATED))
+}
+
+fn import_line(file: &str) -> String {
+    format!("@import url(\"{file}\");")
+}
+
+/// Add the import of `file` to the end of `gtk.css`, so that it overrides the colors of the
+/// theme. Anything else in the file is kept.
+pub fn add_import(gtk_css: &Path, file: &str) -> io::Result<()> {
+    let contents = match fs::read_to_string(gtk_css) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+
+    let import = import_line(file);
+    if contents.lines().any(|line| line.trim() == import) {
+        return Ok(());
+    }
+
+    let mut contents = contents;
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(&import);
+    contents.push('\n');
+    crate::files::write_atomic(gtk_css, &contents)
+}
+
+/// Remove the import of `file` from `gtk.css`.
+pub fn remove_import(gtk_css: &Path, file: &str) -> io::Result<()> {
+    let Ok(contents) = fs::read_to_string(gtk_css) else {
+        return Ok(());
+    };
+
+    let import = import_line(file);
+    if !contents.lines().any(|line| line.trim() == import) {
+        return Ok(());
+    }
+
+    let lines: Vec<&str> = contents
+        .lines()
+        .filter(|line| line.trim() != import)
+        .collect();
+    crate::files::write_atomic(gtk_css, &(lines.join("\n") + "\n"))
+}
+
+/// Write the CSS of a theme to `cosmic/dark.css` or `cosmic/light.css` of the GTK config
+/// directories, which `apply` copies to `gtk.css`.
+pub fn write(theme: &Theme) -> io::Result<()> {
+    let css = theme.as_gtk4();
+    for dir in gtk_dirs()? {
+        let path = dir.join("cosmic").join(css_name(theme.is_dark));
+        crate::files::write_atomic(&path, &css)?;
+    }
+    Ok(())
+}
+
+/// Replace `gtk.css` with the CSS of the theme of the mode, so that GTK applications load it.
+///
+/// The file is replaced rather than linked to the theme, so that applications never read it while
+/// it is written. The imports of the accent and high contrast colors are kept.
+pub fn apply(is_dark: bool) -> io::Result<()> {
+    for dir in gtk_dirs()? {
+        let css = fs::read_to_string(dir.join("cosmic").join(css_name(is_dark)))?;
+        let gtk_css = dir.join("gtk.css");
+
+        if gtk_css.exists() && !is_generated(&gtk_css) && !dir.join(BACKUP).exists() {
+            fs::rename(&gtk_css, dir.join(BACKUP))?;
+        }
+
+        let current = fs::read_to_string(&gtk_css).unwrap_or_default();
+        let mut contents = format!("{GENERATED}\n{css}");
+        if !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        for import in IMPORTS.map(import_line) {
+            if current.lines().any(|line| line.trim() == import) {
+                contents.push_str(&import);
+                contents.push('\n');
+            }
+        }
+
+        // A link is replaced by a file rather than written through.
+        if gtk_css.is_symlink() {
+            fs::remove_file(&gtk_css)?;
+        }
+        crate::files::write_atomic(&gtk_css, &contents)?;
+    }
+    Ok(())
+}
+
+/// Stop applying the theme to GTK, restoring the `gtk.css` of the user if there was one.
+pub fn reset() -> io::Result<()> {
+    for dir in gtk_dirs()? {
+        let gtk_css = dir.join("gtk.css");
+        if is_generated(&gtk_css) {
+            fs::remove_file(&gtk_css)?;
+        }
+
+        let backup = dir.join(BACKUP);
+        if backup.exists() && !gtk_css.exists() {
+            fs::rename(&backup, &gtk_css)?;
+        }
+    }
+    Ok(())
+}
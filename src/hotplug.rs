@@ -0,0 +1,111 @@
+use std::io;
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::{io::unix::AsyncFd, sync::mpsc::Receiver};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Hotplug";
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct HotplugConfig {
+    /// Subsystems whose devices run the hooks, optionally with a device type as `subsystem/devtype`.
+    pub subsystems: Vec<String>,
+}
+
+impl Default for HotplugConfig {
+    fn default() -> Self {
+        Self {
+            subsystems: vec!["usb/usb_device".to_owned(), "bluetooth".to_owned()],
+        }
+    }
+}
+
+fn monitor_socket(config: &HotplugConfig) -> io::Result<AsyncFd<udev::MonitorSocket>> {
+    let mut builder = udev::MonitorBuilder::new()?;
+    for subsystem in &config.subsystems {
+        builder = match subsystem.split_once('/') {
+            Some((subsystem, devtype)) => builder.match_subsystem_devtype(subsystem, devtype)?,
+            None => builder.match_subsystem(subsystem)?,
+        };
+    }
+
+    AsyncFd::new(builder.listen()?)
+}
+
+/// The udev properties of a device, which are passed to hooks just like to udev `RUN` programs.
+fn hook_env(event: &udev::Event) -> Vec<(String, String)> {
+    event
+        .properties()
+        .map(|property| {
+            (
+                property.name().to_string_lossy().into_owned(),
+                property.value().to_string_lossy().into_owned(),
+            )
+        })
+        .collect()
+}
+
+/// Run the `device-added` and `device-removed` hooks as devices of the configured subsystems
+/// come and go.
+pub async fn monitor(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<HotplugConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load hotplug config {err:?}");
+            return;
+        }
+    };
+
+    'socket: loop {
+        // Without subsystems to match, the monitor would report every device.
+        let socket = if config.subsystems.is_empty() {
+            None
+        } else {
+            match monitor_socket(&config) {
+                Ok(socket) => Some(socket),
+                Err(err) => {
+                    eprintln!("Error creating udev hotplug monitor: {err}");
+                    None
+                }
+            }
+        };
+
+        loop {
+            tokio::select! {
+                key = rx.recv() => {
+                    let Some(key) = key else {
+                        break 'socket;
+                    };
+
+                    if !crate::config::update(&mut config, &helper, &key).is_empty() {
+                        continue 'socket;
+                    }
+                }
+
+                guard = async { socket.as_ref()?.readable().await.ok() }, if socket.is_some() => {
+                    let Some(mut guard) = guard else {
+                        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                        continue 'socket;
+                    };
+
+                    for event in guard.get_inner().iter() {
+                        if !crate::seat::owns(&event.device()) {
+                            continue;
+                        }
+
+                        let hook = match event.event_type() {
+                            udev::EventType::Add => "device-added",
+                            udev::EventType::Remove => "device-removed",
+                            _ => continue,
+                        };
+
+                        crate::hooks::run(hook, hook_env(&event));
+                    }
+
+                    guard.clear_ready();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use std::{
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::mpsc::Receiver, time::Instant};
+use walkdir::WalkDir;
+use zbus::Connection;
+
+use crate::DBUS_PATH;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Housekeeping";
+
+/// Delay before the first cleanup, to stay out of the way while the session starts.
+const STARTUP_DELAY: Duration = Duration::from_secs(5 * 60);
+
+const INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct HousekeepingConfig {
+    /// Clean up once a day.
+    pub enabled: bool,
+    /// Remove the least recently used thumbnails while the thumbnail cache is larger than this
+    /// many megabytes. `0` keeps every thumbnail.
+    pub thumbnail_cache_limit_mb: u64,
+    /// Remove files which have been in the trash for this many days. `0` keeps the trash.
+    pub trash_max_age_days: u64,
+    /// Remove files of the user in the temporary directory which have not been modified for this
+    /// many days. `0` keeps temporary files.
+    pub temp_max_age_days: u64,
+}
+
+impl Default for HousekeepingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            thumbnail_cache_limit_mb: 512,
+            trash_max_age_days: 30,
+            temp_max_age_days: 30,
+        }
+    }
+}
+
+/// A file or directory which a cleanup removes.
+struct Candidate {
+    category: &'static str,
+    path: PathBuf,
+    size: u64,
+    /// The `.trashinfo` file of a trashed file.
+    trash_info: Option<PathBuf>,
+}
+
+impl Candidate {
+    fn remove(&self) -> std::io::Result<()> {
+        if fs::symlink_metadata(&self.path)?.is_dir() {
+            fs::remove_dir_all(&self.path)?;
+        } else {
+            fs::remove_file(&self.path)?;
+        }
+
+        if let Some(info) = self.trash_info.as_ref() {
+            fs::remove_file(info)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn older_than(time: SystemTime, days: u64) -> bool {
+    SystemTime::now()
+        .duration_since(time)
+        .is_ok_and(|age| age.as_secs() > days * 24 * 60 * 60)
+}
+
+/// The least recently used thumbnails beyond the size limit.
+fn thumbnails(limit_mb: u64) -> Vec<Candidate> {
+    let Some(dir) = dirs::cache_dir().map(|dir| dir.join("thumbnails")) else {
+        return Vec::new();
+    };
+
+    let mut files: Vec<(SystemTime, PathBuf, u64)> = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            let used = metadata.accessed().or_else(|_| metadata.modified()).ok()?;
+            Some((used, entry.into_path(), metadata.len()))
+        })
+        .collect();
+
+    let mut total: u64 = files.iter().map(|(_, _, size)| size).sum();
+    let limit = limit_mb * 1_000_000;
+
+    files.sort_unstable_by_key(|(used, _, _)| *used);
+    files
+        .into_iter()
+        .take_while(|(_, _, size)| {
+            let over = total > limit;
+            total = total.saturating_sub(*size);
+            over
+        })
+        .map(|(_, path, size)| Candidate {
+            category: "thumbnails",
+            path,
+            size,
+            trash_info: None,
+        })
+        .collect()
+}
+
+fn size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Files which were trashed more than `max_age_days` ago.
+fn trash(max_age_days: u64) -> Vec<Candidate> {
+    let Some(trash) = dirs::data_dir().map(|dir| dir.join("Trash")) else {
+        return Vec::new();
+    };
+
+    let Ok(entries) = fs::read_dir(trash.join("info")) else {
+        return Vec::new();
+    };
+
+    let oldest = chrono::Local::now().naive_local() - chrono::Duration::days(max_age_days as i64);
+
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let info = entry.path();
+            let name = info.file_stem()?.to_owned();
+            if info.extension()? != "trashinfo" {
+                return None;
+            }
+
+            let deleted = fs::read_to_string(&info)
+                .ok()?
+                .lines()
+                .find_map(|line| line.strip_prefix("DeletionDate="))
+                .and_then(|date| {
+                    chrono::NaiveDateTime::parse_from_str(date.trim(), "%Y-%m-%dT%H:%M:%S").ok()
+                })?;
+
+            if deleted >= oldest {
+                return None;
+            }
+
+            let path = trash.join("files").join(name);
+            Some(Candidate {
+                category: "trash",
+                size: size(&path),
+                path,
+                trash_info: Some(info),
+            })
+        })
+        .collect()
+}
+
+/// Files of the user in the temporary directory which have not been modified in `max_age_days`.
+///
+/// Anything containing sockets or pipes is kept, since those belong to running programs.
+fn temp_files(max_age_days: u64) -> Vec<Candidate> {
+    let Ok(entries) = fs::read_dir(std::env::temp_dir()) else {
+        return Vec::new();
+    };
+
+    // SAFETY: getuid cannot fail.
+    let uid = unsafe { libc::getuid() };
+
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.metadata().is_ok_and(|m| m.uid() == uid))
+        .filter_map(|entry| {
+            let path = entry.path();
+            let mut size = 0;
+            for entry in WalkDir::new(&path) {
+                let metadata = entry.ok()?.metadata().ok()?;
+                if !(metadata.is_file() || metadata.is_dir()) || metadata.uid() != uid {
+                    return None;
+                }
+
+                if !older_than(metadata.modified().ok()?, max_age_days) {
+                    return None;
+                }
+
+                if metadata.is_file() {
+                    size += metadata.len();
+                }
+            }
+
+            Some(Candidate {
+                category: "temp",
+                path,
+                size,
+                trash_info: None,
+            })
+        })
+        .collect()
+}
+
+fn candidates(config: &HousekeepingConfig) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+
+    if config.thumbnail_cache_limit_mb > 0 {
+        candidates.extend(thumbnails(config.thumbnail_cache_limit_mb));
+    }
+
+    if config.trash_max_age_days > 0 {
+        candidates.extend(trash(config.trash_max_age_days));
+    }
+
+    if config.temp_max_age_days > 0 {
+        candidates.extend(temp_files(config.temp_max_age_days));
+    }
+
+    candidates
+}
+
+/// Remove everything the config allows, returning the number of files and bytes removed.
+async fn clean(config: HousekeepingConfig) -> (u32, u64) {
+    tokio::task::spawn_blocking(move || {
+        let mut removed = (0, 0);
+        for candidate in candidates(&config) {
+            match candidate.remove() {
+                Ok(()) => {
+                    removed.0 += 1;
+                    removed.1 += candidate.size;
+                }
+                Err(err) => eprintln!("Failed to remove {}: {err}", candidate.path.display()),
+            }
+        }
+        removed
+    })
+    .await
+    .unwrap_or_default()
+}
+
+/// Cleans up the thumbnail cache, the trash, and temporary files.
+pub struct Housekeeping {
+    helper: Option<cosmic_config::Config>,
+    config: HousekeepingConfig,
+}
+
+impl Housekeeping {
+    pub fn new() -> Self {
+        let (helper, config) = match crate::config::load::<HousekeepingConfig>(ID) {
+            Ok((helper, config)) => (Some(helper), config),
+            Err(err) => {
+                eprintln!("Failed to load housekeeping config {err:?}");
+                (None, HousekeepingConfig::default())
+            }
+        };
+
+        Self { helper, config }
+    }
+}
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.Housekeeping")]
+impl Housekeeping {
+    /// The category, path, and size in bytes of everything a cleanup would remove now.
+    async fn dry_run(&self) -> Vec<(String, String, u64)> {
+        let config = self.config.clone();
+        tokio::task::spawn_blocking(move || {
+            candidates(&config)
+                .into_iter()
+                .map(|c| (c.category.to_owned(), c.path.display().to_string(), c.size))
+                .collect()
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    /// Clean up now, even if scheduled cleanups are disabled.
+    ///
+    /// Returns the number of files and bytes removed.
+    async fn run_now(&self) -> (u32, u64) {
+        clean(self.config.clone()).await
+    }
+}
+
+/// Clean up once a day while housekeeping is enabled.
+pub async fn run(conn: Connection, mut rx: Receiver<String>) {
+    let Ok(interface) = conn
+        .object_server()
+        .interface::<_, Housekeeping>(DBUS_PATH)
+        .await
+    else {
+        return;
+    };
+
+    let mut next_run = Instant::now() + STARTUP_DELAY;
+
+    loop {
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+
+                let mut housekeeping = interface.get_mut().await;
+                let housekeeping = &mut *housekeeping;
+                if let Some(helper) = housekeeping.helper.as_ref() {
+                    crate::config::update(&mut housekeeping.config, helper, &key);
+                }
+            }
+
+            _ = tokio::time::sleep_until(next_run) => {
+                next_run = Instant::now() + INTERVAL;
+
+                let config = interface.get().await.config.clone();
+                if config.enabled {
+                    clean(config).await;
+                }
+            }
+        }
+    }
+}
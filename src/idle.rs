@@ -0,0 +1,193 @@
+use std::{
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::watch;
+use wayland_client::{
+    delegate_noop,
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{wl_registry::WlRegistry, wl_seat::WlSeat},
+    Connection, Dispatch, Proxy, QueueHandle,
+};
+
+use self::protocol::{
+    ext_idle_notification_v1::{self, ExtIdleNotificationV1},
+    ext_idle_notifier_v1::ExtIdleNotifierV1,
+};
+
+/// Version 2 of the idle notify protocol, whose input idle notifications ignore idle inhibitors.
+#[allow(
+    dead_code,
+    non_upper_case_globals,
+    unused_imports,
+    clippy::single_component_path_imports
+)]
+mod protocol {
+    use wayland_client;
+    use wayland_client::protocol::*;
+
+    pub mod __interfaces {
+        use wayland_client::protocol::__interfaces::*;
+        wayland_scanner::generate_interfaces!("protocols/ext-idle-notify-v1.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_client_code!("protocols/ext-idle-notify-v1.xml");
+}
+
+/// The compositor reports the user idle after this long without input, so idle times are
+/// accurate to it.
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+static STATE: Mutex<States> = Mutex::new(States {
+    input: None,
+    session: None,
+});
+static CHANGES: OnceLock<watch::Sender<()>> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug)]
+enum Idle {
+    Active,
+    /// Without input since then.
+    Since(Instant),
+}
+
+/// The idle notifications, told apart by their user data.
+#[derive(Clone, Copy, Debug)]
+enum Kind {
+    /// Idle without input, even while idle is inhibited.
+    Input,
+    /// Idle without input, unless idle is inhibited, such as while a video plays.
+    Session,
+}
+
+struct States {
+    /// `None` if the compositor does not have input idle notifications.
+    input: Option<Idle>,
+    session: Option<Idle>,
+}
+
+fn changes() -> &'static watch::Sender<()> {
+    CHANGES.get_or_init(|| watch::channel(()).0)
+}
+
+/// Notified whenever the session becomes idle or active.
+pub fn subscribe() -> watch::Receiver<()> {
+    changes().subscribe()
+}
+
+/// How long the user has been without input, or `None` if the compositor does not report it.
+///
+/// Inputs within the last second count as no time idle. Compositors without input idle
+/// notifications report the user active while idle is inhibited.
+pub fn idle_time() -> Option<Duration> {
+    let state = STATE.lock().ok()?;
+    match state.input.or(state.session)? {
+        Idle::Active => Some(Duration::ZERO),
+        Idle::Since(since) => Some(since.elapsed()),
+    }
+}
+
+/// Since when the session is idle, or `None` while it is active, idle is inhibited, or the
+/// compositor does not report it.
+pub fn session_idle_since() -> Option<Instant> {
+    match STATE.lock().ok()?.session? {
+        Idle::Active => None,
+        Idle::Since(since) => Some(since),
+    }
+}
+
+fn set(kind: Kind, idle: Option<Idle>) {
+    if let Ok(mut state) = STATE.lock() {
+        match kind {
+            Kind::Input => state.input = idle,
+            Kind::Session => state.session = idle,
+        }
+    }
+
+    if let Kind::Session = kind {
+        changes().send_replace(());
+    }
+}
+
+struct State;
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtIdleNotificationV1, Kind> for State {
+    fn event(
+        _: &mut Self,
+        _: &ExtIdleNotificationV1,
+        event: ext_idle_notification_v1::Event,
+        kind: &Kind,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_idle_notification_v1::Event::Idled => {
+                let now = Instant::now();
+                set(
+                    *kind,
+                    Some(Idle::Since(now.checked_sub(TIMEOUT).unwrap_or(now))),
+                );
+            }
+            ext_idle_notification_v1::Event::Resumed => set(*kind, Some(Idle::Active)),
+        }
+    }
+}
+
+delegate_noop!(State: ignore WlSeat);
+delegate_noop!(State: ExtIdleNotifierV1);
+
+fn watch() -> anyhow::Result<()> {
+    let conn = Connection::connect_to_env()?;
+    let (globals, mut queue) = registry_queue_init::<State>(&conn)?;
+    let qh = queue.handle();
+
+    let notifier: ExtIdleNotifierV1 = globals.bind(&qh, 1..=2, ())?;
+    let seat: WlSeat = globals.bind(&qh, 1..=1, ())?;
+    let timeout = TIMEOUT.as_millis() as u32;
+
+    // Until the compositor reports the user idle, they are active.
+    let _session = notifier.get_idle_notification(timeout, &seat, &qh, Kind::Session);
+    set(Kind::Session, Some(Idle::Active));
+    let _input = (notifier.version() >= 2).then(|| {
+        set(Kind::Input, Some(Idle::Active));
+        notifier.get_input_idle_notification(timeout, &seat, &qh, Kind::Input)
+    });
+
+    loop {
+        queue.blocking_dispatch(&mut State)?;
+    }
+}
+
+/// Track the idle time with the idle notifications of the compositor, on a thread of its own.
+///
+/// The idle time of the user follows only their input, while that of the session follows idle
+/// inhibitors, so the session does not become idle while a video plays.
+pub fn run() {
+    let res = std::thread::Builder::new()
+        .name("idle".to_owned())
+        .spawn(|| {
+            if let Err(err) = watch() {
+                eprintln!("Failed to watch idle notifications of the compositor: {err}");
+            }
+            set(Kind::Input, None);
+            set(Kind::Session, None);
+        });
+
+    if let Err(err) = res {
+        eprintln!("Failed to start the idle thread: {err}");
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    collections::BTreeMap,
+    os::unix::fs::MetadataExt,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+};
+
+use tokio_stream::StreamExt;
+use zbus::{fdo, zvariant::OwnedFd, Connection, MessageHeader};
+
+use crate::logind_manager::LogindManagerProxy;
+
+pub const SCREENSAVER_NAME: &str = "org.freedesktop.ScreenSaver";
+
+/// Applications call the screen saver interface at either path.
+pub const SCREENSAVER_PATHS: [&str; 2] = ["/org/freedesktop/ScreenSaver", "/ScreenSaver"];
+
+const LOGIND_PREFIX: &str = "logind:";
+const SCREENSAVER_PREFIX: &str = "screensaver:";
+
+static COOKIES: AtomicU32 = AtomicU32::new(1);
+
+static SCREENSAVER: Mutex<BTreeMap<u32, ScreenSaverInhibitor>> = Mutex::new(BTreeMap::new());
+
+struct ScreenSaverInhibitor {
+    /// The unique name of the application on the session bus.
+    sender: String,
+    application: String,
+    reason: String,
+    /// The idle inhibitor of logind which the inhibitor is passed on to, so that logind and the
+    /// idle lock honor it. It is released when dropped.
+    _logind: Option<OwnedFd>,
+}
+
+/// Pass a screen saver inhibitor on to logind.
+async fn inhibit_logind(application: &str, reason: &str) -> Option<OwnedFd> {
+    let conn = crate::bus::system().await.ok()?;
+    let manager = LogindManagerProxy::new(&conn).await.ok()?;
+    match manager.inhibit("idle", application, reason, "block").await {
+        Ok(fd) => Some(fd),
+        Err(err) => {
+            eprintln!("Failed to pass the idle inhibitor of {application} on to logind: {err}");
+            None
+        }
+    }
+}
+
+/// The screen saver interface, which applications such as video players inhibit idle with.
+pub struct ScreenSaver;
+
+#[zbus::dbus_interface(name = "org.freedesktop.ScreenSaver")]
+impl ScreenSaver {
+    async fn inhibit(
+        &self,
+        application_name: String,
+        reason_for_inhibit: String,
+        #[zbus(header)] header: MessageHeader<'_>,
+    ) -> u32 {
+        let sender = header
+            .sender()
+            .ok()
+            .flatten()
+            .map(|sender| sender.to_string())
+            .unwrap_or_default();
+        let logind = inhibit_logind(&application_name, &reason_for_inhibit).await;
+
+        let cookie = COOKIES.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut inhibitors) = SCREENSAVER.lock() {
+            inhibitors.insert(
+                cookie,
+                ScreenSaverInhibitor {
+                    sender,
+                    application: application_name,
+                    reason: reason_for_inhibit,
+                    _logind: logind,
+                },
+            );
+        }
+        cookie
+    }
+
+    async fn un_inhibit(&self, cookie: u32) {
+        if let Ok(mut inhibitors) = SCREENSAVER.lock() {
+            inhibitors.remove(&cookie);
+        }
+    }
+}
+
+async fn logind_inhibitors() -> zbus::Result<Vec<(String, String, String, String, u32, u32)>> {
+    let conn = crate::bus::system().await?;
+    LogindManagerProxy::new(&conn)
+        .await?
+        .list_inhibitors()
+        .await
+}
+
+/// End the process holding logind inhibitors, which is the only way to release them.
+///
+/// Only applications are ended, so that an inhibitor of the compositor, the session, or a
+/// service cannot be used to end them.
+fn release_logind(pid: u32) -> fdo::Result<()> {
+    if pid == std::process::id() {
+        return Err(fdo::Error::AccessDenied(
+            "The inhibitors of the settings daemon cannot be released".to_owned(),
+        ));
+    }
+
+    // SAFETY: getuid cannot fail.
+    let uid = unsafe { libc::getuid() };
+    let owner = std::fs::metadata(format!("/proc/{pid}"))
+        .map_err(|_| fdo::Error::InvalidArgs(format!("No process {pid}")))?
+        .uid();
+    if owner != uid {
+        return Err(fdo::Error::AccessDenied(format!(
+            "Process {pid} belongs to another user"
+        )));
+    }
+    if !cosmic_settings_daemon_lib::cgroup::is_app(pid) {
+        return Err(fdo::Error::AccessDenied(format!(
+            "Process {pid} is not an application"
+        )));
+    }
+
+    // SAFETY: kill has no memory safety requirements.
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+        return Err(fdo::Error::IOError(
+            std::io::Error::last_os_error().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// The active idle and suspend inhibitors, for finding out why the device does not suspend.
+///
+/// The inhibitors come from logind and from the screen saver interface. The Inhibit portal
+/// cannot list its inhibitors, so they are only listed once the portal passes them on to one of
+/// these.
+pub struct Inhibitors;
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.Inhibitors")]
+impl Inhibitors {
+    /// The inhibitors as `(id, what, who, why, mode)`, where `what` is a list of operations
+    /// separated by colons such as `idle:sleep`, and `mode` is `block` or `delay`.
+    async fn list(&self) -> fdo::Result<Vec<(String, String, String, String, String)>> {
+        let mut list = Vec::new();
+        let mut passed_on = Vec::new();
+        if let Ok(inhibitors) = SCREENSAVER.lock() {
+            for (cookie, inhibitor) in inhibitors.iter() {
+                list.push((
+                    format!("{SCREENSAVER_PREFIX}{cookie}"),
+                    "idle".to_owned(),
+                    inhibitor.application.clone(),
+                    inhibitor.reason.clone(),
+                    "block".to_owned(),
+                ));
+                passed_on.push((inhibitor.application.clone(), inhibitor.reason.clone()));
+            }
+        }
+
+        let own_pid = std::process::id();
+        let logind = logind_inhibitors()
+            .await
+            .map_err(|err| fdo::Error::Failed(format!("Failed to list inhibitors: {err}")))?;
+        for (what, who, why, mode, _uid, pid) in logind {
+            // Those passed on from the screen saver interface are listed already.
+            let from_screensaver = passed_on.iter().any(|(application, reason)| {
+                pid == own_pid && *application == who && *reason == why
+            });
+            if from_screensaver && what == "idle" && mode == "block" {
+                continue;
+            }
+            list.push((format!("{LOGIND_PREFIX}{pid}"), what, who, why, mode));
+        }
+
+        Ok(list)
+    }
+
+    /// Release a stuck inhibitor by its id from `List`.
+    ///
+    /// logind inhibitors are only released when their process closes them, so the process
+    /// holding it is terminated, which releases all of its inhibitors. Only applications of the
+    /// user, which run in their own `app-*.scope`, can be.
+    async fn release(&self, id: &str) -> fdo::Result<()> {
+        if let Some(cookie) = id.strip_prefix(SCREENSAVER_PREFIX) {
+            let removed = cookie
+                .parse::<u32>()
+                .ok()
+                .and_then(|cookie| SCREENSAVER.lock().ok()?.remove(&cookie));
+            return match removed {
+                Some(_) => Ok(()),
+                None => Err(fdo::Error::InvalidArgs(format!("No inhibitor {id}"))),
+            };
+        }
+
+        match id
+            .strip_prefix(LOGIND_PREFIX)
+            .and_then(|pid| pid.parse().ok())
+        {
+            Some(pid) => release_logind(pid),
+            None => Err(fdo::Error::InvalidArgs(format!("No inhibitor {id}"))),
+        }
+    }
+}
+
+/// Serve the screen saver interface if nothing else does, and release the inhibitors of
+/// applications which leave the bus without releasing them.
+pub async fn run(conn: Connection) {
+    let flags = fdo::RequestNameFlags::DoNotQueue;
+    if let Err(err) = conn
+        .request_name_with_flags(SCREENSAVER_NAME, flags.into())
+        .await
+    {
+        eprintln!("Not serving the screen saver interface: {err}");
+        return;
+    }
+
+    let owner_changes = match fdo::DBusProxy::new(&conn).await {
+        Ok(proxy) => proxy.receive_name_owner_changed().await,
+        Err(err) => Err(err),
+    };
+    let mut owner_changes = match owner_changes {
+        Ok(changes) => changes,
+        Err(err) => {
+            eprintln!("Failed to watch applications leaving the session bus: {err}");
+            return;
+        }
+    };
+
+    while let Some(signal) = owner_changes.next().await {
+        let Ok(args) = signal.args() else {
+            continue;
+        };
+        if args.new_owner().is_some() {
+            continue;
+        }
+
+        let name = args.name().to_string();
+        if let Ok(mut inhibitors) = SCREENSAVER.lock() {
+            inhibitors.retain(|_, inhibitor| inhibitor.sender != name);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::{io, path::PathBuf};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::{io::unix::AsyncFd, sync::mpsc::Receiver};
+
+use crate::{
+    brightness_device::BrightnessDevice,
+    clock::SystemClock,
+    schedule::{LocationReceiver, Schedule},
+    LogindSessionProxy,
+};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.KeyboardBacklight";
+
+/// The color and brightness of the keyboard backlight.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct KeyboardLight {
+    /// The color, for keyboards with a controllable color.
+    pub color: [u8; 3],
+    /// The brightness, from 0 to 100.
+    pub brightness: u8,
+}
+
+/// A keyboard backlight which changes at sunrise and sunset, such as a warm and dim one at night.
+///
+/// Changing the brightness by hand, with the keys or the keyboard brightness methods, lasts until
+/// the next sunrise or sunset. The color needs write access to the `color` attribute of System76
+/// keyboards, or to `multi_intensity` of multicolor LEDs, such as from a udev rule.
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct KeyboardBacklightConfig {
+    /// Set the keyboard backlight to `day` from sunrise and to `night` from sunset, or by the
+    /// fixed dark hours while location services are off.
+    pub schedule: bool,
+    pub day: KeyboardLight,
+    pub night: KeyboardLight,
+    /// The highest brightness of the schedule while on AC power, from 0 to 100.
+    pub ac_max_brightness: u8,
+    /// The highest brightness of the schedule while on battery, from 0 to 100.
+    pub battery_max_brightness: u8,
+}
+
+impl Default for KeyboardBacklightConfig {
+    fn default() -> Self {
+        Self {
+            schedule: false,
+            day: KeyboardLight {
+                color: [255, 255, 255],
+                brightness: 100,
+            },
+            night: KeyboardLight {
+                color: [255, 120, 40],
+                brightness: 30,
+            },
+            ac_max_brightness: 100,
+            battery_max_brightness: 50,
+        }
+    }
+}
+
+/// The name of the keyboard backlight LED of the seat, such as `system76_acpi::kbd_backlight`.
+fn find() -> io::Result<Option<String>> {
+    let mut enumerator = udev::Enumerator::new()?;
+    enumerator.match_subsystem("leds")?;
+    Ok(enumerator
+        .scan_devices()?
+        .filter(|device| crate::seat::owns(device))
+        .map(|device| device.sysname().to_string_lossy().into_owned())
+        .find(|name| name.ends_with("::kbd_backlight")))
+}
+
+/// The keyboard backlight, if the device has one.
+pub async fn device() -> Option<BrightnessDevice> {
+    let name = find().ok().flatten()?;
+    BrightnessDevice::new("leds", name).await.ok()
+}
+
+/// Set the color of a keyboard backlight, if it has a controllable color.
+fn set_color(name: &str, [r, g, b]: [u8; 3]) -> io::Result<()> {
+    let dir = PathBuf::from("/sys/class/leds").join(name);
+
+    // System76 keyboards take the color in hex.
+    let color = dir.join("color");
+    if color.exists() {
+        return std::fs::write(color, format!("{r:02X}{g:02X}{b:02X}"));
+    }
+
+    // Multicolor LEDs take an intensity per color, from 0 to the maximum brightness, in the
+    // order of `multi_index`.
+    let Ok(index) = std::fs::read_to_string(dir.join("multi_index")) else {
+        return Ok(());
+    };
+    let max: u32 = std::fs::read_to_string(dir.join("max_brightness"))?
+        .trim()
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let intensities: Vec<String> = index
+        .split_whitespace()
+        .map(|color| {
+            let value = match color {
+                "red" => r,
+                "green" => g,
+                "blue" => b,
+                _ => 0,
+            };
+            (u32::from(value) * max / 255).to_string()
+        })
+        .collect();
+    std::fs::write(dir.join("multi_intensity"), intensities.join(" "))
+}
+
+/// Set the keyboard backlight to `light`, with its brightness limited to `max` percent.
+async fn apply(session: &LogindSessionProxy<'_>, light: KeyboardLight, max: u8) {
+    let Ok(Some(name)) = find() else {
+        return;
+    };
+
+    if let Err(err) = set_color(&name, light.color) {
+        eprintln!("Failed to set the keyboard backlight color: {err}");
+    }
+
+    let Ok(device) = BrightnessDevice::new("leds", name).await else {
+        return;
+    };
+
+    let percent = u32::from(light.brightness.min(max).min(100));
+    let value = (percent * device.max_brightness() + 50) / 100;
+    if let Err(err) = device.set_brightness(session, value).await {
+        eprintln!("Failed to set the keyboard backlight brightness: {err}");
+    }
+}
+
+/// Set the brightness of the keyboard backlight by hand, which lasts until the next sunrise or
+/// sunset.
+pub async fn set_brightness(session: &LogindSessionProxy<'_>, value: i32) {
+    let Some(device) = device().await else {
+        return;
+    };
+
+    let value = value.clamp(0, device.max_brightness() as i32) as u32;
+    if let Err(err) = device.set_brightness(session, value).await {
+        eprintln!("Failed to set the keyboard backlight brightness: {err}");
+    }
+}
+
+impl crate::demand::Feature for KeyboardBacklightConfig {
+    fn enabled(&self) -> bool {
+        self.schedule
+    }
+}
+
+fn monitor_socket() -> io::Result<AsyncFd<udev::MonitorSocket>> {
+    let socket = udev::MonitorBuilder::new()?
+        .match_subsystem("power_supply")?
+        .listen()?;
+    AsyncFd::new(socket)
+}
+
+/// Set the keyboard backlight at sunrise and sunset, and when the power source changes, while
+/// the schedule is enabled.
+pub async fn run(mut rx: Receiver<String>, mut location_rx: LocationReceiver) {
+    let (helper, mut config) = match crate::config::load_async::<KeyboardBacklightConfig>(ID).await
+    {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load keyboard backlight config {err:?}");
+            return;
+        }
+    };
+
+    let Ok(conn) = crate::bus::system().await else {
+        return;
+    };
+    let session = match LogindSessionProxy::builder(&conn).build().await {
+        Ok(session) => session,
+        Err(err) => {
+            eprintln!("Failed to connect to logind for the keyboard backlight: {err}");
+            return;
+        }
+    };
+
+    let mut socket = match monitor_socket() {
+        Ok(socket) => Some(socket),
+        Err(err) => {
+            eprintln!("Error creating udev keyboard backlight monitor: {err}");
+            None
+        }
+    };
+
+    let mut location_state = crate::location::subscribe();
+    let mut location_open = true;
+    // The backlight is only set when the part of the day, the power source, or the config
+    // changes, so that a brightness set by hand lasts until then.
+    let mut applied: Option<(bool, bool, KeyboardBacklightConfig)> = None;
+
+    loop {
+        crate::location::want("keyboard_backlight", config.schedule);
+        let fallback = location_state.borrow_and_update().fallback_dark();
+        let location = *location_rx.borrow_and_update();
+        let schedule = fallback.unwrap_or(Schedule::SunsetToSunrise);
+        let (is_night, next_transition) = schedule.state(&SystemClock, location);
+
+        // Until the location is known, the backlight stays as it is.
+        let known = fallback.is_some() || location.is_some();
+        if config.schedule && known {
+            let on_ac = crate::dock::on_ac().unwrap_or(true);
+            let state = Some((is_night, on_ac, config.clone()));
+            if applied != state {
+                let light = if is_night { config.night } else { config.day };
+                let max = if on_ac {
+                    config.ac_max_brightness
+                } else {
+                    config.battery_max_brightness
+                };
+                apply(&session, light, max).await;
+                applied = state;
+            }
+        } else {
+            applied = None;
+        }
+
+        let next_transition = next_transition.filter(|_| config.schedule);
+
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                crate::config::update(&mut config, &helper, &key);
+            }
+
+            guard = async { socket.as_ref()?.readable().await.ok() }, if socket.is_some() => {
+                let Some(mut guard) = guard else {
+                    eprintln!("Error reading udev keyboard backlight monitor");
+                    socket = None;
+                    continue;
+                };
+
+                for _event in guard.get_inner().iter() {}
+                guard.clear_ready();
+            }
+
+            res = location_rx.changed(), if location_open => {
+                location_open = res.is_ok();
+            }
+
+            res = location_state.changed() => {
+                if res.is_err() {
+                    break;
+                }
+            }
+
+            () = crate::schedule::sleep_until(next_transition) => (),
+        }
+    }
+}
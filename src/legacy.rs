@@ -0,0 +1,177 @@
+use std::{fs, io, path::Path, sync::Mutex};
+
+use anyhow::Context;
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Legacy";
+
+static CONFIG: Mutex<Option<LegacyConfig>> = Mutex::new(None);
+
+/// Hints of the theme mode for applications which ignore the settings portal and GNOME's
+/// settings, such as Electron and GTK 2 applications.
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct LegacyConfig {
+    /// Set `GTK_THEME` in the session environment.
+    ///
+    /// This overrides the theme of every GTK application started afterwards, including
+    /// libadwaita applications, so it is off by default.
+    pub environment: bool,
+    /// Set the theme in `~/.gtkrc-2.0`, if the theme supports GTK 2.
+    pub gtk2: bool,
+}
+
+/// `GTK_THEME` for the theme mode. This is the theme GNOME's `gtk-theme` setting is set to, and
+/// otherwise the dark variant of the default theme.
+fn gtk_theme(is_dark: bool) -> Option<String> {
+    match crate::theme::adw_gtk3_theme(is_dark) {
+        Some((theme, _)) => Some(theme.to_owned()),
+        None if is_dark => Some("Adwaita:dark".to_owned()),
+        None => None,
+    }
+}
+
+/// The comment above the `gtk-theme-name` entry set in a gtkrc file, so that it can be removed
+/// again without touching one set by the user.
+const GTKRC_MARKER: &str = "# Set by cosmic-settings-daemon for the theme mode.";
+
+/// The lines of a gtkrc file without the marked entry.
+fn without_marked_entry(contents: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut marked = false;
+    for line in contents.lines() {
+        if line.trim() == GTKRC_MARKER {
+            marked = true;
+            continue;
+        }
+        let is_theme = line
+            .split_once('=')
+            .is_some_and(|(name, _)| name.trim() == "gtk-theme-name");
+        if !(marked && is_theme) {
+            lines.push(line.to_owned());
+        }
+        marked = false;
+    }
+    lines
+}
+
+/// Set `gtk-theme-name` in a gtkrc file, keeping everything else.
+fn set_gtkrc_theme(path: &Path, theme: &str) -> io::Result<()> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+
+    // An entry of the user is overridden by the marked one, which GTK reads last.
+    let mut lines = without_marked_entry(&contents);
+    lines.push(GTKRC_MARKER.to_owned());
+    lines.push(format!("gtk-theme-name=\"{theme}\""));
+
+    crate::files::write_atomic(path, &(lines.join("\n") + "\n"))
+}
+
+/// Remove the `gtk-theme-name` entry set in a gtkrc file, and the file if nothing else is left.
+fn remove_gtkrc_theme(path: &Path) -> io::Result<()> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    if !contents.lines().any(|line| line.trim() == GTKRC_MARKER) {
+        return Ok(());
+    }
+
+    let lines = without_marked_entry(&contents);
+    if lines.iter().all(|line| line.trim().is_empty()) {
+        fs::remove_file(path)
+    } else {
+        crate::files::write_atomic(path, &(lines.join("\n") + "\n"))
+    }
+}
+
+fn apply(config: &LegacyConfig, is_dark: bool) {
+    let theme = config.environment.then(|| gtk_theme(is_dark)).flatten();
+    crate::environment::set("legacy", vec![("GTK_THEME", theme)]);
+    apply_gtk2(config, is_dark);
+}
+
+fn apply_gtk2(config: &LegacyConfig, is_dark: bool) {
+    let Some(home) = dirs::home_dir() else {
+        return;
+    };
+    let gtkrc = home.join(".gtkrc-2.0");
+
+    let theme = crate::theme::adw_gtk3_theme(is_dark)
+        .filter(|(_, path)| config.gtk2 && path.join("gtk-2.0").is_dir());
+    let res = match theme {
+        Some((theme, _)) => set_gtkrc_theme(&gtkrc, theme),
+        // Without a theme for GTK 2, the one set before would be wrong for the mode.
+        None => remove_gtkrc_theme(&gtkrc),
+    };
+    if let Err(err) = res {
+        eprintln!("Failed to write {}: {err}", gtkrc.display());
+    }
+}
+
+/// Disable the legacy hints, unset `GTK_THEME`, and remove the theme from `~/.gtkrc-2.0`.
+///
+/// From the command line, `GTK_THEME` is unset by the running daemon once it sees the config
+/// change.
+pub fn reset() -> anyhow::Result<()> {
+    let (helper, config) = crate::config::load::<LegacyConfig>(ID)?;
+    config.set_environment(&helper, false)?;
+    config.set_gtk2(&helper, false)?;
+    crate::environment::set("legacy", vec![("GTK_THEME", None)]);
+
+    let gtkrc = dirs::home_dir()
+        .context("no home directory")?
+        .join(".gtkrc-2.0");
+    remove_gtkrc_theme(&gtkrc).with_context(|| format!("failed to clean up {}", gtkrc.display()))
+}
+
+/// Update the hints after the theme mode changed.
+pub fn theme_mode_changed(is_dark: bool) {
+    let config = CONFIG.lock().ok().and_then(|config| config.clone());
+    if let Some(config) = config {
+        apply(&config, is_dark);
+    }
+}
+
+/// Maintain the legacy hints of the theme mode while they are enabled.
+pub async fn run(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<LegacyConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load legacy hints config {err:?}");
+            return;
+        }
+    };
+
+    if let Ok(mut global) = CONFIG.lock() {
+        *global = Some(config.clone());
+    }
+
+    if config != LegacyConfig::default() {
+        apply(&config, crate::theme::current().0);
+    } else {
+        // A `GTK_THEME` from elsewhere is kept, but the entry of GTK 2 is removed if it was
+        // disabled while the daemon was not running.
+        apply_gtk2(&config, crate::theme::current().0);
+    }
+
+    while let Some(key) = rx.recv().await {
+        if crate::config::update(&mut config, &helper, &key).is_empty() {
+            continue;
+        }
+
+        if let Ok(mut global) = CONFIG.lock() {
+            *global = Some(config.clone());
+        }
+
+        apply(&config, crate::theme::current().0);
+    }
+}
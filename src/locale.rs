@@ -0,0 +1,172 @@
+use std::process::Stdio;
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Locale";
+
+/// The categories which follow the regional formats rather than the language.
+const FORMAT_CATEGORIES: &[&str] = &[
+    "LC_ADDRESS",
+    "LC_IDENTIFICATION",
+    "LC_MEASUREMENT",
+    "LC_MONETARY",
+    "LC_NAME",
+    "LC_NUMERIC",
+    "LC_PAPER",
+    "LC_TELEPHONE",
+    "LC_TIME",
+];
+
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct LocaleConfig {
+    /// Locale of the language, such as `en_US.UTF-8`, or the system default if unset.
+    pub language: Option<String>,
+    /// Locale of dates, numbers, and units, or the same as the language if unset.
+    pub formats: Option<String>,
+}
+
+impl LocaleConfig {
+    /// The variables to export, where unset preferences fall back to the environment the
+    /// session started with.
+    fn vars(&self) -> Vec<(&'static str, Option<String>)> {
+        let mut vars = vec![(
+            "LANG",
+            self.language.clone().or_else(|| std::env::var("LANG").ok()),
+        )];
+        vars.extend(FORMAT_CATEGORIES.iter().map(|category| {
+            let value = self
+                .formats
+                .clone()
+                .or_else(|| std::env::var(category).ok());
+            (*category, value)
+        }));
+        vars
+    }
+}
+
+/// Compare locale names the way glibc does, ignoring the case and dashes of the codeset.
+fn normalize(locale: &str) -> String {
+    let (name, modifier) = match locale.split_once('@') {
+        Some((name, modifier)) => (name, Some(modifier)),
+        None => (locale, None),
+    };
+
+    let mut normalized = match name.split_once('.') {
+        Some((language, codeset)) => {
+            let codeset: String = codeset
+                .chars()
+                .filter(char::is_ascii_alphanumeric)
+                .map(|c| c.to_ascii_lowercase())
+                .collect();
+            format!("{language}.{codeset}")
+        }
+        None => name.to_owned(),
+    };
+
+    if let Some(modifier) = modifier {
+        normalized.push('@');
+        normalized.push_str(modifier);
+    }
+
+    normalized
+}
+
+/// The configured locales which are not generated on the system.
+async fn missing_locales(config: &LocaleConfig) -> Vec<String> {
+    let output = tokio::process::Command::new("locale")
+        .arg("-a")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await;
+
+    let Ok(output) = output else {
+        return Vec::new();
+    };
+
+    let available: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(normalize)
+        .collect();
+
+    let mut missing: Vec<String> = [&config.language, &config.formats]
+        .into_iter()
+        .flatten()
+        .filter(|locale| !available.contains(&normalize(locale)))
+        .cloned()
+        .collect();
+    missing.dedup();
+    missing
+}
+
+fn notify(summary: &'static str, body: String) {
+    tokio::task::spawn_local(async move {
+        let _res = Notification::new()
+            .appname("")
+            .summary(summary)
+            .body(&body)
+            .icon("preferences-desktop-locale-symbolic")
+            .show_async()
+            .await;
+    });
+}
+
+/// Export the locale to the session, warning about locales which are not installed. Returns
+/// whether it was exported.
+async fn apply(config: &LocaleConfig) -> bool {
+    let missing = missing_locales(config).await;
+    if !missing.is_empty() {
+        notify(
+            "Language Not Installed",
+            format!(
+                "{} must be generated or installed before it can be used.",
+                missing.join(" and ")
+            ),
+        );
+    }
+
+    match crate::environment::set_exported("locale", config.vars()).await {
+        Ok(()) => true,
+        Err(err) => {
+            eprintln!("Failed to export the locale: {err}");
+            false
+        }
+    }
+}
+
+/// Export the configured language and regional formats to applications started in the
+/// session, asking to log out when they change so that running applications follow.
+pub async fn run(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<LocaleConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load locale config {err:?}");
+            return;
+        }
+    };
+
+    // The session already has the system locale unless it was overridden.
+    if config != LocaleConfig::default() {
+        _ = apply(&config).await;
+    }
+
+    while let Some(key) = rx.recv().await {
+        if crate::config::update(&mut config, &helper, &key).is_empty() {
+            continue;
+        }
+
+        // Logging out would not change the language of applications if it was not exported.
+        if !apply(&config).await {
+            continue;
+        }
+        notify(
+            "Log Out to Change Language",
+            "Applications will use the new language and formats after you log out and back in."
+                .to_owned(),
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use std::{collections::BTreeSet, path::PathBuf, process::Child, sync::OnceLock};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc::Receiver, watch};
+use zbus::{Connection, InterfaceRef};
+
+use crate::{
+    schedule::{Location, Schedule, TimeOfDay},
+    DBUS_PATH, GEOCLUE_AGENT,
+};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Location";
+
+static STATE: OnceLock<watch::Sender<State>> = OnceLock::new();
+
+static FIXES: OnceLock<watch::Sender<Option<Fix>>> = OnceLock::new();
+
+/// Where the location comes from.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum LocationSource {
+    /// GeoClue, which locates the device with Wi-Fi networks, cell towers, and GPS.
+    #[default]
+    GeoClue,
+    /// A fixed location, for devices which do not move.
+    Manual { latitude: f64, longitude: f64 },
+    /// gpsd, at an address such as `127.0.0.1:2947`.
+    Gpsd { address: String },
+    /// A GPS receiver sending NMEA sentences, at a serial device such as `/dev/ttyACM0`.
+    Nmea { device: PathBuf, baud_rate: u32 },
+}
+
+impl LocationSource {
+    /// The name of the source, for the status of location services.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::GeoClue => "geoclue",
+            Self::Manual { .. } => "manual",
+            Self::Gpsd { .. } => "gpsd",
+            Self::Nmea { .. } => "nmea",
+        }
+    }
+}
+
+/// A location from a source other than GeoClue.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fix {
+    pub location: Location,
+    /// The radius of the uncertainty of the location, in meters.
+    pub accuracy: f64,
+}
+
+/// Location services, which the daemon gets the location from for switching the theme at sunset
+/// and sunrise, quiet hours, and the automatic time zone.
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct LocationConfig {
+    /// Allow the location to be looked up. While off, no source is used, neither the GeoClue
+    /// agent nor a GeoClue client is started, and the last known location is forgotten.
+    pub enabled: bool,
+    pub source: LocationSource,
+    /// The fixed hours of the dark theme for the automatic theme mode while location services
+    /// are off.
+    pub fallback_dark: Schedule,
+}
+
+impl Default for LocationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            source: LocationSource::GeoClue,
+            fallback_dark: Schedule::Fixed {
+                start: TimeOfDay {
+                    hour: 19,
+                    minute: 0,
+                },
+                end: TimeOfDay { hour: 7, minute: 0 },
+            },
+        }
+    }
+}
+
+/// The modules which currently need the location, and whether they may have it.
+#[derive(Clone, Debug, Default)]
+pub struct State {
+    owners: BTreeSet<&'static str>,
+    /// `None` until the config is loaded, so that nothing looks up the location before it is
+    /// known whether location services are off.
+    config: Option<LocationConfig>,
+}
+
+impl State {
+    /// Whether the config is loaded, and `enabled` known.
+    pub fn loaded(&self) -> bool {
+        self.config.is_some()
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.as_ref().is_some_and(|config| config.enabled)
+    }
+
+    /// Whether the source should be used, because some module needs the location and location
+    /// services are on.
+    pub fn in_use(&self) -> bool {
+        self.enabled() && !self.owners.is_empty()
+    }
+
+    /// The source to look up the location with, while it is in use.
+    pub fn source(&self) -> Option<&LocationSource> {
+        self.config
+            .as_ref()
+            .filter(|_| self.in_use())
+            .map(|config| &config.source)
+    }
+
+    /// Whether the location is looked up with GeoClue.
+    pub fn geoclue(&self) -> bool {
+        self.source() == Some(&LocationSource::GeoClue)
+    }
+
+    /// The dark hours of the automatic theme mode while location services are off.
+    pub fn fallback_dark(&self) -> Option<Schedule> {
+        match &self.config {
+            Some(config) if !config.enabled => Some(config.fallback_dark),
+            _ => None,
+        }
+    }
+}
+
+fn state() -> &'static watch::Sender<State> {
+    STATE.get_or_init(|| watch::channel(State::default()).0)
+}
+
+/// Declare whether `owner` needs the location. The source is only used while some module does.
+pub fn want(owner: &'static str, wanted: bool) {
+    state().send_if_modified(|state| {
+        if wanted {
+            state.owners.insert(owner)
+        } else {
+            state.owners.remove(owner)
+        }
+    });
+}
+
+pub fn subscribe() -> watch::Receiver<State> {
+    state().subscribe()
+}
+
+fn fixes() -> &'static watch::Sender<Option<Fix>> {
+    FIXES.get_or_init(|| watch::channel(None).0)
+}
+
+/// Report the location from a source other than GeoClue, or `None` once it is unknown.
+pub fn report(fix: Option<Fix>) {
+    fixes().send_if_modified(|current| {
+        let changed = *current != fix;
+        *current = fix;
+        changed
+    });
+}
+
+/// The locations from sources other than GeoClue.
+pub fn subscribe_fixes() -> watch::Receiver<Option<Fix>> {
+    fixes().subscribe()
+}
+
+/// The status of location services.
+#[derive(Default)]
+pub struct LocationServices {
+    enabled: bool,
+    in_use: bool,
+    source: String,
+    users: Vec<String>,
+}
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.Location")]
+impl LocationServices {
+    /// Whether location services are on. While off, GeoClue is never used, and the automatic
+    /// theme mode follows the fallback schedule.
+    #[dbus_interface(property)]
+    async fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Whether the location is being looked up.
+    #[dbus_interface(property)]
+    async fn in_use(&self) -> bool {
+        self.in_use
+    }
+
+    /// The source of the location: `geoclue`, `manual`, `gpsd`, or `nmea`.
+    #[dbus_interface(property)]
+    async fn source(&self) -> String {
+        self.source.clone()
+    }
+
+    /// The features which need the location, whether or not location services are on.
+    #[dbus_interface(property)]
+    async fn users(&self) -> Vec<String> {
+        self.users.clone()
+    }
+}
+
+async fn publish(interface: &InterfaceRef<LocationServices>, state: &State) {
+    let mut status = interface.get_mut().await;
+    let ctxt = interface.signal_context();
+
+    if status.enabled != state.enabled() {
+        status.enabled = state.enabled();
+        _ = status.enabled_changed(ctxt).await;
+    }
+
+    if status.in_use != state.in_use() {
+        status.in_use = state.in_use();
+        _ = status.in_use_changed(ctxt).await;
+    }
+
+    let source = state
+        .config
+        .as_ref()
+        .map_or("", |config| config.source.name());
+    if status.source != source {
+        status.source = source.to_owned();
+        _ = status.source_changed(ctxt).await;
+    }
+
+    let users: Vec<String> = state
+        .owners
+        .iter()
+        .map(|owner| (*owner).to_owned())
+        .collect();
+    if status.users != users {
+        status.users = users;
+        _ = status.users_changed(ctxt).await;
+    }
+}
+
+/// The GeoClue agent, which authorizes the GeoClue client of the theme watcher.
+fn start_agent() -> Option<Child> {
+    let agent = GEOCLUE_AGENT.unwrap_or("/usr/libexec/geoclue-2.0/demos/agent");
+    match std::process::Command::new(agent).spawn() {
+        Ok(child) => Some(child),
+        Err(err) => {
+            eprintln!("Failed to start the GeoClue agent {agent}: {err}");
+            None
+        }
+    }
+}
+
+fn stop_agent(mut child: Child) {
+    if let Err(err) = child.kill().and_then(|()| child.wait().map(drop)) {
+        eprintln!("Failed to stop the GeoClue agent: {err}");
+    }
+}
+
+/// Apply the location services switch, and publish the status of location services.
+pub async fn run(conn: Connection, mut rx: Receiver<String>) {
+    let Ok(interface) = conn
+        .object_server()
+        .interface::<_, LocationServices>(DBUS_PATH)
+        .await
+    else {
+        return;
+    };
+
+    // Without the config, location services stay off.
+    let (helper, mut config) = match crate::config::load_async::<LocationConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load location services config {err:?}");
+            return;
+        }
+    };
+
+    let mut state_rx = subscribe();
+    let mut agent: Option<Child> = None;
+
+    loop {
+        state().send_if_modified(|state| {
+            let changed = state.config.as_ref() != Some(&config);
+            state.config = Some(config.clone());
+            changed
+        });
+
+        let state = state_rx.borrow_and_update().clone();
+        publish(&interface, &state).await;
+
+        let geoclue = config.enabled && config.source == LocationSource::GeoClue;
+        if geoclue && agent.is_none() {
+            agent = start_agent();
+        } else if !geoclue {
+            if let Some(child) = agent.take() {
+                stop_agent(child);
+            }
+        }
+
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                crate::config::update(&mut config, &helper, &key);
+            }
+
+            res = state_rx.changed() => {
+                if res.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
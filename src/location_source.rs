@@ -0,0 +1,248 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, Read},
+    os::{fd::AsRawFd, unix::fs::OpenOptionsExt},
+    path::Path,
+    time::Duration,
+};
+
+use serde::Deserialize;
+use tokio::io::{unix::AsyncFd, AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::{
+    location::{Fix, LocationSource},
+    schedule::Location,
+};
+
+/// How long to wait before connecting again to a source which failed or ended.
+const RETRY: Duration = Duration::from_secs(10);
+
+/// Fixes closer than this to the reported one are not reported, so that the jitter of a GPS
+/// receiver sending a fix every second does not wake every user of the location.
+const MIN_MOVE_METERS: f64 = 100.0;
+
+/// The accuracy of fixes without an estimate of their error.
+const DEFAULT_ACCURACY_METERS: f64 = 50.0;
+
+/// The error of a fix per unit of horizontal dilution of precision, which is roughly that of
+/// consumer GPS receivers.
+const HDOP_METERS: f64 = 5.0;
+
+const EARTH_RADIUS_METERS: f64 = 6_371_000.0;
+
+/// A report of gpsd. Only `TPV` reports have a position, in `lat` and `lon`, while `mode` is 2 or
+/// more.
+#[derive(Deserialize)]
+struct GpsdReport {
+    class: String,
+    #[serde(default)]
+    mode: u8,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    /// The estimated horizontal error, in meters.
+    eph: Option<f64>,
+    /// The estimated longitude and latitude errors, in meters.
+    epx: Option<f64>,
+    epy: Option<f64>,
+}
+
+/// The approximate distance between locations which are close, in meters.
+fn distance(a: Location, b: Location) -> f64 {
+    let latitude = (a.latitude + b.latitude).to_radians() / 2.0;
+    let x = (b.longitude - a.longitude).to_radians() * latitude.cos();
+    let y = (b.latitude - a.latitude).to_radians();
+    EARTH_RADIUS_METERS * x.hypot(y)
+}
+
+/// Report a fix, unless it is close to the reported one.
+fn report(reported: &mut Option<Fix>, fix: Fix) {
+    if reported.is_some_and(|reported| {
+        distance(reported.location, fix.location) < MIN_MOVE_METERS
+            && fix.accuracy >= reported.accuracy / 2.0
+    }) {
+        return;
+    }
+
+    *reported = Some(fix);
+    crate::location::report(Some(fix));
+}
+
+/// Report the fixes of gpsd until it disconnects.
+async fn follow_gpsd(address: &str) -> io::Result<()> {
+    let mut stream = tokio::net::TcpStream::connect(address).await?;
+    stream
+        .write_all(b"?WATCH={\"enable\":true,\"json\":true}\n")
+        .await?;
+
+    let mut lines = BufReader::new(stream).lines();
+    let mut reported = None;
+    while let Some(line) = lines.next_line().await? {
+        let Ok(gpsd) = serde_json::from_str::<GpsdReport>(&line) else {
+            continue;
+        };
+
+        let (Some(latitude), Some(longitude)) = (gpsd.lat, gpsd.lon) else {
+            continue;
+        };
+        if gpsd.class != "TPV" || gpsd.mode < 2 {
+            continue;
+        }
+
+        let accuracy = gpsd
+            .eph
+            .or_else(|| Some(gpsd.epx?.max(gpsd.epy?)))
+            .unwrap_or(DEFAULT_ACCURACY_METERS);
+        let location = Location {
+            latitude,
+            longitude,
+        };
+        report(&mut reported, Fix { location, accuracy });
+    }
+
+    Ok(())
+}
+
+/// Open a serial device for reading raw at a baud rate, without blocking. Other files which can
+/// be polled, such as pipes, are opened as they are.
+fn open_serial(device: &Path, baud_rate: u32) -> io::Result<std::fs::File> {
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+        .open(device)?;
+
+    let speed = match baud_rate {
+        4800 => libc::B4800,
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported baud rate {baud_rate}"),
+            ))
+        }
+    };
+
+    let fd = file.as_raw_fd();
+    // SAFETY: the termios is only used after `tcgetattr` fills it from the open file.
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut termios) != 0 {
+            let err = io::Error::last_os_error();
+            return match err.raw_os_error() {
+                Some(libc::ENOTTY) => Ok(file),
+                _ => Err(err),
+            };
+        }
+
+        libc::cfmakeraw(&mut termios);
+        if libc::cfsetspeed(&mut termios, speed) != 0
+            || libc::tcsetattr(fd, libc::TCSANOW, &termios) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(file)
+}
+
+/// Report the fixes of a GPS receiver sending NMEA sentences, until the device goes away.
+///
+/// The device is polled rather than read by tokio's file reader, which would hold a thread of the
+/// blocking pool for as long as the receiver is connected.
+async fn follow_nmea(device: &Path, baud_rate: u32) -> io::Result<()> {
+    let file = AsyncFd::new(open_serial(device, baud_rate)?)?;
+
+    let mut buf = [0; 1024];
+    let mut pending = Vec::new();
+    let mut reported = None;
+    loop {
+        let mut guard = file.readable().await?;
+        let n = match guard.try_io(|file| file.get_ref().read(&mut buf)) {
+            Ok(res) => res?,
+            Err(_would_block) => continue,
+        };
+        if n == 0 {
+            return Ok(());
+        }
+
+        pending.extend_from_slice(&buf[..n]);
+        while let Some(end) = pending.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            let Some(position) = std::str::from_utf8(&line).ok().and_then(crate::nmea::parse)
+            else {
+                continue;
+            };
+
+            let accuracy = position
+                .hdop
+                .map_or(DEFAULT_ACCURACY_METERS, |hdop| hdop * HDOP_METERS);
+            let location = position.location;
+            report(&mut reported, Fix { location, accuracy });
+        }
+
+        // A device sending something other than sentences is not buffered without end.
+        if pending.len() > buf.len() {
+            pending.clear();
+        }
+    }
+}
+
+/// Report the location from the manual, gpsd, or NMEA source, while it is the source in use.
+///
+/// The GeoClue source is followed by the theme watcher, which owns the GeoClue client.
+pub async fn run() {
+    let mut state_rx = crate::location::subscribe();
+
+    loop {
+        let source = state_rx.borrow_and_update().source().cloned();
+
+        let follow = async {
+            let res = match &source {
+                Some(LocationSource::Manual {
+                    latitude,
+                    longitude,
+                }) => {
+                    let location = Location {
+                        latitude: *latitude,
+                        longitude: *longitude,
+                    };
+                    crate::location::report(Some(Fix {
+                        location,
+                        accuracy: 0.0,
+                    }));
+                    std::future::pending().await
+                }
+                Some(LocationSource::Gpsd { address }) => follow_gpsd(address).await,
+                Some(LocationSource::Nmea { device, baud_rate }) => {
+                    follow_nmea(device, *baud_rate).await
+                }
+                Some(LocationSource::GeoClue) | None => {
+                    crate::location::report(None);
+                    std::future::pending().await
+                }
+            };
+
+            // The last fix is not known to still hold.
+            crate::location::report(None);
+            match res {
+                Ok(()) => eprintln!("The location source ended"),
+                Err(err) => eprintln!("Failed to read the location source: {err}"),
+            }
+            tokio::time::sleep(RETRY).await;
+        };
+
+        tokio::select! {
+            // Changes of the modules which need the location do not reconnect to the source.
+            res = state_rx.wait_for(|state| state.source() != source.as_ref()) => {
+                if res.is_err() {
+                    break;
+                }
+            }
+
+            () = follow => {}
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::time::Duration;
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::mpsc::Receiver,
+    time::{sleep_until, Instant},
+};
+use tokio_stream::StreamExt;
+use zbus::zvariant::OwnedFd;
+
+use crate::{
+    logind_manager::{LogindManagerProxy, PrepareForSleepStream},
+    LogindSessionProxy,
+};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Lock";
+
+/// How long to wait before trying again to lock an idle session while idle is inhibited.
+const INHIBITED_RETRY: Duration = Duration::from_secs(60);
+
+/// The longest suspending waits for the screen locker.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct LockConfig {
+    /// Lock the session when the lid is closed, before the device suspends. Like the lid switch
+    /// of logind, this is ignored while docked.
+    pub lock_on_lid_close: bool,
+    /// Minutes of idle before the session is locked, or 0 to not lock an idle session. This is
+    /// counted from when the compositor reports the session idle, whether or not the screen is
+    /// blanked, so idle inhibitors postpone it.
+    pub idle_lock_minutes: u64,
+}
+
+impl Default for LockConfig {
+    fn default() -> Self {
+        Self {
+            lock_on_lid_close: true,
+            idle_lock_minutes: 0,
+        }
+    }
+}
+
+impl crate::demand::Feature for LockConfig {
+    fn enabled(&self) -> bool {
+        self.lock_on_lid_close || self.idle_lock_minutes > 0
+    }
+}
+
+/// Delay suspending until the session is locked, for as long as logind allows.
+async fn inhibit_sleep(manager: &LogindManagerProxy<'_>) -> Option<OwnedFd> {
+    match manager
+        .inhibit(
+            "sleep",
+            "COSMIC Settings Daemon",
+            "Lock the session",
+            "delay",
+        )
+        .await
+    {
+        Ok(fd) => Some(fd),
+        Err(err) => {
+            eprintln!("Failed to delay suspending until the session is locked: {err}");
+            None
+        }
+    }
+}
+
+async fn lock(session: &LogindSessionProxy<'_>) {
+    if session.locked_hint().await.unwrap_or(false) {
+        return;
+    }
+
+    if let Err(err) = session.lock().await {
+        eprintln!("Failed to lock the session: {err}");
+    }
+}
+
+/// Lock the session before suspending, and wait for the screen locker to report it locked, so
+/// that the unlocked session is not shown on resume.
+async fn lock_before_sleep(session: &LogindSessionProxy<'_>) {
+    let mut locked_changes = session.receive_locked_hint_changed().await;
+    lock(session).await;
+
+    let locked = async {
+        while !session.locked_hint().await.unwrap_or(false) {
+            if locked_changes.next().await.is_none() {
+                break;
+            }
+        }
+    };
+    _ = tokio::time::timeout(LOCK_TIMEOUT, locked).await;
+}
+
+/// Whether the lid is closed while undocked.
+async fn lid_closed(manager: &LogindManagerProxy<'_>) -> bool {
+    manager.lid_closed().await.unwrap_or(false) && !manager.docked().await.unwrap_or(false)
+}
+
+/// Since when the compositor reports the session idle.
+fn session_idle_since() -> Option<Instant> {
+    crate::idle::session_idle_since().map(Instant::from_std)
+}
+
+/// Whether a logind inhibitor blocks the session from going idle, which the idle notifications
+/// of the compositor do not follow.
+async fn idle_inhibited(manager: &LogindManagerProxy<'_>) -> bool {
+    manager
+        .block_inhibited()
+        .await
+        .is_ok_and(|blocked| blocked.split(':').any(|what| what == "idle"))
+}
+
+/// Wait for the next `PrepareForSleep` signal, or forever without the signal.
+async fn prepare_for_sleep(stream: &mut Option<PrepareForSleepStream<'_>>) -> bool {
+    if let Some(signals) = stream {
+        if let Some(signal) = signals.next().await {
+            return signal.args().is_ok_and(|args| args.start);
+        }
+    }
+
+    *stream = None;
+    std::future::pending().await
+}
+
+/// Lock the session when the lid is closed, and after a period of idle.
+pub async fn run(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<LockConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load screen lock config {err:?}");
+            return;
+        }
+    };
+
+    let Ok(conn) = crate::bus::system().await else {
+        return;
+    };
+
+    let (manager, session) = match tokio::join!(
+        LogindManagerProxy::new(&conn),
+        LogindSessionProxy::builder(&conn).build()
+    ) {
+        (Ok(manager), Ok(session)) => (manager, session),
+        (Err(err), _) | (_, Err(err)) => {
+            eprintln!("Failed to connect to logind for locking the session: {err}");
+            return;
+        }
+    };
+
+    let mut lid_changes = manager.receive_lid_closed_changed().await;
+    let mut idle_changes = crate::idle::subscribe();
+    let mut sleeps = manager.receive_prepare_for_sleep().await.ok();
+
+    let mut inhibitor: Option<OwnedFd> = None;
+    let mut sleeping = false;
+    let mut idle_since = session_idle_since();
+    let mut not_before: Option<Instant> = None;
+
+    loop {
+        if config.lock_on_lid_close && !sleeping {
+            if inhibitor.is_none() {
+                inhibitor = inhibit_sleep(&manager).await;
+            }
+        } else {
+            // Closing the inhibitor lets the suspend go ahead.
+            inhibitor = None;
+        }
+
+        let timeout = Duration::from_secs(config.idle_lock_minutes * 60);
+        let due = idle_since
+            .filter(|_| config.idle_lock_minutes > 0)
+            .map(|since| not_before.map_or(since + timeout, |retry| retry.max(since + timeout)));
+
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                crate::config::update(&mut config, &helper, &key);
+            }
+
+            Some(_) = lid_changes.next() => {
+                if config.lock_on_lid_close && lid_closed(&manager).await {
+                    lock(&session).await;
+                }
+            }
+
+            Ok(()) = idle_changes.changed() => {
+                idle_since = session_idle_since();
+                not_before = None;
+            }
+
+            start = prepare_for_sleep(&mut sleeps) => {
+                if start && config.lock_on_lid_close && lid_closed(&manager).await {
+                    lock_before_sleep(&session).await;
+                }
+                sleeping = start;
+            }
+
+            () = sleep_until(due.unwrap_or_else(Instant::now)), if due.is_some() => {
+                if idle_inhibited(&manager).await {
+                    not_before = Some(Instant::now() + INHIBITED_RETRY);
+                } else {
+                    lock(&session).await;
+                    idle_since = None;
+                    not_before = None;
+                }
+            }
+        }
+    }
+}
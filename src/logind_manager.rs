@@ -0,0 +1,30 @@
+#[zbus::dbus_proxy(
+    default_service = "org.freedesktop.login1",
+    interface = "org.freedesktop.login1.Manager",
+    default_path = "/org/freedesktop/login1"
+)]
+trait LogindManager {
+    fn inhibit(
+        &self,
+        what: &str,
+        who: &str,
+        why: &str,
+        mode: &str,
+    ) -> zbus::Result<zbus::zvariant::OwnedFd>;
+
+    /// The inhibitors as `(what, who, why, mode, uid, pid)`.
+    fn list_inhibitors(&self) -> zbus::Result<Vec<(String, String, String, String, u32, u32)>>;
+
+    #[dbus_proxy(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+
+    /// The operations blocked by inhibitors, separated by colons, such as `idle:sleep`.
+    #[dbus_proxy(property)]
+    fn block_inhibited(&self) -> zbus::Result<String>;
+
+    #[dbus_proxy(property)]
+    fn docked(&self) -> zbus::Result<bool>;
+
+    #[dbus_proxy(property)]
+    fn lid_closed(&self) -> zbus::Result<bool>;
+}
@@ -5,4 +5,14 @@
 )]
 trait LogindSession {
     fn set_brightness(&self, subsystem: &str, name: &str, brightness: u32) -> zbus::Result<()>;
+
+    /// Ask the screen locker of the session to lock it.
+    fn lock(&self) -> zbus::Result<()>;
+
+    /// Whether the session is in the foreground of its seat.
+    #[dbus_proxy(property)]
+    fn active(&self) -> zbus::Result<bool>;
+
+    #[dbus_proxy(property)]
+    fn locked_hint(&self) -> zbus::Result<bool>;
 }
@@ -1,18 +1,24 @@
 use brightness_device::BrightnessDevice;
+use cosmic_settings_daemon_lib::{
+    clock,
+    config::{ConfigDir, ConfigProvider},
+    files, hooks, nmea, schedule, sun,
+};
 use logind_session::LogindSessionProxy;
-use notify::{event::ModifyKind, EventKind, Watcher};
 use std::sync::atomic::AtomicU64;
 use std::time::Duration;
 use std::{
-    collections::{HashMap, HashSet},
-    future, io,
-    path::PathBuf,
+    collections::{BTreeMap, HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+    process::ExitCode,
     sync::{atomic::Ordering, Arc},
 };
 use theme::watch_theme;
 use tokio::{
     io::{unix::AsyncFd, Interest},
-    sync::RwLock,
+    signal::unix::{signal, SignalKind},
+    sync::{mpsc::UnboundedSender, RwLock},
     task,
 };
 use tokio_stream::StreamExt;
@@ -21,11 +27,92 @@ use zbus::{
     zvariant::ObjectPath,
     Connection, MatchRule, MessageStream, SignalContext,
 };
+mod a11y;
+mod accent;
+mod atspi;
+mod audio;
+mod backup;
 mod battery;
+mod bluetooth;
+mod bluez;
+mod breaks;
 mod brightness_device;
+mod bus;
+mod capture;
+mod color_picker;
+mod config;
+mod contrast;
+mod cursor;
+mod datetime;
+mod debounce;
+mod demand;
+mod disk_space;
+mod dnd;
+mod dock;
+mod environment;
+mod errors;
+mod export;
+mod firmware;
+mod flatpak;
+mod fonts;
+mod geofence;
+mod graphics;
+mod greeter;
+mod gsettings;
+mod gtk;
+mod hotplug;
+mod housekeeping;
+mod idle;
+mod inhibitors;
+mod keyboard_backlight;
+mod legacy;
+mod locale;
+mod location;
+mod location_source;
+mod lock;
+mod logind_manager;
 mod logind_session;
+mod media;
+mod memory;
+mod network;
+mod network_manager;
+mod notifications;
+mod osd;
+mod pactl;
+mod palette;
 mod pipewire;
+mod pointer;
+mod portal;
+mod power_profiles;
+mod presence;
+mod printers;
+mod privacy;
+mod profiles;
+mod proxy;
+mod qtct;
+mod reset;
+mod rfkill;
+mod screenshot;
+mod screenshot_portal;
+mod seasons;
+mod seat;
+mod sensor_proxy;
+mod shortcuts;
+mod sounds;
+mod startup;
+mod stats;
+mod system76_power;
+mod systemd;
 mod theme;
+mod thermal;
+mod timedate;
+mod toplevels;
+mod udisks2;
+mod v4l2;
+mod visual_bell;
+mod wakeups;
+mod wallpaper;
+mod watch;
 
 // Use seperate HasDisplayBrightness, or -1?
 // Is it fair to assume a display device will notify on change?
@@ -144,21 +231,47 @@ impl SettingsDaemon {
 
     #[dbus_interface(property)]
     async fn keyboard_brightness(&self) -> i32 {
-        -1
+        match keyboard_backlight::device().await {
+            Some(device) => device.brightness().await.map_or(-1, |x| x as i32),
+            None => -1,
+        }
     }
 
+    /// The steps of the startup in the order they finished, with their durations in
+    /// microseconds. `ready` is the time from the start until every module was started.
     #[dbus_interface(property)]
-    async fn set_keyboard_brightness(&self, _value: i32) {}
+    async fn startup_times(&self) -> Vec<(String, u64)> {
+        startup::times()
+    }
+
+    /// How often the daemon woke up in the last full minute, for finding modules which poll.
+    /// Reading this wakes the daemon too.
+    #[dbus_interface(property)]
+    async fn wakeups_per_minute(&self) -> u32 {
+        wakeups::per_minute()
+    }
+
+    #[dbus_interface(property)]
+    async fn set_keyboard_brightness(&self, value: i32) {
+        if let Some(logind_session) = self.logind_session.as_ref() {
+            keyboard_backlight::set_brightness(logind_session, value).await;
+        }
+    }
 
     async fn increase_display_brightness(
         &self,
         #[zbus(signal_context)] ctxt: zbus::SignalContext<'_>,
     ) {
+        if !seat::is_active() {
+            return;
+        }
+
         let value = self.display_brightness().await;
         if let Some(brightness_device) = self.display_brightness_device.as_ref() {
             let step = brightness_device.brightness_step() as i32;
             self.set_display_brightness((value + step).max(0)).await;
             _ = self.display_brightness_changed(&ctxt).await;
+            self.show_display_brightness().await;
         }
     }
 
@@ -167,17 +280,59 @@ impl SettingsDaemon {
 
         #[zbus(signal_context)] ctxt: zbus::SignalContext<'_>,
     ) {
+        if !seat::is_active() {
+            return;
+        }
+
         let value = self.display_brightness().await;
         if let Some(brightness_device) = self.display_brightness_device.as_ref() {
             let step = brightness_device.brightness_step() as i32;
             self.set_display_brightness((value - step).max(0)).await;
             _ = self.display_brightness_changed(&ctxt).await;
+            self.show_display_brightness().await;
+        }
+    }
+
+    async fn increase_keyboard_brightness(&self) {
+        self.step_keyboard_brightness(1).await;
+    }
+
+    async fn decrease_keyboard_brightness(&self) {
+        self.step_keyboard_brightness(-1).await;
+    }
+
+    /// Restore the default theme and fonts, turn off the automatic theme mode, and remove every
+    /// export of the appearance, for recovering from a theme which makes the desktop unreadable.
+    /// This is also
+    /// `cosmic-settings-daemon reset-appearance`.
+    async fn reset_appearance(&self) -> zbus::fdo::Result<()> {
+        let errors = tokio::task::spawn_blocking(reset::reset_appearance)
+            .await
+            .map_err(|err| zbus::fdo::Error::Failed(err.to_string()))?;
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(zbus::fdo::Error::Failed(errors.join("; ")))
         }
     }
 
-    async fn increase_keyboard_brightness(&self) {}
+    /// Counts since the daemon started: config changes per config as `events.<id>`, D-Bus calls
+    /// as `dbus.<interface>.<method>`, files written, export passes, and wakeups. This is also
+    /// `cosmic-settings-daemon stats`.
+    async fn get_stats(&self) -> BTreeMap<String, u64> {
+        stats::stats()
+    }
 
-    async fn decrease_keyboard_brightness(&self) {}
+    /// Milliseconds since the last input of the user, from the idle notifications of the
+    /// compositor, so that applets and presence tools need not bind the protocol themselves.
+    async fn get_idle_time(&self) -> zbus::fdo::Result<u64> {
+        idle::idle_time()
+            .map(|idle| idle.as_millis() as u64)
+            .ok_or_else(|| {
+                zbus::fdo::Error::NotSupported("The compositor does not report idle".to_owned())
+            })
+    }
 
     async fn watch_config(
         &mut self,
@@ -195,9 +350,67 @@ impl SettingsDaemon {
     ) -> zbus::fdo::Result<(ObjectPath<'static>, WellKnownName<'static>)> {
         Self::watch_config_inner(self, Config::new_state(), id, version).await
     }
+
+    /// A key of a config the daemon applies changed, with its new value in RON, or empty if the
+    /// key was removed.
+    #[dbus_interface(signal)]
+    async fn changed(
+        ctxt: &SignalContext<'_>,
+        namespace: &str,
+        key: &str,
+        value: &str,
+    ) -> zbus::Result<()>;
 }
 
 impl SettingsDaemon {
+    /// Show the display brightness as a percentage on the OSD.
+    async fn show_display_brightness(&self) {
+        let Some(brightness_device) = self.display_brightness_device.as_ref() else {
+            return;
+        };
+
+        let Ok(brightness) = brightness_device.brightness().await else {
+            return;
+        };
+
+        let max = brightness_device.max_brightness().max(1);
+        osd::show(
+            "display-brightness",
+            (brightness * 100 / max) as i32,
+            "display-brightness-symbolic",
+        );
+    }
+
+    /// Step the keyboard backlight up or down, and show its brightness on the OSD. Like
+    /// brightness keys on the keyboard, this lasts until the next sunrise or sunset of the
+    /// keyboard backlight schedule.
+    async fn step_keyboard_brightness(&self, direction: i32) {
+        if !seat::is_active() {
+            return;
+        }
+
+        let (Some(logind_session), Some(device)) = (
+            self.logind_session.as_ref(),
+            keyboard_backlight::device().await,
+        ) else {
+            return;
+        };
+
+        let Ok(brightness) = device.brightness().await else {
+            return;
+        };
+
+        let value = brightness as i32 + direction * device.brightness_step() as i32;
+        keyboard_backlight::set_brightness(logind_session, value).await;
+
+        let max = device.max_brightness().max(1) as i32;
+        osd::show(
+            "keyboard-brightness",
+            value.clamp(0, max) * 100 / max,
+            "keyboard-brightness-symbolic",
+        );
+    }
+
     async fn watch_config_inner(
         &mut self,
         config: Config,
@@ -246,7 +459,7 @@ async fn choose_best_backlight(
 ) -> Option<BrightnessDevice> {
     let mut best_backlight = None;
     let mut best_max_brightness = 0;
-    for device in udev_devices.values() {
+    for device in udev_devices.values().filter(|device| seat::owns(device)) {
         if let Some(sysname) = device.sysname().to_str() {
             match BrightnessDevice::new("backlight", sysname.to_owned()).await {
                 Ok(brightness_device) => {
@@ -318,133 +531,228 @@ async fn backlight_monitor_task(
     };
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum Change {
     Config(String, String, u64),
     State(String, String, u64),
     Ping(String, u64),
 }
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> zbus::Result<()> {
-    std::process::Command::new(GEOCLUE_AGENT.unwrap_or("/usr/libexec/geoclue-2.0/demos/agent"))
-        .spawn()?;
+/// Reads the number of worker threads of the runtime. Unset or `0` runs everything on the main
+/// thread, which suits the mostly timer- and D-Bus-driven work of the daemon.
+const WORKERS_VAR: &str = "COSMIC_SETTINGS_DAEMON_WORKERS";
+
+/// Reads the upper bound of the threads for blocking file system work.
+const BLOCKING_THREADS_VAR: &str = "COSMIC_SETTINGS_DAEMON_BLOCKING_THREADS";
+
+const DEFAULT_BLOCKING_THREADS: usize = 4;
+
+fn env_count(name: &str) -> Option<usize> {
+    std::env::var(name).ok()?.trim().parse().ok()
+}
+
+/// The runtime, as configured by the environment.
+///
+/// The modules run on a `LocalSet` on the main thread either way, so worker threads only run
+/// tasks spawned with `tokio::spawn`, such as calls of `gsettings`.
+fn runtime() -> std::io::Result<tokio::runtime::Runtime> {
+    let workers = env_count(WORKERS_VAR).unwrap_or(0);
+    let blocking_threads = env_count(BLOCKING_THREADS_VAR)
+        .filter(|threads| *threads > 0)
+        .unwrap_or(DEFAULT_BLOCKING_THREADS);
+
+    let mut builder = if workers == 0 {
+        tokio::runtime::Builder::new_current_thread()
+    } else {
+        tokio::runtime::Builder::new_multi_thread()
+    };
+
+    if workers > 0 {
+        builder.worker_threads(workers);
+    }
+
+    builder
+        .enable_all()
+        .max_blocking_threads(blocking_threads)
+        .on_thread_unpark(wakeups::count)
+        .build()
+}
+
+fn main() -> zbus::Result<ExitCode> {
+    let matches = clap::Command::new("cosmic-settings-daemon")
+        .subcommand(
+            clap::Command::new("reset-appearance")
+                .about("Restore the default theme, and remove every export of the appearance"),
+        )
+        .subcommand(
+            clap::Command::new("stats").about("Print the counts of events of the running daemon"),
+        )
+        .subcommand(
+            clap::Command::new("greeter-sync")
+                .about("Share the appearance from the standard input with the login screen")
+                .hide(true),
+        )
+        .get_matches();
+
+    // Works without the session bus, and whether or not the daemon is running.
+    if matches.subcommand_matches("reset-appearance").is_some() {
+        return Ok(reset::cli());
+    }
+
+    // Run as root by pkexec, for the daemon of the session.
+    if matches.subcommand_matches("greeter-sync").is_some() {
+        return Ok(greeter::cli());
+    }
+
+    if matches.subcommand_matches("stats").is_some() {
+        return Ok(runtime()?.block_on(stats::cli()));
+    }
+
+    startup::start();
+    runtime()?.block_on(run())?;
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn run() -> zbus::Result<()> {
     task::LocalSet::new()
         .run_until(async {
-            let backlights = match backlight_enumerate() {
-                Ok(backlights) => backlights,
-                Err(err) => {
-                    eprintln!("Failed to enumerate backlights: {}", err);
-                    Vec::new()
-                }
-            };
-            let backlights: HashMap<_, _> = backlights
-                .into_iter()
-                .map(|i| (i.syspath().to_owned(), i))
-                .collect();
-            let display_brightness_device = choose_best_backlight(&backlights).await;
-
-            let logind_session = async {
-                let connection = zbus::Connection::system().await?;
+            // The devices and system services behind the interfaces of the session bus, the
+            // config watcher, and the Wayland clients are independent of each other, so they
+            // start together. The session bus serves every interface before it takes the name,
+            // so that clients never see a partial daemon, and the OSD channel exists before the
+            // key handlers can be called. The modules after the graph use its connection, and
+            // read their configs in parallel.
+            let mut graph = startup::Graph::default();
+            let backlights = graph.step("backlights", &[], async {
+                let backlights = match backlight_enumerate() {
+                    Ok(backlights) => backlights,
+                    Err(err) => {
+                        eprintln!("Failed to enumerate backlights: {}", err);
+                        Vec::new()
+                    }
+                };
+                let backlights: HashMap<_, _> = backlights
+                    .into_iter()
+                    .map(|i| (i.syspath().to_owned(), i))
+                    .collect();
+                let display_brightness_device = choose_best_backlight(&backlights).await;
+                (backlights, display_brightness_device)
+            });
+            let logind_session = graph.step("logind", &[], async {
+                let connection = crate::bus::system().await?;
                 LogindSessionProxy::builder(&connection).build().await
-            }
-            .await;
-            let xdg_config = dirs::config_dir()
-                .map(|x| x.join("cosmic"))
-                .or_else(|| dirs::home_dir().map(|p| p.join(".config/cosmic")));
-            let xdg_state = dirs::state_dir()
-                .map(|x| x.join("cosmic"))
-                .or_else(|| dirs::home_dir().map(|p| p.join(".local/state/cosmic")));
-            let xdg_config_clone = xdg_config.clone();
-            let xdg_state_clone = xdg_state.clone();
-            let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
-            let mut watcher =
-                notify::recommended_watcher(move |res: Result<notify::Event, notify::Error>| {
-                    if let Ok(event) = res {
-                        match &event.kind {
-                            EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_)) => {
-                                // Data not mutated
-                                return;
-                            }
-                            _ => {}
-                        }
-                        let msgs: Vec<_> = event
-                            .paths
-                            .into_iter()
-                            .filter_map(|path| {
-                                if !path.is_file() {
-                                    return None;
-                                }
-                                let (path, is_state) = if let Some(path) = xdg_config_clone
-                                    .as_ref()
-                                    .and_then(|prefix| path.strip_prefix(prefix).ok())
-                                {
-                                    (path, false)
-                                } else if let Some(path) = xdg_state_clone
-                                    .as_ref()
-                                    .and_then(|prefix| path.strip_prefix(prefix).ok())
-                                {
-                                    (path, true)
-                                } else {
-                                    return None;
-                                };
-                                // really only care about keys
-                                if path.starts_with(".atomicwrite") {
-                                    return None;
-                                }
+            });
+            let graphics = graph.step("graphics", &[], graphics::Graphics::new());
+            let audio = graph.step("audio", &[], audio::Audio::new());
+            let osd_rx = graph.step("osd channel", &[], async { osd::channel() });
 
-                                let key = path.file_name().map(|f| f.to_string_lossy())?;
-                                let version = path.parent().and_then(|parent_dir| {
-                                    parent_dir
-                                        .file_name()
-                                        .and_then(|f| f.to_str())
-                                        .and_then(|f| {
-                                            f.strip_prefix('v').and_then(|f| f.parse::<u64>().ok())
-                                        })
-                                })?;
-
-                                let id = path.parent().and_then(|parent_dir| {
-                                    parent_dir.parent().map(|f| f.to_string_lossy())
-                                })?;
-
-                                if is_state {
-                                    Some(Change::State(id.into_owned(), key.into_owned(), version))
-                                } else {
-                                    Some(Change::Config(id.into_owned(), key.into_owned(), version))
-                                }
-                            })
-                            .collect();
-                        if let Err(err) = tx.send(msgs) {
-                            eprintln!("Failed to send config change: {}", err);
-                        }
+            let changes_rx = graph.step("config watcher", &[], async {
+                let xdg_config = ConfigDir::user();
+                let xdg_state = dirs::state_dir()
+                    .map(|x| x.join("cosmic"))
+                    .or_else(|| dirs::home_dir().map(|p| p.join(".local/state/cosmic")));
+                let (tx, watcher_rx) = tokio::sync::mpsc::unbounded_channel();
+                watch_keys(xdg_config.as_ref().map(|c| c.0.clone()), false, tx.clone());
+                watch_keys(xdg_state, true, tx);
+
+                // Bursts of changes to a key are coalesced before they are applied.
+                let (debounced_tx, rx) = tokio::sync::mpsc::unbounded_channel();
+                task::spawn_local(debounce::run(watcher_rx, debounced_tx));
+                rx
+            });
+
+            graph.step("wayland", &[], async {
+                idle::run();
+                toplevels::run();
+                visual_bell::run();
+            });
+
+            let watched_configs = Arc::new(RwLock::new(HashMap::new()));
+            let watched_states = Arc::new(RwLock::new(HashMap::new()));
+            let display_brightness_device = backlights.clone();
+            let session = logind_session.clone();
+            let configs = watched_configs.clone();
+            let states = watched_states.clone();
+            let connection = graph.step(
+                "session bus",
+                &["backlights", "logind", "graphics", "audio", "osd channel"],
+                async move {
+                    let settings_daemon = SettingsDaemon {
+                        logind_session: session.with(|res| res.as_ref().ok().cloned()).flatten(),
+                        display_brightness_device: display_brightness_device
+                            .with(|(_, device)| device.take())
+                            .flatten(),
+                        watched_configs: configs,
+                        watched_states: states,
+                    };
+
+                    let mut builder = zbus::ConnectionBuilder::session()?
+                        .name(DBUS_NAME)?
+                        .serve_at(DBUS_PATH, settings_daemon)?;
+                    if let Some(graphics) = graphics.take() {
+                        builder = builder.serve_at(DBUS_PATH, graphics)?;
+                    }
+                    if let Some(audio) = audio.take() {
+                        builder = builder.serve_at(DBUS_PATH, audio)?;
                     }
-                })
-                .expect("Failed to create notify watcher");
 
-            if let Some(xdg_config) = xdg_config {
-                if let Err(err) = watcher.watch(&xdg_config, notify::RecursiveMode::Recursive) {
-                    eprintln!("Failed to watch xdg config dir: {}", err);
-                }
+                    builder
+                        .serve_at(DBUS_PATH, sounds::Sounds)?
+                        .serve_at(DBUS_PATH, rfkill::Rfkill::new())?
+                        .serve_at(DBUS_PATH, housekeeping::Housekeeping::new())?
+                        .serve_at(DBUS_PATH, a11y::A11y::default())?
+                        .serve_at(DBUS_PATH, backup::Backup)?
+                        .serve_at(DBUS_PATH, capture::Capture::default())?
+                        .serve_at(DBUS_PATH, color_picker::ColorPicker)?
+                        .serve_at(DBUS_PATH, datetime::DateTime::default())?
+                        .serve_at(DBUS_PATH, dnd::DoNotDisturb::default())?
+                        .serve_at(DBUS_PATH, environment::Environment::default())?
+                        .serve_at(DBUS_PATH, errors::Errors::default())?
+                        .serve_at(DBUS_PATH, inhibitors::Inhibitors)?
+                        .serve_at(DBUS_PATH, location::LocationServices::default())?
+                        .serve_at(DBUS_PATH, network::Network::default())?
+                        .serve_at(DBUS_PATH, pointer::Pointer::default())?
+                        .serve_at(DBUS_PATH, proxy::Proxy::default())?
+                        .serve_at(DBUS_PATH, screenshot::Screenshot)?
+                        .serve_at(osd::OSD_PATH, osd::Osd::default())?
+                        .serve_at(inhibitors::SCREENSAVER_PATHS[0], inhibitors::ScreenSaver)?
+                        .serve_at(inhibitors::SCREENSAVER_PATHS[1], inhibitors::ScreenSaver)?
+                        .serve_at(portal::PORTAL_PATH, portal::Settings::default())?
+                        .build()
+                        .await
+                },
+            );
+
+            // The interfaces the shortcuts call are served by now.
+            graph.step("shortcuts", &["session bus"], async {
+                shortcuts::register();
+            });
+
+            graph.run().await;
+
+            let connection = connection
+                .take()
+                .unwrap_or_else(|| Err(zbus::Error::Failure("no session bus".to_owned())))?;
+            let (backlights, _) = backlights.take().unwrap_or_default();
+            let logind_session = logind_session.take().and_then(Result::ok);
+            let mut rx = changes_rx
+                .take()
+                .unwrap_or_else(|| tokio::sync::mpsc::unbounded_channel().1);
+
+            if let Some(osd_rx) = osd_rx.take().flatten() {
+                task::spawn_local(osd::run(connection.clone(), osd_rx));
             }
-            if let Some(xdg_state) = xdg_state {
-                if let Err(err) = watcher.watch(&xdg_state, notify::RecursiveMode::Recursive) {
-                    eprintln!("Failed to watch xdg state dir: {}", err);
-                }
+            if let Some(session) = logind_session {
+                task::spawn_local(seat::run(connection.clone(), session));
             }
-            let watched_configs = Arc::new(RwLock::new(HashMap::new()));
-            let watched_states = Arc::new(RwLock::new(HashMap::new()));
-            let settings_daemon = SettingsDaemon {
-                logind_session: logind_session.ok(),
-                display_brightness_device,
-                watched_configs: watched_configs.clone(),
-                watched_states: watched_states.clone(),
-            };
+            task::spawn_local(environment::run(connection.clone()));
+            task::spawn_local(inhibitors::run(connection.clone()));
+            task::spawn_local(stats::run(connection.clone()));
 
-            let connection = zbus::ConnectionBuilder::session()?
-                .name(DBUS_NAME)?
-                .serve_at(DBUS_PATH, settings_daemon)?
-                .build()
-                .await?;
+            // Failures are recorded from the start, and published once the interface is served.
+            hooks::report_to(errors::hook_outcome);
+            let (errors_tx, errors_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(errors::run(connection.clone(), errors_rx));
 
             let conn_clone = connection.clone();
             task::spawn_local(async move {
@@ -453,12 +761,198 @@ async fn main() -> zbus::Result<()> {
 
             tokio::task::spawn_local(battery::monitor());
 
+            let (bluetooth_tx, bluetooth_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(bluetooth::run(bluetooth_rx));
+            task::spawn_local(rfkill::monitor(connection.clone()));
+            task::spawn_local(network::monitor(connection.clone()));
+
+            // Updated by the theme watcher, which owns the GeoClue client and follows the other
+            // location sources.
+            let (location_tx, location_rx) = tokio::sync::watch::channel(None);
+            let (location_services_tx, location_services_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(location::run(connection.clone(), location_services_rx));
+            task::spawn_local(location_source::run());
+
+            let (a11y_tx, a11y_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(a11y::run(connection.clone(), a11y_rx));
+
+            let (thermal_tx, thermal_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(demand::run::<thermal::ThermalConfig, _, _>(
+                thermal::ID,
+                thermal_rx,
+                thermal::monitor,
+            ));
+
+            let (breaks_tx, breaks_rx) = tokio::sync::mpsc::channel(10);
+            let conn_clone = connection.clone();
+            task::spawn_local(demand::run::<breaks::BreaksConfig, _, _>(
+                breaks::ID,
+                breaks_rx,
+                move |rx| breaks::run(conn_clone.clone(), rx),
+            ));
+
+            let (lock_tx, lock_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(demand::run::<lock::LockConfig, _, _>(
+                lock::ID,
+                lock_rx,
+                lock::run,
+            ));
+
+            let (presence_tx, presence_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(demand::run::<presence::PresenceConfig, _, _>(
+                presence::ID,
+                presence_rx,
+                presence::run,
+            ));
+
+            let (cursor_tx, cursor_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(cursor::run(cursor_rx));
+
+            let (geofence_tx, geofence_rx) = tokio::sync::mpsc::channel(10);
+            let location_clone = location_rx.clone();
+            task::spawn_local(demand::run::<geofence::GeofenceConfig, _, _>(
+                geofence::ID,
+                geofence_rx,
+                move |rx| geofence::run(rx, location_clone.clone()),
+            ));
+
+            let (dock_tx, dock_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(dock::run(dock_rx));
+
+            let (keyboard_backlight_tx, keyboard_backlight_rx) = tokio::sync::mpsc::channel(10);
+            let location_clone = location_rx.clone();
+            task::spawn_local(demand::run::<
+                keyboard_backlight::KeyboardBacklightConfig,
+                _,
+                _,
+            >(
+                keyboard_backlight::ID,
+                keyboard_backlight_rx,
+                move |rx| keyboard_backlight::run(rx, location_clone.clone()),
+            ));
+
+            let (seasons_tx, seasons_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(seasons::run(seasons_rx));
+
+            let (wallpaper_tx, wallpaper_rx) = tokio::sync::mpsc::channel(10);
+            let location_clone = location_rx.clone();
+            task::spawn_local(demand::run::<wallpaper::WallpaperConfig, _, _>(
+                wallpaper::ID,
+                wallpaper_rx,
+                move |rx| wallpaper::run(rx, location_clone.clone()),
+            ));
+
+            let (profiles_tx, profiles_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(profiles::run(profiles_rx));
+
+            let (datetime_tx, datetime_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(datetime::run(
+                connection.clone(),
+                datetime_rx,
+                location_rx.clone(),
+            ));
+
+            let (disk_space_tx, disk_space_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(disk_space::monitor(disk_space_rx));
+
+            let (firmware_tx, firmware_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(firmware::monitor(firmware_rx));
+
+            let (fonts_tx, fonts_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(fonts::run(fonts_rx));
+
+            let (hotplug_tx, hotplug_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(hotplug::monitor(hotplug_rx));
+
+            let (housekeeping_tx, housekeeping_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(housekeeping::run(connection.clone(), housekeeping_rx));
+
+            let (legacy_tx, legacy_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(legacy::run(legacy_rx));
+
+            let (locale_tx, locale_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(locale::run(locale_rx));
+
+            let (audio_tx, audio_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(audio::watch_config(
+                connection.clone(),
+                audio_rx,
+                location_rx.clone(),
+            ));
+            task::spawn_local(audio::policy(connection.clone()));
+
+            let (media_tx, media_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(demand::run::<media::MediaConfig, _, _>(
+                media::ID,
+                media_rx,
+                media::monitor,
+            ));
+
+            let (memory_tx, memory_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(demand::run::<memory::MemoryConfig, _, _>(
+                memory::ID,
+                memory_rx,
+                memory::monitor,
+            ));
+
+            let (pointer_tx, pointer_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(pointer::run(connection.clone(), pointer_rx));
+
+            let (printers_tx, printers_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(demand::run::<printers::PrintersConfig, _, _>(
+                printers::ID,
+                printers_rx,
+                printers::monitor,
+            ));
+
+            let (privacy_tx, privacy_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(privacy::watch_config(privacy_rx));
+
+            let (proxy_tx, proxy_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(proxy::run(connection.clone(), proxy_rx));
+
+            let (dnd_tx, dnd_rx) = tokio::sync::mpsc::channel(10);
+            let (notifications_tx, notifications_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(dnd::run(
+                connection.clone(),
+                dnd_rx,
+                notifications_rx,
+                location_rx.clone(),
+            ));
+
+            let (capture_tx, capture_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(capture::run(connection.clone(), capture_rx));
+
+            let (portal_tx, portal_rx) = tokio::sync::mpsc::channel(1);
+            task::spawn_local(portal::run(connection.clone(), portal_rx));
+
+            let (accent_tx, accent_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(accent::run(accent_rx));
+
+            let (flatpak_tx, flatpak_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(flatpak::run(flatpak_rx));
+
+            let (qtct_tx, qtct_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(qtct::run(qtct_rx));
+
+            let (greeter_tx, greeter_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(greeter::run(greeter_rx));
+
+            let (palette_tx, palette_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(palette::run(palette_rx));
+
+            let (export_tx, export_rx) = tokio::sync::mpsc::channel(1);
+            task::spawn_local(export::run(export_rx));
+
+            let (sounds_tx, sounds_rx) = tokio::sync::mpsc::channel(10);
+            task::spawn_local(sounds::watch_config(sounds_rx));
+
             let (theme_tx, mut theme_rx) = tokio::sync::mpsc::channel(10);
             task::spawn_local(async move {
                 let mut sleep = Duration::from_millis(100);
 
                 loop {
-                    if let Err(err) = watch_theme(&mut theme_rx).await {
+                    if let Err(err) = watch_theme(&mut theme_rx, &location_tx).await {
                         eprintln!(
                             "Failed to watch theme {err:?}. Will try again in {}s",
                             sleep.as_secs()
@@ -478,19 +972,22 @@ async fn main() -> zbus::Result<()> {
                 }
             });
 
+            startup::ready();
+
             let conn_clone = connection.clone();
             task::spawn_local(async move {
                 while let Some(changes) = rx.recv().await {
-                    let Ok(settings_daemon) = conn_clone
+                    let Ok(interface) = conn_clone
                         .object_server()
                         .interface::<_, SettingsDaemon>(DBUS_PATH)
                         .await
                     else {
                         continue;
                     };
-                    let settings_daemon = settings_daemon.get().await;
+                    let settings_daemon = interface.get().await;
                     for c in changes {
                         if let Change::Config(id, key, version) = c {
+                            stats::count("events", &id);
                             if id.as_str() == cosmic_theme::THEME_MODE_ID {
                                 if let Err(err) =
                                     theme_tx.send(theme::ThemeMsg::ThemeMode(key.clone())).await
@@ -503,6 +1000,13 @@ async fn main() -> zbus::Result<()> {
                                 {
                                     eprintln!("Failed to send theme toolkit update {err:?}");
                                 }
+                            } else if id.as_str() == theme::SWITCH_NOTICE_ID {
+                                if let Err(err) = theme_tx
+                                    .send(theme::ThemeMsg::SwitchNotice(key.clone()))
+                                    .await
+                                {
+                                    eprintln!("Failed to send theme switch notice update {err:?}");
+                                }
                             } else if id.as_str() == cosmic_theme::DARK_THEME_ID {
                                 if let Err(err) = theme_tx.send(theme::ThemeMsg::Theme(true)).await
                                 {
@@ -514,6 +1018,97 @@ async fn main() -> zbus::Result<()> {
                                     eprintln!("Failed to send dark theme update {err:?}");
                                 }
                             }
+
+                            let theme_ids = [
+                                cosmic_theme::THEME_MODE_ID,
+                                cosmic_theme::DARK_THEME_ID,
+                                cosmic_theme::LIGHT_THEME_ID,
+                            ];
+                            let is_portal = [a11y::ID, fonts::ID].contains(&id.as_str());
+                            if theme_ids.contains(&id.as_str()) || is_portal {
+                                // A full channel already has an update pending.
+                                let _res = portal_tx.try_send(());
+                            }
+
+                            // qtct also writes the icon theme of the toolkit config, and the
+                            // palette and the greeter follow the wallpaper.
+                            let is_toolkit = id.as_str() == cosmic::config::toolkit::ID;
+                            let is_background = id.as_str() == wallpaper::BACKGROUND_ID;
+                            if theme_ids.contains(&id.as_str()) || is_toolkit || is_background {
+                                let _res = export_tx.try_send(());
+                            }
+
+                            match id.as_str() {
+                                a11y::ID => send_change(&a11y_tx, &id, key.clone()).await,
+                                accent::ID => send_change(&accent_tx, &id, key.clone()).await,
+                                audio::ID => send_change(&audio_tx, &id, key.clone()).await,
+                                bluetooth::ID => send_change(&bluetooth_tx, &id, key.clone()).await,
+                                breaks::ID => send_change(&breaks_tx, &id, key.clone()).await,
+                                capture::ID => send_change(&capture_tx, &id, key.clone()).await,
+                                cursor::ID => send_change(&cursor_tx, &id, key.clone()).await,
+                                datetime::ID => send_change(&datetime_tx, &id, key.clone()).await,
+                                disk_space::ID => {
+                                    send_change(&disk_space_tx, &id, key.clone()).await
+                                }
+                                dnd::ID => send_change(&dnd_tx, &id, key.clone()).await,
+                                dnd::NOTIFICATIONS_ID => {
+                                    send_change(&notifications_tx, &id, key.clone()).await
+                                }
+                                dock::ID => send_change(&dock_tx, &id, key.clone()).await,
+                                errors::ID => send_change(&errors_tx, &id, key.clone()).await,
+                                firmware::ID => send_change(&firmware_tx, &id, key.clone()).await,
+                                flatpak::ID => send_change(&flatpak_tx, &id, key.clone()).await,
+                                fonts::ID => send_change(&fonts_tx, &id, key.clone()).await,
+                                geofence::ID => send_change(&geofence_tx, &id, key.clone()).await,
+                                greeter::ID => send_change(&greeter_tx, &id, key.clone()).await,
+                                hotplug::ID => send_change(&hotplug_tx, &id, key.clone()).await,
+                                housekeeping::ID => {
+                                    send_change(&housekeeping_tx, &id, key.clone()).await
+                                }
+                                keyboard_backlight::ID => {
+                                    send_change(&keyboard_backlight_tx, &id, key.clone()).await
+                                }
+                                legacy::ID => send_change(&legacy_tx, &id, key.clone()).await,
+                                locale::ID => send_change(&locale_tx, &id, key.clone()).await,
+                                location::ID => {
+                                    send_change(&location_services_tx, &id, key.clone()).await
+                                }
+                                lock::ID => send_change(&lock_tx, &id, key.clone()).await,
+                                media::ID => send_change(&media_tx, &id, key.clone()).await,
+                                memory::ID => send_change(&memory_tx, &id, key.clone()).await,
+                                palette::ID => send_change(&palette_tx, &id, key.clone()).await,
+                                pointer::ID => send_change(&pointer_tx, &id, key.clone()).await,
+                                presence::ID => send_change(&presence_tx, &id, key.clone()).await,
+                                printers::ID => send_change(&printers_tx, &id, key.clone()).await,
+                                profiles::ID => send_change(&profiles_tx, &id, key.clone()).await,
+                                privacy::ID => send_change(&privacy_tx, &id, key.clone()).await,
+                                proxy::ID => send_change(&proxy_tx, &id, key.clone()).await,
+                                qtct::ID => send_change(&qtct_tx, &id, key.clone()).await,
+                                seasons::ID => send_change(&seasons_tx, &id, key.clone()).await,
+                                sounds::ID => send_change(&sounds_tx, &id, key.clone()).await,
+                                thermal::ID => send_change(&thermal_tx, &id, key.clone()).await,
+                                wallpaper::ID => send_change(&wallpaper_tx, &id, key.clone()).await,
+                                _ => (),
+                            }
+
+                            if is_managed(&id) {
+                                let value = xdg_config
+                                    .as_ref()
+                                    .and_then(|config| config.read_key(&id, version, &key))
+                                    .unwrap_or_default();
+
+                                if let Err(err) = SettingsDaemon::changed(
+                                    interface.signal_context(),
+                                    &id,
+                                    &key,
+                                    &value,
+                                )
+                                .await
+                                {
+                                    eprintln!("Failed to send setting changed signal: {}", err);
+                                }
+                            }
+
                             let read_guard = settings_daemon.watched_configs.read().await;
                             let Some((conn, path, _)) = read_guard.get(&(id.to_string(), version))
                             else {
@@ -560,13 +1155,107 @@ async fn main() -> zbus::Result<()> {
                 }
             });
 
-            future::pending::<()>().await;
+            let mut sigterm = signal(SignalKind::terminate())?;
+            tokio::select! {
+                _ = sigterm.recv() => (),
+                _ = tokio::signal::ctrl_c() => (),
+            }
+
+            sounds::play(sounds::Event::Logout);
+            graphics::logout(&connection).await;
+            bluetooth::logout().await;
+            privacy::logout();
 
             Ok(())
         })
         .await
 }
 
+/// The change of a config or state key at `path`, in `dir`.
+fn key_change(dir: &Path, path: &Path, is_state: bool) -> Option<Change> {
+    if !path.is_file() {
+        return None;
+    }
+    let path = path.strip_prefix(dir).ok()?;
+    // really only care about keys
+    if path.starts_with(".atomicwrite") {
+        return None;
+    }
+
+    let key = path.file_name().map(|f| f.to_string_lossy())?;
+    // Keys are never hidden, unlike temporary files.
+    if key.starts_with('.') {
+        return None;
+    }
+    let version = path.parent().and_then(|parent_dir| {
+        parent_dir
+            .file_name()
+            .and_then(|f| f.to_str())
+            .and_then(|f| f.strip_prefix('v').and_then(|f| f.parse::<u64>().ok()))
+    })?;
+
+    let id = path
+        .parent()
+        .and_then(|parent_dir| parent_dir.parent().map(|f| f.to_string_lossy()))?;
+
+    if is_state {
+        Some(Change::State(id.into_owned(), key.into_owned(), version))
+    } else {
+        Some(Change::Config(id.into_owned(), key.into_owned(), version))
+    }
+}
+
+/// Send the changes of the config or state keys in `dir`, `<id>/v<version>/<key>`, to `tx`.
+fn watch_keys(dir: Option<PathBuf>, is_state: bool, tx: UnboundedSender<Vec<Change>>) {
+    let Some(dir) = dir else {
+        return;
+    };
+
+    let mut rx = match watch::register(&dir, "*/v*/*", true) {
+        Ok(rx) => rx,
+        Err(err) => {
+            eprintln!("Failed to watch {}: {}", dir.display(), err);
+            return;
+        }
+    };
+
+    task::spawn_local(async move {
+        while let Some(paths) = rx.recv().await {
+            let changes: Vec<_> = paths
+                .iter()
+                .filter_map(|path| key_change(&dir, path, is_state))
+                .collect();
+            if changes.is_empty() {
+                continue;
+            }
+            if let Err(err) = tx.send(changes) {
+                eprintln!("Failed to send config change: {}", err);
+                break;
+            }
+        }
+    });
+}
+
+/// Whether the daemon applies the config `id`, which are its own configs and the theme.
+fn is_managed(id: &str) -> bool {
+    id.strip_prefix(DBUS_NAME)
+        .is_some_and(|module| module.starts_with('.'))
+        || [
+            cosmic_theme::THEME_MODE_ID,
+            cosmic_theme::DARK_THEME_ID,
+            cosmic_theme::LIGHT_THEME_ID,
+            cosmic::config::toolkit::ID,
+        ]
+        .contains(&id)
+}
+
+/// Forward a changed config key to the module which owns the config.
+async fn send_change(tx: &tokio::sync::mpsc::Sender<String>, id: &str, key: String) {
+    if let Err(err) = tx.send(key).await {
+        eprintln!("Failed to send {id} update {err:?}");
+    }
+}
+
 async fn watch_config_message_stream(
     conn: Connection,
     watched_configs: Arc<
@@ -0,0 +1,344 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    time::Duration,
+};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+use tokio_stream::StreamExt;
+use zbus::{
+    fdo::ObjectManagerProxy,
+    zvariant::{ObjectPath, OwnedValue},
+    Connection, MatchRule, MessageStream,
+};
+
+use crate::udisks2::{BlockProxy, DriveProxy, FilesystemProxy};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Media";
+
+const UDISKS: &str = "org.freedesktop.UDisks2";
+const BLOCK_INTERFACE: &str = "org.freedesktop.UDisks2.Block";
+const DRIVE_INTERFACE: &str = "org.freedesktop.UDisks2.Drive";
+const FILESYSTEM_INTERFACE: &str = "org.freedesktop.UDisks2.Filesystem";
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct MediaConfig {
+    /// Mount removable media when it is inserted.
+    pub automount: bool,
+    /// Open the folder of removable media after mounting it, unless an action is set for its type.
+    pub open_folder: bool,
+    /// Never ask what to do or start programs when media is inserted.
+    pub never_prompt: bool,
+    /// What to do when media of a type is inserted, by type: `audio-cd`, `blank-disc`,
+    /// `camera`, or `storage`.
+    ///
+    /// An action is `ask`, `nothing`, `open-folder`, or a command, which is run with the
+    /// folder or URI of the media as its argument.
+    pub actions: BTreeMap<String, String>,
+}
+
+impl Default for MediaConfig {
+    fn default() -> Self {
+        Self {
+            automount: true,
+            open_folder: true,
+            never_prompt: false,
+            actions: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MediaType {
+    AudioCd,
+    BlankDisc,
+    Camera,
+    Storage,
+}
+
+impl MediaType {
+    fn name(self) -> &'static str {
+        match self {
+            MediaType::AudioCd => "audio-cd",
+            MediaType::BlankDisc => "blank-disc",
+            MediaType::Camera => "camera",
+            MediaType::Storage => "storage",
+        }
+    }
+
+    fn summary(self) -> &'static str {
+        match self {
+            MediaType::AudioCd => "Audio CD Inserted",
+            MediaType::BlankDisc => "Blank Disc Inserted",
+            MediaType::Camera => "Camera Connected",
+            MediaType::Storage => "Removable Drive Connected",
+        }
+    }
+}
+
+impl MediaConfig {
+    fn action(&self, media: MediaType) -> &str {
+        if let Some(action) = self.actions.get(media.name()) {
+            return action;
+        }
+
+        match media {
+            MediaType::AudioCd | MediaType::BlankDisc => "ask",
+            MediaType::Camera | MediaType::Storage if self.open_folder => "open-folder",
+            MediaType::Camera | MediaType::Storage => "nothing",
+        }
+    }
+}
+
+impl crate::demand::Feature for MediaConfig {
+    /// Whether inserted filesystems are mounted, or inserted discs do anything, which are the
+    /// media UDisks is watched for.
+    fn enabled(&self) -> bool {
+        self.automount
+            || [MediaType::AudioCd, MediaType::BlankDisc]
+                .into_iter()
+                .any(|media| match self.action(media) {
+                    "nothing" => false,
+                    "open-folder" => true,
+                    _ => !self.never_prompt,
+                })
+    }
+}
+
+fn open(location: &str) {
+    if let Err(err) = std::process::Command::new("xdg-open").arg(location).spawn() {
+        eprintln!("Failed to open {location}: {err}");
+    }
+}
+
+/// Apply the configured action for inserted media at `location`.
+fn run_action(config: &MediaConfig, media: MediaType, location: String) {
+    match config.action(media) {
+        "nothing" => (),
+        "open-folder" => open(&location),
+        _ if config.never_prompt => (),
+
+        "ask" => {
+            tokio::task::spawn_local(async move {
+                let Ok(handle) = Notification::new()
+                    .appname("")
+                    .summary(media.summary())
+                    .icon("media-removable-symbolic")
+                    .action("open", "Open")
+                    .timeout(Duration::from_secs(10))
+                    .show_async()
+                    .await
+                else {
+                    return;
+                };
+
+                if crate::notifications::wait_for_action(handle)
+                    .await
+                    .as_deref()
+                    == Some("open")
+                {
+                    open(&location);
+                }
+            });
+        }
+
+        command => {
+            if let Err(err) = std::process::Command::new(command).arg(&location).spawn() {
+                eprintln!("Failed to run {command} for {}: {err}", media.name());
+            }
+        }
+    }
+}
+
+fn device_name(device: Vec<u8>) -> String {
+    let device = device.strip_suffix(&[0]).unwrap_or(&device);
+    String::from_utf8_lossy(device).into_owned()
+}
+
+/// Mount a new filesystem if it is on removable media.
+async fn filesystem_added(
+    conn: &Connection,
+    path: ObjectPath<'_>,
+    config: &MediaConfig,
+) -> zbus::Result<()> {
+    let block = BlockProxy::builder(conn)
+        .path(path.clone())?
+        .build()
+        .await?;
+    if !block.hint_auto().await? || block.hint_ignore().await? || block.hint_system().await? {
+        return Ok(());
+    }
+
+    let filesystem = FilesystemProxy::builder(conn).path(path)?.build().await?;
+    let mount_point = filesystem.mount(HashMap::new()).await?;
+
+    let media = if Path::new(&mount_point).join("DCIM").is_dir() {
+        MediaType::Camera
+    } else {
+        MediaType::Storage
+    };
+
+    run_action(config, media, mount_point);
+    Ok(())
+}
+
+/// Apply the action for audio CDs and blank discs, which have no filesystem to mount.
+async fn disc_inserted(
+    conn: &Connection,
+    manager: &ObjectManagerProxy<'_>,
+    drive_path: ObjectPath<'_>,
+    config: &MediaConfig,
+) -> zbus::Result<()> {
+    let drive = DriveProxy::builder(conn)
+        .path(drive_path.clone())?
+        .build()
+        .await?;
+
+    let media = if drive.optical_blank().await? {
+        MediaType::BlankDisc
+    } else if drive.optical_num_audio_tracks().await? > 0 {
+        MediaType::AudioCd
+    } else {
+        return Ok(());
+    };
+
+    for (path, interfaces) in manager.get_managed_objects().await? {
+        if !interfaces
+            .keys()
+            .any(|name| name.as_str() == BLOCK_INTERFACE)
+        {
+            continue;
+        }
+
+        let block = BlockProxy::builder(conn).path(path)?.build().await?;
+        if block.drive().await?.as_str() != drive_path.as_str() {
+            continue;
+        }
+
+        let device = device_name(block.preferred_device().await?);
+        let location = match media {
+            MediaType::AudioCd => {
+                format!("cdda://{}", device.strip_prefix("/dev/").unwrap_or(&device))
+            }
+            _ => device,
+        };
+
+        run_action(config, media, location);
+        break;
+    }
+
+    Ok(())
+}
+
+/// Automount removable media through UDisks, and apply the action for its type.
+pub async fn monitor(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<MediaConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load media config {err:?}");
+            return;
+        }
+    };
+
+    let Ok(conn) = crate::bus::system().await else {
+        return;
+    };
+
+    let manager = async {
+        ObjectManagerProxy::builder(&conn)
+            .destination(UDISKS)?
+            .path("/org/freedesktop/UDisks2")?
+            .build()
+            .await
+    }
+    .await;
+
+    let Ok(manager) = manager else {
+        return;
+    };
+
+    let Ok(mut interfaces_added) = manager.receive_interfaces_added().await else {
+        return;
+    };
+
+    let drive_rule = MatchRule::builder()
+        .msg_type(zbus::MessageType::Signal)
+        .sender(UDISKS)
+        .and_then(|rule| rule.interface("org.freedesktop.DBus.Properties"))
+        .and_then(|rule| rule.member("PropertiesChanged"))
+        .and_then(|rule| rule.arg(0, DRIVE_INTERFACE))
+        .map(|rule| rule.build());
+
+    let Ok(drive_rule) = drive_rule else {
+        return;
+    };
+
+    let Ok(mut drive_changes) = MessageStream::for_match_rule(drive_rule, &conn, Some(16)).await
+    else {
+        return;
+    };
+
+    loop {
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                crate::config::update(&mut config, &helper, &key);
+            }
+
+            signal = interfaces_added.next() => {
+                let Some(signal) = signal else {
+                    break;
+                };
+
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+
+                let is_filesystem = args
+                    .interfaces_and_properties()
+                    .keys()
+                    .any(|name| name.as_str() == FILESYSTEM_INTERFACE);
+
+                if config.automount && is_filesystem {
+                    let path = args.object_path().to_owned();
+                    if let Err(err) = filesystem_added(&conn, path, &config).await {
+                        eprintln!("Failed to mount removable media: {err}");
+                    }
+                }
+            }
+
+            msg = drive_changes.next() => {
+                let Some(Ok(msg)) = msg else {
+                    break;
+                };
+
+                type Changed = (String, HashMap<String, OwnedValue>, Vec<String>);
+                let Ok((_, changed, _)) = msg.body::<Changed>() else {
+                    continue;
+                };
+
+                let media_available = changed
+                    .get("MediaAvailable")
+                    .and_then(|value| value.downcast_ref::<bool>())
+                    .copied()
+                    .unwrap_or(false);
+
+                let Some(path) = msg.path() else {
+                    continue;
+                };
+
+                if media_available {
+                    if let Err(err) = disc_inserted(&conn, &manager, path.to_owned(), &config).await {
+                        eprintln!("Failed to handle inserted disc: {err}");
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::fs::{MetadataExt, OpenOptionsExt},
+    },
+    time::{Duration, Instant},
+};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{unix::AsyncFd, Interest},
+    sync::mpsc::Receiver,
+};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Memory";
+
+const PRESSURE: &str = "/proc/pressure/memory";
+
+/// The window of the pressure stall triggers, which unprivileged users may only set to
+/// multiples of 2 seconds.
+const PRESSURE_WINDOW: Duration = Duration::from_secs(2);
+
+/// The reclaiming of memory stalls some tasks before it runs low, so a stall of this part of the
+/// window wakes the monitor to check the available memory and swap.
+const SOME_PRESSURE_DIVISOR: u32 = 20;
+
+/// While memory is low, it is checked again this often, until it is not.
+const LOW_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Pressure comes and goes, so the warning is not repeated more often than this.
+const WARNING_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct MemoryConfig {
+    /// Warn when the system is low on memory.
+    pub enabled: bool,
+    /// Warn when every task was stalled on memory for this percentage of the last 10 seconds.
+    pub full_pressure_percent: f32,
+    /// Warn when less than this percentage of memory is available.
+    pub available_percent: u32,
+    /// Warn when less than this percentage of swap is free, if there is swap.
+    pub swap_free_percent: u32,
+    /// Offer to close the application using the most memory. It is off by default, since
+    /// closing it loses its unsaved work.
+    pub offer_terminate: bool,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            full_pressure_percent: 10.0,
+            available_percent: 5,
+            swap_free_percent: 10,
+            offer_terminate: false,
+        }
+    }
+}
+
+impl crate::demand::Feature for MemoryConfig {
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+struct MemoryStatus {
+    /// `full avg10` of the memory pressure stall information.
+    full_pressure: f32,
+    available_percent: u64,
+    swap_free_percent: Option<u64>,
+}
+
+fn full_pressure() -> Option<f32> {
+    let pressure = fs::read_to_string("/proc/pressure/memory").ok()?;
+    pressure
+        .lines()
+        .find(|line| line.starts_with("full "))?
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("avg10="))?
+        .parse()
+        .ok()
+}
+
+fn status() -> Option<MemoryStatus> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let field = |name: &str| -> Option<u64> {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))?
+            .trim()
+            .trim_end_matches("kB")
+            .trim()
+            .parse()
+            .ok()
+    };
+
+    let total = field("MemTotal").filter(|total| *total > 0)?;
+    let available = field("MemAvailable")?;
+    let swap_total = field("SwapTotal").unwrap_or(0);
+    let swap_free = field("SwapFree").unwrap_or(0);
+
+    Some(MemoryStatus {
+        full_pressure: full_pressure().unwrap_or(0.0),
+        available_percent: available * 100 / total,
+        swap_free_percent: (swap_total > 0).then(|| swap_free * 100 / swap_total),
+    })
+}
+
+impl MemoryConfig {
+    fn is_low(&self, status: &MemoryStatus) -> bool {
+        let swap_low = status
+            .swap_free_percent
+            .map_or(true, |free| free < u64::from(self.swap_free_percent));
+
+        status.full_pressure >= self.full_pressure_percent
+            || (status.available_percent < u64::from(self.available_percent) && swap_low)
+    }
+}
+
+/// Open a pidfd of a process, which keeps referring to that process after it exits, rather than
+/// to another process reusing its ID.
+fn pidfd_open(pid: i32) -> io::Result<OwnedFd> {
+    // SAFETY: pidfd_open takes no pointers.
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: pidfd_open returned a new file descriptor, which nothing else owns.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+}
+
+fn pidfd_send_signal(pidfd: &OwnedFd, signal: i32) -> io::Result<()> {
+    // SAFETY: The pidfd is open, and without signal info the signal is sent as by kill.
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_pidfd_send_signal,
+            pidfd.as_raw_fd(),
+            signal,
+            std::ptr::null::<libc::siginfo_t>(),
+            0,
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The application with the largest resident set, as a pidfd, and its name. Only processes in an
+/// `app-*.scope` are considered, so that the compositor and the session are never offered.
+fn largest_process() -> Option<(OwnedFd, String)> {
+    // SAFETY: getuid cannot fail.
+    let uid = unsafe { libc::getuid() };
+    let own_pid = std::process::id() as i32;
+
+    fs::read_dir("/proc")
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.metadata().is_ok_and(|m| m.uid() == uid))
+        .filter_map(|entry| {
+            let pid: i32 = entry.file_name().to_str()?.parse().ok()?;
+            if !cosmic_settings_daemon_lib::cgroup::is_app(pid as u32) {
+                return None;
+            }
+            let status = fs::read_to_string(entry.path().join("status")).ok()?;
+            let name = status.lines().find_map(|line| line.strip_prefix("Name:"))?;
+            let rss: u64 = status
+                .lines()
+                .find_map(|line| line.strip_prefix("VmRSS:"))?
+                .trim()
+                .trim_end_matches("kB")
+                .trim()
+                .parse()
+                .ok()?;
+            Some((rss, pid, name.trim().to_owned()))
+        })
+        .filter(|(_, pid, _)| *pid != own_pid)
+        .max_by_key(|(rss, _, _)| *rss)
+        .and_then(|(_, pid, name)| {
+            // The process is checked again once the pidfd is open, in case it exited and another
+            // process took its ID in the meantime.
+            let pidfd = pidfd_open(pid).ok()?;
+            cosmic_settings_daemon_lib::cgroup::is_app(pid as u32).then_some((pidfd, name))
+        })
+}
+
+fn warn(offer_terminate: bool) {
+    tokio::task::spawn_local(async move {
+        // Reading the status of every process is slow with many processes.
+        let process = if offer_terminate {
+            tokio::task::spawn_blocking(largest_process)
+                .await
+                .ok()
+                .flatten()
+        } else {
+            None
+        };
+
+        let mut notification = Notification::new();
+        notification
+            .appname("")
+            .summary("System Is Low on Memory")
+            .body("Close some applications to keep the system responsive.")
+            .icon("dialog-warning-symbolic")
+            .urgency(notify_rust::Urgency::Critical);
+
+        if let Some((_, name)) = process.as_ref() {
+            notification.action("terminate", &format!("Close {name}"));
+        }
+
+        let Ok(handle) = notification.show_async().await else {
+            return;
+        };
+
+        if crate::notifications::wait_for_action(handle)
+            .await
+            .as_deref()
+            == Some("terminate")
+        {
+            if let Some((pidfd, name)) = process {
+                // Signalling through the pidfd never reaches another process which took the ID
+                // of the offered one after it exited.
+                if let Err(err) = pidfd_send_signal(&pidfd, libc::SIGTERM) {
+                    eprintln!("Failed to close {name}: {err}");
+                }
+            }
+        }
+    });
+}
+
+/// The pressure stall triggers, which are readable with priority when their stall is exceeded.
+struct Triggers {
+    full: AsyncFd<File>,
+    some: AsyncFd<File>,
+}
+
+fn trigger(kind: &str, stall: Duration) -> io::Result<AsyncFd<File>> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(PRESSURE)?;
+    // The kernel rejects stalls which are not within the window, and replaces the last byte
+    // written with a nul.
+    let window = PRESSURE_WINDOW.as_micros();
+    let stall = stall.as_micros().clamp(1, window - 1);
+    file.write_all(format!("{kind} {stall} {window}\0").as_bytes())?;
+    AsyncFd::with_interest(file, Interest::PRIORITY)
+}
+
+fn open_triggers(config: &MemoryConfig) -> io::Result<Triggers> {
+    let full = PRESSURE_WINDOW.mul_f32(config.full_pressure_percent.clamp(0.0, 100.0) / 100.0);
+    Ok(Triggers {
+        full: trigger("full", full)?,
+        some: trigger("some", PRESSURE_WINDOW / SOME_PRESSURE_DIVISOR)?,
+    })
+}
+
+/// Wait for a trigger to be exceeded, or forever without triggers.
+async fn pressure(triggers: Option<&Triggers>) -> io::Result<()> {
+    let Some(triggers) = triggers else {
+        return std::future::pending().await;
+    };
+
+    let mut guard = tokio::select! {
+        guard = triggers.full.ready(Interest::PRIORITY) => guard?,
+        guard = triggers.some.ready(Interest::PRIORITY) => guard?,
+    };
+    guard.clear_ready();
+    Ok(())
+}
+
+/// Warn before the system runs out of memory, using pressure stall information and free swap.
+///
+/// The monitor only wakes when the pressure stall triggers of the kernel are exceeded, and then
+/// while memory is low. Nothing runs while the warning is disabled.
+pub async fn monitor(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<MemoryConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load memory config {err:?}");
+            return;
+        }
+    };
+
+    let mut low = false;
+    let mut last_warning: Option<Instant> = None;
+
+    'triggers: loop {
+        let triggers = if config.enabled {
+            match open_triggers(&config) {
+                Ok(triggers) => Some(triggers),
+                Err(err) => {
+                    eprintln!("Failed to watch the memory pressure: {err}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        if triggers.is_none() {
+            low = false;
+        }
+
+        loop {
+            let recheck = async move {
+                if low {
+                    tokio::time::sleep(LOW_INTERVAL).await;
+                } else {
+                    std::future::pending().await
+                }
+            };
+
+            tokio::select! {
+                key = rx.recv() => {
+                    let Some(key) = key else {
+                        break 'triggers;
+                    };
+
+                    let changes = crate::config::update(&mut config, &helper, &key);
+                    if changes.contains(&"enabled") || changes.contains(&"full_pressure_percent") {
+                        continue 'triggers;
+                    }
+                    continue;
+                }
+
+                res = pressure(triggers.as_ref()), if triggers.is_some() => {
+                    if let Err(err) = res {
+                        eprintln!("Failed to wait for memory pressure: {err}");
+                        tokio::time::sleep(LOW_INTERVAL).await;
+                        continue 'triggers;
+                    }
+                }
+
+                () = recheck => (),
+            }
+
+            let Some(status) = status() else {
+                continue;
+            };
+
+            let is_low = config.is_low(&status);
+            let recently_warned = last_warning.is_some_and(|t| t.elapsed() < WARNING_INTERVAL);
+            if is_low && !low && !recently_warned {
+                last_warning = Some(Instant::now());
+                warn(config.offer_terminate);
+            }
+            low = is_low;
+        }
+    }
+}
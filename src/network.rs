@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tokio_stream::StreamExt;
+use zbus::{Connection, InterfaceRef, SignalContext};
+
+use crate::{network_manager::NetworkManagerProxy, DBUS_PATH};
+
+static METERED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the primary connection is metered, so that downloads which can wait should.
+pub fn is_metered() -> bool {
+    METERED.load(Ordering::Relaxed)
+}
+
+/// Whether an `NMMetered` value is metered, including when NetworkManager guessed it.
+fn metered(value: u32) -> bool {
+    matches!(value, 1 | 3)
+}
+
+/// Network state for other components of the desktop.
+#[derive(Default)]
+pub struct Network {
+    metered: bool,
+}
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.Network")]
+impl Network {
+    /// Whether the primary connection is metered.
+    #[dbus_interface(property)]
+    async fn is_metered(&self) -> bool {
+        self.metered
+    }
+
+    #[dbus_interface(signal)]
+    async fn metered_changed(ctxt: &SignalContext<'_>, metered: bool) -> zbus::Result<()>;
+}
+
+/// Follow whether the primary connection of NetworkManager is metered.
+pub async fn monitor(conn: Connection) {
+    let Ok(interface) = conn
+        .object_server()
+        .interface::<_, Network>(DBUS_PATH)
+        .await
+    else {
+        return;
+    };
+
+    let Ok(system_conn) = crate::bus::system().await else {
+        return;
+    };
+
+    let Ok(network_manager) = NetworkManagerProxy::new(&system_conn).await else {
+        return;
+    };
+
+    let mut changes = network_manager.receive_metered_changed().await;
+    if let Ok(value) = network_manager.metered().await {
+        publish(&interface, metered(value)).await;
+    }
+
+    while let Some(change) = changes.next().await {
+        if let Ok(value) = change.get().await {
+            publish(&interface, metered(value)).await;
+        }
+    }
+}
+
+async fn publish(interface: &InterfaceRef<Network>, metered: bool) {
+    METERED.store(metered, Ordering::Relaxed);
+
+    let mut network = interface.get_mut().await;
+    if network.metered != metered {
+        network.metered = metered;
+        let ctxt = interface.signal_context();
+        _ = network.is_metered_changed(ctxt).await;
+        _ = Network::metered_changed(ctxt, metered).await;
+    }
+}
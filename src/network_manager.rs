@@ -0,0 +1,10 @@
+#[zbus::dbus_proxy(
+    default_service = "org.freedesktop.NetworkManager",
+    interface = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager"
+)]
+trait NetworkManager {
+    /// Whether the primary connection is metered, as an `NMMetered` value.
+    #[dbus_proxy(property)]
+    fn metered(&self) -> zbus::Result<u32>;
+}
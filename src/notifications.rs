@@ -0,0 +1,48 @@
+use notify_rust::NotificationHandle;
+use tokio_stream::StreamExt;
+
+#[zbus::dbus_proxy(
+    default_service = "org.freedesktop.Notifications",
+    interface = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[dbus_proxy(signal)]
+    fn action_invoked(&self, id: u32, action_key: String) -> zbus::Result<()>;
+
+    #[dbus_proxy(signal)]
+    fn notification_closed(&self, id: u32, reason: u32) -> zbus::Result<()>;
+}
+
+/// Wait for the user to invoke one of the actions of a notification.
+///
+/// Returns `None` if the notification was closed without invoking an action. The signals of the
+/// notification server are followed on the shared session connection, so that open
+/// notifications do not hold threads of the blocking pool.
+pub async fn wait_for_action(handle: NotificationHandle) -> Option<String> {
+    let id = handle.id();
+    let conn = crate::bus::session().await.ok()?;
+    let proxy = NotificationsProxy::new(&conn).await.ok()?;
+    // The signals are of every notification, as only string arguments can be matched.
+    let mut actions = proxy.receive_action_invoked().await.ok()?;
+    let mut closed = proxy.receive_notification_closed().await.ok()?;
+
+    loop {
+        tokio::select! {
+            signal = actions.next() => {
+                let Ok(args) = signal?.args() else {
+                    continue;
+                };
+                if args.id == id {
+                    return Some(args.action_key);
+                }
+            }
+
+            signal = closed.next() => {
+                if signal?.args().is_ok_and(|args| args.id == id) {
+                    return None;
+                }
+            }
+        }
+    }
+}
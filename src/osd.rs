@@ -0,0 +1,152 @@
+use std::{sync::OnceLock, time::Duration};
+
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    time::Instant,
+};
+use zbus::{Connection, SignalContext};
+
+pub const OSD_PATH: &str = "/com/system76/CosmicSettingsDaemon/Osd";
+
+/// Events of the same kind arriving within this window are published once, with the latest value.
+const COALESCE_WINDOW: Duration = Duration::from_millis(50);
+
+static TX: OnceLock<UnboundedSender<OsdEvent>> = OnceLock::new();
+
+#[derive(Clone, Debug)]
+pub struct OsdEvent {
+    kind: &'static str,
+    value: i32,
+    icon: String,
+}
+
+/// Publish a transient indicator, such as the new volume after a volume key was pressed.
+///
+/// `value` is a percentage, `0` or `1` for toggles, a color as `0xRRGGBB` for `color-picked`, or
+/// `-1` if there is no value.
+pub fn show(kind: &'static str, value: i32, icon: impl Into<String>) {
+    // Events of an inactive session would show once it is active again.
+    if !crate::seat::is_active() {
+        return;
+    }
+
+    if let Some(tx) = TX.get() {
+        let _res = tx.send(OsdEvent {
+            kind,
+            value,
+            icon: icon.into(),
+        });
+    }
+}
+
+/// Icon for a volume percentage.
+pub fn volume_icon(device: &str, volume: i32, muted: bool) -> String {
+    let level = if muted || volume <= 0 {
+        "muted"
+    } else if volume < 33 {
+        "low"
+    } else if volume < 66 {
+        "medium"
+    } else if volume <= 100 {
+        "high"
+    } else {
+        "overamplified"
+    };
+
+    format!("{device}-volume-{level}-symbolic")
+}
+
+const LOCK_KEYS: [&str; 3] = ["caps-lock", "num-lock", "scroll-lock"];
+
+/// The channel the OSD subscribes to for indicators from all modules.
+#[derive(Default)]
+pub struct Osd {
+    sequence: u64,
+}
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.Osd")]
+impl Osd {
+    /// Sequence number of the last event, which increases monotonically.
+    #[dbus_interface(property)]
+    async fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Report a change of a lock key by the compositor.
+    async fn lock_key_changed(&self, key: &str, enabled: bool) -> zbus::fdo::Result<()> {
+        let Some(kind) = LOCK_KEYS.into_iter().find(|k| *k == key) else {
+            return Err(zbus::fdo::Error::InvalidArgs(format!(
+                "unknown lock key {key}"
+            )));
+        };
+
+        let state = if enabled { "enabled" } else { "disabled" };
+        show(kind, i32::from(enabled), format!("{kind}-{state}-symbolic"));
+        Ok(())
+    }
+
+    #[dbus_interface(signal)]
+    async fn show(
+        ctxt: &SignalContext<'_>,
+        sequence: u64,
+        kind: &str,
+        value: i32,
+        icon: &str,
+    ) -> zbus::Result<()>;
+}
+
+/// The receiver of the indicators shown from then on, for `run`, or `None` if it was taken
+/// already. The indicators of key presses handled before the session bus serves the OSD are
+/// published once it does.
+pub fn channel() -> Option<UnboundedReceiver<OsdEvent>> {
+    let (tx, rx) = unbounded_channel();
+    TX.set(tx).is_ok().then_some(rx)
+}
+
+/// Publish OSD events on D-Bus, coalescing rapid repeats of the same kind.
+pub async fn run(conn: Connection, mut rx: UnboundedReceiver<OsdEvent>) {
+    let Ok(interface) = conn.object_server().interface::<_, Osd>(OSD_PATH).await else {
+        return;
+    };
+
+    let mut pending: Vec<OsdEvent> = Vec::new();
+
+    while let Some(event) = rx.recv().await {
+        pending.push(event);
+
+        let deadline = Instant::now() + COALESCE_WINDOW;
+        loop {
+            tokio::select! {
+                event = rx.recv() => {
+                    let Some(event) = event else {
+                        break;
+                    };
+
+                    match pending.iter_mut().find(|p| p.kind == event.kind) {
+                        Some(p) => *p = event,
+                        None => pending.push(event),
+                    }
+                }
+
+                _ = tokio::time::sleep_until(deadline) => break,
+            }
+        }
+
+        let mut osd = interface.get_mut().await;
+        for event in pending.drain(..) {
+            osd.sequence += 1;
+            if let Err(err) = Osd::show(
+                interface.signal_context(),
+                osd.sequence,
+                event.kind,
+                event.value,
+                &event.icon,
+            )
+            .await
+            {
+                eprintln!("Failed to send OSD event: {err}");
+            }
+        }
+        _ = osd.sequence_changed(interface.signal_context()).await;
+    }
+}
@@ -0,0 +1,366 @@
+//! Audio device control through `pactl`, which works with both PulseAudio and pipewire-pulse.
+
+use std::{collections::BTreeMap, io, process::Stdio};
+
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    sync::mpsc::Receiver,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeviceKind {
+    Sink,
+    Source,
+}
+
+impl DeviceKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeviceKind::Sink => "sink",
+            DeviceKind::Source => "source",
+        }
+    }
+
+    fn plural(self) -> &'static str {
+        match self {
+            DeviceKind::Sink => "sinks",
+            DeviceKind::Source => "sources",
+        }
+    }
+
+    pub fn default_device(self) -> &'static str {
+        match self {
+            DeviceKind::Sink => "@DEFAULT_SINK@",
+            DeviceKind::Source => "@DEFAULT_SOURCE@",
+        }
+    }
+}
+
+async fn pactl(args: &[&str]) -> io::Result<String> {
+    let output = tokio::process::Command::new("pactl")
+        .args(args)
+        // Output is parsed, so it must not be translated.
+        .env("LC_ALL", "C")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("pactl {} exited with {}", args.join(" "), output.status),
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The average volume of all channels of a device, as a percentage.
+pub async fn volume(kind: DeviceKind, device: &str) -> io::Result<u32> {
+    let output = pactl(&[&format!("get-{}-volume", kind.as_str()), device]).await?;
+
+    // Volume: front-left: 26214 /  40% / -23.88 dB,   front-right: 26214 /  40% / -23.88 dB
+    let channels: Vec<u32> = output
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter_map(|word| word.strip_suffix('%')?.parse().ok())
+        .collect();
+
+    if channels.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("failed to parse volume of {device}"),
+        ));
+    }
+
+    Ok(channels.iter().sum::<u32>() / channels.len() as u32)
+}
+
+pub async fn set_volume(kind: DeviceKind, device: &str, percent: u32) -> io::Result<()> {
+    pactl(&[
+        &format!("set-{}-volume", kind.as_str()),
+        device,
+        &format!("{percent}%"),
+    ])
+    .await
+    .map(|_| ())
+}
+
+pub async fn mute(kind: DeviceKind, device: &str) -> io::Result<bool> {
+    let output = pactl(&[&format!("get-{}-mute", kind.as_str()), device]).await?;
+    Ok(output.trim() == "Mute: yes")
+}
+
+pub async fn set_mute(kind: DeviceKind, device: &str, mute: bool) -> io::Result<()> {
+    pactl(&[
+        &format!("set-{}-mute", kind.as_str()),
+        device,
+        if mute { "1" } else { "0" },
+    ])
+    .await
+    .map(|_| ())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    New,
+    Change,
+    Remove,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Facility {
+    Sink,
+    Source,
+    SinkInput,
+    SourceOutput,
+    Card,
+    Server,
+    Other,
+}
+
+/// A change reported by `pactl subscribe`.
+#[derive(Clone, Copy, Debug)]
+pub struct Event {
+    pub kind: EventKind,
+    pub facility: Facility,
+    pub index: Option<u32>,
+}
+
+impl Event {
+    /// Parse a line such as `Event 'new' on sink #56`.
+    fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        if words.next()? != "Event" {
+            return None;
+        }
+
+        let kind = match words.next()?.trim_matches('\'') {
+            "new" => EventKind::New,
+            "change" => EventKind::Change,
+            "remove" => EventKind::Remove,
+            _ => return None,
+        };
+
+        if words.next()? != "on" {
+            return None;
+        }
+
+        let facility = match words.next()? {
+            "sink" => Facility::Sink,
+            "source" => Facility::Source,
+            "sink-input" => Facility::SinkInput,
+            "source-output" => Facility::SourceOutput,
+            "card" => Facility::Card,
+            "server" => Facility::Server,
+            _ => Facility::Other,
+        };
+
+        let index = words
+            .next()
+            .and_then(|index| index.strip_prefix('#')?.parse().ok());
+
+        Some(Self {
+            kind,
+            facility,
+            index,
+        })
+    }
+}
+
+/// Spawn `pactl subscribe`, returning its events until the audio server goes away.
+pub fn subscribe() -> io::Result<Receiver<Event>> {
+    let mut child = tokio::process::Command::new("pactl")
+        .arg("subscribe")
+        .env("LC_ALL", "C")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let Some(stdout) = child.stdout.take() else {
+        return Err(io::Error::new(
+            io::ErrorKind::BrokenPipe,
+            "pactl subscribe has no stdout",
+        ));
+    };
+
+    let (tx, rx) = tokio::sync::mpsc::channel(64);
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stdout).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let Some(event) = Event::parse(&line) {
+                if tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+        }
+
+        let _res = child.kill().await;
+    });
+
+    Ok(rx)
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Port {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default, rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub availability: String,
+}
+
+impl Port {
+    pub fn is_available(&self) -> bool {
+        self.availability != "not available"
+    }
+
+    pub fn is_headphones(&self) -> bool {
+        matches!(self.kind.as_str(), "Headphones" | "Headset") || self.name.contains("headphones")
+    }
+}
+
+/// A sink or source, as listed by `pactl --format=json`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Device {
+    pub index: u32,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub mute: bool,
+    #[serde(default)]
+    pub properties: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub ports: Vec<Port>,
+    #[serde(default)]
+    pub active_port: Option<String>,
+}
+
+impl Device {
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).and_then(|value| value.as_str())
+    }
+
+    /// The name of the card the device belongs to.
+    pub fn card_name(&self) -> Option<&str> {
+        self.property("device.name")
+    }
+
+    /// USB and Bluetooth devices, which come and go.
+    pub fn is_external(&self) -> bool {
+        matches!(self.property("device.bus"), Some("usb" | "bluetooth"))
+    }
+
+    /// Sources which only mirror the output of a sink.
+    pub fn is_monitor(&self) -> bool {
+        self.name.ends_with(".monitor")
+    }
+}
+
+pub async fn devices(kind: DeviceKind) -> io::Result<Vec<Device>> {
+    list(kind.plural()).await
+}
+
+pub async fn default_device_name(kind: DeviceKind) -> io::Result<String> {
+    let output = pactl(&[&format!("get-default-{}", kind.as_str())]).await?;
+    Ok(output.trim().to_owned())
+}
+
+pub async fn set_port(kind: DeviceKind, device: &str, port: &str) -> io::Result<()> {
+    pactl(&[&format!("set-{}-port", kind.as_str()), device, port])
+        .await
+        .map(|_| ())
+}
+
+pub async fn set_default(kind: DeviceKind, device: &str) -> io::Result<()> {
+    pactl(&[&format!("set-default-{}", kind.as_str()), device])
+        .await
+        .map(|_| ())
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Profile {
+    #[serde(default)]
+    pub available: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Card {
+    pub index: u32,
+    pub name: String,
+    #[serde(default)]
+    pub properties: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    #[serde(default)]
+    pub active_profile: String,
+}
+
+impl Card {
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).and_then(|value| value.as_str())
+    }
+}
+
+/// A stream recording from a source.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SourceOutput {
+    pub index: u32,
+    pub source: u32,
+    #[serde(default)]
+    pub properties: BTreeMap<String, serde_json::Value>,
+}
+
+impl SourceOutput {
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).and_then(|value| value.as_str())
+    }
+}
+
+async fn list<T: serde::de::DeserializeOwned>(kind: &str) -> io::Result<Vec<T>> {
+    let output = pactl(&["--format=json", "list", kind]).await?;
+    serde_json::from_str(&output).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub async fn cards() -> io::Result<Vec<Card>> {
+    list("cards").await
+}
+
+pub async fn source_outputs() -> io::Result<Vec<SourceOutput>> {
+    list("source-outputs").await
+}
+
+pub async fn set_card_profile(card: &str, profile: &str) -> io::Result<()> {
+    pactl(&["set-card-profile", card, profile])
+        .await
+        .map(|_| ())
+}
+
+/// Load a module into the audio server, returning its index.
+pub async fn load_module(name: &str, args: &[&str]) -> io::Result<u32> {
+    let mut command = vec!["load-module", name];
+    command.extend_from_slice(args);
+
+    let output = pactl(&command).await?;
+    output.trim().parse().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("failed to parse index of module {name}"),
+        )
+    })
+}
+
+pub async fn unload_module(index: u32) -> io::Result<()> {
+    pactl(&["unload-module", &index.to_string()])
+        .await
+        .map(|_| ())
+}
@@ -0,0 +1,289 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::Context;
+use cosmic::theme::CosmicTheme;
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use cosmic_settings_daemon_lib::{
+    config::{ConfigDir, ConfigProvider},
+    palette::{hex, Palette, Rgb},
+};
+use cosmic_theme::{palette::Srgb, ThemeBuilder};
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Palette";
+
+/// The image is scaled down to at most this size before its colors are counted.
+const THUMBNAIL_SIZE: u32 = 64;
+
+static CONFIG: Mutex<Option<PaletteConfig>> = Mutex::new(None);
+
+/// The wallpaper and theme mode the palette was last generated for, so that edits of the theme,
+/// such as the accent set from the palette, do not decode the wallpaper again.
+static GENERATED: Mutex<Option<(PathBuf, bool)>> = Mutex::new(None);
+
+/// A palette of 16 colors generated from the wallpaper, like that of pywal, for terminals and
+/// other applications which are themed with templates.
+///
+/// The palette is written to `$XDG_CACHE_HOME/cosmic-settings-daemon/palette/` as
+/// `colors.json`, `colors.Xresources`, and `sequences` of terminal escape codes, which a shell
+/// can print to recolor its terminal. Every file in
+/// `$XDG_DATA_HOME/cosmic-settings-daemon/palette-templates/` is written there too, with
+/// `{color0}` to `{color15}`, `{background}`, `{foreground}`, `{cursor}`, and `{wallpaper}`
+/// replaced. The `palette-changed` hook runs afterwards, such as for reloading applications.
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct PaletteConfig {
+    /// Generate the palette whenever the wallpaper of all outputs or the theme mode changes.
+    pub enabled: bool,
+    /// Also set the accent color of the theme of the current mode to the most vivid color of
+    /// the wallpaper, which is exported to the toolkits with the rest of the theme.
+    pub accent: bool,
+}
+
+/// A background entry of cosmic-bg, of which only the source is needed.
+#[derive(Deserialize)]
+struct BackgroundEntry {
+    source: BackgroundSource,
+}
+
+#[derive(Deserialize)]
+enum BackgroundSource {
+    Path(PathBuf),
+    Color(IgnoredAny),
+}
+
+/// The image of the wallpaper of all outputs, unless it is a color or a slideshow directory,
+/// which has no single image.
+pub fn wallpaper() -> Option<PathBuf> {
+    let entry = ConfigDir::user()?.read_key(crate::wallpaper::BACKGROUND_ID, 1, "all")?;
+    match ron::from_str::<BackgroundEntry>(&entry).ok()?.source {
+        BackgroundSource::Path(path) if path.is_file() => Some(path),
+        _ => None,
+    }
+}
+
+/// The values of the placeholders of templates.
+fn variables(palette: &Palette, wallpaper: &Path) -> Vec<(String, String)> {
+    let mut variables: Vec<(String, String)> = palette
+        .colors
+        .iter()
+        .enumerate()
+        .map(|(i, color)| (format!("color{i}"), hex(*color)))
+        .collect();
+    variables.push(("background".to_owned(), hex(palette.background())));
+    variables.push(("foreground".to_owned(), hex(palette.foreground())));
+    variables.push(("cursor".to_owned(), hex(palette.foreground())));
+    variables.push(("wallpaper".to_owned(), wallpaper.display().to_string()));
+    variables
+}
+
+#[derive(Serialize)]
+struct ColorsJson {
+    wallpaper: PathBuf,
+    special: BTreeMap<&'static str, String>,
+    colors: BTreeMap<String, String>,
+}
+
+fn colors_json(palette: &Palette, wallpaper: &Path) -> serde_json::Result<String> {
+    let foreground = hex(palette.foreground());
+    let colors = ColorsJson {
+        wallpaper: wallpaper.to_owned(),
+        special: BTreeMap::from([
+            ("background", hex(palette.background())),
+            ("foreground", foreground.clone()),
+            ("cursor", foreground),
+        ]),
+        colors: palette
+            .colors
+            .iter()
+            .enumerate()
+            .map(|(i, color)| (format!("color{i}"), hex(*color)))
+            .collect(),
+    };
+    serde_json::to_string_pretty(&colors)
+}
+
+fn xresources(palette: &Palette) -> String {
+    let mut contents = String::new();
+    _ = writeln!(contents, "*.background: {}", hex(palette.background()));
+    _ = writeln!(contents, "*.foreground: {}", hex(palette.foreground()));
+    _ = writeln!(contents, "*.cursorColor: {}", hex(palette.foreground()));
+    for (i, color) in palette.colors.iter().enumerate() {
+        _ = writeln!(contents, "*.color{i}: {}", hex(*color));
+    }
+    contents
+}
+
+/// The escape codes which set the palette, foreground, background, and cursor of a terminal.
+fn sequences(palette: &Palette) -> String {
+    let mut contents = String::new();
+    for (i, color) in palette.colors.iter().enumerate() {
+        _ = write!(contents, "\x1b]4;{i};{}\x1b\\", hex(*color));
+    }
+    _ = write!(contents, "\x1b]10;{}\x1b\\", hex(palette.foreground()));
+    _ = write!(contents, "\x1b]11;{}\x1b\\", hex(palette.background()));
+    _ = write!(contents, "\x1b]12;{}\x1b\\", hex(palette.foreground()));
+    contents
+}
+
+fn output_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("cosmic-settings-daemon").join("palette"))
+}
+
+fn templates_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("cosmic-settings-daemon").join("palette-templates"))
+}
+
+/// Render every template with the palette into `output`.
+fn render_templates(output: &Path, variables: &[(String, String)]) {
+    let Some(Ok(templates)) = templates_dir().map(fs::read_dir) else {
+        return;
+    };
+
+    for template in templates.filter_map(Result::ok).map(|entry| entry.path()) {
+        let (Some(name), Ok(mut contents)) = (template.file_name(), fs::read_to_string(&template))
+        else {
+            continue;
+        };
+
+        for (variable, value) in variables {
+            contents = contents.replace(&format!("{{{variable}}}"), value);
+        }
+
+        let path = output.join(name);
+        if let Err(err) = crate::files::write_atomic(&path, &contents) {
+            eprintln!(
+                "Failed to write the palette template {}: {err}",
+                path.display()
+            );
+        }
+    }
+}
+
+/// Set the accent color of the theme of the mode, and rebuild the theme from it.
+pub fn set_accent(is_dark: bool, [r, g, b]: Rgb) -> anyhow::Result<()> {
+    let builder_config = if is_dark {
+        ThemeBuilder::dark_config()?
+    } else {
+        ThemeBuilder::light_config()?
+    };
+    let mut builder = match ThemeBuilder::get_entry(&builder_config) {
+        Ok(builder) => builder,
+        Err((_, builder)) => builder,
+    };
+
+    let accent = Srgb::new(r, g, b).into_format();
+    if builder.accent == Some(accent) {
+        return Ok(());
+    }
+
+    builder.accent = Some(accent);
+    builder.write_entry(&builder_config)?;
+
+    let theme_config = if is_dark {
+        CosmicTheme::dark_config()?
+    } else {
+        CosmicTheme::light_config()?
+    };
+    builder.build().write_entry(&theme_config)?;
+    Ok(())
+}
+
+/// Generate the palette of the wallpaper, and write it and the templates.
+fn generate(config: &PaletteConfig, wallpaper: &Path, is_dark: bool) -> anyhow::Result<PathBuf> {
+    let image = image::open(wallpaper)
+        .with_context(|| format!("failed to open {}", wallpaper.display()))?
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .into_rgb8();
+    let pixels = image.pixels().map(|pixel| pixel.0).collect();
+    let palette = Palette::from_pixels(pixels, is_dark).context("the wallpaper has no pixels")?;
+
+    let output = output_dir().context("no cache directory")?;
+    crate::files::write_atomic(
+        &output.join("colors.json"),
+        &colors_json(&palette, wallpaper)?,
+    )?;
+    crate::files::write_atomic(&output.join("colors.Xresources"), &xresources(&palette))?;
+    crate::files::write_atomic(&output.join("sequences"), &sequences(&palette))?;
+    render_templates(&output, &variables(&palette, wallpaper));
+
+    if config.accent {
+        set_accent(is_dark, palette.accent).context("failed to set the accent color")?;
+    }
+
+    Ok(output)
+}
+
+/// Generate the palette after the theme or the wallpaper changed, if enabled and the wallpaper
+/// or the theme mode is new.
+pub async fn theme_changed(is_dark: bool) {
+    let config = CONFIG.lock().ok().and_then(|config| config.clone());
+    let Some(config) = config.filter(|config| config.enabled) else {
+        return;
+    };
+
+    let Some(wallpaper) = wallpaper() else {
+        return;
+    };
+
+    let key = Some((wallpaper.clone(), is_dark));
+    if GENERATED.lock().is_ok_and(|generated| *generated == key) {
+        return;
+    }
+
+    // Decoding the wallpaper takes a while.
+    let res = tokio::task::spawn_blocking(move || generate(&config, &wallpaper, is_dark)).await;
+    match res {
+        Ok(Ok(output)) => {
+            if let Ok(mut generated) = GENERATED.lock() {
+                *generated = key;
+            }
+            crate::hooks::run(
+                "palette-changed",
+                vec![("COSMIC_PALETTE_DIR", output.display().to_string())],
+            );
+        }
+        Ok(Err(err)) => eprintln!("Failed to generate the palette of the wallpaper: {err:#}"),
+        Err(err) => eprintln!("Failed to generate the palette of the wallpaper: {err}"),
+    }
+}
+
+/// Generate the palette again whenever the config changes.
+pub async fn run(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<PaletteConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load palette config {err:?}");
+            return;
+        }
+    };
+
+    loop {
+        if let Ok(mut global) = CONFIG.lock() {
+            *global = Some(config.clone());
+        }
+        if let Ok(mut generated) = GENERATED.lock() {
+            *generated = None;
+        }
+
+        let (is_dark, _) = crate::theme::current();
+        theme_changed(is_dark).await;
+
+        loop {
+            let Some(key) = rx.recv().await else {
+                return;
+            };
+
+            if !crate::config::update(&mut config, &helper, &key).is_empty() {
+                break;
+            }
+        }
+    }
+}
@@ -1,9 +1,15 @@
 // Copyright 2023 System76 <info@system76.com>
 // SPDX-License-Identifier: MPL-2.0
 
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufReader};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::OnceLock;
+use std::time::Duration;
 
+use serde::Deserialize;
+use tokio::sync::watch;
 use walkdir::WalkDir;
 
 /// Plays an audio file.
@@ -16,15 +22,22 @@ pub fn play(path: &Path) {
         .spawn();
 }
 
-pub fn play_sound(theme: &'static str, sound: &'static str) {
-    if let Some(path) = sound_path(theme, sound).or_else(|| sound_path("freedesktop", sound)) {
+/// Plays a sound from a theme, or the freedesktop theme. Returns `false` if the sound was not found.
+pub fn play_sound(theme: &str, sound: &str) -> bool {
+    let path = sound_path(theme.to_owned(), sound.to_owned())
+        .or_else(|| sound_path("freedesktop".to_owned(), sound.to_owned()));
+
+    if let Some(path) = path {
         play(&path);
+        true
+    } else {
+        false
     }
 }
 
 #[memoize::memoize]
-fn sound_path(theme: &'static str, sound: &'static str) -> Option<PathBuf> {
-    let entries = WalkDir::new(&["/usr/share/sounds/", theme].concat())
+fn sound_path(theme: String, sound: String) -> Option<PathBuf> {
+    let entries = WalkDir::new(["/usr/share/sounds/", &theme].concat())
         .follow_links(true)
         .into_iter()
         .filter_map(Result::ok);
@@ -38,3 +51,297 @@ fn sound_path(theme: &'static str, sound: &'static str) -> Option<PathBuf> {
 
     None
 }
+
+#[derive(Deserialize)]
+struct Object {
+    id: u32,
+    #[serde(default, rename = "type")]
+    kind: String,
+    /// `None` once the object was removed.
+    info: Option<NodeInfo>,
+}
+
+#[derive(Deserialize)]
+struct NodeInfo {
+    state: Option<String>,
+    #[serde(default)]
+    props: BTreeMap<String, serde_json::Value>,
+}
+
+/// Screencasts are video sources which are not backed by a device such as a camera.
+fn is_screencast(props: &BTreeMap<String, serde_json::Value>) -> bool {
+    props.get("media.class").and_then(|class| class.as_str()) == Some("Video/Source")
+        && !props.contains_key("device.api")
+        && !props.contains_key("device.id")
+}
+
+/// Update the nodes of interest from a batch of changed objects, where `classify` tells which
+/// nodes are of interest from their properties.
+fn update_nodes<T>(
+    nodes: &mut BTreeMap<u32, (T, bool)>,
+    objects: &[Object],
+    classify: impl Fn(&BTreeMap<String, serde_json::Value>) -> Option<T>,
+) {
+    for object in objects {
+        match &object.info {
+            None => {
+                nodes.remove(&object.id);
+            }
+
+            Some(info) if object.kind == "PipeWire:Interface:Node" => {
+                // Updates only contain the properties which changed.
+                let class = if info.props.is_empty() {
+                    None
+                } else if let Some(class) = classify(&info.props) {
+                    Some(class)
+                } else {
+                    nodes.remove(&object.id);
+                    continue;
+                };
+
+                let previous = nodes.remove(&object.id);
+                let running = match &info.state {
+                    Some(state) => state == "running",
+                    None => previous.as_ref().is_some_and(|(_, running)| *running),
+                };
+                if let Some(class) = class.or(previous.map(|(class, _)| class)) {
+                    nodes.insert(object.id, (class, running));
+                }
+            }
+
+            Some(_) => (),
+        }
+    }
+}
+
+/// The kinds of nodes which show that the microphone or the camera is in use.
+#[derive(Clone, Debug)]
+enum CaptureNode {
+    /// A recording stream of an application.
+    Microphone { application: String },
+    /// A camera, which runs while an application uses it.
+    Camera,
+    /// A video stream of an application, which may be of a camera or of a screencast.
+    Video { application: String },
+}
+
+/// The applications which are using the microphone and the camera.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Captures {
+    pub microphone: BTreeSet<String>,
+    pub camera: BTreeSet<String>,
+}
+
+/// Application names are only known to PipeWire when the application sets them.
+const UNKNOWN_APPLICATION: &str = "Unknown application";
+
+fn is_true(value: Option<&serde_json::Value>) -> bool {
+    value.is_some_and(|value| value.as_bool() == Some(true) || value.as_str() == Some("true"))
+}
+
+/// The name of the application of a stream. Sandboxed applications, which reach the camera
+/// through the camera portal, are named by their app ID, which they cannot change.
+fn application(
+    props: &BTreeMap<String, serde_json::Value>,
+    clients: &BTreeMap<u32, String>,
+) -> String {
+    let client = props.get("client.id").and_then(|id| match id {
+        serde_json::Value::String(id) => id.parse().ok(),
+        id => id.as_u64().and_then(|id| u32::try_from(id).ok()),
+    });
+    if let Some(app_id) = client.and_then(|client| clients.get(&client)) {
+        return app_id.clone();
+    }
+
+    ["application.name", "application.process.binary"]
+        .into_iter()
+        .find_map(|key| props.get(key)?.as_str())
+        .unwrap_or(UNKNOWN_APPLICATION)
+        .to_owned()
+}
+
+fn capture_node(
+    props: &BTreeMap<String, serde_json::Value>,
+    clients: &BTreeMap<u32, String>,
+) -> Option<CaptureNode> {
+    match props.get("media.class")?.as_str()? {
+        // Recordings of the output, such as level meters, do not use the microphone.
+        "Stream/Input/Audio"
+            if !is_true(props.get("stream.capture.sink"))
+                && !is_true(props.get("stream.monitor")) =>
+        {
+            Some(CaptureNode::Microphone {
+                application: application(props, clients),
+            })
+        }
+        "Stream/Input/Video" => Some(CaptureNode::Video {
+            application: application(props, clients),
+        }),
+        "Video/Source" if !is_screencast(props) => Some(CaptureNode::Camera),
+        _ => None,
+    }
+}
+
+fn running_captures(nodes: &BTreeMap<u32, (CaptureNode, bool)>) -> Captures {
+    let running = || {
+        nodes
+            .values()
+            .filter(|(_, running)| *running)
+            .map(|(node, _)| node)
+    };
+    let mut captures = Captures::default();
+    let camera_running = running().any(|node| matches!(node, CaptureNode::Camera));
+
+    for node in running() {
+        match node {
+            CaptureNode::Microphone { application } => {
+                captures.microphone.insert(application.clone());
+            }
+            CaptureNode::Video { application } if camera_running => {
+                captures.camera.insert(application.clone());
+            }
+            _ => (),
+        }
+    }
+
+    // The camera only runs while some application uses it, even one without a known stream.
+    if camera_running && captures.camera.is_empty() {
+        captures.camera.insert(UNKNOWN_APPLICATION.to_owned());
+    }
+    captures
+}
+
+/// Update the app IDs of the clients of sandboxed applications from a batch of changed objects.
+fn update_clients(clients: &mut BTreeMap<u32, String>, objects: &[Object]) {
+    for object in objects {
+        match &object.info {
+            None => {
+                clients.remove(&object.id);
+            }
+
+            Some(info) if object.kind == "PipeWire:Interface:Client" => {
+                // Applications on the host have an empty app ID.
+                let app_id = info
+                    .props
+                    .get("pipewire.access.portal.app_id")
+                    .and_then(|app_id| app_id.as_str())
+                    .filter(|app_id| !app_id.is_empty());
+                if let Some(app_id) = app_id {
+                    clients.insert(object.id, app_id.to_owned());
+                }
+            }
+
+            Some(_) => (),
+        }
+    }
+}
+
+/// The state of the graph which modules follow, from a single `pw-dump --monitor`.
+struct Graph {
+    screencast: watch::Sender<bool>,
+    captures: watch::Sender<Captures>,
+}
+
+static GRAPH: OnceLock<Graph> = OnceLock::new();
+
+fn graph() -> &'static Graph {
+    GRAPH.get_or_init(|| {
+        let res = std::thread::Builder::new()
+            .name("pipewire".to_owned())
+            .spawn(monitor);
+
+        if let Err(err) = res {
+            eprintln!("Failed to start the PipeWire thread: {err}");
+        }
+        Graph {
+            screencast: watch::channel(false).0,
+            captures: watch::channel(Captures::default()).0,
+        }
+    })
+}
+
+fn set<T: PartialEq>(tx: &watch::Sender<T>, value: T) {
+    tx.send_if_modified(|current| {
+        if *current == value {
+            return false;
+        }
+        *current = value;
+        true
+    });
+}
+
+/// Follow the graph with `pw-dump --monitor` until it exits.
+fn dump(graph: &Graph) -> io::Result<()> {
+    let mut child = std::process::Command::new("pw-dump")
+        .args(["--monitor", "--no-colors"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let Some(stdout) = child.stdout.take() else {
+        return Err(io::Error::new(
+            io::ErrorKind::BrokenPipe,
+            "pw-dump has no stdout",
+        ));
+    };
+
+    // Whether each screencast node is running, by id.
+    let mut screencasts: BTreeMap<u32, ((), bool)> = BTreeMap::new();
+    let mut nodes: BTreeMap<u32, (CaptureNode, bool)> = BTreeMap::new();
+    let mut clients: BTreeMap<u32, String> = BTreeMap::new();
+
+    let updates =
+        serde_json::Deserializer::from_reader(BufReader::new(stdout)).into_iter::<Vec<Object>>();
+    for objects in updates {
+        let Ok(objects) = objects else {
+            break;
+        };
+
+        // Clients connect before they create their streams.
+        update_clients(&mut clients, &objects);
+        update_nodes(&mut screencasts, &objects, |props| {
+            is_screencast(props).then_some(())
+        });
+        update_nodes(&mut nodes, &objects, |props| capture_node(props, &clients));
+
+        set(
+            &graph.screencast,
+            screencasts.values().any(|(_, running)| *running),
+        );
+        set(&graph.captures, running_captures(&nodes));
+    }
+
+    let _res = child.kill();
+    let _res = child.wait();
+    Ok(())
+}
+
+/// Follow the graph, restarting pw-dump if it exits.
+fn monitor() {
+    let graph = graph();
+    loop {
+        if let Err(err) = dump(graph) {
+            eprintln!("Failed to monitor PipeWire: {err}");
+            return;
+        }
+
+        // Nothing is known to run until pw-dump is back.
+        set(&graph.screencast, false);
+        set(&graph.captures, Captures::default());
+        std::thread::sleep(Duration::from_secs(5));
+    }
+}
+
+/// Whether the screen is being captured. Monitoring starts with the first subscriber.
+pub fn screencast() -> watch::Receiver<bool> {
+    graph().screencast.subscribe()
+}
+
+/// The applications using the microphone and the camera through PipeWire. Monitoring starts
+/// with the first subscriber.
+///
+/// Applications which open a camera directly with V4L2 are seen by `crate::v4l2` instead.
+pub fn captures() -> watch::Receiver<Captures> {
+    graph().captures.subscribe()
+}
@@ -0,0 +1,184 @@
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+use zbus::{Connection, InterfaceRef};
+
+use crate::DBUS_PATH;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Pointer";
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct PointerConfig {
+    /// Longest time between the clicks of a double click, in milliseconds.
+    pub double_click_time: u32,
+    /// Click when the pointer rests in one place.
+    pub hover_click: bool,
+    /// How long the pointer must rest before clicking, in milliseconds.
+    pub hover_click_delay: u32,
+    /// How far the pointer may move while resting, in pixels.
+    pub hover_click_threshold: u32,
+}
+
+impl Default for PointerConfig {
+    fn default() -> Self {
+        Self {
+            double_click_time: 400,
+            hover_click: false,
+            hover_click_delay: 1200,
+            hover_click_threshold: 3,
+        }
+    }
+}
+
+impl PointerConfig {
+    fn double_click_time(&self) -> u32 {
+        self.double_click_time.clamp(100, 2000)
+    }
+
+    fn hover_click_delay(&self) -> u32 {
+        self.hover_click_delay.clamp(200, 5000)
+    }
+
+    fn hover_click_threshold(&self) -> u32 {
+        self.hover_click_threshold.clamp(1, 30)
+    }
+}
+
+/// Pointer settings for the compositor, which detects hover clicks from pointer motion.
+#[derive(Default)]
+pub struct Pointer {
+    config: PointerConfig,
+}
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.Pointer")]
+impl Pointer {
+    /// Longest time between the clicks of a double click, in milliseconds.
+    #[dbus_interface(property)]
+    async fn double_click_time(&self) -> u32 {
+        self.config.double_click_time()
+    }
+
+    #[dbus_interface(property)]
+    async fn hover_click(&self) -> bool {
+        self.config.hover_click
+    }
+
+    /// In milliseconds.
+    #[dbus_interface(property)]
+    async fn hover_click_delay(&self) -> u32 {
+        self.config.hover_click_delay()
+    }
+
+    /// In pixels.
+    #[dbus_interface(property)]
+    async fn hover_click_threshold(&self) -> u32 {
+        self.config.hover_click_threshold()
+    }
+}
+
+/// Export the settings to GNOME, for the XSettings of GTK applications and for the dwell clicks
+/// of mousetweaks, and to GTK's `settings.ini` and KDE.
+fn export(config: &PointerConfig) {
+    let double_click_time = config.double_click_time().to_string();
+    crate::gsettings::set(vec![
+        (
+            "org.gnome.desktop.peripherals.mouse",
+            "double-click",
+            double_click_time.clone(),
+        ),
+        (
+            "org.gnome.desktop.a11y.mouse",
+            "dwell-click-enabled",
+            config.hover_click.to_string(),
+        ),
+        (
+            "org.gnome.desktop.a11y.mouse",
+            "dwell-time",
+            (f64::from(config.hover_click_delay()) / 1000.0).to_string(),
+        ),
+        (
+            "org.gnome.desktop.a11y.mouse",
+            "dwell-threshold",
+            config.hover_click_threshold().to_string(),
+        ),
+    ]);
+
+    let Some(config_dir) = dirs::config_dir() else {
+        return;
+    };
+
+    let files = [
+        (
+            config_dir.join("gtk-3.0/settings.ini"),
+            "Settings",
+            "gtk-double-click-time",
+        ),
+        (
+            config_dir.join("gtk-4.0/settings.ini"),
+            "Settings",
+            "gtk-double-click-time",
+        ),
+        (config_dir.join("kdeglobals"), "KDE", "DoubleClickInterval"),
+    ];
+
+    for (path, section, key) in files {
+        if let Err(err) = crate::files::set_ini_value(&path, section, key, &double_click_time) {
+            eprintln!("Failed to write {}: {err}", path.display());
+        }
+    }
+}
+
+async fn publish(interface: &InterfaceRef<Pointer>, config: &PointerConfig) {
+    let mut pointer = interface.get_mut().await;
+    let previous = std::mem::replace(&mut pointer.config, config.clone());
+    let ctxt = interface.signal_context();
+
+    if previous.double_click_time() != config.double_click_time() {
+        _ = pointer.double_click_time_changed(ctxt).await;
+    }
+
+    if previous.hover_click != config.hover_click {
+        _ = pointer.hover_click_changed(ctxt).await;
+    }
+
+    if previous.hover_click_delay() != config.hover_click_delay() {
+        _ = pointer.hover_click_delay_changed(ctxt).await;
+    }
+
+    if previous.hover_click_threshold() != config.hover_click_threshold() {
+        _ = pointer.hover_click_threshold_changed(ctxt).await;
+    }
+}
+
+/// Apply the double click time and hover click settings.
+pub async fn run(conn: Connection, mut rx: Receiver<String>) {
+    let Ok(interface) = conn
+        .object_server()
+        .interface::<_, Pointer>(DBUS_PATH)
+        .await
+    else {
+        return;
+    };
+
+    let (helper, mut config) = match crate::config::load_async::<PointerConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load pointer config {err:?}");
+            return;
+        }
+    };
+
+    // The defaults match GNOME and GTK, so there is nothing to export until they change.
+    if config != PointerConfig::default() {
+        export(&config);
+    }
+    publish(&interface, &config).await;
+
+    while let Some(key) = rx.recv().await {
+        if !crate::config::update(&mut config, &helper, &key).is_empty() {
+            export(&config);
+            publish(&interface, &config).await;
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use tokio::sync::mpsc::Receiver;
+use zbus::{
+    zvariant::{OwnedValue, Value},
+    Connection, InterfaceRef, SignalContext,
+};
+
+pub const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+
+const APPEARANCE: &str = "org.freedesktop.appearance";
+
+const SETTINGS_INTERFACE: &str = "org.freedesktop.impl.portal.Settings";
+
+/// The settings which xdg-desktop-portal-cosmic serves when it has a settings backend too.
+const COSMIC_PORTAL_SETTINGS: [(&str, &str); 3] = [
+    (APPEARANCE, "color-scheme"),
+    (APPEARANCE, "accent-color"),
+    (APPEARANCE, "contrast"),
+];
+
+/// GNOME's interface settings, which GTK reads through the portal inside of Flatpak.
+const GNOME_INTERFACE: &str = "org.gnome.desktop.interface";
+
+/// The appearance settings, as their portal values.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Appearance {
+    /// 1 to prefer dark, 2 to prefer light.
+    color_scheme: u32,
+    accent_color: (f64, f64, f64),
+    /// 1 for high contrast.
+    contrast: u32,
+    /// 1 to reduce motion.
+    reduced_motion: u32,
+    fonts: crate::fonts::GnomeFontRendering,
+}
+
+impl Appearance {
+    fn load() -> Self {
+        let (is_dark, theme) = crate::theme::current();
+        let accent = theme.accent_color();
+        Self {
+            color_scheme: if is_dark { 1 } else { 2 },
+            accent_color: (
+                f64::from(accent.red),
+                f64::from(accent.green),
+                f64::from(accent.blue),
+            ),
+            contrast: u32::from(theme.is_high_contrast),
+            reduced_motion: u32::from(crate::a11y::reduce_motion()),
+            fonts: crate::fonts::current_gnome_font_rendering(),
+        }
+    }
+
+    /// The settings, by namespace and key.
+    fn settings(&self) -> [(&'static str, &'static str, OwnedValue); 9] {
+        [
+            (
+                APPEARANCE,
+                "color-scheme",
+                Value::from(self.color_scheme).into(),
+            ),
+            (
+                APPEARANCE,
+                "accent-color",
+                Value::from(self.accent_color).into(),
+            ),
+            (APPEARANCE, "contrast", Value::from(self.contrast).into()),
+            (
+                APPEARANCE,
+                "reduced-motion",
+                Value::from(self.reduced_motion).into(),
+            ),
+            (
+                GNOME_INTERFACE,
+                "enable-animations",
+                Value::from(self.reduced_motion == 0).into(),
+            ),
+            (
+                GNOME_INTERFACE,
+                "font-antialiasing",
+                Value::from(self.fonts.antialiasing).into(),
+            ),
+            (
+                GNOME_INTERFACE,
+                "font-hinting",
+                Value::from(self.fonts.hinting).into(),
+            ),
+            (
+                GNOME_INTERFACE,
+                "font-rgba-order",
+                Value::from(self.fonts.rgba_order).into(),
+            ),
+            (
+                GNOME_INTERFACE,
+                "text-scaling-factor",
+                Value::from(self.fonts.text_scaling_factor).into(),
+            ),
+        ]
+    }
+}
+
+/// Whether a namespace matches a pattern of `ReadAll`, which may end in `*`.
+fn matches(pattern: &str, namespace: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => namespace.starts_with(prefix),
+        None => pattern == namespace,
+    }
+}
+
+/// Whether xdg-desktop-portal-cosmic has a settings backend, as its portal file says.
+fn cosmic_portal_has_settings() -> bool {
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_owned());
+
+    data_dirs.split(':').any(|dir| {
+        let path = Path::new(dir).join("xdg-desktop-portal/portals/cosmic.portal");
+        fs::read_to_string(path).is_ok_and(|portal| {
+            portal.lines().any(|line| {
+                line.strip_prefix("Interfaces=").is_some_and(|interfaces| {
+                    interfaces.split(';').any(|i| i == SETTINGS_INTERFACE)
+                })
+            })
+        })
+    })
+}
+
+#[derive(Debug, zbus::DBusError)]
+#[dbus_error(prefix = "org.freedesktop.portal.Error")]
+pub enum PortalError {
+    #[dbus_error(zbus_error)]
+    ZBus(zbus::Error),
+    NotFound(String),
+}
+
+/// The settings portal backend, for Flatpak applications to follow the theme.
+///
+/// xdg-desktop-portal reads the settings of every backend. The settings of the appearance which
+/// xdg-desktop-portal-cosmic serves are left to it if it has a settings backend, so that
+/// applications do not see two values of them, and this one serves the rest.
+#[derive(Default)]
+pub struct Settings {
+    appearance: Appearance,
+    cosmic_portal: bool,
+}
+
+impl Settings {
+    fn serves(&self, namespace: &str, key: &str) -> bool {
+        !(self.cosmic_portal && COSMIC_PORTAL_SETTINGS.contains(&(namespace, key)))
+    }
+
+    /// The settings this backend serves, by namespace and key.
+    fn settings(&self) -> impl Iterator<Item = (&'static str, &'static str, OwnedValue)> + '_ {
+        self.appearance
+            .settings()
+            .into_iter()
+            .filter(|(namespace, key, _)| self.serves(namespace, key))
+    }
+}
+
+#[zbus::dbus_interface(name = "org.freedesktop.impl.portal.Settings")]
+impl Settings {
+    async fn read_all(
+        &self,
+        namespaces: Vec<String>,
+    ) -> HashMap<String, HashMap<String, OwnedValue>> {
+        let mut all: HashMap<String, HashMap<String, OwnedValue>> = HashMap::new();
+        for (namespace, key, value) in self.settings() {
+            let matched = namespaces.is_empty()
+                || namespaces.iter().any(|pattern| matches(pattern, namespace));
+
+            if matched {
+                all.entry(namespace.to_owned())
+                    .or_default()
+                    .insert(key.to_owned(), value);
+            }
+        }
+
+        all
+    }
+
+    async fn read(&self, namespace: &str, key: &str) -> Result<OwnedValue, PortalError> {
+        let setting = self
+            .settings()
+            .find(|(ns, name, _)| *ns == namespace && *name == key);
+
+        match setting {
+            Some((_, _, value)) => Ok(value),
+            None => Err(PortalError::NotFound(format!(
+                "setting {namespace} {key} not found"
+            ))),
+        }
+    }
+
+    /// The version of the interface which is implemented.
+    #[dbus_interface(property, name = "version")]
+    async fn version(&self) -> u32 {
+        1
+    }
+
+    #[dbus_interface(signal)]
+    async fn setting_changed(
+        ctxt: &SignalContext<'_>,
+        namespace: &str,
+        key: &str,
+        value: Value<'_>,
+    ) -> zbus::Result<()>;
+}
+
+async fn publish(interface: &InterfaceRef<Settings>) {
+    let appearance = Appearance::load();
+
+    let mut settings = interface.get_mut().await;
+    let previous = std::mem::replace(&mut settings.appearance, appearance);
+    if previous == appearance {
+        return;
+    }
+
+    let changed: Vec<_> = appearance
+        .settings()
+        .into_iter()
+        .zip(previous.settings())
+        .filter(|((namespace, key, new), (_, _, old))| {
+            new != old && settings.serves(namespace, key)
+        })
+        .collect();
+    drop(settings);
+
+    for ((namespace, key, value), _) in changed {
+        if let Err(err) =
+            Settings::setting_changed(interface.signal_context(), namespace, key, value.into())
+                .await
+        {
+            eprintln!("Failed to send portal setting change: {err}");
+        }
+    }
+}
+
+/// Follow the theme mode, the themes, the accessibility settings, and the fonts for the settings
+/// portal.
+///
+/// `rx` receives a message whenever the theme mode, either theme, the accessibility config, or
+/// the fonts config changes.
+pub async fn run(conn: Connection, mut rx: Receiver<()>) {
+    let Ok(interface) = conn
+        .object_server()
+        .interface::<_, Settings>(PORTAL_PATH)
+        .await
+    else {
+        return;
+    };
+
+    interface.get_mut().await.cosmic_portal = cosmic_portal_has_settings();
+    publish(&interface).await;
+    while rx.recv().await.is_some() {
+        publish(&interface).await;
+    }
+}
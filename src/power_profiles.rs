@@ -0,0 +1,12 @@
+#[zbus::dbus_proxy(
+    default_service = "net.hadess.PowerProfiles",
+    interface = "net.hadess.PowerProfiles",
+    default_path = "/net/hadess/PowerProfiles"
+)]
+trait PowerProfiles {
+    #[dbus_proxy(property)]
+    fn active_profile(&self) -> zbus::Result<String>;
+
+    #[dbus_proxy(property)]
+    fn set_active_profile(&self, profile: &str) -> zbus::Result<()>;
+}
@@ -0,0 +1,158 @@
+use std::time::Duration;
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::mpsc::Receiver,
+    time::{sleep_until, Instant},
+};
+use tokio_stream::StreamExt;
+use zbus::zvariant::OwnedFd;
+
+use crate::{
+    logind_manager::LogindManagerProxy, sensor_proxy::SensorProxyProxy, LogindSessionProxy,
+};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Presence";
+
+/// The idle inhibitor held while the user is present.
+const INHIBITOR_WHO: &str = "COSMIC Settings Daemon";
+const INHIBITOR_WHY: &str = "The user is in front of the device";
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct PresenceConfig {
+    /// Follow the proximity sensor of the device, through iio-sensor-proxy.
+    pub enabled: bool,
+    /// Inhibit idle with logind while the user is in front of the device, which postpones the
+    /// idle lock of the daemon and the idle actions of anything else which honors logind's idle
+    /// inhibitors. The dimming and blanking of the screen follow the idle notifications of the
+    /// compositor, which only Wayland idle inhibitors postpone, so they are not postponed.
+    pub postpone_idle: bool,
+    /// Lock the session when the user walks away.
+    pub lock_on_leave: bool,
+    /// How long the user must be away before the session is locked, so that leaning back does
+    /// not lock it.
+    pub leave_delay_seconds: u64,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            postpone_idle: true,
+            lock_on_leave: false,
+            leave_delay_seconds: 10,
+        }
+    }
+}
+
+impl crate::demand::Feature for PresenceConfig {
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// Hold a logind idle inhibitor, which the compositor does not follow.
+async fn inhibit_idle(manager: &LogindManagerProxy<'_>) -> Option<OwnedFd> {
+    match manager
+        .inhibit("idle", INHIBITOR_WHO, INHIBITOR_WHY, "block")
+        .await
+    {
+        Ok(fd) => Some(fd),
+        Err(err) => {
+            eprintln!("Failed to inhibit idle while the user is present: {err}");
+            None
+        }
+    }
+}
+
+/// Postpone the idle lock while the user is in front of the device, and lock the session when
+/// they walk away, while enabled.
+pub async fn run(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<PresenceConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load presence config {err:?}");
+            return;
+        }
+    };
+
+    let Ok(conn) = crate::bus::system().await else {
+        return;
+    };
+
+    let (sensor, manager, session) = match tokio::join!(
+        SensorProxyProxy::new(&conn),
+        LogindManagerProxy::new(&conn),
+        LogindSessionProxy::builder(&conn).build()
+    ) {
+        (Ok(sensor), Ok(manager), Ok(session)) => (sensor, manager, session),
+        (Err(err), _, _) | (_, Err(err), _) | (_, _, Err(err)) => {
+            eprintln!("Failed to connect to the proximity sensor and logind: {err}");
+            return;
+        }
+    };
+
+    let mut near_changes = sensor.receive_proximity_near_changed().await;
+    let mut has_changes = sensor.receive_has_proximity_changed().await;
+
+    let mut claimed = false;
+    let mut present = false;
+    let mut inhibitor: Option<OwnedFd> = None;
+    let mut lock_at: Option<Instant> = None;
+
+    loop {
+        let wanted = config.enabled && sensor.has_proximity().await.unwrap_or(false);
+        if wanted != claimed {
+            let res = if wanted {
+                sensor.claim_proximity().await
+            } else {
+                sensor.release_proximity().await
+            };
+            match res {
+                Ok(()) => claimed = wanted,
+                Err(err) => eprintln!("Failed to claim the proximity sensor: {err}"),
+            }
+        }
+
+        let was_present = present;
+        present = claimed && sensor.proximity_near().await.unwrap_or(false);
+
+        if present && config.postpone_idle {
+            if inhibitor.is_none() {
+                inhibitor = inhibit_idle(&manager).await;
+            }
+        } else {
+            inhibitor = None;
+        }
+
+        if present || !config.lock_on_leave {
+            lock_at = None;
+        } else if was_present {
+            lock_at = Some(Instant::now() + Duration::from_secs(config.leave_delay_seconds));
+        }
+
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                crate::config::update(&mut config, &helper, &key);
+            }
+
+            Some(_) = near_changes.next() => (),
+
+            Some(_) = has_changes.next() => (),
+
+            () = sleep_until(lock_at.unwrap_or_else(Instant::now)), if lock_at.is_some() => {
+                lock_at = None;
+                if crate::seat::is_active() && !session.locked_hint().await.unwrap_or(false) {
+                    if let Err(err) = session.lock().await {
+                        eprintln!("Failed to lock the session after the user left: {err}");
+                    }
+                }
+            }
+        }
+    }
+}
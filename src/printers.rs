@@ -0,0 +1,305 @@
+use std::{collections::BTreeSet, process::Stdio, time::Duration};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    sync::mpsc::Receiver,
+};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Printers";
+
+/// Document formats which identify a printer supporting IPP Everywhere or AirPrint.
+const DRIVERLESS_FORMATS: [&str; 3] = ["image/pwg-raster", "image/urf", "application/pdf"];
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct PrintersConfig {
+    /// Notify when a printer appears on the network.
+    pub notify: bool,
+    /// Add a queue for printers which work without a driver.
+    pub auto_setup_driverless: bool,
+    /// Application opened by the notification to set up the printer.
+    pub settings_command: String,
+}
+
+impl Default for PrintersConfig {
+    fn default() -> Self {
+        Self {
+            notify: true,
+            auto_setup_driverless: false,
+            settings_command: "system-config-printer".to_owned(),
+        }
+    }
+}
+
+impl crate::demand::Feature for PrintersConfig {
+    fn enabled(&self) -> bool {
+        self.notify || self.auto_setup_driverless
+    }
+}
+
+/// A printer advertised through DNS-SD.
+struct Printer {
+    name: String,
+    host: String,
+    address: String,
+    port: u16,
+    secure: bool,
+    txt: Vec<String>,
+}
+
+impl Printer {
+    fn txt(&self, key: &str) -> Option<&str> {
+        self.txt.iter().find_map(|record| {
+            let (k, v) = record.split_once('=')?;
+            k.eq_ignore_ascii_case(key).then_some(v)
+        })
+    }
+
+    fn is_driverless(&self) -> bool {
+        self.txt("URF").is_some()
+            || self.txt("pdl").is_some_and(|pdl| {
+                pdl.split(',')
+                    .any(|format| DRIVERLESS_FORMATS.contains(&format.trim()))
+            })
+    }
+
+    fn uri(&self) -> String {
+        let scheme = if self.secure { "ipps" } else { "ipp" };
+        let resource = self.txt("rp").unwrap_or("ipp/print");
+        format!("{scheme}://{}:{}/{resource}", self.host, self.port)
+    }
+
+    /// A CUPS queue name derived from the service name.
+    fn queue(&self) -> String {
+        self.name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    }
+}
+
+/// Decode the `\DDD` escapes of `avahi-browse --parsable`.
+fn unescape(value: &str) -> String {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'\\' && tail.len() >= 3 {
+            if let Some(decoded) = std::str::from_utf8(&tail[..3])
+                .ok()
+                .and_then(|digits| digits.parse::<u8>().ok())
+            {
+                bytes.push(decoded);
+                rest = &tail[3..];
+                continue;
+            }
+        }
+
+        bytes.push(byte);
+        rest = tail;
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Parse a resolved service line, `=;iface;proto;name;type;domain;host;address;port;"txt" ...`.
+fn parse_resolved(line: &str) -> Option<Printer> {
+    let mut fields = line.splitn(10, ';');
+    if fields.next()? != "=" {
+        return None;
+    }
+
+    let _interface = fields.next()?;
+    let _protocol = fields.next()?;
+    let name = unescape(fields.next()?);
+    let service = fields.next()?;
+    let _domain = fields.next()?;
+    let host = fields.next()?.to_owned();
+    let address = fields.next()?.to_owned();
+    let port = fields.next()?.parse().ok()?;
+    let txt = fields
+        .next()
+        .unwrap_or_default()
+        .split("\" \"")
+        .map(|record| record.trim_matches('"').to_owned())
+        .filter(|record| !record.is_empty())
+        .collect();
+
+    Some(Printer {
+        name,
+        host,
+        address,
+        port,
+        secure: service == "_ipps._tcp",
+        txt,
+    })
+}
+
+/// Whether CUPS already has a queue for the printer.
+async fn is_configured(printer: &Printer) -> bool {
+    let Ok(output) = tokio::process::Command::new("lpstat")
+        .arg("-v")
+        .env("LC_ALL", "C")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await
+    else {
+        return false;
+    };
+
+    let host = printer.host.trim_end_matches('.');
+    String::from_utf8_lossy(&output.stdout).lines().any(|line| {
+        line.contains(host) || line.contains(&printer.address) || line.contains(&printer.name)
+    })
+}
+
+/// Add a queue for a driverless printer, returning whether it succeeded.
+async fn set_up(printer: &Printer) -> bool {
+    let status = tokio::process::Command::new("lpadmin")
+        .args([
+            "-p",
+            &printer.queue(),
+            "-E",
+            "-v",
+            &printer.uri(),
+            "-m",
+            "everywhere",
+        ])
+        .stdin(Stdio::null())
+        .status()
+        .await;
+
+    match status {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            eprintln!("lpadmin failed to add {} with {status}", printer.name);
+            false
+        }
+        Err(err) => {
+            eprintln!("Failed to run lpadmin: {err}");
+            false
+        }
+    }
+}
+
+/// Announce a printer, which was set up automatically if `added` is set.
+fn notify(printer: &Printer, added: bool, command: String) {
+    let (summary, body) = if added {
+        (
+            "Printer Added",
+            format!("{} is ready to use.", printer.name),
+        )
+    } else {
+        (
+            "Printer Found",
+            format!("{} is available on the network.", printer.name),
+        )
+    };
+
+    tokio::task::spawn_local(async move {
+        let Ok(handle) = Notification::new()
+            .appname("")
+            .summary(summary)
+            .body(&body)
+            .icon("printer-symbolic")
+            .action("settings", "Printer Settings")
+            .timeout(Duration::from_secs(10))
+            .show_async()
+            .await
+        else {
+            return;
+        };
+
+        if crate::notifications::wait_for_action(handle)
+            .await
+            .as_deref()
+            == Some("settings")
+        {
+            if let Err(err) = tokio::process::Command::new(&command).spawn() {
+                eprintln!("Failed to open {command}: {err}");
+            }
+        }
+    });
+}
+
+/// Watch for network printers with `avahi-browse`, announcing each once per session.
+pub async fn monitor(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<PrintersConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load printers config {err:?}");
+            return;
+        }
+    };
+
+    let mut seen: BTreeSet<String> = BTreeSet::new();
+
+    loop {
+        let mut child = match tokio::process::Command::new("avahi-browse")
+            // Printers which support IPPS also advertise IPP.
+            .args(["--parsable", "--resolve", "--no-db-lookup", "_ipp._tcp"])
+            .env("LC_ALL", "C")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(err) => {
+                eprintln!("Failed to run avahi-browse: {err}");
+                return;
+            }
+        };
+
+        let Some(stdout) = child.stdout.take() else {
+            return;
+        };
+
+        let mut lines = BufReader::new(stdout).lines();
+
+        loop {
+            tokio::select! {
+                key = rx.recv() => {
+                    let Some(key) = key else {
+                        return;
+                    };
+                    crate::config::update(&mut config, &helper, &key);
+                }
+
+                line = lines.next_line() => {
+                    let Ok(Some(line)) = line else {
+                        break;
+                    };
+
+                    let Some(printer) = parse_resolved(&line) else {
+                        continue;
+                    };
+
+                    if !seen.insert(printer.name.clone()) || is_configured(&printer).await {
+                        continue;
+                    }
+
+                    if config.auto_setup_driverless && printer.is_driverless() && set_up(&printer).await {
+                        notify(&printer, true, config.settings_command.clone());
+                    } else if config.notify {
+                        notify(&printer, false, config.settings_command.clone());
+                    }
+                }
+            }
+        }
+
+        let _res = child.kill().await;
+        tokio::time::sleep(Duration::from_secs(30)).await;
+    }
+}
@@ -0,0 +1,247 @@
+use std::{fs, path::PathBuf, sync::Mutex, time::Duration};
+
+use chrono::{DateTime, Utc};
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{Receiver, UnboundedReceiver};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Privacy";
+
+/// Old entries are purged this often. The history is cleared as soon as it is written instead.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const EMPTY_XBEL: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xbel version="1.0"
+      xmlns:bookmark="http://www.freedesktop.org/standards/desktop-bookmarks"
+      xmlns:mime="http://www.freedesktop.org/standards/shared-mime-info"
+>
+</xbel>
+"#;
+
+static CONFIG: Mutex<Option<PrivacyConfig>> = Mutex::new(None);
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct PrivacyConfig {
+    /// Let applications record recently used files. If disabled, the history is kept empty.
+    pub remember_recent_files: bool,
+    /// Forget recently used files after this many days. `0` keeps them until they are cleared.
+    pub recent_files_max_age_days: u64,
+    /// Clear the recently used files when the session ends.
+    pub clear_recent_files_at_logout: bool,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            remember_recent_files: true,
+            recent_files_max_age_days: 0,
+            clear_recent_files_at_logout: false,
+        }
+    }
+}
+
+fn recently_used() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("recently-used.xbel"))
+}
+
+/// The directory of recent documents used by KDE applications.
+fn recent_documents() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("RecentDocuments"))
+}
+
+/// The time a bookmark was last visited or modified.
+fn bookmark_time(bookmark: &str) -> Option<DateTime<Utc>> {
+    let tag = &bookmark[..bookmark.find('>')?];
+    ["visited", "modified", "added"]
+        .into_iter()
+        .filter_map(|attr| {
+            let start = tag.find(&format!(" {attr}=\""))? + attr.len() + 3;
+            let end = start + tag[start..].find('"')?;
+            DateTime::parse_from_rfc3339(&tag[start..end]).ok()
+        })
+        .map(|time| time.with_timezone(&Utc))
+        .max()
+}
+
+/// Remove bookmarks older than `oldest` from the contents of `recently-used.xbel`.
+///
+/// Returns `None` if nothing was removed.
+fn purge_xbel(xbel: &str, oldest: DateTime<Utc>) -> Option<String> {
+    let mut purged = String::with_capacity(xbel.len());
+    let mut rest = xbel;
+    let mut removed = false;
+
+    while let Some(start) = rest.find("<bookmark ") {
+        let end = start + rest[start..].find("</bookmark>")? + "</bookmark>".len();
+        let bookmark = &rest[start..end];
+
+        if bookmark_time(bookmark).is_some_and(|time| time < oldest) {
+            // Drop the indentation and line break around the bookmark too.
+            purged.push_str(rest[..start].trim_end_matches([' ', '\t']));
+            rest = rest[end..].strip_prefix('\n').unwrap_or(&rest[end..]);
+            removed = true;
+        } else {
+            purged.push_str(&rest[..end]);
+            rest = &rest[end..];
+        }
+    }
+
+    purged.push_str(rest);
+    removed.then_some(purged)
+}
+
+/// Forget every recently used file.
+fn clear() {
+    if let Some(path) = recently_used() {
+        if fs::read_to_string(&path).is_ok_and(|xbel| xbel != EMPTY_XBEL) {
+            if let Err(err) = crate::files::write_atomic(&path, EMPTY_XBEL) {
+                eprintln!("Failed to clear {}: {err}", path.display());
+            }
+        }
+    }
+
+    if let Some(Ok(entries)) = recent_documents().map(fs::read_dir) {
+        for entry in entries.filter_map(Result::ok) {
+            let _res = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Forget recently used files which were last used more than `max_age_days` ago.
+fn purge(max_age_days: u64) {
+    let oldest = Utc::now() - chrono::Duration::days(max_age_days as i64);
+
+    if let Some(path) = recently_used() {
+        if let Some(purged) = fs::read_to_string(&path)
+            .ok()
+            .and_then(|xbel| purge_xbel(&xbel, oldest))
+        {
+            if let Err(err) = crate::files::write_atomic(&path, &purged) {
+                eprintln!("Failed to purge {}: {err}", path.display());
+            }
+        }
+    }
+
+    if let Some(Ok(entries)) = recent_documents().map(fs::read_dir) {
+        for entry in entries.filter_map(Result::ok) {
+            let modified = entry.metadata().and_then(|m| m.modified());
+            if modified.is_ok_and(|modified| DateTime::<Utc>::from(modified) < oldest) {
+                let _res = fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+/// Clearing and purging run on a blocking thread, since the history can be large.
+async fn apply(config: &PrivacyConfig) {
+    let config = config.clone();
+    let _res = tokio::task::spawn_blocking(move || {
+        if !config.remember_recent_files {
+            clear();
+        } else if config.recent_files_max_age_days > 0 {
+            purge(config.recent_files_max_age_days);
+        }
+    })
+    .await;
+}
+
+/// Tell GTK applications whether to record recently used files.
+fn set_gnome_remember_recent_files(remember: bool) {
+    crate::gsettings::set(vec![(
+        "org.gnome.desktop.privacy",
+        "remember-recent-files",
+        remember.to_string(),
+    )]);
+}
+
+fn watch(dir: Option<PathBuf>, pattern: &str) -> Option<UnboundedReceiver<Vec<PathBuf>>> {
+    let dir = dir.filter(|dir| dir.is_dir())?;
+    match crate::watch::register(&dir, pattern, false) {
+        Ok(rx) => Some(rx),
+        Err(err) => {
+            eprintln!("Failed to watch {}: {err}", dir.display());
+            None
+        }
+    }
+}
+
+/// Wait until a watched history is written, or forever if it is not watched.
+async fn written(rx: &mut Option<UnboundedReceiver<Vec<PathBuf>>>) {
+    if let Some(history) = rx {
+        if history.recv().await.is_some() {
+            return;
+        }
+    }
+
+    *rx = None;
+    std::future::pending().await
+}
+
+/// Enforce the privacy config, then apply changes to it.
+pub async fn watch_config(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<PrivacyConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load privacy config {err:?}");
+            return;
+        }
+    };
+
+    set_config(config.clone());
+    set_gnome_remember_recent_files(config.remember_recent_files);
+
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    let mut recently_used_rx = watch(dirs::data_dir(), "recently-used.xbel");
+    let mut recent_documents_rx = watch(recent_documents(), "*");
+
+    loop {
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+
+                let changes = crate::config::update(&mut config, &helper, &key);
+                set_config(config.clone());
+                if changes.contains(&"remember_recent_files") {
+                    set_gnome_remember_recent_files(config.remember_recent_files);
+                }
+                apply(&config).await;
+            }
+
+            _ = interval.tick() => apply(&config).await,
+
+            _ = written(&mut recently_used_rx) => {
+                if !config.remember_recent_files {
+                    apply(&config).await;
+                }
+            }
+
+            _ = written(&mut recent_documents_rx) => {
+                if !config.remember_recent_files {
+                    apply(&config).await;
+                }
+            }
+        }
+    }
+}
+
+fn set_config(config: PrivacyConfig) {
+    if let Ok(mut global) = CONFIG.lock() {
+        *global = Some(config);
+    }
+}
+
+/// Clear the recently used files if the config asks for it at the end of the session.
+pub fn logout() {
+    let clear_at_logout = CONFIG
+        .lock()
+        .ok()
+        .and_then(|config| config.clone())
+        .is_some_and(|config| config.clear_recent_files_at_logout || !config.remember_recent_files);
+
+    if clear_at_logout {
+        clear();
+    }
+}
@@ -0,0 +1,283 @@
+use std::{collections::BTreeMap, io, sync::OnceLock};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use cosmic_settings_daemon_lib::config::{ConfigDir, ConfigProvider};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc::Receiver, watch};
+
+use crate::pactl::DeviceKind;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Profiles";
+
+/// Modules which activate profiles, from the lowest to the highest priority. Where the active
+/// profiles of two sources set the same key, the later source wins, so the region the device is
+/// in overrides the season, and being docked overrides both.
+const SOURCES: &[&str] = &["season", "geofence", "dock"];
+
+/// The profile each source asked for, if it asked since the daemon started.
+type Reports = BTreeMap<&'static str, Option<String>>;
+
+static REPORTS: OnceLock<watch::Sender<Reports>> = OnceLock::new();
+
+/// A config key and its value, as it is stored in the config directory.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Setting {
+    pub id: String,
+    pub version: u64,
+    pub key: String,
+    /// The value in RON, such as `true` or `"Dark"`.
+    pub value: String,
+}
+
+/// A change of a profile which is not a config key, made when the profile becomes active.
+///
+/// Nothing restores what an action changed once its profile is deactivated, so a profile such as
+/// the undocked one changes it back.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Action {
+    /// Set the mode and scale of an output, such as `DP-1`, through cosmic-randr.
+    OutputMode {
+        output: String,
+        width: u32,
+        height: u32,
+        scale: f64,
+    },
+    /// Make an audio output the default one, by its name in `pactl list sinks`.
+    DefaultSink(String),
+    /// Make an audio input the default one, by its name in `pactl list sources`.
+    DefaultSource(String),
+}
+
+impl Action {
+    async fn run(&self) -> io::Result<()> {
+        match self {
+            Action::OutputMode {
+                output,
+                width,
+                height,
+                scale,
+            } => {
+                let status = tokio::process::Command::new("cosmic-randr")
+                    .arg("mode")
+                    .arg("--scale")
+                    .arg(scale.to_string())
+                    .arg(output)
+                    .arg(width.to_string())
+                    .arg(height.to_string())
+                    .status()
+                    .await?;
+                if !status.success() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("cosmic-randr exited with {status}"),
+                    ));
+                }
+                Ok(())
+            }
+            Action::DefaultSink(name) => crate::pactl::set_default(DeviceKind::Sink, name).await,
+            Action::DefaultSource(name) => {
+                crate::pactl::set_default(DeviceKind::Source, name).await
+            }
+        }
+    }
+}
+
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct ProfilesConfig {
+    /// The settings of each profile by name, applied while the profile is active.
+    pub profiles: BTreeMap<String, Vec<Setting>>,
+    /// The actions of each profile by name, such as the scale of the displays of a dock, run
+    /// when the profile becomes active.
+    pub actions: BTreeMap<String, Vec<Action>>,
+}
+
+/// The value of a key before a profile replaced it.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Saved {
+    pub id: String,
+    pub version: u64,
+    pub key: String,
+    /// `None` if the key was unset.
+    pub value: Option<String>,
+}
+
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct ProfilesState {
+    /// The active profile of each source.
+    pub active: BTreeMap<String, String>,
+    /// The values replaced by the active profiles, which are restored once no active profile
+    /// sets them.
+    pub saved: Vec<Saved>,
+}
+
+fn reports() -> &'static watch::Sender<Reports> {
+    REPORTS.get_or_init(|| watch::channel(Reports::new()).0)
+}
+
+/// Activate `profile` for `source`, or deactivate the profile of `source` if `None`.
+///
+/// Until a source reports, the profile it activated before the daemon restarted stays active.
+pub fn activate(source: &'static str, profile: Option<String>) {
+    reports().send_if_modified(|reports| {
+        let previous = reports.insert(source, profile.clone());
+        previous != Some(profile)
+    });
+}
+
+/// IDs and keys are single path components.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains('/') && !name.starts_with('.')
+}
+
+/// Apply the settings of the active profiles, restoring the keys they no longer set.
+///
+/// A key changed by hand while a profile sets it gets its value from before the profile back
+/// once the profile is deactivated.
+fn apply(config: &ProfilesConfig, state: &mut ProfilesState, state_helper: &cosmic_config::Config) {
+    let Some(config_dir) = ConfigDir::user() else {
+        return;
+    };
+
+    let mut desired: BTreeMap<(&str, u64, &str), &str> = BTreeMap::new();
+    for source in SOURCES {
+        let Some(name) = state.active.get(*source) else {
+            continue;
+        };
+
+        let Some(settings) = config.profiles.get(name) else {
+            eprintln!("Profile {name} of {source} does not exist");
+            continue;
+        };
+
+        for setting in settings {
+            if is_valid_name(&setting.id) && is_valid_name(&setting.key) {
+                let key = (setting.id.as_str(), setting.version, setting.key.as_str());
+                desired.insert(key, &setting.value);
+            } else {
+                eprintln!(
+                    "Invalid key {}/{} in profile {name}",
+                    setting.id, setting.key
+                );
+            }
+        }
+    }
+
+    let (mut saved, restore): (Vec<Saved>, Vec<Saved>) = state
+        .saved
+        .iter()
+        .cloned()
+        .partition(|s| desired.contains_key(&(s.id.as_str(), s.version, s.key.as_str())));
+
+    for &(id, version, key) in desired.keys() {
+        if !saved
+            .iter()
+            .any(|s| s.id == id && s.version == version && s.key == key)
+        {
+            saved.push(Saved {
+                id: id.to_owned(),
+                version,
+                key: key.to_owned(),
+                value: config_dir.read_key(id, version, key),
+            });
+        }
+    }
+
+    // The previous values are saved before they are replaced.
+    if state.saved != saved {
+        if let Err(err) = state.set_saved(state_helper, saved.clone()) {
+            eprintln!("Failed to save the settings replaced by profiles {err:?}");
+            return;
+        }
+        state.saved = saved;
+    }
+
+    for s in &restore {
+        if let Err(err) = config_dir.write_key(&s.id, s.version, &s.key, s.value.as_deref()) {
+            eprintln!("Failed to restore {}/{}: {err}", s.id, s.key);
+        }
+    }
+
+    for (&(id, version, key), value) in &desired {
+        if let Err(err) = config_dir.write_key(id, version, key, Some(value)) {
+            eprintln!("Failed to apply {id}/{key}: {err}");
+        }
+    }
+}
+
+/// Run the actions of the profiles which became active, in the order of their sources.
+async fn run_actions(config: &ProfilesConfig, activated: &[String]) {
+    for name in activated {
+        for action in config.actions.get(name).into_iter().flatten() {
+            if let Err(err) = action.run().await {
+                eprintln!("Failed to run {action:?} of profile {name}: {err}");
+            }
+        }
+    }
+}
+
+/// Apply the profiles which the other modules activate.
+pub async fn run(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<ProfilesConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load profiles config {err:?}");
+            return;
+        }
+    };
+
+    let (state_helper, mut state) = match crate::config::load_state_async::<ProfilesState>(ID).await
+    {
+        Ok(state) => state,
+        Err(err) => {
+            eprintln!("Failed to load profiles state {err:?}");
+            return;
+        }
+    };
+
+    let mut reports_rx = reports().subscribe();
+
+    loop {
+        let mut active = state.active.clone();
+        for (source, profile) in reports_rx.borrow_and_update().iter() {
+            match profile {
+                Some(profile) => active.insert((*source).to_owned(), profile.clone()),
+                None => active.remove(*source),
+            };
+        }
+
+        let activated: Vec<String> = SOURCES
+            .iter()
+            .filter_map(|source| {
+                let profile = active.get(*source)?;
+                (state.active.get(*source) != Some(profile)).then(|| profile.clone())
+            })
+            .collect();
+
+        if state.active != active {
+            if let Err(err) = state.set_active(&state_helper, active.clone()) {
+                eprintln!("Failed to save the active profiles {err:?}");
+            }
+            state.active = active;
+        }
+
+        apply(&config, &mut state, &state_helper);
+        run_actions(&config, &activated).await;
+
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                crate::config::update(&mut config, &helper, &key);
+            }
+
+            res = reports_rx.changed() => {
+                if res.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+use zbus::{Connection, InterfaceRef};
+
+use crate::DBUS_PATH;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Proxy";
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub enum ProxyMode {
+    #[default]
+    None,
+    /// Use the configured proxy servers.
+    Manual,
+    /// Use the proxy auto-config file at `autoconfig_url`.
+    Auto,
+}
+
+impl ProxyMode {
+    fn name(self) -> &'static str {
+        match self {
+            ProxyMode::None => "none",
+            ProxyMode::Manual => "manual",
+            ProxyMode::Auto => "auto",
+        }
+    }
+}
+
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct ProxyConfig {
+    pub mode: ProxyMode,
+    /// HTTP proxy, as `host:port` or a URL. IPv6 hosts are in brackets, as in `[::1]:3128`.
+    pub http: Option<String>,
+    /// HTTPS proxy, as `host:port` or a URL.
+    pub https: Option<String>,
+    /// SOCKS proxy, as `host:port`.
+    pub socks: Option<String>,
+    /// Hosts and networks which are connected to directly in manual mode.
+    pub ignore_hosts: Vec<String>,
+    pub autoconfig_url: Option<String>,
+}
+
+/// Split a proxy into its host and port, ignoring any scheme and credentials.
+///
+/// IPv6 addresses are in brackets, as in `[::1]:3128`, and are returned without them, which is
+/// how GNOME's settings take them.
+fn host_port(proxy: &str) -> Option<(&str, u16)> {
+    let proxy = proxy.split_once("://").map_or(proxy, |(_, rest)| rest);
+    let proxy = proxy.rsplit_once('@').map_or(proxy, |(_, rest)| rest);
+    let proxy = proxy.trim_end_matches('/');
+    let (host, port) = match proxy.strip_prefix('[') {
+        Some(bracketed) => bracketed.split_once("]:")?,
+        None => proxy.rsplit_once(':')?,
+    };
+
+    // Without brackets, the port of an IPv6 address cannot be told apart from the address.
+    if host.contains(':') && !proxy.starts_with('[') {
+        return None;
+    }
+
+    Some((host, port.parse().ok()?))
+}
+
+fn proxy_url(proxy: &str, scheme: &str) -> String {
+    if proxy.contains("://") {
+        proxy.to_owned()
+    } else {
+        format!("{scheme}://{proxy}")
+    }
+}
+
+impl ProxyConfig {
+    /// The proxy variables read by command line programs, which only support manual proxies.
+    fn environment(&self) -> HashMap<String, String> {
+        let mut env = HashMap::new();
+        if self.mode != ProxyMode::Manual {
+            return env;
+        }
+
+        let servers = [
+            ("http_proxy", self.http.as_deref(), "http"),
+            ("https_proxy", self.https.as_deref(), "http"),
+            ("all_proxy", self.socks.as_deref(), "socks"),
+        ];
+
+        for (name, proxy, scheme) in servers {
+            if let Some(proxy) = proxy.filter(|proxy| !proxy.is_empty()) {
+                env.insert(name.to_owned(), proxy_url(proxy, scheme));
+            }
+        }
+
+        if !self.ignore_hosts.is_empty() {
+            env.insert("no_proxy".to_owned(), self.ignore_hosts.join(","));
+        }
+
+        // Some programs only read the uppercase variants.
+        for (name, value) in env.clone() {
+            env.insert(name.to_uppercase(), value);
+        }
+
+        env
+    }
+}
+
+const VARIABLES: &[&str] = &[
+    "http_proxy",
+    "https_proxy",
+    "all_proxy",
+    "no_proxy",
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "ALL_PROXY",
+    "NO_PROXY",
+];
+
+/// The proxy settings applied to the session.
+#[derive(Default)]
+pub struct Proxy {
+    mode: String,
+    autoconfig_url: String,
+    environment: HashMap<String, String>,
+}
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.Proxy")]
+impl Proxy {
+    /// `none`, `manual`, or `auto`.
+    #[dbus_interface(property)]
+    async fn mode(&self) -> String {
+        self.mode.clone()
+    }
+
+    /// Empty unless the mode is `auto`.
+    #[dbus_interface(property)]
+    async fn autoconfig_url(&self) -> String {
+        self.autoconfig_url.clone()
+    }
+
+    /// The proxy variables exported to the session.
+    #[dbus_interface(property)]
+    async fn environment(&self) -> HashMap<String, String> {
+        self.environment.clone()
+    }
+}
+
+/// Mirror the settings to `org.gnome.system.proxy`, which GNOME applications and libproxy use.
+fn set_gnome_proxy(config: &ProxyConfig) {
+    let ignore_hosts: Vec<String> = config
+        .ignore_hosts
+        .iter()
+        .map(|host| format!("'{}'", host.replace('\'', "")))
+        .collect();
+    let mut settings = vec![
+        (
+            "org.gnome.system.proxy",
+            "mode",
+            config.mode.name().to_owned(),
+        ),
+        (
+            "org.gnome.system.proxy",
+            "autoconfig-url",
+            config.autoconfig_url.clone().unwrap_or_default(),
+        ),
+        (
+            "org.gnome.system.proxy",
+            "ignore-hosts",
+            format!("[{}]", ignore_hosts.join(", ")),
+        ),
+    ];
+
+    let servers = [
+        ("org.gnome.system.proxy.http", config.http.as_deref()),
+        ("org.gnome.system.proxy.https", config.https.as_deref()),
+        ("org.gnome.system.proxy.socks", config.socks.as_deref()),
+    ];
+
+    for (schema, proxy) in servers {
+        let (host, port) = proxy.and_then(host_port).unwrap_or(("", 0));
+        settings.push((schema, "host", host.to_owned()));
+        settings.push((schema, "port", port.to_string()));
+    }
+
+    crate::gsettings::set(settings);
+}
+
+fn apply(config: &ProxyConfig) {
+    let mut environment = config.environment();
+    let vars = VARIABLES
+        .iter()
+        .map(|name| (*name, environment.remove(*name)))
+        .collect();
+    crate::environment::set("proxy", vars);
+
+    set_gnome_proxy(config);
+}
+
+async fn publish(interface: &InterfaceRef<Proxy>, config: &ProxyConfig) {
+    let mode = config.mode.name();
+    let autoconfig_url = match config.mode {
+        ProxyMode::Auto => config.autoconfig_url.clone().unwrap_or_default(),
+        _ => String::new(),
+    };
+    let environment = config.environment();
+
+    let mut proxy = interface.get_mut().await;
+    let ctxt = interface.signal_context();
+    if proxy.mode != mode {
+        proxy.mode = mode.to_owned();
+        _ = proxy.mode_changed(ctxt).await;
+    }
+
+    if proxy.autoconfig_url != autoconfig_url {
+        proxy.autoconfig_url = autoconfig_url;
+        _ = proxy.autoconfig_url_changed(ctxt).await;
+    }
+
+    if proxy.environment != environment {
+        proxy.environment = environment;
+        _ = proxy.environment_changed(ctxt).await;
+    }
+}
+
+/// Apply the desktop-wide proxy to the session environment and GNOME settings.
+pub async fn run(conn: Connection, mut rx: Receiver<String>) {
+    let Ok(interface) = conn.object_server().interface::<_, Proxy>(DBUS_PATH).await else {
+        return;
+    };
+
+    let (helper, mut config) = match crate::config::load_async::<ProxyConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load proxy config {err:?}");
+            return;
+        }
+    };
+
+    // Leave any proxy the session was started with alone until one is configured.
+    if config != ProxyConfig::default() {
+        apply(&config);
+    }
+    publish(&interface, &config).await;
+
+    while let Some(key) = rx.recv().await {
+        if !crate::config::update(&mut config, &helper, &key).is_empty() {
+            apply(&config);
+            publish(&interface, &config).await;
+        }
+    }
+}
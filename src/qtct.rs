@@ -0,0 +1,250 @@
+use std::{fs, io, path::Path, process::Stdio, sync::Mutex};
+
+use anyhow::Context;
+use cosmic::config::CosmicTk;
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use cosmic_theme::{palette::Srgba, Theme};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Qtct";
+
+static CONFIG: Mutex<Option<QtctConfig>> = Mutex::new(None);
+
+const PALETTE_FILE: &str = "cosmic.conf";
+
+/// Configuration of Qt applications through qt5ct and qt6ct, for sessions without Plasma's
+/// platform theme.
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct QtctConfig {
+    /// Write the qt5ct and qt6ct configuration from the COSMIC theme.
+    pub enabled: bool,
+    /// Set as `QT_QPA_PLATFORMTHEME` while enabled.
+    pub platform_theme: String,
+    /// Qt widget style.
+    pub style: String,
+    /// Font size in points.
+    pub font_size: u32,
+}
+
+impl Default for QtctConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            platform_theme: "qt5ct".to_owned(),
+            style: "Fusion".to_owned(),
+            font_size: 10,
+        }
+    }
+}
+
+fn argb(color: Srgba) -> String {
+    let [r, g, b, a] = [color.red, color.green, color.blue, color.alpha]
+        .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+    format!("#{a:02x}{r:02x}{g:02x}{b:02x}")
+}
+
+/// The colors of a palette group, in the order of `QPalette::ColorRole`, from the high contrast
+/// palette if the theme is high contrast.
+fn colors(theme: &Theme, disabled: bool) -> String {
+    let colors = crate::contrast::Colors::new(theme);
+    let window = colors.window;
+    let window_text = colors.window_text;
+    let base = colors.view;
+    let text = colors.view_text;
+    let button = colors.button;
+    let button_text = colors.button_text;
+    let (text, window_text, button_text) = if disabled {
+        let dim = |mut color: Srgba| {
+            color.alpha *= 0.5;
+            color
+        };
+        (dim(text), dim(window_text), dim(button_text))
+    } else {
+        (text, window_text, button_text)
+    };
+    let highlight = colors.accent;
+    let highlighted_text = colors.accent_text;
+    let divider = colors.divider;
+    let shadow = Srgba::new(0.0, 0.0, 0.0, 1.0);
+
+    let roles = [
+        window_text,      // WindowText
+        button,           // Button
+        base,             // Light
+        button,           // Midlight
+        divider,          // Dark
+        divider,          // Mid
+        text,             // Text
+        text,             // BrightText
+        button_text,      // ButtonText
+        base,             // Base
+        window,           // Window
+        shadow,           // Shadow
+        highlight,        // Highlight
+        highlighted_text, // HighlightedText
+        highlight,        // Link
+        highlight,        // LinkVisited
+        window,           // AlternateBase
+        window,           // NoRole
+        base,             // ToolTipBase
+        text,             // ToolTipText
+        divider,          // PlaceholderText
+    ];
+
+    roles.map(argb).join(", ")
+}
+
+fn palette(theme: &Theme) -> String {
+    let active = colors(theme, false);
+    let disabled = colors(theme, true);
+    format!(
+        "[ColorScheme]\n\
+         active_colors={active}\n\
+         disabled_colors={disabled}\n\
+         inactive_colors={active}\n"
+    )
+}
+
+/// The family of the default sans-serif font.
+async fn font_family() -> String {
+    let output = tokio::process::Command::new("fc-match")
+        .args(["-f", "%{family[0]}", "sans-serif"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await;
+
+    match output {
+        Ok(output) if output.status.success() && !output.stdout.is_empty() => {
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        }
+        _ => "Sans Serif".to_owned(),
+    }
+}
+
+fn icon_theme() -> String {
+    CosmicTk::config()
+        .ok()
+        .map(|helper| match CosmicTk::get_entry(&helper) {
+            Ok(tk) => tk.icon_theme,
+            Err((_, tk)) => tk.icon_theme,
+        })
+        .unwrap_or_else(|| CosmicTk::default().icon_theme)
+}
+
+fn write_qtct(dir: &Path, config: &QtctConfig, palette: &str, font: &str, icon_theme: &str) {
+    let palette_path = dir.join("colors").join(PALETTE_FILE);
+    if let Err(err) = crate::files::write_atomic(&palette_path, palette) {
+        crate::export::failed(format!("Failed to write {}: {err}", palette_path.display()));
+        return;
+    }
+
+    let Some(name) = dir.file_name().and_then(|name| name.to_str()) else {
+        return;
+    };
+
+    // The font is a serialized `QFont`: family, point size, pixel size, style hint, and weight.
+    let font = format!("\"{font},{},-1,5,50,0,0,0,0,0\"", config.font_size);
+    let path = dir.join(format!("{name}.conf"));
+    let palette_path = palette_path.display().to_string();
+    let values = [
+        ("Appearance", "custom_palette", "true"),
+        ("Appearance", "color_scheme_path", &palette_path),
+        ("Appearance", "icon_theme", icon_theme),
+        ("Appearance", "style", &config.style),
+        ("Fonts", "general", &font),
+        ("Fonts", "fixed", &font),
+    ];
+
+    if let Err(err) = crate::files::set_ini_values(&path, &values) {
+        crate::export::failed(format!("Failed to write {}: {err}", path.display()));
+    }
+}
+
+async fn export(config: &QtctConfig, theme: &Theme) {
+    let platform_theme = config.enabled.then(|| config.platform_theme.clone());
+    crate::environment::set("qtct", vec![("QT_QPA_PLATFORMTHEME", platform_theme)]);
+
+    if !config.enabled {
+        return;
+    }
+
+    let Some(config_dir) = dirs::config_dir() else {
+        return;
+    };
+
+    let palette = palette(theme);
+    let font = font_family().await;
+    let icon_theme = icon_theme();
+
+    for name in ["qt5ct", "qt6ct"] {
+        write_qtct(&config_dir.join(name), config, &palette, &font, &icon_theme);
+    }
+}
+
+/// Write the qt5ct and qt6ct configuration after the theme or the toolkit config changed, if
+/// enabled.
+pub async fn theme_changed(theme: &Theme) {
+    let config = CONFIG.lock().ok().and_then(|config| config.clone());
+    if let Some(config) = config.filter(|config| config.enabled) {
+        export(&config, theme).await;
+    }
+}
+
+/// Stop writing the qt5ct and qt6ct configuration, and remove the COSMIC palette from it, so that
+/// Qt applications go back to the default palette of qt5ct and qt6ct.
+pub fn reset() -> anyhow::Result<()> {
+    let (helper, config) = crate::config::load::<QtctConfig>(ID)?;
+    config.set_enabled(&helper, false)?;
+
+    let config_dir = dirs::config_dir().context("no config directory")?;
+    for name in ["qt5ct", "qt6ct"] {
+        let dir = config_dir.join(name);
+        match fs::remove_file(dir.join("colors").join(PALETTE_FILE)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                return Err(err).with_context(|| format!("failed to remove the {name} palette"));
+            }
+            _ => (),
+        }
+
+        let path = dir.join(format!("{name}.conf"));
+        if path.exists() {
+            crate::files::set_ini_values(&path, &[("Appearance", "custom_palette", "false")])
+                .with_context(|| format!("failed to write {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the qt5ct and qt6ct configuration while enabled, and again whenever the config changes.
+pub async fn run(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<QtctConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load qtct config {err:?}");
+            return;
+        }
+    };
+
+    if let Ok(mut global) = CONFIG.lock() {
+        *global = Some(config.clone());
+    }
+
+    if config.enabled {
+        export(&config, &crate::theme::current().1).await;
+    }
+
+    while let Some(key) = rx.recv().await {
+        if crate::config::update(&mut config, &helper, &key).is_empty() {
+            continue;
+        }
+
+        if let Ok(mut global) = CONFIG.lock() {
+            *global = Some(config.clone());
+        }
+
+        export(&config, &crate::theme::current().1).await;
+    }
+}
@@ -0,0 +1,36 @@
+/// Restore the default appearance, as a recovery path from a custom theme or an export which
+/// leaves the desktop unreadable.
+///
+/// The exports are turned off and their files removed before the theme is reset, so that the
+/// default theme is not exported either, and every step runs even if another failed. Returns the
+/// errors.
+pub fn reset_appearance() -> Vec<String> {
+    let steps: [(&str, fn() -> anyhow::Result<()>); 7] = [
+        ("Qt", crate::qtct::reset),
+        ("accent colors", crate::accent::reset),
+        ("high contrast", crate::contrast::reset),
+        ("legacy hints", crate::legacy::reset),
+        ("Flatpak applications", crate::flatpak::reset),
+        ("fonts", crate::fonts::reset),
+        ("theme", crate::theme::reset),
+    ];
+
+    steps
+        .into_iter()
+        .filter_map(|(name, step)| step().err().map(|err| format!("{name}: {err:#}")))
+        .collect()
+}
+
+/// Reset the appearance from the command line, such as from a TTY.
+pub fn cli() -> std::process::ExitCode {
+    let errors = reset_appearance();
+    if errors.is_empty() {
+        println!("Restored the default appearance");
+        return std::process::ExitCode::SUCCESS;
+    }
+
+    for err in errors {
+        eprintln!("Failed to reset the appearance of {err}");
+    }
+    std::process::ExitCode::FAILURE
+}
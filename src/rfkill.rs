@@ -0,0 +1,364 @@
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::{Read, Write},
+    os::unix::fs::OpenOptionsExt,
+};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::io::unix::AsyncFd;
+use zbus::{Connection, SignalContext};
+
+use crate::DBUS_PATH;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Rfkill";
+
+const RFKILL_DEVICE: &str = "/dev/rfkill";
+
+const RFKILL_OP_ADD: u8 = 0;
+const RFKILL_OP_DEL: u8 = 1;
+const RFKILL_OP_CHANGE: u8 = 2;
+const RFKILL_OP_CHANGE_ALL: u8 = 3;
+
+/// Radios which airplane mode blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Radio {
+    Wifi,
+    Bluetooth,
+}
+
+impl Radio {
+    const ALL: [Radio; 2] = [Radio::Wifi, Radio::Bluetooth];
+
+    /// The `RFKILL_TYPE_*` of the radio.
+    fn kind(self) -> u8 {
+        match self {
+            Radio::Wifi => 1,
+            Radio::Bluetooth => 2,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Radio::Wifi => "wifi",
+            Radio::Bluetooth => "bluetooth",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|radio| radio.name() == name)
+    }
+}
+
+/// A `struct rfkill_event`, without the extended fields of newer kernels.
+#[derive(Clone, Copy, Debug)]
+struct RfkillEvent {
+    idx: u32,
+    kind: u8,
+    op: u8,
+    soft: bool,
+    hard: bool,
+}
+
+impl RfkillEvent {
+    const SIZE: usize = 8;
+
+    fn from_bytes(buf: [u8; Self::SIZE]) -> Self {
+        Self {
+            idx: u32::from_ne_bytes([buf[0], buf[1], buf[2], buf[3]]),
+            kind: buf[4],
+            op: buf[5],
+            soft: buf[6] != 0,
+            hard: buf[7] != 0,
+        }
+    }
+
+    fn to_bytes(self) -> [u8; Self::SIZE] {
+        let idx = self.idx.to_ne_bytes();
+        [
+            idx[0],
+            idx[1],
+            idx[2],
+            idx[3],
+            self.kind,
+            self.op,
+            u8::from(self.soft),
+            u8::from(self.hard),
+        ]
+    }
+}
+
+/// Soft-block or unblock every device of a radio.
+fn set_blocked(radio: Radio, blocked: bool) -> std::io::Result<()> {
+    let event = RfkillEvent {
+        idx: 0,
+        kind: radio.kind(),
+        op: RFKILL_OP_CHANGE_ALL,
+        soft: blocked,
+        hard: false,
+    };
+
+    OpenOptions::new()
+        .write(true)
+        .open(RFKILL_DEVICE)?
+        .write_all(&event.to_bytes())
+}
+
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct RfkillState {
+    pub airplane_mode: bool,
+    /// Radios which were on when airplane mode was enabled, to turn back on when it is disabled.
+    /// `None` if nothing was saved, as opposed to every radio having been off.
+    pub restore: Option<Vec<String>>,
+}
+
+/// Airplane mode, which soft-blocks Wi-Fi and Bluetooth through rfkill.
+pub struct Rfkill {
+    helper: Option<cosmic_config::Config>,
+    state: RfkillState,
+    /// The latest event of each rfkill device, by index.
+    devices: BTreeMap<u32, RfkillEvent>,
+}
+
+impl Rfkill {
+    pub fn new() -> Self {
+        let (helper, state) = match crate::config::load_state::<RfkillState>(ID) {
+            Ok((helper, state)) => (Some(helper), state),
+            Err(err) => {
+                eprintln!("Failed to load rfkill state {err:?}");
+                (None, RfkillState::default())
+            }
+        };
+
+        Self {
+            helper,
+            state,
+            devices: BTreeMap::new(),
+        }
+    }
+
+    fn radio_devices(&self, radio: Radio) -> impl Iterator<Item = &RfkillEvent> {
+        self.devices
+            .values()
+            .filter(move |dev| dev.kind == radio.kind())
+    }
+
+    /// Whether a radio is present, and every device of it is soft-blocked.
+    fn blocked(&self, radio: Radio) -> bool {
+        let mut devices = self.radio_devices(radio).peekable();
+        devices.peek().is_some() && devices.all(|dev| dev.soft)
+    }
+
+    fn save(&mut self) {
+        let Some(helper) = self.helper.as_ref() else {
+            return;
+        };
+
+        if let Err(err) = self
+            .state
+            .set_airplane_mode(helper, self.state.airplane_mode)
+            .and_then(|()| self.state.set_restore(helper, self.state.restore.clone()))
+        {
+            eprintln!("Failed to save rfkill state {err:?}");
+        }
+    }
+
+    async fn set_airplane_mode_inner(
+        &mut self,
+        enabled: bool,
+        ctxt: &SignalContext<'_>,
+    ) -> zbus::fdo::Result<()> {
+        if enabled == self.state.airplane_mode {
+            return Ok(());
+        }
+
+        let radios = if enabled {
+            self.state.restore = Some(
+                Radio::ALL
+                    .into_iter()
+                    .filter(|radio| {
+                        self.radio_devices(*radio).next().is_some() && !self.blocked(*radio)
+                    })
+                    .map(|radio| radio.name().to_owned())
+                    .collect(),
+            );
+            Radio::ALL.to_vec()
+        } else {
+            match self.state.restore.take() {
+                Some(names) => names
+                    .iter()
+                    .filter_map(|name| Radio::from_name(name))
+                    .collect(),
+                // Without the saved state, such as airplane mode enabled by an older version,
+                // every radio is turned back on.
+                None => Radio::ALL.to_vec(),
+            }
+        };
+
+        // Set before blocking, so that events from the change are not mistaken for the user
+        // turning a radio back on.
+        self.state.airplane_mode = enabled;
+
+        for radio in radios {
+            if let Err(err) = set_blocked(radio, enabled) {
+                self.state.airplane_mode = !enabled;
+                return Err(zbus::fdo::Error::Failed(format!(
+                    "failed to change {} rfkill state: {err}",
+                    radio.name()
+                )));
+            }
+        }
+
+        self.save();
+        _ = self.airplane_mode_changed(ctxt).await;
+
+        crate::osd::show(
+            "airplane-mode",
+            i32::from(enabled),
+            if enabled {
+                "airplane-mode-symbolic"
+            } else {
+                "airplane-mode-disabled-symbolic"
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn handle(&mut self, event: RfkillEvent, ctxt: &SignalContext<'_>) {
+        match event.op {
+            RFKILL_OP_ADD => {
+                self.devices.insert(event.idx, event);
+
+                // Radios connected during airplane mode, such as a Bluetooth adapter, are blocked too.
+                let radio = Radio::ALL
+                    .into_iter()
+                    .find(|radio| radio.kind() == event.kind);
+                if let Some(radio) = radio.filter(|_| self.state.airplane_mode && !event.soft) {
+                    if let Err(err) = set_blocked(radio, true) {
+                        eprintln!("Failed to block new {} device: {err}", radio.name());
+                    }
+                }
+            }
+
+            RFKILL_OP_CHANGE => {
+                let unblocked =
+                    !event.soft && self.devices.get(&event.idx).is_some_and(|prev| prev.soft);
+                self.devices.insert(event.idx, event);
+
+                // A radio turned back on by the user ends airplane mode.
+                if unblocked
+                    && self.state.airplane_mode
+                    && Radio::ALL.iter().any(|radio| radio.kind() == event.kind)
+                {
+                    self.state.airplane_mode = false;
+                    self.state.restore = None;
+                    self.save();
+                    _ = self.airplane_mode_changed(ctxt).await;
+                }
+            }
+
+            RFKILL_OP_DEL => {
+                self.devices.remove(&event.idx);
+            }
+
+            _ => return,
+        }
+
+        _ = self.wifi_blocked_changed(ctxt).await;
+        _ = self.bluetooth_blocked_changed(ctxt).await;
+        _ = self.hardware_blocked_changed(ctxt).await;
+    }
+}
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.Rfkill")]
+impl Rfkill {
+    #[dbus_interface(property)]
+    async fn airplane_mode(&self) -> bool {
+        self.state.airplane_mode
+    }
+
+    #[dbus_interface(property)]
+    async fn wifi_blocked(&self) -> bool {
+        self.blocked(Radio::Wifi)
+    }
+
+    #[dbus_interface(property)]
+    async fn bluetooth_blocked(&self) -> bool {
+        self.blocked(Radio::Bluetooth)
+    }
+
+    /// Whether a hardware switch is blocking a radio, which airplane mode cannot override.
+    #[dbus_interface(property)]
+    async fn hardware_blocked(&self) -> bool {
+        self.devices.values().any(|dev| dev.hard)
+    }
+
+    async fn set_airplane_mode(
+        &mut self,
+        enabled: bool,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> zbus::fdo::Result<()> {
+        self.set_airplane_mode_inner(enabled, &ctxt).await
+    }
+
+    /// Bound to the XF86RFKill key.
+    async fn toggle_airplane_mode(
+        &mut self,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> zbus::fdo::Result<()> {
+        let enabled = !self.state.airplane_mode;
+        self.set_airplane_mode_inner(enabled, &ctxt).await
+    }
+}
+
+/// Track rfkill devices, starting with an add event for each existing device.
+///
+/// The device is polled rather than read by tokio's file reader, which would hold a thread of the
+/// blocking pool for as long as the daemon runs.
+pub async fn monitor(conn: Connection) {
+    let Ok(interface) = conn.object_server().interface::<_, Rfkill>(DBUS_PATH).await else {
+        return;
+    };
+
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(RFKILL_DEVICE)
+        .and_then(AsyncFd::new);
+    let file = match file {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("Failed to open {RFKILL_DEVICE}: {err}");
+            return;
+        }
+    };
+
+    // Each read returns one event, cut to the size of the buffer on kernels with longer events.
+    let mut buf = [0; RfkillEvent::SIZE];
+    loop {
+        let Ok(mut guard) = file.readable().await else {
+            return;
+        };
+        let n = match guard.try_io(|file| file.get_ref().read(&mut buf)) {
+            Ok(Ok(n)) => n,
+            Ok(Err(err)) => {
+                eprintln!("Failed to read {RFKILL_DEVICE}: {err}");
+                return;
+            }
+            Err(_would_block) => continue,
+        };
+        if n < RfkillEvent::SIZE {
+            return;
+        }
+
+        let event = RfkillEvent::from_bytes(buf);
+        interface
+            .get_mut()
+            .await
+            .handle(event, interface.signal_context())
+            .await;
+    }
+}
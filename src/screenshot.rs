@@ -0,0 +1,215 @@
+use std::{
+    fmt::Write,
+    fs, io,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+
+use crate::toplevels::ActiveWindow;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Screenshot";
+
+const DEFAULT_FILENAME_PATTERN: &str = "Screenshot_%Y-%m-%d_%H-%M-%S";
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct ScreenshotConfig {
+    /// Where screenshots are saved, or the pictures directory if `None`.
+    pub save_dir: Option<PathBuf>,
+    /// The name screenshots are saved as, with the date and time as in `strftime`. `.png` is
+    /// added to it.
+    pub filename_pattern: String,
+}
+
+impl Default for ScreenshotConfig {
+    fn default() -> Self {
+        Self {
+            save_dir: None,
+            filename_pattern: DEFAULT_FILENAME_PATTERN.to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Target {
+    /// Save the screenshot in the save directory.
+    File,
+    Clipboard,
+}
+
+fn save_dir(config: &ScreenshotConfig) -> Option<PathBuf> {
+    config.save_dir.clone().or_else(dirs::picture_dir)
+}
+
+/// The path to save a screenshot taken now at, which does not exist yet.
+fn file_path(config: &ScreenshotConfig, dir: &Path) -> PathBuf {
+    let now = chrono::Local::now();
+    let mut name = String::new();
+    if write!(name, "{}", now.format(&config.filename_pattern)).is_err() || name.contains('/') {
+        eprintln!(
+            "Invalid screenshot filename pattern {:?}",
+            config.filename_pattern
+        );
+        name.clear();
+        _ = write!(name, "{}", now.format(DEFAULT_FILENAME_PATTERN));
+    }
+
+    let mut path = dir.join(format!("{name}.png"));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{name}-{n}.png"));
+        n += 1;
+    }
+    path
+}
+
+/// A new directory for cosmic-screenshot to save into, so that the screenshot can be found.
+///
+/// It is made in the directory the screenshot ends up in, so that it can be moved there.
+fn staging_dir(dir: &Path) -> io::Result<PathBuf> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos());
+    let staging = dir.join(format!(".cosmic-screenshot-{nanos}"));
+    fs::create_dir_all(&staging)?;
+    Ok(staging)
+}
+
+async fn copy_to_clipboard(path: &Path) -> io::Result<()> {
+    let status = tokio::process::Command::new("wl-copy")
+        .args(["--type", "image/png"])
+        .stdin(Stdio::from(fs::File::open(path)?))
+        .status()
+        .await?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("wl-copy failed with {status}")))
+    }
+}
+
+/// Cut a screenshot of the whole screen down to the active window.
+///
+/// The screenshot covers the bounds of every output, at the same scale for all of them.
+fn crop(path: &Path, window: ActiveWindow) -> io::Result<()> {
+    let image = image::open(path).map_err(io::Error::other)?;
+    let (screen, window) = (window.screen, window.window);
+    if screen.width <= 0 || screen.height <= 0 {
+        return Err(io::Error::other("the outputs have no size"));
+    }
+
+    let scale = f64::from(image.width()) / f64::from(screen.width);
+    let to_pixels =
+        |logical: i32, max: u32| ((f64::from(logical) * scale).round().max(0.0) as u32).min(max);
+    let x = to_pixels(window.x - screen.x, image.width());
+    let y = to_pixels(window.y - screen.y, image.height());
+    let right = to_pixels(window.x + window.width - screen.x, image.width());
+    let bottom = to_pixels(window.y + window.height - screen.y, image.height());
+    if right <= x || bottom <= y {
+        return Err(io::Error::other("the active window is not on the screen"));
+    }
+
+    image
+        .crop_imm(x, y, right - x, bottom - y)
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(io::Error::other)
+}
+
+/// Take a screenshot with cosmic-screenshot, and save it or copy it to the clipboard.
+///
+/// With a `window`, a screenshot of the whole screen is cut down to it.
+async fn take(interactive: bool, window: Option<ActiveWindow>, target: Target) -> io::Result<()> {
+    let config = crate::config::load::<ScreenshotConfig>(ID)
+        .map(|(_, config)| config)
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to load screenshot config {err:?}");
+            ScreenshotConfig::default()
+        });
+
+    let dir = match target {
+        Target::File => save_dir(&config),
+        Target::Clipboard => dirs::runtime_dir(),
+    }
+    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no directory for screenshots"))?;
+    let staging = staging_dir(&dir)?;
+
+    let status = tokio::process::Command::new("cosmic-screenshot")
+        .arg(format!("--interactive={interactive}"))
+        .arg(format!("--notify={}", target == Target::File))
+        .arg("--save-dir")
+        .arg(&staging)
+        .status()
+        .await;
+
+    // Nothing is saved if the screenshot was cancelled.
+    let screenshot = fs::read_dir(&staging)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "png"));
+
+    let res = match (status, screenshot) {
+        (Err(err), _) => Err(err),
+        (Ok(_), None) => Ok(()),
+        (Ok(_), Some(screenshot)) => match window.map(|window| crop(&screenshot, window)) {
+            Some(Err(err)) => Err(err),
+            _ => match target {
+                Target::File => fs::rename(&screenshot, file_path(&config, &dir)),
+                Target::Clipboard => copy_to_clipboard(&screenshot).await,
+            },
+        },
+    };
+
+    _ = fs::remove_dir_all(&staging);
+    res
+}
+
+fn spawn(interactive: bool, window: Option<ActiveWindow>, target: Target) {
+    tokio::spawn(async move {
+        if let Err(err) = take(interactive, window, target).await {
+            eprintln!("Failed to take a screenshot: {err}");
+        }
+    });
+}
+
+/// Screenshots for the Print shortcuts, which are added to the custom shortcuts.
+///
+/// Print takes a screenshot of an area, Shift+Print of the active window, and Super+Print of the
+/// whole screen. With Ctrl, the screenshot is copied to the clipboard instead of saved.
+pub struct Screenshot;
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.Screenshot")]
+impl Screenshot {
+    /// Select an area to take a screenshot of, for Print and Ctrl+Print.
+    async fn area(&self, clipboard: bool) {
+        spawn(true, None, target(clipboard));
+    }
+
+    /// Take a screenshot of the active window, for Shift+Print and Ctrl+Shift+Print.
+    ///
+    /// While the compositor does not send where the active window is, a window is selected in
+    /// the same selection as an area instead.
+    async fn window(&self, clipboard: bool) {
+        match crate::toplevels::active_window() {
+            Some(window) => spawn(false, Some(window), target(clipboard)),
+            None => spawn(true, None, target(clipboard)),
+        }
+    }
+
+    /// Take a screenshot of the whole screen, for Super+Print and Ctrl+Super+Print.
+    async fn screen(&self, clipboard: bool) {
+        spawn(false, None, target(clipboard));
+    }
+}
+
+fn target(clipboard: bool) -> Target {
+    if clipboard {
+        Target::Clipboard
+    } else {
+        Target::File
+    }
+}
@@ -0,0 +1,27 @@
+use std::collections::HashMap;
+
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+#[zbus::dbus_proxy(
+    default_service = "org.freedesktop.portal.Desktop",
+    interface = "org.freedesktop.portal.Screenshot",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait ScreenshotPortal {
+    fn pick_color(
+        &self,
+        parent_window: &str,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[zbus::dbus_proxy(
+    default_service = "org.freedesktop.portal.Desktop",
+    interface = "org.freedesktop.portal.Request"
+)]
+trait PortalRequest {
+    /// The result of a request, where `response` is 0 on success, 1 if it was cancelled, and 2
+    /// if it failed.
+    #[dbus_proxy(signal)]
+    fn response(&self, response: u32, results: HashMap<String, OwnedValue>) -> zbus::Result<()>;
+}
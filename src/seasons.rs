@@ -0,0 +1,68 @@
+use chrono::Local;
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+
+use crate::schedule::DateRange;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Seasons";
+
+/// A profile which is active during the same days of every year.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Season {
+    pub name: String,
+    pub dates: DateRange,
+    pub profile: String,
+}
+
+/// Settings profiles by calendar date, such as an accent color in December or the high contrast
+/// themes during the bright summer months.
+///
+/// The profile of a season is beneath those of regions and of docking, and the automatic theme
+/// mode keeps switching between the dark and light themes, so a season changes the themes rather
+/// than which one is in use.
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct SeasonsConfig {
+    /// The first season which contains the date is active.
+    pub seasons: Vec<Season>,
+}
+
+/// Activate the profile of the current season, checking again at the start of every day.
+pub async fn run(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<SeasonsConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load seasons config {err:?}");
+            return;
+        }
+    };
+
+    loop {
+        let now = Local::now();
+        let season = config
+            .seasons
+            .iter()
+            .find(|season| season.dates.contains(now.date_naive()));
+        crate::profiles::activate("season", season.map(|season| season.profile.clone()));
+
+        // Without seasons there is nothing to check every day.
+        let next_day = if config.seasons.is_empty() {
+            None
+        } else {
+            crate::schedule::next_day(now)
+        };
+
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                crate::config::update(&mut config, &helper, &key);
+            }
+
+            // The realtime clock also ends the sleep when the clock is set.
+            () = crate::schedule::sleep_until(next_day) => (),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    OnceLock,
+};
+
+use tokio_stream::StreamExt;
+use zbus::Connection;
+
+use crate::{LogindSessionProxy, SettingsDaemon, DBUS_PATH};
+
+/// The seat of devices which no seat was assigned to, like in logind.
+const DEFAULT_SEAT: &str = "seat0";
+
+static SEAT: OnceLock<String> = OnceLock::new();
+
+static ACTIVE: AtomicBool = AtomicBool::new(true);
+
+/// The seat of the session, which pam_systemd sets as `XDG_SEAT`. Sessions without a seat use the
+/// default seat.
+pub fn seat() -> &'static str {
+    SEAT.get_or_init(|| {
+        std::env::var("XDG_SEAT")
+            .ok()
+            .filter(|seat| !seat.is_empty())
+            .unwrap_or_else(|| DEFAULT_SEAT.to_owned())
+    })
+}
+
+fn device_seat(device: &udev::Device) -> Option<String> {
+    match device.property_value("ID_SEAT") {
+        Some(seat) => Some(seat.to_string_lossy().into_owned()),
+        None => device_seat(&device.parent()?),
+    }
+}
+
+/// Whether a device belongs to the seat of the session, so that the daemon only acts on the
+/// devices and outputs of its own seat on a multi-seat machine.
+pub fn owns(device: &udev::Device) -> bool {
+    device_seat(device).as_deref().unwrap_or(DEFAULT_SEAT) == seat()
+}
+
+/// Whether the session is in the foreground of its seat. While another session is, such as after
+/// switching users, the keys are not handled and the OSD is not shown.
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// The other session may have changed the backlight, which is shared by the seat.
+async fn activated(conn: &Connection) {
+    let Ok(interface) = conn
+        .object_server()
+        .interface::<_, SettingsDaemon>(DBUS_PATH)
+        .await
+    else {
+        return;
+    };
+
+    let daemon = interface.get().await;
+    _ = daemon
+        .display_brightness_changed(interface.signal_context())
+        .await;
+}
+
+/// Follow whether the session is active, pausing and resuming the handling of keys.
+pub async fn run(conn: Connection, session: LogindSessionProxy<'static>) {
+    let mut changes = session.receive_active_changed().await;
+    let mut active = session.active().await.unwrap_or(true);
+    ACTIVE.store(active, Ordering::Relaxed);
+
+    while changes.next().await.is_some() {
+        let now_active = session.active().await.unwrap_or(true);
+        if now_active == active {
+            continue;
+        }
+
+        active = now_active;
+        ACTIVE.store(active, Ordering::Relaxed);
+        if active {
+            activated(&conn).await;
+        }
+    }
+}
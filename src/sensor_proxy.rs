@@ -0,0 +1,19 @@
+/// iio-sensor-proxy, which reads the IIO sensors of the device.
+#[zbus::dbus_proxy(
+    default_service = "net.hadess.SensorProxy",
+    interface = "net.hadess.SensorProxy",
+    default_path = "/net/hadess/SensorProxy"
+)]
+trait SensorProxy {
+    /// Start updating the proximity, until released or until the caller leaves the bus.
+    fn claim_proximity(&self) -> zbus::Result<()>;
+
+    fn release_proximity(&self) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn has_proximity(&self) -> zbus::Result<bool>;
+
+    /// Whether something is near the proximity sensor, such as the user in front of the device.
+    #[dbus_proxy(property)]
+    fn proximity_near(&self) -> zbus::Result<bool>;
+}
@@ -0,0 +1,294 @@
+use std::{collections::BTreeSet, path::PathBuf};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use cosmic_settings_daemon_lib::{
+    config::{ConfigDir, ConfigProvider},
+    shortcuts::{self, Binding, Modifier},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{DBUS_NAME, DBUS_PATH};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Shortcuts";
+
+/// The shortcuts of COSMIC, with the `defaults` of the system and the `custom` ones of the user.
+const SHORTCUTS_ID: &str = "com.system76.CosmicSettings.Shortcuts";
+
+/// The shortcuts which were added to the custom shortcuts, so that one the user removed is not
+/// added again.
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct ShortcutsState {
+    pub added: BTreeSet<String>,
+}
+
+/// A shortcut calling a method of the daemon.
+struct Shortcut {
+    name: &'static str,
+    modifiers: &'static [Modifier],
+    key: &'static str,
+    /// The interface, without the prefix of the daemon's name.
+    interface: &'static str,
+    method: &'static str,
+    /// The arguments as `busctl` takes them, such as `b true`.
+    args: &'static str,
+    /// Take the place of a default shortcut of COSMIC with the same binding, rather than leave
+    /// it bound to that.
+    replaces_default: bool,
+}
+
+const SHORTCUTS: &[Shortcut] = &[
+    Shortcut {
+        name: "screen-reader",
+        modifiers: &[Modifier::Super, Modifier::Alt],
+        key: "s",
+        interface: "A11y",
+        method: "ToggleScreenReader",
+        args: "",
+        replaces_default: false,
+    },
+    Shortcut {
+        name: "magnifier",
+        modifiers: &[Modifier::Super, Modifier::Alt],
+        key: "8",
+        interface: "A11y",
+        method: "ToggleMagnifier",
+        args: "",
+        replaces_default: false,
+    },
+    Shortcut {
+        name: "zoom-in",
+        modifiers: &[Modifier::Super, Modifier::Alt],
+        key: "equal",
+        interface: "A11y",
+        method: "ZoomIn",
+        args: "",
+        replaces_default: false,
+    },
+    Shortcut {
+        name: "zoom-out",
+        modifiers: &[Modifier::Super, Modifier::Alt],
+        key: "minus",
+        interface: "A11y",
+        method: "ZoomOut",
+        args: "",
+        replaces_default: false,
+    },
+    Shortcut {
+        name: "color-picker",
+        modifiers: &[Modifier::Super, Modifier::Shift],
+        key: "c",
+        interface: "ColorPicker",
+        method: "Pick",
+        args: "",
+        replaces_default: false,
+    },
+    // The volume and microphone mute keys of the daemon step, limit, and show the volume in
+    // place of the default ones.
+    Shortcut {
+        name: "volume-raise",
+        modifiers: &[],
+        key: "XF86AudioRaiseVolume",
+        interface: "Audio",
+        method: "IncreaseVolume",
+        args: "",
+        replaces_default: true,
+    },
+    Shortcut {
+        name: "volume-lower",
+        modifiers: &[],
+        key: "XF86AudioLowerVolume",
+        interface: "Audio",
+        method: "DecreaseVolume",
+        args: "",
+        replaces_default: true,
+    },
+    Shortcut {
+        name: "volume-mute",
+        modifiers: &[],
+        key: "XF86AudioMute",
+        interface: "Audio",
+        method: "ToggleMute",
+        args: "",
+        replaces_default: true,
+    },
+    Shortcut {
+        name: "microphone-mute",
+        modifiers: &[],
+        key: "XF86AudioMicMute",
+        interface: "Audio",
+        method: "ToggleMicrophoneMute",
+        args: "",
+        replaces_default: true,
+    },
+    Shortcut {
+        name: "airplane-mode",
+        modifiers: &[],
+        key: "XF86RFKill",
+        interface: "Rfkill",
+        method: "ToggleAirplaneMode",
+        args: "",
+        replaces_default: false,
+    },
+    // The screenshots of the daemon take the place of the default ones.
+    Shortcut {
+        name: "screenshot-area",
+        modifiers: &[],
+        key: "Print",
+        interface: "Screenshot",
+        method: "Area",
+        args: "b false",
+        replaces_default: true,
+    },
+    Shortcut {
+        name: "screenshot-area-clipboard",
+        modifiers: &[Modifier::Ctrl],
+        key: "Print",
+        interface: "Screenshot",
+        method: "Area",
+        args: "b true",
+        replaces_default: true,
+    },
+    Shortcut {
+        name: "screenshot-window",
+        modifiers: &[Modifier::Shift],
+        key: "Print",
+        interface: "Screenshot",
+        method: "Window",
+        args: "b false",
+        replaces_default: true,
+    },
+    Shortcut {
+        name: "screenshot-window-clipboard",
+        modifiers: &[Modifier::Shift, Modifier::Ctrl],
+        key: "Print",
+        interface: "Screenshot",
+        method: "Window",
+        args: "b true",
+        replaces_default: true,
+    },
+    Shortcut {
+        name: "screenshot-screen",
+        modifiers: &[Modifier::Super],
+        key: "Print",
+        interface: "Screenshot",
+        method: "Screen",
+        args: "b false",
+        replaces_default: true,
+    },
+    Shortcut {
+        name: "screenshot-screen-clipboard",
+        modifiers: &[Modifier::Super, Modifier::Ctrl],
+        key: "Print",
+        interface: "Screenshot",
+        method: "Screen",
+        args: "b true",
+        replaces_default: true,
+    },
+];
+
+impl Shortcut {
+    fn binding(&self) -> Binding {
+        Binding::new(self.modifiers, self.key)
+    }
+
+    fn command(&self) -> String {
+        let command = format!(
+            "busctl --user call {DBUS_NAME} {DBUS_PATH} {DBUS_NAME}.{} {}",
+            self.interface, self.method
+        );
+        if self.args.is_empty() {
+            command
+        } else {
+            format!("{command} {}", self.args)
+        }
+    }
+}
+
+/// The directories of the system configs, in which the default shortcuts are.
+fn system_config_dirs() -> Vec<ConfigDir> {
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_owned());
+    data_dirs
+        .split(':')
+        .map(|dir| ConfigDir(PathBuf::from(dir).join("cosmic")))
+        .collect()
+}
+
+fn default_bindings() -> BTreeSet<Binding> {
+    system_config_dirs()
+        .iter()
+        .filter_map(|dir| dir.read_key(SHORTCUTS_ID, 1, "defaults"))
+        .filter_map(|defaults| shortcuts::bindings(&defaults).ok())
+        .flatten()
+        .collect()
+}
+
+/// Add the shortcuts of the daemon's features to the custom shortcuts, once each, unless their
+/// keys are already bound.
+pub fn register() {
+    let (helper, mut state) = match crate::config::load_state::<ShortcutsState>(ID) {
+        Ok(state) => state,
+        Err(err) => {
+            eprintln!("Failed to load shortcuts state {err:?}");
+            return;
+        }
+    };
+
+    let Some(config_dir) = ConfigDir::user() else {
+        return;
+    };
+
+    let custom = config_dir
+        .read_key(SHORTCUTS_ID, 1, "custom")
+        .unwrap_or_default();
+    // Shortcuts which cannot be read are not written over.
+    let custom_bindings = match shortcuts::bindings(&custom) {
+        Ok(bindings) => bindings,
+        Err(err) => {
+            eprintln!("Failed to read the custom shortcuts: {err}");
+            return;
+        }
+    };
+    let default_bindings = default_bindings();
+
+    let added: Vec<&Shortcut> = SHORTCUTS
+        .iter()
+        .filter(|shortcut| !state.added.contains(shortcut.name))
+        .filter(|shortcut| {
+            let binding = shortcut.binding();
+            !custom_bindings.contains(&binding)
+                && (shortcut.replaces_default || !default_bindings.contains(&binding))
+        })
+        .collect();
+    if added.is_empty() {
+        return;
+    }
+
+    let commands: Vec<(Binding, String)> = added
+        .iter()
+        .map(|shortcut| (shortcut.binding(), shortcut.command()))
+        .collect();
+    let commands: Vec<(Binding, &str)> = commands
+        .iter()
+        .map(|(binding, command)| (binding.clone(), command.as_str()))
+        .collect();
+    let Some(custom) = shortcuts::add(&custom, &commands) else {
+        eprintln!("Failed to add to the custom shortcuts, which are not a map");
+        return;
+    };
+
+    if let Err(err) = config_dir.write_key(SHORTCUTS_ID, 1, "custom", Some(&custom)) {
+        eprintln!("Failed to write the custom shortcuts: {err}");
+        return;
+    }
+
+    state
+        .added
+        .extend(added.iter().map(|shortcut| shortcut.name.to_owned()));
+    if let Err(err) = state.set_added(&helper, state.added.clone()) {
+        eprintln!("Failed to save the added shortcuts {err:?}");
+    }
+}
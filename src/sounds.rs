@@ -0,0 +1,184 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Sounds";
+
+static CONFIG: Mutex<Option<SoundsConfig>> = Mutex::new(None);
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct SoundsConfig {
+    /// Play event sounds.
+    pub enabled: bool,
+    /// The XDG sound theme to play sounds from. Missing sounds are played from the freedesktop theme.
+    pub theme: String,
+    /// Sounds to play instead of the default sound of an event, by event name.
+    ///
+    /// An empty sound name disables the event.
+    pub overrides: BTreeMap<String, String>,
+}
+
+impl Default for SoundsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            theme: "Pop".to_owned(),
+            overrides: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Login,
+    Logout,
+    VolumeChange,
+    BatteryCaution,
+    BatteryLow,
+    BatteryFull,
+    PowerPlug,
+    PowerUnplug,
+    PowerUnplugBatteryLow,
+    Notification,
+    Bell,
+}
+
+impl Event {
+    const ALL: [Event; 11] = [
+        Event::Login,
+        Event::Logout,
+        Event::VolumeChange,
+        Event::BatteryCaution,
+        Event::BatteryLow,
+        Event::BatteryFull,
+        Event::PowerPlug,
+        Event::PowerUnplug,
+        Event::PowerUnplugBatteryLow,
+        Event::Notification,
+        Event::Bell,
+    ];
+
+    /// The name of the event in the config and the D-Bus API.
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Login => "login",
+            Event::Logout => "logout",
+            Event::VolumeChange => "volume-change",
+            Event::BatteryCaution => "battery-caution",
+            Event::BatteryLow => "battery-low",
+            Event::BatteryFull => "battery-full",
+            Event::PowerPlug => "power-plug",
+            Event::PowerUnplug => "power-unplug",
+            Event::PowerUnplugBatteryLow => "power-unplug-battery-low",
+            Event::Notification => "notification",
+            Event::Bell => "bell",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|event| event.name() == name)
+    }
+
+    /// The name of the sound in the XDG sound naming specification.
+    fn sound(self) -> &'static str {
+        match self {
+            Event::Login => "desktop-login",
+            Event::Logout => "desktop-logout",
+            Event::VolumeChange => "audio-volume-change",
+            Event::BatteryCaution => "battery-caution",
+            Event::BatteryLow => "battery-low",
+            Event::BatteryFull => "battery-full",
+            Event::PowerPlug => "power-plug",
+            Event::PowerUnplug => "power-unplug",
+            Event::PowerUnplugBatteryLow => "power-unplug-battery-low",
+            Event::Notification => "message-new-instant",
+            Event::Bell => "bell",
+        }
+    }
+
+    /// A sound to play if the theme has no sound for the event.
+    fn fallback(self) -> Option<&'static str> {
+        match self {
+            Event::PowerUnplugBatteryLow => Some("power-unplug"),
+            Event::Notification => Some("message"),
+            _ => None,
+        }
+    }
+}
+
+/// Play the sound of an event from the configured sound theme.
+pub fn play(event: Event) {
+    // The visual bell works even when event sounds are disabled.
+    if event == Event::Bell && !crate::a11y::ring_visual_bell() {
+        return;
+    }
+
+    let config = CONFIG
+        .lock()
+        .ok()
+        .and_then(|config| config.clone())
+        .unwrap_or_default();
+
+    if !config.enabled {
+        return;
+    }
+
+    // Keep warning of a critical battery, as the system is about to shut down.
+    if crate::audio::quiet_hours_active() && event != Event::BatteryLow {
+        return;
+    }
+
+    let sound = match config.overrides.get(event.name()) {
+        Some(sound) if sound.is_empty() => return,
+        Some(sound) => sound.as_str(),
+        None => event.sound(),
+    };
+
+    if !crate::pipewire::play_sound(&config.theme, sound) {
+        if let Some(fallback) = event.fallback() {
+            crate::pipewire::play_sound(&config.theme, fallback);
+        }
+    }
+}
+
+/// Plays event sounds on behalf of other desktop components.
+pub struct Sounds;
+
+#[zbus::dbus_interface(name = "com.system76.CosmicSettingsDaemon.Sounds")]
+impl Sounds {
+    async fn play(&self, event: &str) -> zbus::fdo::Result<()> {
+        let event = Event::from_name(event)
+            .ok_or_else(|| zbus::fdo::Error::InvalidArgs(format!("unknown sound event {event}")))?;
+        play(event);
+        Ok(())
+    }
+}
+
+/// Load the sounds config and play the login sound, then apply changes to the config.
+pub async fn watch_config(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<SoundsConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load sounds config {err:?}");
+            play(Event::Login);
+            return;
+        }
+    };
+
+    set_config(config.clone());
+    play(Event::Login);
+
+    while let Some(key) = rx.recv().await {
+        crate::config::update(&mut config, &helper, &key);
+        set_config(config.clone());
+    }
+}
+
+fn set_config(config: SoundsConfig) {
+    if let Ok(mut global) = CONFIG.lock() {
+        *global = Some(config);
+    }
+}
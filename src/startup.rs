@@ -0,0 +1,165 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use tokio::{sync::watch, task};
+
+static START: OnceLock<Instant> = OnceLock::new();
+
+/// The steps of the startup in the order they finished, and how long each took.
+static TIMES: Mutex<Vec<(&'static str, Duration)>> = Mutex::new(Vec::new());
+
+/// Mark the start of the daemon, which `ready` measures from.
+pub fn start() {
+    START.get_or_init(Instant::now);
+}
+
+fn record(name: &'static str, elapsed: Duration) {
+    if let Ok(mut times) = TIMES.lock() {
+        times.push((name, elapsed));
+    }
+}
+
+/// Run a step of the startup, recording how long it took.
+pub async fn step<T>(name: &'static str, future: impl Future<Output = T>) -> T {
+    let start = Instant::now();
+    let output = future.await;
+    record(name, start.elapsed());
+    output
+}
+
+/// Record that every module has been started, as the `ready` step.
+pub fn ready() {
+    if let Some(start) = START.get() {
+        record("ready", start.elapsed());
+    }
+}
+
+/// The steps of the startup with their durations in microseconds.
+pub fn times() -> Vec<(String, u64)> {
+    let Ok(times) = TIMES.lock() else {
+        return Vec::new();
+    };
+
+    times
+        .iter()
+        .map(|(name, elapsed)| ((*name).to_owned(), elapsed.as_micros() as u64))
+        .collect()
+}
+
+/// The output of a step of the startup, for the steps after it and for after the startup.
+pub struct Output<T>(Rc<RefCell<Option<T>>>);
+
+impl<T> Clone for Output<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Output<T> {
+    /// Take the output, which is `None` until the step has finished, or if it was skipped or
+    /// taken already.
+    pub fn take(&self) -> Option<T> {
+        self.0.borrow_mut().take()
+    }
+
+    /// Apply `f` to the output, for outputs of which several steps use or take a part, or `None`
+    /// until the step has finished.
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.0.borrow_mut().as_mut().map(f)
+    }
+}
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+/// The startup as a graph of steps, each of which runs as soon as the steps it depends on have
+/// finished, so that independent steps run concurrently.
+#[derive(Default)]
+pub struct Graph {
+    steps: Vec<(&'static str, &'static [&'static str], Task)>,
+}
+
+impl Graph {
+    /// Add the step `name`, which runs after the steps `after` and records how long it took.
+    pub fn step<T: 'static>(
+        &mut self,
+        name: &'static str,
+        after: &'static [&'static str],
+        future: impl Future<Output = T> + 'static,
+    ) -> Output<T> {
+        let output = Output(Rc::new(RefCell::new(None)));
+        let slot = output.clone();
+        let task = Box::pin(async move {
+            let value = step(name, future).await;
+            *slot.0.borrow_mut() = Some(value);
+        });
+
+        self.steps.push((name, after, task));
+        output
+    }
+
+    /// Run every step, on the local set of the daemon, and wait for them to finish.
+    ///
+    /// Steps after a step which does not exist, or after themselves through other steps, are
+    /// reported and skipped.
+    pub async fn run(self) {
+        // The steps whose dependencies can all run, found by adding them in dependency order.
+        let mut runnable: HashSet<&str> = HashSet::new();
+        loop {
+            let count = runnable.len();
+            for (name, after, _) in &self.steps {
+                if after.iter().all(|dep| runnable.contains(dep)) {
+                    runnable.insert(name);
+                }
+            }
+            if runnable.len() == count {
+                break;
+            }
+        }
+
+        let mut done: HashMap<&str, watch::Receiver<bool>> = HashMap::new();
+        let mut senders = Vec::new();
+        for (name, _, _) in &self.steps {
+            let (tx, rx) = watch::channel(false);
+            done.insert(name, rx);
+            senders.push(tx);
+        }
+
+        let mut handles = Vec::new();
+        for ((name, after, task), tx) in self.steps.into_iter().zip(senders) {
+            if !runnable.contains(name) {
+                eprintln!(
+                    "Skipped startup step {name}, as a step before it is missing or after it"
+                );
+                continue;
+            }
+
+            let mut deps: Vec<watch::Receiver<bool>> = after
+                .iter()
+                .filter_map(|dep| done.get(dep).cloned())
+                .collect();
+            handles.push(task::spawn_local(async move {
+                for dep in &mut deps {
+                    while !*dep.borrow_and_update() {
+                        if dep.changed().await.is_err() {
+                            return;
+                        }
+                    }
+                }
+
+                task.await;
+                tx.send_replace(true);
+            }));
+        }
+
+        for handle in handles {
+            _ = handle.await;
+        }
+    }
+}
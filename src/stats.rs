@@ -0,0 +1,87 @@
+use std::{collections::BTreeMap, process::ExitCode, sync::Mutex};
+
+use tokio_stream::StreamExt;
+use zbus::{Connection, MatchRule, MessageStream};
+
+use crate::{DBUS_NAME, DBUS_PATH};
+
+static COUNTS: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+
+/// Count an event, such as a config change of a module or a D-Bus call, as `category.name`.
+pub fn count(category: &str, name: &str) {
+    if let Ok(mut counts) = COUNTS.lock() {
+        *counts.entry(format!("{category}.{name}")).or_default() += 1;
+    }
+}
+
+/// The counts since the daemon started, for diagnosing battery drain and busy loops.
+pub fn stats() -> BTreeMap<String, u64> {
+    let mut stats = COUNTS
+        .lock()
+        .map(|counts| counts.clone())
+        .unwrap_or_default();
+    stats.insert("files.written".to_owned(), crate::files::writes());
+    stats.insert("wakeups.total".to_owned(), crate::wakeups::total());
+    stats.insert(
+        "wakeups.last_minute".to_owned(),
+        u64::from(crate::wakeups::per_minute()),
+    );
+    stats
+}
+
+/// Count the D-Bus method calls of the daemon, by interface and method.
+pub async fn run(conn: Connection) {
+    let rule = MatchRule::builder()
+        .msg_type(zbus::MessageType::MethodCall)
+        .build();
+    let mut calls = match MessageStream::for_match_rule(rule, &conn, Some(100)).await {
+        Ok(calls) => calls,
+        Err(err) => {
+            eprintln!("Failed to count D-Bus calls: {err}");
+            return;
+        }
+    };
+
+    while let Some(msg) = calls.next().await {
+        let Ok(msg) = msg else {
+            continue;
+        };
+        let (Some(interface), Some(member)) = (msg.interface(), msg.member()) else {
+            continue;
+        };
+        count(
+            "dbus",
+            &format!("{}.{}", interface.as_str(), member.as_str()),
+        );
+    }
+}
+
+/// Print the stats of the running daemon, as `cosmic-settings-daemon stats`.
+pub async fn cli() -> ExitCode {
+    let stats = async {
+        let conn = Connection::session().await?;
+        let reply = conn
+            .call_method(
+                Some(DBUS_NAME),
+                DBUS_PATH,
+                Some("com.system76.CosmicSettingsDaemon"),
+                "GetStats",
+                &(),
+            )
+            .await?;
+        reply.body::<BTreeMap<String, u64>>()
+    };
+
+    match stats.await {
+        Ok(stats) => {
+            for (name, count) in stats {
+                println!("{name} {count}");
+            }
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("Failed to get the stats of the daemon: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,12 @@
+#[zbus::dbus_proxy(
+    default_service = "com.system76.PowerDaemon",
+    interface = "com.system76.PowerDaemon",
+    default_path = "/com/system76/PowerDaemon"
+)]
+trait PowerDaemon {
+    fn get_graphics(&self) -> zbus::Result<String>;
+
+    fn set_graphics(&self, vendor: &str) -> zbus::Result<()>;
+
+    fn get_switchable(&self) -> zbus::Result<bool>;
+}
@@ -0,0 +1,10 @@
+#[zbus::dbus_proxy(
+    default_service = "org.freedesktop.systemd1",
+    interface = "org.freedesktop.systemd1.Manager",
+    default_path = "/org/freedesktop/systemd1"
+)]
+trait SystemdManager {
+    fn set_environment(&self, assignments: &[&str]) -> zbus::Result<()>;
+
+    fn unset_environment(&self, names: &[&str]) -> zbus::Result<()>;
+}
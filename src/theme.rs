@@ -1,32 +1,54 @@
 // TODO later...
 // If configured to, run scripts in XDG_DATA_DIR/dark-mode.d/ or XDG_DATA_DIR/light-mode.d/
 // when the theme is set to auto-export color palette, write to gtk3 / gtk4 / kde / ... css files
-// read config file for lat/long
 
-use std::path::Path;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{path::Path, time::Duration};
 
-use anyhow::bail;
-use chrono::{DateTime, Datelike, Days, Local};
+use anyhow::{bail, Context};
+use chrono::{DateTime, Local};
 use cosmic::{config::CosmicTk, theme::CosmicTheme};
-use cosmic_config::CosmicConfigEntry;
-use cosmic_theme::{Theme, ThemeMode};
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, ConfigSet, CosmicConfigEntry};
+use cosmic_theme::{Theme, ThemeBuilder, ThemeMode};
 use geoclue2::{Accuracy, LocationProxy};
-
-use tokio::time::Instant;
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
 use tokio_stream::StreamExt;
 
-use crate::DBUS_NAME;
-
-#[derive(Debug)]
-pub struct SunriseSunset {
-    last_update: DateTime<Local>,
-    sunrise: Instant,
-    sunset: Instant,
-    lat: f64,
-    long: f64,
-    /// accuracy in meters
-    accuracy: f64,
+use crate::{
+    location::Fix,
+    schedule::{Location, Schedule},
+    sun::SunriseSunset,
+    DBUS_NAME,
+};
+
+pub const SWITCH_NOTICE_ID: &str = "com.system76.CosmicSettingsDaemon.ThemeSwitch";
+
+/// A notice before the automatic theme mode switches between dark and light, so that the switch
+/// does not surprise anyone, such as while sharing the screen.
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct SwitchNoticeConfig {
+    /// Notify before the switch, offering to switch now or to snooze the switch.
+    pub enabled: bool,
+    /// How long before the switch to notify, in minutes.
+    pub minutes_before: u64,
+    /// How long snoozing postpones the switch, in minutes.
+    pub snooze_minutes: u64,
+    /// Defer the switch while a window is fullscreen, such as a game, a video, or a
+    /// presentation, until it leaves fullscreen.
+    pub defer_while_fullscreen: bool,
+}
+
+impl Default for SwitchNoticeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            minutes_before: 5,
+            snooze_minutes: 60,
+            defer_while_fullscreen: false,
+        }
+    }
 }
 
 pub enum ThemeMsg {
@@ -34,102 +56,72 @@ pub enum ThemeMsg {
     /// true if dark
     Theme(bool),
     Tk(String),
+    SwitchNotice(String),
 }
 
-impl SunriseSunset {
-    pub fn new(
-        lat: f64,
-        long: f64,
-        t: Option<DateTime<Local>>,
-        accuracy: f64,
-    ) -> anyhow::Result<Self> {
-        let (system_t, instant_t, t) = if let Some(t) = t {
-            let system_t = SystemTime::from(t);
-            let system_now = SystemTime::now();
-            let delta_t = system_t.duration_since(system_now)?;
-            let instant_t = Instant::now()
-                .checked_add(delta_t)
-                .ok_or(anyhow::anyhow!("Could not calculate instant"))?;
-
-            (system_t, instant_t, t)
-        } else {
-            (SystemTime::now(), Instant::now(), Local::now())
-        };
-        let year = t.year();
-        let month = t.month();
-        let day = t.day();
-        let (sunrise, sunset) = sunrise::sunrise_sunset(lat, long, year, month, day);
+/// An action of the notice of the switch at the given time.
+#[derive(Clone, Copy, Debug)]
+enum NoticeAction {
+    /// Switch to the mode, which is dark if true.
+    SwitchNow(bool),
+    Snooze,
+}
 
-        let Some(sunrise) =
-            UNIX_EPOCH.checked_add(std::time::Duration::from_secs(u64::try_from(sunrise)?))
-        else {
-            bail!("Failed to calculate sunrise time");
-        };
+fn minutes(n: u64) -> String {
+    match n {
+        1 => "1 minute".to_owned(),
+        60 => "1 hour".to_owned(),
+        n if n % 60 == 0 => format!("{} hours", n / 60),
+        n => format!("{n} minutes"),
+    }
+}
 
-        let Some(sunset) =
-            UNIX_EPOCH.checked_add(std::time::Duration::from_secs(u64::try_from(sunset)?))
+/// Notify that the theme switches to `is_dark` at `switch`, sending the action the user invoked.
+fn notify_switch(
+    config: &SwitchNoticeConfig,
+    is_dark: bool,
+    switch: DateTime<Local>,
+    tx: UnboundedSender<(NoticeAction, DateTime<Local>)>,
+) {
+    let mode = if is_dark { "Dark" } else { "Light" };
+    let body = format!(
+        "The {} theme is applied in {}. Snoozing postpones it by {}.",
+        mode.to_lowercase(),
+        minutes(config.minutes_before),
+        minutes(config.snooze_minutes),
+    );
+    let timeout = (switch - Local::now()).to_std().unwrap_or_default();
+
+    tokio::task::spawn_local(async move {
+        let Ok(handle) = Notification::new()
+            .appname("")
+            .summary(&format!("Switching to the {mode} Theme"))
+            .body(&body)
+            .icon("preferences-desktop-appearance-symbolic")
+            .action("switch", "Switch Now")
+            .action("snooze", "Snooze")
+            .timeout(timeout.max(Duration::from_secs(10)))
+            .show_async()
+            .await
         else {
-            bail!("Failed to calculate sunset time");
+            return;
         };
 
-        let st_to_instant = |now: SystemTime, st: SystemTime| -> anyhow::Result<Instant> {
-            Ok(if st > now {
-                instant_t
-                    .checked_add(st.duration_since(now)?)
-                    .ok_or(anyhow::anyhow!("Failed to convert system time to instant"))?
-            } else {
-                instant_t
-                    .checked_sub(now.duration_since(st)?)
-                    .ok_or(anyhow::anyhow!("Failed to convert system time to instant"))?
-            })
+        let action = match crate::notifications::wait_for_action(handle)
+            .await
+            .as_deref()
+        {
+            Some("switch") => NoticeAction::SwitchNow(is_dark),
+            Some("snooze") => NoticeAction::Snooze,
+            _ => return,
         };
-
-        Ok(Self {
-            last_update: t,
-            sunrise: st_to_instant(system_t, sunrise)?,
-            sunset: st_to_instant(system_t, sunset)?,
-            lat,
-            long,
-            accuracy,
-        })
-    }
-
-    pub fn is_dark(&self) -> anyhow::Result<bool> {
-        if self.last_update.date_naive() != Local::now().date_naive() {
-            bail!("SunriseSunset out of date");
-        }
-
-        let now = Instant::now();
-        Ok(now < self.sunrise || now >= self.sunset)
-    }
-
-    pub fn next(&self) -> anyhow::Result<Instant> {
-        let now = Instant::now();
-        if self.sunrise.checked_duration_since(now).is_some() {
-            Ok(self.sunrise)
-        } else if self.sunset.checked_duration_since(now).is_some() {
-            Ok(self.sunset)
-        } else {
-            bail!("SunriseSunset instants have already passed...");
-        }
-    }
-
-    pub fn update_next(&mut self) -> anyhow::Result<Instant> {
-        match self.next() {
-            Ok(i) => Ok(i),
-            Err(_) => {
-                let Some(tomorrow) = self.last_update.checked_add_days(Days::new(1)) else {
-                    bail!("Failed to calculate next date for theme auto-switch.");
-                };
-                *self = Self::new(self.lat, self.long, Some(tomorrow), self.accuracy)?;
-                self.next()
-            }
-        }
-    }
+        let _res = tx.send((action, switch));
+    });
 }
 
 pub async fn watch_theme(
     theme_mode_rx: &mut tokio::sync::mpsc::Receiver<ThemeMsg>,
+    location_tx: &tokio::sync::watch::Sender<Option<Location>>,
 ) -> anyhow::Result<()> {
     let helper = ThemeMode::config()?;
     let mut theme_mode = match ThemeMode::get_entry(&helper) {
@@ -167,7 +159,9 @@ pub async fn watch_theme(
                 t
             }
         };
-        _ = dark.write_gtk4();
+        if let Err(err) = crate::gtk::write(&dark) {
+            eprintln!("Failed to write the dark theme for gtk. {err:?}");
+        }
         let light = match Theme::get_entry(&light_helper) {
             Ok(t) => t,
             Err((errs, t)) => {
@@ -177,61 +171,169 @@ pub async fn watch_theme(
                 t
             }
         };
-        _ = light.write_gtk4();
+        if let Err(err) = crate::gtk::write(&light) {
+            eprintln!("Failed to write the light theme for gtk. {err:?}");
+        }
         _ = std::process::Command::new("flatpak")
             .arg("override")
             .arg("--user")
             .arg("--filesystem=xdg-config/gtk-4.0:ro")
             .spawn();
-        if let Err(err) = Theme::apply_gtk(theme_mode.is_dark) {
-            eprintln!("Failed to apply the theme to gtk. {err:?}");
-        }
-
-        set_gnome_desktop_interface(theme_mode.is_dark);
+        apply_theme_mode(&tk, theme_mode.is_dark);
     } else {
-        if let Err(err) = Theme::reset_gtk() {
+        if let Err(err) = crate::gtk::reset() {
             eprintln!("Failed to reset the application of the theme to gtk. {err:?}");
         }
     }
-    let conn = zbus::Connection::system().await?;
-    let mgr = geoclue2::ManagerProxy::new(&conn).await?;
-    let client = mgr.get_client().await?;
-    client
-        .set_requested_accuracy_level(Accuracy::Exact as u32)
-        .await?;
-    client.set_desktop_id(DBUS_NAME).await?;
-    // TODO allow preference for config file instead?
-    let mut location_updates = Some(client.receive_location_updated().await?);
-    client.start().await?;
+    let conn = crate::bus::system().await?;
+    // The GeoClue client only exists while some module needs the location, location services
+    // are on, and GeoClue is the source. The fixes of other sources are followed otherwise.
+    let mut location_state = crate::location::subscribe();
+    let mut geoclue = None;
+    let mut fixes = crate::location::subscribe_fixes();
+    let mut following_fixes = false;
+    // The accuracy of the last location from GeoClue, which later ones are compared with.
+    let mut geoclue_accuracy: Option<f64> = None;
+
+    let (notice_helper, mut notice_config) =
+        crate::config::load_async::<SwitchNoticeConfig>(SWITCH_NOTICE_ID).await?;
+    let (notice_tx, mut notice_rx) = unbounded_channel();
+    // The automatic theme mode is not applied until then, after the user snoozed a switch or
+    // switched early.
+    let mut held_until: Option<DateTime<Local>> = None;
+    // The switch the user was last notified of.
+    let mut noticed: Option<DateTime<Local>> = None;
+    // Whether a switch waits for the fullscreen window to leave fullscreen, which the compositor
+    // reports to the do not disturb module.
+    let mut fullscreen_changes = crate::dnd::subscribe();
+    let mut deferred = false;
 
     let mut sunrise_sunset: Option<SunriseSunset> = None;
     loop {
-        let sunset_deadline =
-            if let Some(Some(s)) = theme_mode.auto_switch.then(|| sunrise_sunset.as_mut()) {
-                Some(s.update_next()?)
-            } else {
-                None
+        crate::location::want("theme", theme_mode.auto_switch);
+        let (wanted, external, fallback) = {
+            let state = location_state.borrow_and_update();
+            (
+                state.geoclue(),
+                state.in_use() && !state.geoclue(),
+                state.fallback_dark(),
+            )
+        };
+
+        // The fix the source already reported is used right away.
+        if external && !following_fixes {
+            fixes.mark_changed();
+        }
+        following_fixes = external;
+
+        if fallback.is_some() {
+            // Nothing may keep using the location after location services are turned off.
+            sunrise_sunset = None;
+            location_tx.send_if_modified(|location| location.take().is_some());
+        }
+
+        if wanted && geoclue.is_none() {
+            let client = async {
+                let mgr = geoclue2::ManagerProxy::new(&conn).await?;
+                let client = mgr.get_client().await?;
+                client
+                    .set_requested_accuracy_level(Accuracy::Exact as u32)
+                    .await?;
+                client.set_desktop_id(DBUS_NAME).await?;
+                let location_updates = client.receive_location_updated().await?;
+                client.start().await?;
+                zbus::Result::Ok((client, location_updates))
             };
 
-        let location_update = async {
-            if let Some(location_updates) = location_updates.as_mut() {
-                location_updates.next().await
-            } else {
-                std::future::pending().await
+            match client.await {
+                Ok(client) => {
+                    crate::errors::succeed("geoclue");
+                    geoclue = Some(client);
+                }
+                Err(err) => {
+                    crate::errors::fail(
+                        "geoclue",
+                        "Location Services Are Unavailable",
+                        format!("GeoClue could not be started, so the location is unknown: {err}"),
+                    );
+                    return Err(err.into());
+                }
             }
+        } else if !wanted {
+            // A failure of GeoClue no longer matters once it is not used.
+            crate::errors::succeed("geoclue");
+            if let Some((client, _)) = geoclue.take() {
+                if let Err(err) = client.stop().await {
+                    eprintln!("Failed to stop the GeoClue client {err:?}");
+                }
+            }
+        }
+
+        let sunset_deadline = if !theme_mode.auto_switch {
+            None
+        } else if let Some(schedule) = fallback {
+            schedule.next_transition(Local::now(), None::<Location>)
+        } else if let Some(s) = sunrise_sunset.as_mut() {
+            Some(s.next_transition()?)
+        } else {
+            None
         };
 
-        let sleep = async move {
-            if !theme_mode.auto_switch {
-                std::future::pending().await
-            } else if let Some(s) = sunset_deadline {
-                tokio::time::sleep_until(s).await
+        let location_update = async {
+            if let Some((_, location_updates)) = geoclue.as_mut() {
+                let Some(location_update) = location_updates.next().await else {
+                    bail!("No location in the update");
+                };
+                let args = location_update.args()?;
+                anyhow::Ok((Some(geoclue_fix(&conn, args.new().clone()).await?), true))
+            } else if external && fixes.changed().await.is_ok() {
+                Ok((*fixes.borrow_and_update(), false))
             } else {
                 std::future::pending().await
             }
         };
 
+        let switch = if theme_mode.auto_switch {
+            held_until.or(sunset_deadline)
+        } else {
+            None
+        };
+        let notice_at = switch
+            .filter(|switch| notice_config.enabled && noticed != Some(*switch))
+            .map(|switch| switch - chrono::Duration::minutes(notice_config.minutes_before as i64));
+
+        // The realtime clock keeps the switch on time across suspend.
+        let sleep = crate::schedule::sleep_until(switch);
+        let notice = crate::schedule::sleep_until(notice_at);
+
         tokio::select! {
+            res = location_state.changed() => {
+                if res.is_err() {
+                    bail!("Location demand changes failed");
+                }
+
+                // Switch to the fallback schedule right away when location services are turned
+                // off.
+                let fallback = location_state.borrow().fallback_dark();
+                if !theme_mode.auto_switch || fallback.is_none() || held_until.is_some() {
+                    continue;
+                }
+
+                let Some(is_dark) = auto_is_dark(&mut sunrise_sunset, fallback) else {
+                    continue;
+                };
+
+                if is_dark == theme_mode.is_dark {
+                    continue;
+                }
+
+                if defer_switch(&notice_config, &theme_mode, is_dark) {
+                    deferred = true;
+                    continue;
+                }
+
+                switch_theme_mode(&helper, &mut theme_mode, &tk, is_dark);
+            }
             changes = theme_mode_rx.recv() => {
 
                 let Some(changes) = changes else {
@@ -247,9 +349,13 @@ pub async fn watch_theme(
                             eprintln!("Error updating the theme mode {err:?}");
                         }
 
+                        if theme_mode.auto_switch != auto_switch_prev {
+                            held_until = None;
+                        }
+
                         // need to set the theme right away
                         if theme_mode.auto_switch && !auto_switch_prev {
-                            let Some(is_dark) = sunrise_sunset.as_ref().and_then(|s| s.is_dark().ok()) else {
+                            let Some(is_dark) = auto_is_dark(&mut sunrise_sunset, fallback) else {
                                 continue;
                             };
 
@@ -257,13 +363,7 @@ pub async fn watch_theme(
                                 eprintln!("Failed to update theme mode {err:?}");
                             }
                         }
-                        if tk.apply_theme_global {
-                            if let Err(err) = Theme::apply_gtk(theme_mode.is_dark) {
-                                eprintln!("Failed to apply the theme to gtk. {err:?}");
-                            }
-
-                            set_gnome_desktop_interface(theme_mode.is_dark);
-                        }
+                        apply_theme_mode(&tk, theme_mode.is_dark);
                     },
                     ThemeMsg::Tk(changes) => {
                         let (errs, changes) = tk.update_keys(&tk_helper, &[changes]);
@@ -274,6 +374,7 @@ pub async fn watch_theme(
 
                         if changes.contains(&"icon_theme") {
                             set_gnome_icon_theme(tk.icon_theme.clone());
+                            set_kde_icon_theme(&tk.icon_theme);
                         }
 
                         if changes.contains(&"show_maximize") || changes.contains(&"show_minimize") {
@@ -295,7 +396,9 @@ pub async fn watch_theme(
                                     t
                                 }
                             };
-                            _ = dark.write_gtk4();
+                            if let Err(err) = crate::gtk::write(&dark) {
+                                eprintln!("Failed to write the dark theme for gtk. {err:?}");
+                            }
                             let light = match Theme::get_entry(&light_helper) {
                                 Ok(t) => t,
                                 Err((errs, t)) => {
@@ -305,23 +408,24 @@ pub async fn watch_theme(
                                     t
                                 }
                             };
-                            _ = light.write_gtk4();
+                            if let Err(err) = crate::gtk::write(&light) {
+                                eprintln!("Failed to write the light theme for gtk. {err:?}");
+                            }
                             let _ = std::process::Command::new("flatpak")
                                 .arg("override")
                                 .arg("--user")
                                 .arg("--filesystem=xdg-config/gtk-4.0:ro")
                                 .spawn();
-                            if let Err(err) = Theme::apply_gtk(theme_mode.is_dark) {
-                                eprintln!("Failed to apply the theme to gtk. {err:?}");
-                            }
-
-                            set_gnome_desktop_interface(theme_mode.is_dark);
+                            apply_theme_mode(&tk, theme_mode.is_dark);
                         } else {
-                            if let Err(err) = Theme::reset_gtk() {
+                            if let Err(err) = crate::gtk::reset() {
                                 eprintln!("Failed to reset the application of the theme to gtk. {err:?}");
                             }
                         }
                     },
+                    ThemeMsg::SwitchNotice(key) => {
+                        crate::config::update(&mut notice_config, &notice_helper, &key);
+                    }
                     ThemeMsg::Theme(is_dark) => {
                         let t = match Theme::get_entry(if is_dark {
                                 &dark_helper
@@ -337,9 +441,14 @@ pub async fn watch_theme(
                                 },
                             };
                             if tk.apply_theme_global {
-                                if let Err(err) = t.write_gtk4() {
+                                if let Err(err) = crate::gtk::write(&t) {
                                     eprintln!("Failed to write gtk4 css. {err:?}");
                                 }
+                                if is_dark == theme_mode.is_dark {
+                                    if let Err(err) = crate::gtk::apply(is_dark) {
+                                        eprintln!("Failed to apply the theme to gtk. {err:?}");
+                                    }
+                                }
 
                                 set_gnome_desktop_interface(theme_mode.is_dark);
                             }
@@ -352,46 +461,102 @@ pub async fn watch_theme(
                 if !theme_mode.auto_switch {
                     continue;
                 }
+
+                // The timer also ends early when the clock is set.
+                if held_until.is_some_and(|until| Local::now() < until) {
+                    continue;
+                }
+                held_until = None;
+
                 // update the theme mode
-                let Some(is_dark) = sunrise_sunset.as_ref().and_then(|s| s.is_dark().ok()) else {
+                let Some(is_dark) = auto_is_dark(&mut sunrise_sunset, fallback) else {
                     continue;
                 };
 
-                if let Err(err) = theme_mode.set_is_dark(&helper, is_dark) {
-                    eprintln!("Failed to update theme mode {err:?}");
+                if defer_switch(&notice_config, &theme_mode, is_dark) {
+                    deferred = true;
+                    continue;
                 }
-                if tk.apply_theme_global {
-                    if let Err(err) = Theme::apply_gtk(theme_mode.is_dark) {
-                        eprintln!("Failed to apply the theme to gtk. {err:?}");
-                    }
 
-                    set_gnome_desktop_interface(theme_mode.is_dark);
+                switch_theme_mode(&helper, &mut theme_mode, &tk, is_dark);
+            }
+            res = fullscreen_changes.changed(), if deferred => {
+                if res.is_ok() && crate::dnd::fullscreen() {
+                    continue;
+                }
+                deferred = false;
+
+                // The pending switch is applied once fullscreen ends, unless it no longer is.
+                if !theme_mode.auto_switch || held_until.is_some() {
+                    continue;
+                }
+
+                let Some(is_dark) = auto_is_dark(&mut sunrise_sunset, fallback) else {
+                    continue;
+                };
+
+                if is_dark == theme_mode.is_dark {
+                    continue;
+                }
+
+                switch_theme_mode(&helper, &mut theme_mode, &tk, is_dark);
+            }
+            _ = notice => {
+                let Some(switch) = switch else {
+                    continue;
+                };
+
+                if notice_at.is_some_and(|at| Local::now() < at) {
+                    continue;
+                }
+
+                noticed = Some(switch);
+                notify_switch(&notice_config, !theme_mode.is_dark, switch, notice_tx.clone());
+            }
+            action = notice_rx.recv() => {
+                let Some((action, noticed_switch)) = action else {
+                    continue;
+                };
+
+                // Actions of the notices of past switches do nothing.
+                if switch != Some(noticed_switch) {
+                    continue;
+                }
+
+                match action {
+                    NoticeAction::SwitchNow(is_dark) => {
+                        held_until = Some(noticed_switch);
+                        switch_theme_mode(&helper, &mut theme_mode, &tk, is_dark);
+                    }
+                    NoticeAction::Snooze => {
+                        let snooze = notice_config.snooze_minutes as i64;
+                        held_until = Some(noticed_switch + chrono::Duration::minutes(snooze));
+                    }
                 }
             }
             location_update = location_update => {
                 // set the next timer
                 // update the theme if necessary
-                let Some(location_update) = location_update else {
-                    bail!("No location in the update");
+                let (fix, from_geoclue) = location_update?;
+                // The last location is kept while the source has no fix.
+                let Some(Fix { location, accuracy }) = fix else {
+                    continue;
                 };
-                let args = location_update.args()?;
-                let new = LocationProxy::builder(&conn)
-                    .path(args.new())?
-                    .build()
-                    .await?;
-                let accuracy = new.accuracy().await?;
 
                 // XXX sometimes location updates seem to be extremely inaccurate
                 // Probably they are updates with the approximate location of the country?
-                if let Some(s) = sunrise_sunset.as_ref() {
-                    if s.accuracy * 10.0 < accuracy {
+                if from_geoclue {
+                    if geoclue_accuracy.is_some_and(|previous| previous * 10.0 < accuracy) {
                         continue;
                     }
+                    geoclue_accuracy = Some(accuracy);
+                } else {
+                    geoclue_accuracy = None;
                 }
 
-                let latitude = new.latitude().await?;
-                let longitude = new.longitude().await?;
-                match SunriseSunset::new(latitude, longitude, None, accuracy) {
+                let Location { latitude, longitude } = location;
+                location_tx.send_replace(Some(location));
+                match SunriseSunset::new(latitude, longitude, accuracy) {
                     Ok(s) => {
                         sunrise_sunset = Some(s);
                     },
@@ -402,94 +567,259 @@ pub async fn watch_theme(
                     },
                 };
 
-                if !theme_mode.auto_switch {
+                if !theme_mode.auto_switch || held_until.is_some() {
                     continue;
                 }
 
-                let Some(is_dark) = sunrise_sunset.as_ref().unwrap().is_dark().ok() else {
+                let Some(is_dark) = sunrise_sunset.as_mut().unwrap().is_dark().ok() else {
                     continue;
                 };
 
-                if let Err(err) = theme_mode.set_is_dark(&helper, is_dark) {
-                    eprintln!("Failed to update theme mode {err:?}");
+                if defer_switch(&notice_config, &theme_mode, is_dark) {
+                    deferred = true;
+                    continue;
                 }
-                if tk.apply_theme_global {
-                    if let Err(err) = Theme::apply_gtk(theme_mode.is_dark) {
-                        eprintln!("Failed to apply the theme to gtk. {err:?}");
-                    }
 
-                    set_gnome_desktop_interface(theme_mode.is_dark);
-                }
+                switch_theme_mode(&helper, &mut theme_mode, &tk, is_dark);
             }
 
         }
     }
 }
 
+/// The location at the path of a `LocationUpdated` signal of the GeoClue client.
+async fn geoclue_fix(
+    conn: &zbus::Connection,
+    path: zbus::zvariant::ObjectPath<'_>,
+) -> anyhow::Result<Fix> {
+    let new = LocationProxy::builder(conn).path(path)?.build().await?;
+
+    let location = Location {
+        latitude: new.latitude().await?,
+        longitude: new.longitude().await?,
+    };
+    Ok(Fix {
+        location,
+        accuracy: new.accuracy().await?,
+    })
+}
+
+/// Whether the automatic switch to `is_dark` waits until no window is fullscreen.
+fn defer_switch(config: &SwitchNoticeConfig, theme_mode: &ThemeMode, is_dark: bool) -> bool {
+    config.defer_while_fullscreen && is_dark != theme_mode.is_dark && crate::dnd::fullscreen()
+}
+
+/// Whether the automatic theme mode is dark: from sunset until sunrise, or during the fallback
+/// schedule while location services are off.
+fn auto_is_dark(
+    sunrise_sunset: &mut Option<SunriseSunset>,
+    fallback: Option<Schedule>,
+) -> Option<bool> {
+    match fallback {
+        Some(schedule) => Some(schedule.is_active(Local::now(), None::<Location>)),
+        None => sunrise_sunset.as_mut().and_then(|s| s.is_dark().ok()),
+    }
+}
+
+/// Whether the dark theme is in use, and the theme in use.
+pub fn current() -> (bool, Theme) {
+    let is_dark = ThemeMode::config()
+        .ok()
+        .map(|helper| match ThemeMode::get_entry(&helper) {
+            Ok(mode) => mode.is_dark,
+            Err((_, mode)) => mode.is_dark,
+        })
+        .unwrap_or(true);
+
+    let helper = if is_dark {
+        CosmicTheme::dark_config()
+    } else {
+        CosmicTheme::light_config()
+    };
+
+    let theme = helper
+        .ok()
+        .map(|helper| match Theme::get_entry(&helper) {
+            Ok(theme) => theme,
+            Err((_, theme)) => theme,
+        })
+        .unwrap_or_else(|| {
+            if is_dark {
+                Theme::dark_default()
+            } else {
+                Theme::light_default()
+            }
+        });
+
+    (is_dark, theme)
+}
+
+/// Restore the default dark and light themes, switch to the dark theme without the automatic
+/// theme mode, and stop applying the theme to GTK, removing it from `settings.ini` and GNOME's
+/// settings.
+pub fn reset() -> anyhow::Result<()> {
+    CosmicTk::config()?.set("apply_theme_global", false)?;
+    if let Err(err) = crate::gtk::reset() {
+        bail!("failed to reset the application of the theme to gtk. {err:?}");
+    }
+    remove_gtk_settings()?;
+
+    // Blocking, as the reset also runs from the command line without a runtime.
+    let status = std::process::Command::new("gsettings")
+        .args(["reset", "org.gnome.desktop.interface", "gtk-theme"])
+        .status()?;
+    if !status.success() {
+        bail!("failed to reset the GTK theme of GNOME: gsettings {status}");
+    }
+
+    ThemeBuilder::dark().write_entry(&ThemeBuilder::dark_config()?)?;
+    ThemeBuilder::light().write_entry(&ThemeBuilder::light_config()?)?;
+    Theme::dark_default().write_entry(&CosmicTheme::dark_config()?)?;
+    Theme::light_default().write_entry(&CosmicTheme::light_config()?)?;
+
+    let mode = ThemeMode {
+        is_dark: true,
+        auto_switch: false,
+    };
+    mode.write_entry(&ThemeMode::config()?)?;
+    Ok(())
+}
+
 fn set_gnome_button_layout(show_maximize: bool, show_minimize: bool) {
-    tokio::spawn(async move {
-        let layout = match (show_maximize, show_minimize) {
-            (true, true) => ":minimize,maximize,close",
-            (true, false) => ":maximize,close",
-            (false, true) => ":minimize,close",
-            (false, false) => ":close",
-        };
+    let layout = match (show_maximize, show_minimize) {
+        (true, true) => ":minimize,maximize,close",
+        (true, false) => ":maximize,close",
+        (false, true) => ":minimize,close",
+        (false, false) => ":close",
+    };
 
-        let _res = tokio::process::Command::new("gsettings")
-            .args(&[
-                "set",
-                "org.gnome.desktop.wm.preferences",
-                "button-layout",
-                layout,
-            ])
-            .status()
-            .await;
-    });
+    crate::gsettings::set(vec![(
+        "org.gnome.desktop.wm.preferences",
+        "button-layout",
+        layout.to_owned(),
+    )]);
+}
+
+/// The adw-gtk3 theme matching libadwaita for GTK 3 applications, and its path, if installed.
+pub fn adw_gtk3_theme(is_dark: bool) -> Option<(&'static str, &'static Path)> {
+    let (theme, path) = if is_dark {
+        ("adw-gtk3-dark", "/usr/share/themes/adw-gtk3-dark")
+    } else {
+        ("adw-gtk3", "/usr/share/themes/adw-gtk3")
+    };
+
+    let path = Path::new(path);
+    path.exists().then_some((theme, path))
+}
+
+/// Apply the theme mode to gtk and the desktop interface settings, if the theme is applied
+/// globally.
+fn apply_theme_mode(tk: &CosmicTk, is_dark: bool) {
+    if !tk.apply_theme_global {
+        return;
+    }
+
+    if let Err(err) = crate::gtk::apply(is_dark) {
+        eprintln!("Failed to apply the theme to gtk. {err:?}");
+    }
+    set_gnome_desktop_interface(is_dark);
+}
+
+/// Switch to the dark or light theme, and apply it.
+fn switch_theme_mode(
+    helper: &cosmic_config::Config,
+    theme_mode: &mut ThemeMode,
+    tk: &CosmicTk,
+    is_dark: bool,
+) {
+    if let Err(err) = theme_mode.set_is_dark(helper, is_dark) {
+        eprintln!("Failed to update theme mode {err:?}");
+    }
+    apply_theme_mode(tk, theme_mode.is_dark);
 }
 
 fn set_gnome_desktop_interface(is_dark: bool) {
-    let (color_scheme, adw_theme, adw_theme_path) = if is_dark {
-        (
-            "prefer-dark",
-            "adw-gtk3-dark",
-            "/usr/share/themes/adw-gtk3-dark",
-        )
+    let color_scheme = if is_dark {
+        "prefer-dark"
     } else {
-        ("prefer-light", "adw-gtk3", "/usr/share/themes/adw-gtk3")
+        "prefer-light"
     };
 
-    tokio::spawn(async {
-        let _res = tokio::process::Command::new("gsettings")
-            .args(&[
-                "set",
-                "org.gnome.desktop.interface",
-                "color-scheme",
-                color_scheme,
-            ])
-            .status()
-            .await;
-    });
+    // The legacy hints and settings.ini name the same theme as GNOME's settings.
+    crate::legacy::theme_mode_changed(is_dark);
+    set_gtk_settings(is_dark);
+
+    let mut settings = vec![(
+        "org.gnome.desktop.interface",
+        "color-scheme",
+        color_scheme.to_owned(),
+    )];
+    if let Some((adw_theme, _)) = adw_gtk3_theme(is_dark) {
+        settings.push((
+            "org.gnome.desktop.interface",
+            "gtk-theme",
+            adw_theme.to_owned(),
+        ));
+    }
+    crate::gsettings::set(settings);
+}
 
-    if Path::new(adw_theme_path).exists() {
-        tokio::spawn(async {
-            let _res = tokio::process::Command::new("gsettings")
-                .args(&["set", "org.gnome.desktop.interface", "gtk-theme", adw_theme])
-                .status()
-                .await;
-        });
+/// Set the theme mode in GTK's `settings.ini`, which some applications read instead of GNOME's
+/// settings. Other keys in the files are kept.
+fn set_gtk_settings(is_dark: bool) {
+    let Some(config_dir) = dirs::config_dir() else {
+        return;
+    };
+
+    let theme = adw_gtk3_theme(is_dark);
+    for gtk in ["gtk-3.0", "gtk-4.0"] {
+        let path = config_dir.join(gtk).join("settings.ini");
+        let prefer_dark = is_dark.to_string();
+        let mut values = vec![(
+            "Settings",
+            "gtk-application-prefer-dark-theme",
+            prefer_dark.as_str(),
+        )];
+        if let Some((name, theme_path)) = theme {
+            if theme_path.join(gtk).is_dir() {
+                values.push(("Settings", "gtk-theme-name", name));
+            }
+        }
+
+        if let Err(err) = crate::files::set_ini_values(&path, &values) {
+            eprintln!("Failed to write {}: {err}", path.display());
+        }
+    }
+}
+
+/// Remove the entries of `set_gtk_settings` from GTK's `settings.ini`.
+fn remove_gtk_settings() -> anyhow::Result<()> {
+    let config_dir = dirs::config_dir().context("no config directory")?;
+    for gtk in ["gtk-3.0", "gtk-4.0"] {
+        let path = config_dir.join(gtk).join("settings.ini");
+        crate::files::remove_ini_values(
+            &path,
+            &[
+                ("Settings", "gtk-application-prefer-dark-theme"),
+                ("Settings", "gtk-theme-name"),
+            ],
+        )
+        .with_context(|| format!("failed to clean up {}", path.display()))?;
     }
+    Ok(())
 }
 
 fn set_gnome_icon_theme(theme: String) {
-    tokio::spawn(async move {
-        let _res = tokio::process::Command::new("gsettings")
-            .args(&[
-                "set",
-                "org.gnome.desktop.interface",
-                "icon-theme",
-                theme.as_str(),
-            ])
-            .status()
-            .await;
-    });
+    crate::gsettings::set(vec![("org.gnome.desktop.interface", "icon-theme", theme)]);
+}
+
+/// KDE applications use Breeze icons unless `kdeglobals` names a theme.
+fn set_kde_icon_theme(theme: &str) {
+    let Some(path) = dirs::config_dir().map(|dir| dir.join("kdeglobals")) else {
+        return;
+    };
+
+    if let Err(err) = crate::files::set_ini_value(&path, "Icons", "Theme", theme) {
+        eprintln!("Failed to write {}: {err}", path.display());
+    }
 }
@@ -0,0 +1,380 @@
+use std::{fs, io, path::PathBuf, time::Duration};
+
+use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use tokio::{io::unix::AsyncFd, sync::mpsc::Receiver};
+
+use crate::power_profiles::PowerProfilesProxy;
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Thermal";
+
+/// While the system is hot, the temperatures are read this often, until it has cooled down.
+const HOT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Degrees a sensor must cool below a threshold before its state is lowered again.
+const HYSTERESIS: i64 = 3000;
+
+#[derive(Clone, CosmicConfigEntry, Debug, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct ThermalConfig {
+    /// Watch the temperatures of the system.
+    pub enabled: bool,
+    /// Warn when a sensor is within this many degrees Celsius of its critical temperature.
+    pub warning_margin: u32,
+    /// Take emergency action when a sensor is within this many degrees Celsius of its critical temperature.
+    pub critical_margin: u32,
+    /// Switch to the power saver profile until the system has cooled down.
+    pub power_saver: bool,
+}
+
+impl Default for ThermalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            warning_margin: 15,
+            critical_margin: 5,
+            power_saver: true,
+        }
+    }
+}
+
+impl crate::demand::Feature for ThermalConfig {
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum ThermalState {
+    Normal,
+    Warning,
+    Critical,
+}
+
+impl ThermalState {
+    fn as_str(self) -> &'static str {
+        match self {
+            ThermalState::Normal => "normal",
+            ThermalState::Warning => "warning",
+            ThermalState::Critical => "critical",
+        }
+    }
+}
+
+/// A temperature input with a critical trip point, in millidegrees Celsius.
+struct Sensor {
+    name: String,
+    input: PathBuf,
+    critical: i64,
+}
+
+impl Sensor {
+    fn state(&self, temp: i64, config: &ThermalConfig, prev: ThermalState) -> ThermalState {
+        let threshold = |margin: u32, state: ThermalState| {
+            let threshold = self.critical - i64::from(margin) * 1000;
+            if prev >= state {
+                threshold - HYSTERESIS
+            } else {
+                threshold
+            }
+        };
+
+        if temp >= threshold(config.critical_margin, ThermalState::Critical) {
+            ThermalState::Critical
+        } else if temp >= threshold(config.warning_margin, ThermalState::Warning) {
+            ThermalState::Warning
+        } else {
+            ThermalState::Normal
+        }
+    }
+}
+
+fn read_millidegrees(path: &std::path::Path) -> Option<i64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Thermal zones and hwmon inputs which advertise a critical temperature.
+fn sensors() -> Vec<Sensor> {
+    let mut sensors = Vec::new();
+
+    if let Ok(entries) = fs::read_dir("/sys/class/thermal") {
+        for zone in entries.filter_map(Result::ok).map(|e| e.path()) {
+            if !zone
+                .file_name()
+                .is_some_and(|f| f.to_string_lossy().starts_with("thermal_zone"))
+            {
+                continue;
+            }
+
+            let name = fs::read_to_string(zone.join("type"))
+                .map(|t| t.trim().to_owned())
+                .unwrap_or_default();
+
+            let critical = (0..)
+                .map_while(|n| {
+                    let kind =
+                        fs::read_to_string(zone.join(format!("trip_point_{n}_type"))).ok()?;
+                    Some((n, kind))
+                })
+                .find(|(_, kind)| kind.trim() == "critical")
+                .and_then(|(n, _)| read_millidegrees(&zone.join(format!("trip_point_{n}_temp"))));
+
+            if let Some(critical) = critical.filter(|c| *c > 0) {
+                sensors.push(Sensor {
+                    name,
+                    input: zone.join("temp"),
+                    critical,
+                });
+            }
+        }
+    }
+
+    if let Ok(entries) = fs::read_dir("/sys/class/hwmon") {
+        for hwmon in entries.filter_map(Result::ok).map(|e| e.path()) {
+            let chip = fs::read_to_string(hwmon.join("name"))
+                .map(|t| t.trim().to_owned())
+                .unwrap_or_default();
+
+            let Ok(files) = fs::read_dir(&hwmon) else {
+                continue;
+            };
+
+            for file in files.filter_map(Result::ok) {
+                let file_name = file.file_name();
+                let Some(prefix) = file_name
+                    .to_str()
+                    .and_then(|f| f.strip_suffix("_crit"))
+                    .filter(|f| f.starts_with("temp"))
+                else {
+                    continue;
+                };
+
+                let Some(critical) = read_millidegrees(&file.path()).filter(|c| *c > 0) else {
+                    continue;
+                };
+
+                let name = fs::read_to_string(hwmon.join(format!("{prefix}_label")))
+                    .map(|label| format!("{chip} {}", label.trim()))
+                    .unwrap_or_else(|_| chip.clone());
+
+                sensors.push(Sensor {
+                    name,
+                    input: hwmon.join(format!("{prefix}_input")),
+                    critical,
+                });
+            }
+        }
+    }
+
+    sensors
+}
+
+/// Read the temperatures, and warn and run the hooks when the state of the hottest sensor changes.
+async fn check(
+    sensors: &[Sensor],
+    config: &ThermalConfig,
+    power_profiles: Option<&PowerProfilesProxy<'_>>,
+    state: &mut ThermalState,
+    previous_profile: &mut Option<String>,
+) {
+    let mut hottest: Option<(&Sensor, i64, ThermalState)> = None;
+    for sensor in sensors {
+        let Some(temp) = read_millidegrees(&sensor.input) else {
+            continue;
+        };
+
+        let sensor_state = sensor.state(temp, config, *state);
+        if hottest.map_or(true, |(_, _, s)| sensor_state > s) {
+            hottest = Some((sensor, temp, sensor_state));
+        }
+    }
+
+    let Some((sensor, temp, new_state)) = hottest else {
+        return;
+    };
+
+    if new_state == *state {
+        return;
+    }
+
+    let prev = *state;
+    *state = new_state;
+    let degrees = temp / 1000;
+
+    match *state {
+        ThermalState::Critical => {
+            let _res = Notification::new()
+                .appname("")
+                .summary("System Temperature Critical")
+                .body(&format!(
+                    "{} has reached {degrees}°C. The system may shut down to prevent damage.",
+                    sensor.name
+                ))
+                .icon("dialog-warning-symbolic")
+                .urgency(notify_rust::Urgency::Critical)
+                .show_async()
+                .await;
+
+            if let Some(proxy) = power_profiles.filter(|_| config.power_saver) {
+                match proxy.active_profile().await {
+                    Ok(profile) if profile != "power-saver" => {
+                        if let Err(err) = proxy.set_active_profile("power-saver").await {
+                            eprintln!("Failed to switch to the power saver profile {err:?}");
+                        } else {
+                            *previous_profile = Some(profile);
+                        }
+                    }
+                    Ok(_) => (),
+                    Err(err) => eprintln!("Failed to get the active power profile {err:?}"),
+                }
+            }
+        }
+
+        ThermalState::Warning if prev == ThermalState::Normal => {
+            let _res = Notification::new()
+                .appname("")
+                .summary("System Is Running Hot")
+                .body(&format!("{} has reached {degrees}°C.", sensor.name))
+                .icon("dialog-warning-symbolic")
+                .urgency(notify_rust::Urgency::Normal)
+                .timeout(Duration::from_secs(10))
+                .show_async()
+                .await;
+        }
+
+        _ => (),
+    }
+
+    if *state < ThermalState::Critical {
+        if let (Some(proxy), Some(profile)) = (power_profiles, previous_profile.take()) {
+            // Leave the profile alone if the user changed it in the meantime.
+            if proxy
+                .active_profile()
+                .await
+                .is_ok_and(|p| p == "power-saver")
+            {
+                if let Err(err) = proxy.set_active_profile(&profile).await {
+                    eprintln!("Failed to restore the {profile} power profile {err:?}");
+                }
+            }
+        }
+    }
+
+    crate::hooks::run(
+        "thermal",
+        vec![
+            ("COSMIC_THERMAL_STATE", state.as_str().to_owned()),
+            ("COSMIC_THERMAL_SENSOR", sensor.name.clone()),
+            ("COSMIC_THERMAL_TEMP", degrees.to_string()),
+            (
+                "COSMIC_THERMAL_CRITICAL",
+                (sensor.critical / 1000).to_string(),
+            ),
+        ],
+    );
+}
+
+fn monitor_socket() -> io::Result<AsyncFd<udev::MonitorSocket>> {
+    let socket = udev::MonitorBuilder::new()?
+        .match_subsystem("thermal")?
+        .match_subsystem("hwmon")?
+        .listen()?;
+    AsyncFd::new(socket)
+}
+
+/// Watch system temperatures, warning before the kernel throttles or shuts down the system.
+///
+/// The temperatures are read when the kernel reports a change of a thermal zone or a sensor,
+/// such as when it crosses a trip point or raises an alarm, and then while the system is hot,
+/// until it has cooled down. Nothing runs while the monitor is disabled.
+///
+/// Scripts in the `thermal.d` hook directory are run on every state change.
+pub async fn monitor(mut rx: Receiver<String>) {
+    let (helper, mut config) = match crate::config::load_async::<ThermalConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load thermal config {err:?}");
+            return;
+        }
+    };
+
+    let sensors = sensors();
+    if sensors.is_empty() {
+        return;
+    }
+
+    let power_profiles = match crate::bus::system().await {
+        Ok(conn) => PowerProfilesProxy::new(&conn).await.ok(),
+        Err(_) => None,
+    };
+
+    let mut state = ThermalState::Normal;
+    // The profile to restore once the system has cooled down.
+    let mut previous_profile: Option<String> = None;
+
+    'socket: loop {
+        let socket = if config.enabled {
+            match monitor_socket() {
+                Ok(socket) => Some(socket),
+                Err(err) => {
+                    eprintln!("Error creating udev thermal monitor: {err}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // The temperatures are read once when the monitor starts.
+        let mut changed = socket.is_some();
+        loop {
+            if changed {
+                check(
+                    &sensors,
+                    &config,
+                    power_profiles.as_ref(),
+                    &mut state,
+                    &mut previous_profile,
+                )
+                .await;
+            }
+
+            let hot = socket.is_some() && state > ThermalState::Normal;
+            let recheck = async move {
+                if hot {
+                    tokio::time::sleep(HOT_INTERVAL).await;
+                } else {
+                    std::future::pending().await
+                }
+            };
+
+            tokio::select! {
+                key = rx.recv() => {
+                    let Some(key) = key else {
+                        break 'socket;
+                    };
+
+                    let changes = crate::config::update(&mut config, &helper, &key);
+                    if changes.contains(&"enabled") {
+                        continue 'socket;
+                    }
+                    changed = socket.is_some() && !changes.is_empty();
+                }
+
+                guard = async { socket.as_ref()?.readable().await.ok() }, if socket.is_some() => {
+                    let Some(mut guard) = guard else {
+                        eprintln!("Error reading udev thermal monitor");
+                        tokio::time::sleep(HOT_INTERVAL).await;
+                        continue 'socket;
+                    };
+
+                    changed = guard.get_inner().iter().count() > 0;
+                    guard.clear_ready();
+                }
+
+                () = recheck => changed = true,
+            }
+        }
+    }
+}
@@ -0,0 +1,17 @@
+#[zbus::dbus_proxy(
+    default_service = "org.freedesktop.timedate1",
+    interface = "org.freedesktop.timedate1",
+    default_path = "/org/freedesktop/timedate1"
+)]
+trait Timedate {
+    fn set_timezone(&self, timezone: &str, interactive: bool) -> zbus::Result<()>;
+
+    #[dbus_proxy(name = "SetNTP")]
+    fn set_ntp(&self, use_ntp: bool, interactive: bool) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn timezone(&self) -> zbus::Result<String>;
+
+    #[dbus_proxy(property, name = "NTP")]
+    fn ntp(&self) -> zbus::Result<bool>;
+}
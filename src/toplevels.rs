@@ -0,0 +1,400 @@
+use std::sync::Mutex;
+
+use wayland_client::{
+    event_created_child,
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{
+        wl_output::{self, WlOutput},
+        wl_registry::{self, WlRegistry},
+    },
+    Connection, Dispatch, Proxy, QueueHandle,
+};
+use wayland_protocols::xdg::xdg_output::zv1::client::{
+    zxdg_output_manager_v1::{self, ZxdgOutputManagerV1},
+    zxdg_output_v1::{self, ZxdgOutputV1},
+};
+
+use self::protocol::{
+    zcosmic_toplevel_handle_v1::{self, ZcosmicToplevelHandleV1},
+    zcosmic_toplevel_info_v1::{self, ZcosmicToplevelInfoV1},
+};
+
+/// The toplevel info protocol of cosmic-comp.
+#[allow(
+    dead_code,
+    non_upper_case_globals,
+    unused_imports,
+    clippy::single_component_path_imports
+)]
+mod protocol {
+    use wayland_client;
+    use wayland_client::protocol::*;
+
+    pub mod __interfaces {
+        use wayland_client::protocol::__interfaces::*;
+        wayland_scanner::generate_interfaces!("protocols/cosmic-toplevel-info-unstable-v1.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_client_code!("protocols/cosmic-toplevel-info-unstable-v1.xml");
+}
+
+/// A rectangle in the logical coordinates of the compositor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    /// The smallest rectangle containing both.
+    fn union(self, other: Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+}
+
+/// The active window, and the outputs it is shown among.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ActiveWindow {
+    pub window: Rect,
+    /// The bounds of every output, which a screenshot of the whole screen covers.
+    pub screen: Rect,
+}
+
+static ACTIVE_WINDOW: Mutex<Option<ActiveWindow>> = Mutex::new(None);
+
+/// The active window, if the compositor sent where it is.
+pub fn active_window() -> Option<ActiveWindow> {
+    ACTIVE_WINDOW.lock().ok().and_then(|window| *window)
+}
+
+fn set_active_window(window: Option<ActiveWindow>) {
+    if let Ok(mut active) = ACTIVE_WINDOW.lock() {
+        *active = window;
+    }
+}
+
+struct Output {
+    /// The name of the global.
+    name: u32,
+    output: WlOutput,
+    xdg_output: Option<ZxdgOutputV1>,
+    rect: Rect,
+}
+
+struct Toplevel {
+    handle: ZcosmicToplevelHandleV1,
+    /// The states since the last `done`, which are applied together.
+    pending: Option<Vec<zcosmic_toplevel_handle_v1::State>>,
+    activated: bool,
+    fullscreen: bool,
+    /// The output the toplevel is on, and where it is on that output, with version 2 of the
+    /// protocol.
+    geometry: Option<(WlOutput, Rect)>,
+}
+
+#[derive(Default)]
+struct State {
+    toplevels: Vec<Toplevel>,
+    outputs: Vec<Output>,
+    xdg_output_manager: Option<ZxdgOutputManagerV1>,
+}
+
+impl State {
+    /// Report whether the active window is fullscreen, and where it is.
+    fn update(&self) {
+        let fullscreen = self
+            .toplevels
+            .iter()
+            .any(|toplevel| toplevel.activated && toplevel.fullscreen);
+        crate::dnd::set_fullscreen(fullscreen);
+
+        set_active_window(self.active_window());
+    }
+
+    fn active_window(&self) -> Option<ActiveWindow> {
+        let (output, geometry) = self
+            .toplevels
+            .iter()
+            .find(|toplevel| toplevel.activated)?
+            .geometry
+            .as_ref()?;
+        let output = self.outputs.iter().find(|o| o.output == *output)?.rect;
+        let screen = self
+            .outputs
+            .iter()
+            .map(|output| output.rect)
+            .reduce(Rect::union)?;
+
+        let window = Rect {
+            x: output.x + geometry.x,
+            y: output.y + geometry.y,
+            ..*geometry
+        };
+        Some(ActiveWindow { window, screen })
+    }
+
+    fn add_output(
+        &mut self,
+        registry: &WlRegistry,
+        name: u32,
+        version: u32,
+        qh: &QueueHandle<Self>,
+    ) {
+        let output: WlOutput = registry.bind(name, version.min(4), qh, ());
+        let xdg_output = self
+            .xdg_output_manager
+            .as_ref()
+            .map(|manager| manager.get_xdg_output(&output, qh, ()));
+        self.outputs.push(Output {
+            name,
+            output,
+            xdg_output,
+            rect: Rect::default(),
+        });
+    }
+}
+
+/// The states of the array of a state event, skipping those newer than the protocol.
+fn states(array: &[u8]) -> Vec<zcosmic_toplevel_handle_v1::State> {
+    array
+        .chunks_exact(4)
+        .filter_map(|state| {
+            let state = u32::from_ne_bytes([state[0], state[1], state[2], state[3]]);
+            zcosmic_toplevel_handle_v1::State::try_from(state).ok()
+        })
+        .collect()
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        state: &mut Self,
+        registry: &WlRegistry,
+        event: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } if interface == WlOutput::interface().name => {
+                state.add_output(registry, name, version, qh);
+            }
+            wl_registry::Event::GlobalRemove { name } => {
+                let Some(index) = state.outputs.iter().position(|o| o.name == name) else {
+                    return;
+                };
+                let output = state.outputs.remove(index);
+                if let Some(xdg_output) = output.xdg_output {
+                    xdg_output.destroy();
+                }
+                if output.output.version() >= 3 {
+                    output.output.release();
+                }
+                state.update();
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<WlOutput, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlOutput,
+        _: wl_output::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZxdgOutputManagerV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ZxdgOutputManagerV1,
+        _: zxdg_output_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZxdgOutputV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        xdg_output: &ZxdgOutputV1,
+        event: zxdg_output_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(output) = state
+            .outputs
+            .iter_mut()
+            .find(|output| output.xdg_output.as_ref() == Some(xdg_output))
+        else {
+            return;
+        };
+
+        match event {
+            zxdg_output_v1::Event::LogicalPosition { x, y } => {
+                output.rect.x = x;
+                output.rect.y = y;
+            }
+            zxdg_output_v1::Event::LogicalSize { width, height } => {
+                output.rect.width = width;
+                output.rect.height = height;
+            }
+            _ => return,
+        }
+        state.update();
+    }
+}
+
+impl Dispatch<ZcosmicToplevelInfoV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZcosmicToplevelInfoV1,
+        event: zcosmic_toplevel_info_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zcosmic_toplevel_info_v1::Event::Toplevel { toplevel } = event {
+            state.toplevels.push(Toplevel {
+                handle: toplevel,
+                pending: None,
+                activated: false,
+                fullscreen: false,
+                geometry: None,
+            });
+        }
+    }
+
+    event_created_child!(State, ZcosmicToplevelInfoV1, [
+        zcosmic_toplevel_info_v1::EVT_TOPLEVEL_OPCODE => (ZcosmicToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZcosmicToplevelHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ZcosmicToplevelHandleV1,
+        event: zcosmic_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(index) = state
+            .toplevels
+            .iter()
+            .position(|toplevel| toplevel.handle == *handle)
+        else {
+            return;
+        };
+
+        match event {
+            zcosmic_toplevel_handle_v1::Event::State { state: array } => {
+                state.toplevels[index].pending = Some(states(&array));
+            }
+            zcosmic_toplevel_handle_v1::Event::Geometry {
+                output,
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let rect = Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                };
+                state.toplevels[index].geometry = Some((output, rect));
+            }
+            zcosmic_toplevel_handle_v1::Event::OutputLeave { output } => {
+                let toplevel = &mut state.toplevels[index];
+                if toplevel
+                    .geometry
+                    .as_ref()
+                    .is_some_and(|(on, _)| *on == output)
+                {
+                    toplevel.geometry = None;
+                    state.update();
+                }
+            }
+            zcosmic_toplevel_handle_v1::Event::Done => {
+                let toplevel = &mut state.toplevels[index];
+                if let Some(states) = toplevel.pending.take() {
+                    toplevel.activated =
+                        states.contains(&zcosmic_toplevel_handle_v1::State::Activated);
+                    toplevel.fullscreen =
+                        states.contains(&zcosmic_toplevel_handle_v1::State::Fullscreen);
+                }
+                state.update();
+            }
+            zcosmic_toplevel_handle_v1::Event::Closed => {
+                state.toplevels.remove(index).handle.destroy();
+                state.update();
+            }
+            _ => (),
+        }
+    }
+}
+
+fn watch() -> anyhow::Result<()> {
+    let conn = Connection::connect_to_env()?;
+    let (globals, mut queue) = registry_queue_init::<State>(&conn)?;
+    let qh = queue.handle();
+
+    let _info: ZcosmicToplevelInfoV1 = globals.bind(&qh, 1..=2, ())?;
+
+    // Without the logical positions of the outputs, the active window is not known.
+    let mut state = State {
+        xdg_output_manager: globals.bind(&qh, 1..=3, ()).ok(),
+        ..State::default()
+    };
+    let registry = globals.registry();
+    for global in globals.contents().clone_list() {
+        if global.interface == WlOutput::interface().name {
+            state.add_output(registry, global.name, global.version, &qh);
+        }
+    }
+
+    loop {
+        queue.blocking_dispatch(&mut state)?;
+    }
+}
+
+/// Track whether the active window is fullscreen with the toplevel info of the compositor, on a
+/// thread of its own, for do not disturb, and where it is, for screenshots of it.
+pub fn run() {
+    let res = std::thread::Builder::new()
+        .name("toplevels".to_owned())
+        .spawn(|| {
+            if let Err(err) = watch() {
+                eprintln!("Failed to watch the toplevels of the compositor: {err}");
+            }
+            crate::dnd::set_fullscreen(false);
+            set_active_window(None);
+        });
+
+    if let Err(err) = res {
+        eprintln!("Failed to start the toplevels thread: {err}");
+    }
+}
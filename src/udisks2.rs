@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+
+use zbus::zvariant::{OwnedObjectPath, Value};
+
+#[zbus::dbus_proxy(
+    default_service = "org.freedesktop.UDisks2",
+    interface = "org.freedesktop.UDisks2.Block"
+)]
+trait Block {
+    #[dbus_proxy(property)]
+    fn drive(&self) -> zbus::Result<OwnedObjectPath>;
+
+    /// The device file, such as `/dev/sdb1`, as a NUL terminated byte string.
+    #[dbus_proxy(property)]
+    fn preferred_device(&self) -> zbus::Result<Vec<u8>>;
+
+    #[dbus_proxy(property)]
+    fn hint_auto(&self) -> zbus::Result<bool>;
+
+    #[dbus_proxy(property)]
+    fn hint_ignore(&self) -> zbus::Result<bool>;
+
+    #[dbus_proxy(property)]
+    fn hint_system(&self) -> zbus::Result<bool>;
+}
+
+#[zbus::dbus_proxy(
+    default_service = "org.freedesktop.UDisks2",
+    interface = "org.freedesktop.UDisks2.Filesystem"
+)]
+trait Filesystem {
+    fn mount(&self, options: HashMap<&str, Value<'_>>) -> zbus::Result<String>;
+}
+
+#[zbus::dbus_proxy(
+    default_service = "org.freedesktop.UDisks2",
+    interface = "org.freedesktop.UDisks2.Drive"
+)]
+trait Drive {
+    #[dbus_proxy(property)]
+    fn optical_blank(&self) -> zbus::Result<bool>;
+
+    #[dbus_proxy(property)]
+    fn optical_num_audio_tracks(&self) -> zbus::Result<u32>;
+}
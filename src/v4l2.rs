@@ -0,0 +1,176 @@
+use std::{
+    collections::BTreeSet,
+    ffi::CString,
+    fs::{self, File},
+    io::{self, Read},
+    os::{
+        fd::{AsRawFd, FromRawFd},
+        unix::ffi::OsStrExt,
+    },
+    path::Path,
+    sync::OnceLock,
+    time::Duration,
+};
+
+use tokio::sync::watch;
+
+/// Processes which open cameras for PipeWire, whose use shows in its graph instead.
+const PIPEWIRE_PROCESSES: &[&str] = &["pipewire", "wireplumber"];
+
+/// Applications open every camera briefly to list them, which is not using them, so the
+/// processes are looked up after opens have settled for this long.
+const SETTLE: Duration = Duration::from_millis(500);
+
+/// The size of the header of an inotify event, before its name.
+const EVENT_HEADER: usize = 16;
+
+static USERS: OnceLock<watch::Sender<BTreeSet<String>>> = OnceLock::new();
+
+fn users() -> &'static watch::Sender<BTreeSet<String>> {
+    USERS.get_or_init(|| {
+        let res = std::thread::Builder::new()
+            .name("v4l2".to_owned())
+            .spawn(|| {
+                if let Err(err) = watch() {
+                    eprintln!("Failed to watch the cameras for direct use: {err}");
+                }
+            });
+
+        if let Err(err) = res {
+            eprintln!("Failed to start the v4l2 thread: {err}");
+        }
+        watch::channel(BTreeSet::new()).0
+    })
+}
+
+/// The names of the processes which use a camera directly through V4L2, rather than through
+/// PipeWire, such as most browsers. Watching starts with the first subscriber.
+pub fn subscribe() -> watch::Receiver<BTreeSet<String>> {
+    users().subscribe()
+}
+
+fn is_camera(path: &Path) -> bool {
+    path.parent() == Some(Path::new("/dev"))
+        && path
+            .file_name()
+            .is_some_and(|name| name.as_bytes().starts_with(b"video"))
+}
+
+/// The names of the processes of the user with a camera open, other than those of PipeWire.
+fn camera_users() -> BTreeSet<String> {
+    let mut users = BTreeSet::new();
+    let Ok(processes) = fs::read_dir("/proc") else {
+        return users;
+    };
+
+    for process in processes.flatten() {
+        let dir = process.path();
+        // The file descriptors of processes of other users cannot be read.
+        let Ok(fds) = fs::read_dir(dir.join("fd")) else {
+            continue;
+        };
+
+        let uses_camera = fds
+            .flatten()
+            .any(|fd| fs::read_link(fd.path()).is_ok_and(|target| is_camera(&target)));
+        if !uses_camera {
+            continue;
+        }
+
+        if let Ok(name) = fs::read_to_string(dir.join("comm")) {
+            let name = name.trim_end();
+            if !PIPEWIRE_PROCESSES.contains(&name) {
+                users.insert(name.to_owned());
+            }
+        }
+    }
+    users
+}
+
+fn add_watch(inotify: &File, path: &Path, mask: u32) -> io::Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    // SAFETY: The descriptor is an inotify instance and the path is a C string.
+    let wd = unsafe { libc::inotify_add_watch(inotify.as_raw_fd(), path.as_ptr(), mask) };
+    if wd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Watch the opens and closes of every camera. Watching one again only replaces its mask.
+fn watch_cameras(inotify: &File) {
+    let Ok(devices) = fs::read_dir("/dev") else {
+        return;
+    };
+
+    for device in devices.flatten() {
+        let path = device.path();
+        if is_camera(&path) {
+            // Cameras are only readable once udev gave the user access, which is watched for.
+            _ = add_watch(inotify, &path, libc::IN_OPEN | libc::IN_CLOSE);
+        }
+    }
+}
+
+/// Whether a buffer of inotify events has one of a file of `/dev` being created or changing
+/// permissions, which may be a camera the user now has access to.
+fn has_new_device(events: &[u8]) -> bool {
+    let mut offset = 0;
+    while let Some(header) = events.get(offset..offset + EVENT_HEADER) {
+        let mask = u32::from_ne_bytes([header[4], header[5], header[6], header[7]]);
+        let len = u32::from_ne_bytes([header[12], header[13], header[14], header[15]]);
+        if mask & (libc::IN_CREATE | libc::IN_ATTRIB) != 0 {
+            return true;
+        }
+        offset += EVENT_HEADER + len as usize;
+    }
+    false
+}
+
+fn set(users: BTreeSet<String>) {
+    self::users().send_if_modified(|current| {
+        if *current == users {
+            return false;
+        }
+        *current = users;
+        true
+    });
+}
+
+/// Look up the processes using a camera whenever one is opened or closed.
+///
+/// This is an inotify instance of its own, as the file watcher of the daemon does not report
+/// opens.
+fn watch() -> io::Result<()> {
+    // SAFETY: No pointers are passed.
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: The descriptor was just created, and nothing else owns it.
+    let mut inotify = unsafe { File::from_raw_fd(fd) };
+
+    add_watch(
+        &inotify,
+        Path::new("/dev"),
+        libc::IN_CREATE | libc::IN_ATTRIB,
+    )?;
+    watch_cameras(&inotify);
+    set(camera_users());
+
+    let mut events = [0u8; 4096];
+    loop {
+        let len = match inotify.read(&mut events) {
+            Ok(len) => len,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+
+        if has_new_device(&events[..len]) {
+            watch_cameras(&inotify);
+        }
+
+        std::thread::sleep(SETTLE);
+        set(camera_users());
+    }
+}
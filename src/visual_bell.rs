@@ -0,0 +1,286 @@
+use std::{
+    fs::File,
+    io::Write,
+    os::fd::{AsFd, FromRawFd},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use wayland_client::{
+    delegate_noop,
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{
+        wl_buffer::WlBuffer,
+        wl_compositor::WlCompositor,
+        wl_output::WlOutput,
+        wl_region::WlRegion,
+        wl_registry::WlRegistry,
+        wl_shm::{self, WlShm},
+        wl_shm_pool::WlShmPool,
+        wl_surface::WlSurface,
+    },
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+};
+use wayland_protocols::wp::viewporter::client::{
+    wp_viewport::WpViewport, wp_viewporter::WpViewporter,
+};
+use wayland_protocols_wlr::layer_shell::v1::client::{
+    zwlr_layer_shell_v1::{Layer, ZwlrLayerShellV1},
+    zwlr_layer_surface_v1::{self, Anchor, ZwlrLayerSurfaceV1},
+};
+
+use crate::a11y::VisualBell;
+
+/// How long the flash stays on the screen.
+const FLASH: Duration = Duration::from_millis(150);
+
+/// White at a quarter of its opacity, premultiplied, as an ARGB8888 pixel.
+const FLASH_PIXEL: u32 = 0x4040_4040;
+
+static TX: Mutex<Option<Sender<VisualBell>>> = Mutex::new(None);
+
+/// Flash the screen or the focused output for the visual bell.
+pub fn flash(target: VisualBell) {
+    if let Ok(tx) = TX.lock() {
+        if let Some(tx) = tx.as_ref() {
+            let _res = tx.send(target);
+        }
+    }
+}
+
+/// The globals a flash needs.
+struct Globals {
+    compositor: WlCompositor,
+    shm: WlShm,
+    viewporter: WpViewporter,
+    layer_shell: ZwlrLayerShellV1,
+}
+
+/// A layer surface covering an output, which is shown once it is configured.
+struct FlashSurface {
+    surface: WlSurface,
+    layer_surface: ZwlrLayerSurfaceV1,
+    viewport: WpViewport,
+    configured: bool,
+    /// Closed by the compositor, such as when its output went away.
+    closed: bool,
+}
+
+#[derive(Default)]
+struct State {
+    surfaces: Vec<FlashSurface>,
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrLayerSurfaceV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        layer_surface: &ZwlrLayerSurfaceV1,
+        event: zwlr_layer_surface_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(flash) = state
+            .surfaces
+            .iter_mut()
+            .find(|flash| flash.layer_surface == *layer_surface)
+        else {
+            return;
+        };
+
+        match event {
+            zwlr_layer_surface_v1::Event::Configure {
+                serial,
+                width,
+                height,
+            } => {
+                layer_surface.ack_configure(serial);
+                flash
+                    .viewport
+                    .set_destination(width.max(1) as i32, height.max(1) as i32);
+                flash.configured = true;
+            }
+            zwlr_layer_surface_v1::Event::Closed => flash.closed = true,
+            _ => (),
+        }
+    }
+}
+
+delegate_noop!(State: WlCompositor);
+delegate_noop!(State: WlShm);
+delegate_noop!(State: WlShmPool);
+delegate_noop!(State: WlBuffer);
+delegate_noop!(State: WlRegion);
+delegate_noop!(State: WlSurface);
+delegate_noop!(State: ignore WlOutput);
+delegate_noop!(State: WpViewporter);
+delegate_noop!(State: WpViewport);
+delegate_noop!(State: ZwlrLayerShellV1);
+
+/// A buffer of a single pixel, which the viewport of each surface stretches over its output.
+fn flash_buffer(globals: &Globals, qh: &QueueHandle<State>) -> anyhow::Result<WlBuffer> {
+    // SAFETY: the name is a valid C string, and the descriptor is owned by the file.
+    let file = unsafe {
+        let fd = libc::memfd_create(c"visual-bell".as_ptr(), libc::MFD_CLOEXEC);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        File::from_raw_fd(fd)
+    };
+    (&file).write_all(&FLASH_PIXEL.to_ne_bytes())?;
+
+    let pool = globals.shm.create_pool(file.as_fd(), 4, qh, ());
+    let buffer = pool.create_buffer(0, 1, 1, 4, wl_shm::Format::Argb8888, qh, ());
+    pool.destroy();
+    Ok(buffer)
+}
+
+fn show(
+    globals: &Globals,
+    queue: &mut EventQueue<State>,
+    state: &mut State,
+    outputs: &[WlOutput],
+) -> anyhow::Result<()> {
+    let qh = queue.handle();
+    let buffer = flash_buffer(globals, &qh)?;
+
+    // Without an output, the compositor puts the surface on the focused one.
+    let outputs: Vec<Option<&WlOutput>> = if outputs.is_empty() {
+        vec![None]
+    } else {
+        outputs.iter().map(Some).collect()
+    };
+    for output in outputs {
+        let surface = globals.compositor.create_surface(&qh, ());
+        let layer_surface = globals.layer_shell.get_layer_surface(
+            &surface,
+            output,
+            Layer::Overlay,
+            "visual-bell".to_owned(),
+            &qh,
+            (),
+        );
+        layer_surface.set_anchor(Anchor::Top | Anchor::Bottom | Anchor::Left | Anchor::Right);
+        layer_surface.set_exclusive_zone(-1);
+
+        // The flash does not take the input of the windows under it.
+        let region = globals.compositor.create_region(&qh, ());
+        surface.set_input_region(Some(&region));
+        region.destroy();
+
+        let viewport = globals.viewporter.get_viewport(&surface, &qh, ());
+        surface.commit();
+        state.surfaces.push(FlashSurface {
+            surface,
+            layer_surface,
+            viewport,
+            configured: false,
+            closed: false,
+        });
+    }
+
+    while state
+        .surfaces
+        .iter()
+        .any(|flash| !flash.configured && !flash.closed)
+    {
+        queue.blocking_dispatch(state)?;
+    }
+
+    for flash in state.surfaces.iter().filter(|flash| !flash.closed) {
+        flash.surface.attach(Some(&buffer), 0, 0);
+        flash.surface.damage_buffer(0, 0, 1, 1);
+        flash.surface.commit();
+    }
+    queue.roundtrip(state)?;
+    std::thread::sleep(FLASH);
+
+    for flash in state.surfaces.drain(..) {
+        flash.viewport.destroy();
+        flash.layer_surface.destroy();
+        flash.surface.destroy();
+    }
+    buffer.destroy();
+    queue.roundtrip(state)?;
+    Ok(())
+}
+
+fn watch(rx: &Receiver<VisualBell>) -> anyhow::Result<()> {
+    let conn = Connection::connect_to_env()?;
+    let (globals, mut queue) = registry_queue_init::<State>(&conn)?;
+    let qh = queue.handle();
+
+    let flash_globals = Globals {
+        compositor: globals.bind(&qh, 4..=6, ())?,
+        shm: globals.bind(&qh, 1..=1, ())?,
+        viewporter: globals.bind(&qh, 1..=1, ())?,
+        layer_shell: globals.bind(&qh, 1..=4, ())?,
+    };
+
+    let mut state = State::default();
+    while let Ok(target) = rx.recv() {
+        // Outputs are bound for each flash, so that ones connected since are flashed too.
+        queue.roundtrip(&mut state)?;
+        let outputs: Vec<WlOutput> = match target {
+            VisualBell::Off => continue,
+            VisualBell::FocusedOutput => Vec::new(),
+            VisualBell::Screen => globals.contents().with_list(|list| {
+                list.iter()
+                    .filter(|global| global.interface == "wl_output")
+                    .map(|global| {
+                        globals
+                            .registry()
+                            .bind(global.name, global.version.min(3), &qh, ())
+                    })
+                    .collect()
+            }),
+        };
+
+        let res = show(&flash_globals, &mut queue, &mut state, &outputs);
+        for output in outputs {
+            if output.version() >= 3 {
+                output.release();
+            }
+        }
+        if let Err(err) = res {
+            eprintln!("Failed to flash the visual bell: {err}");
+        }
+    }
+    Ok(())
+}
+
+/// Flash the visual bell on a layer surface of the compositor, from a thread of its own.
+pub fn run() {
+    let (tx, rx) = channel();
+    let res = std::thread::Builder::new()
+        .name("visual-bell".to_owned())
+        .spawn(move || {
+            if let Err(err) = watch(&rx) {
+                eprintln!("Failed to connect to the compositor for the visual bell: {err}");
+            }
+        });
+
+    match res {
+        Ok(_) => {
+            if let Ok(mut global) = TX.lock() {
+                *global = Some(tx);
+            }
+        }
+        Err(err) => eprintln!("Failed to start the visual bell thread: {err}"),
+    }
+}
@@ -0,0 +1,64 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+static COUNTER: Mutex<Counter> = Mutex::new(Counter {
+    start: None,
+    count: 0,
+    last_minute: 0,
+    total: 0,
+});
+
+/// Wakeups of the runtime, counted per minute.
+struct Counter {
+    /// The start of the current minute.
+    start: Option<Instant>,
+    count: u32,
+    last_minute: u32,
+    /// Since the daemon started.
+    total: u64,
+}
+
+impl Counter {
+    fn roll(&mut self, now: Instant) {
+        let Some(start) = self.start else {
+            self.start = Some(now);
+            return;
+        };
+
+        let elapsed = now.duration_since(start);
+        if elapsed >= MINUTE {
+            // Nothing happened in the minute before `now` if more than one went by.
+            self.last_minute = if elapsed < 2 * MINUTE { self.count } else { 0 };
+            self.count = 0;
+            self.start = Some(now);
+        }
+    }
+}
+
+/// Count a wakeup of the runtime, when it stops waiting for events to run tasks.
+pub fn count() {
+    if let Ok(mut counter) = COUNTER.lock() {
+        counter.roll(Instant::now());
+        counter.count += 1;
+        counter.total += 1;
+    }
+}
+
+/// The wakeups of the runtime in the last full minute.
+pub fn per_minute() -> u32 {
+    let Ok(mut counter) = COUNTER.lock() else {
+        return 0;
+    };
+
+    counter.roll(Instant::now());
+    counter.last_minute
+}
+
+/// The wakeups of the runtime since the daemon started.
+pub fn total() -> u64 {
+    COUNTER.lock().map_or(0, |counter| counter.total)
+}
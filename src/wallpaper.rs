@@ -0,0 +1,341 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Days, Local, NaiveDate};
+use cosmic_config::{
+    cosmic_config_derive::CosmicConfigEntry, ConfigGet, ConfigSet, CosmicConfigEntry,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+
+use crate::{
+    demand::Feature,
+    schedule::{Location, LocationReceiver, Schedule},
+};
+
+pub const ID: &str = "com.system76.CosmicSettingsDaemon.Wallpaper";
+
+/// The config of cosmic-bg, which has the wallpaper.
+pub const BACKGROUND_ID: &str = "com.system76.CosmicBackground";
+
+/// The key of cosmic-bg with the outputs which have wallpapers of their own.
+const BACKGROUNDS: &str = "backgrounds";
+
+/// The key and output name of the wallpaper of all outputs.
+const DEFAULT_BACKGROUND: &str = "all";
+
+/// The key of cosmic-bg which shows the wallpaper of all outputs everywhere.
+const SAME_ON_ALL: &str = "same-on-all";
+
+/// A background entry of cosmic-bg, as `cosmic-bg-config` defines it, so that the settings of
+/// an entry other than its image are kept as they are.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Entry {
+    output: String,
+    source: Source,
+    #[serde(default)]
+    filter_by_theme: bool,
+    #[serde(default = "default_rotation_frequency")]
+    rotation_frequency: u64,
+    #[serde(default)]
+    filter_method: FilterMethod,
+    #[serde(default)]
+    scaling_mode: ScalingMode,
+    #[serde(default)]
+    sampling_method: SamplingMethod,
+}
+
+fn default_rotation_frequency() -> u64 {
+    300
+}
+
+impl Entry {
+    /// An entry with the defaults of cosmic-bg for everything but the image.
+    pub fn new(output: String, path: PathBuf) -> Self {
+        Self {
+            output,
+            source: Source::Path(path),
+            filter_by_theme: false,
+            rotation_frequency: default_rotation_frequency(),
+            filter_method: FilterMethod::default(),
+            scaling_mode: ScalingMode::default(),
+            sampling_method: SamplingMethod::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+enum Source {
+    /// An image, or a directory of images for a slideshow.
+    Path(PathBuf),
+    Color(Color),
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+enum Color {
+    Single([f32; 3]),
+    Gradient(Gradient),
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct Gradient {
+    colors: Vec<[f32; 3]>,
+    radius: f32,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+enum FilterMethod {
+    Nearest,
+    Linear,
+    #[default]
+    Lanczos,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+enum ScalingMode {
+    /// Fit the image, with bars of this color around it.
+    Fit([f32; 3]),
+    Stretch,
+    #[default]
+    Zoom,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+enum SamplingMethod {
+    #[default]
+    Alphanumeric,
+    Random,
+}
+
+/// A part of the day, which has its own wallpaper.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Phase {
+    /// The first quarter of the daylight, from sunrise.
+    Morning,
+    /// The middle half of the daylight.
+    Noon,
+    /// The last quarter of the daylight, until sunset.
+    Evening,
+    /// From sunset until sunrise.
+    Night,
+}
+
+/// The wallpapers of the parts of the day, like those of dynamic wallpapers.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct WallpaperSet {
+    pub morning: PathBuf,
+    pub noon: PathBuf,
+    pub evening: PathBuf,
+    pub night: PathBuf,
+}
+
+impl WallpaperSet {
+    fn get(&self, phase: Phase) -> &Path {
+        match phase {
+            Phase::Morning => &self.morning,
+            Phase::Noon => &self.noon,
+            Phase::Evening => &self.evening,
+            Phase::Night => &self.night,
+        }
+    }
+}
+
+/// Wallpapers which change across the day, at the parts of the day from the sunrise and sunset
+/// of the theme mode, or from the fixed dark hours while location services are off.
+///
+/// The image of the wallpaper of cosmic-bg is replaced at the start of every part of the day, so
+/// a wallpaper chosen by hand only lasts until then. Its scaling and filtering are kept.
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[version = 1]
+pub struct WallpaperConfig {
+    /// The wallpapers of all outputs without wallpapers of their own, or `None` to leave them to
+    /// cosmic-bg.
+    pub all: Option<WallpaperSet>,
+    /// The wallpapers of single outputs by connector name, such as `DP-1`, instead of those of
+    /// all outputs. Other outputs show the wallpaper of all outputs.
+    pub outputs: BTreeMap<String, WallpaperSet>,
+}
+
+impl crate::demand::Feature for WallpaperConfig {
+    fn enabled(&self) -> bool {
+        self.all.is_some() || !self.outputs.is_empty()
+    }
+}
+
+/// Sunrise and sunset of a day, as the fixed dark hours end and start while location services
+/// are off.
+fn sun_times(
+    date: NaiveDate,
+    location: Option<Location>,
+    fallback: Option<Schedule>,
+) -> Option<(DateTime<Local>, DateTime<Local>)> {
+    if let Some(Schedule::Fixed { start, end }) = fallback {
+        Some((end.on(date)?, start.on(date)?))
+    } else {
+        crate::schedule::sunrise_sunset(date, location?)
+    }
+}
+
+/// The starts of the parts of the days from yesterday until tomorrow, in order.
+fn phases(
+    now: DateTime<Local>,
+    location: Option<Location>,
+    fallback: Option<Schedule>,
+) -> Vec<(DateTime<Local>, Phase)> {
+    let today = now.date_naive();
+    let mut phases: Vec<_> = [
+        today.checked_sub_days(Days::new(1)),
+        Some(today),
+        today.checked_add_days(Days::new(1)),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|date| sun_times(date, location, fallback))
+    // Days without a sunrise or sunset near the poles are skipped.
+    .filter(|(sunrise, sunset)| sunrise < sunset)
+    .flat_map(|(sunrise, sunset)| {
+        let quarter = (sunset - sunrise) / 4;
+        [
+            (sunrise, Phase::Morning),
+            (sunrise + quarter, Phase::Noon),
+            (sunset - quarter, Phase::Evening),
+            (sunset, Phase::Night),
+        ]
+    })
+    .collect();
+
+    phases.sort_by_key(|(start, _)| *start);
+    phases
+}
+
+/// Show the image `path` on `output` of cosmic-bg, or on all outputs for `all`, keeping the
+/// other settings of its entry.
+fn set_entry(background: &cosmic_config::Config, output: &str, path: &Path) {
+    let key = if output == DEFAULT_BACKGROUND {
+        output.to_owned()
+    } else {
+        format!("output.{output}")
+    };
+
+    let mut entry = background
+        .get::<Entry>(&key)
+        .unwrap_or_else(|_| Entry::new(output.to_owned(), PathBuf::new()));
+    if matches!(&entry.source, Source::Path(current) if current == path) {
+        return;
+    }
+
+    entry.source = Source::Path(path.to_owned());
+    if let Err(err) = background.set(&key, entry) {
+        eprintln!("Failed to set the wallpaper of {output}: {err:?}");
+    }
+}
+
+/// Write the wallpapers of `phase` to the config of cosmic-bg.
+///
+/// The outputs with wallpapers of their own are added to the backgrounds of cosmic-bg, which
+/// otherwise shows the wallpaper of all outputs everywhere, and the other entries are left as
+/// they are.
+fn apply(config: &WallpaperConfig, phase: Phase) {
+    let background = match cosmic_config::Config::new(BACKGROUND_ID, 1) {
+        Ok(background) => background,
+        Err(err) => {
+            eprintln!("Failed to open the cosmic-bg config {err:?}");
+            return;
+        }
+    };
+
+    if let Some(set) = &config.all {
+        set_entry(&background, DEFAULT_BACKGROUND, set.get(phase));
+    }
+
+    for (output, set) in &config.outputs {
+        set_entry(&background, output, set.get(phase));
+    }
+
+    if config.outputs.is_empty() {
+        return;
+    }
+
+    let mut backgrounds = background
+        .get::<Vec<String>>(BACKGROUNDS)
+        .unwrap_or_default();
+    let missing: Vec<String> = config
+        .outputs
+        .keys()
+        .filter(|output| !backgrounds.contains(output))
+        .cloned()
+        .collect();
+    if !missing.is_empty() {
+        backgrounds.extend(missing);
+        if let Err(err) = background.set(BACKGROUNDS, backgrounds) {
+            eprintln!("Failed to set the wallpaper outputs: {err:?}");
+        }
+    }
+
+    if background.get::<bool>(SAME_ON_ALL).unwrap_or(true) {
+        if let Err(err) = background.set(SAME_ON_ALL, false) {
+            eprintln!("Failed to set wallpapers by output: {err:?}");
+        }
+    }
+}
+
+/// Switch the wallpapers at the start of every part of the day, while any are configured.
+pub async fn run(mut rx: Receiver<String>, mut location_rx: LocationReceiver) {
+    let (helper, mut config) = match crate::config::load_async::<WallpaperConfig>(ID).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load wallpaper config {err:?}");
+            return;
+        }
+    };
+
+    let mut location_state = crate::location::subscribe();
+    let mut location_open = true;
+
+    loop {
+        crate::location::want("wallpaper", config.enabled());
+        let fallback = location_state.borrow_and_update().fallback_dark();
+        let location = *location_rx.borrow_and_update();
+        let now = Local::now();
+
+        // Until the location is known, the wallpaper from before stays.
+        let phases = if config.enabled() {
+            phases(now, location, fallback)
+        } else {
+            Vec::new()
+        };
+
+        if let Some((_, phase)) = phases.iter().rev().find(|(start, _)| *start <= now) {
+            apply(&config, *phase);
+        }
+
+        let next = phases
+            .iter()
+            .map(|(start, _)| *start)
+            .find(|start| *start > now);
+
+        tokio::select! {
+            key = rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                crate::config::update(&mut config, &helper, &key);
+            }
+
+            res = location_rx.changed(), if location_open => {
+                location_open = res.is_ok();
+            }
+
+            res = location_state.changed() => {
+                if res.is_err() {
+                    break;
+                }
+            }
+
+            // The realtime clock keeps the switch on time across suspend.
+            () = crate::schedule::sleep_until(next) => (),
+        }
+    }
+}
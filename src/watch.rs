@@ -0,0 +1,145 @@
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+};
+
+use notify::{event::ModifyKind, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/// The file watcher of the daemon, shared by every module so that there is only one inotify
+/// instance, and the directories it watches.
+static WATCHER: Mutex<Option<(RecommendedWatcher, HashMap<PathBuf, RecursiveMode>)>> =
+    Mutex::new(None);
+
+static REGISTRATIONS: Mutex<Vec<Registration>> = Mutex::new(Vec::new());
+
+struct Registration {
+    dir: PathBuf,
+    pattern: String,
+    tx: UnboundedSender<Vec<PathBuf>>,
+}
+
+/// Whether a path relative to the watched directory matches a glob pattern.
+///
+/// `*` matches any characters of a file name, `?` one character, and a `**` component any
+/// number of directories.
+fn matches(pattern: &str, path: &Path) -> bool {
+    let names: Vec<&str> = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => name.to_str(),
+            _ => None,
+        })
+        .collect();
+    let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+    matches_components(&components, &names)
+}
+
+fn matches_components(pattern: &[&str], names: &[&str]) -> bool {
+    match (pattern.split_first(), names.split_first()) {
+        (None, None) => true,
+        (Some((&"**", rest)), _) => {
+            matches_components(rest, names)
+                || (!names.is_empty() && matches_components(pattern, &names[1..]))
+        }
+        (Some((component, rest)), Some((name, names))) => {
+            matches_name(component.as_bytes(), name.as_bytes()) && matches_components(rest, names)
+        }
+        _ => false,
+    }
+}
+
+fn matches_name(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, None) => true,
+        (Some((b'*', rest)), _) => {
+            matches_name(rest, name) || (!name.is_empty() && matches_name(pattern, &name[1..]))
+        }
+        (Some((b'?', rest)), Some((_, name))) => matches_name(rest, name),
+        (Some((c, rest)), Some((n, name))) => c == n && matches_name(rest, name),
+        _ => false,
+    }
+}
+
+/// Send the paths of an event to the registrations they match.
+fn dispatch(res: notify::Result<notify::Event>) {
+    let Ok(event) = res else {
+        return;
+    };
+
+    if let EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_)) = event.kind {
+        // Data not mutated
+        return;
+    }
+
+    let Ok(mut registrations) = REGISTRATIONS.lock() else {
+        return;
+    };
+
+    // Modules unregister by dropping their receiver.
+    registrations.retain(|registration| !registration.tx.is_closed());
+
+    for registration in registrations.iter() {
+        let paths: Vec<PathBuf> = event
+            .paths
+            .iter()
+            .filter(|path| {
+                path.strip_prefix(&registration.dir)
+                    .is_ok_and(|relative| matches(&registration.pattern, relative))
+            })
+            .cloned()
+            .collect();
+
+        if !paths.is_empty() {
+            let _res = registration.tx.send(paths);
+        }
+    }
+}
+
+/// Watch the files of `dir` which match the glob `pattern`, relative to `dir`.
+///
+/// Each message has the matching paths of one event. Files in subdirectories are only seen if
+/// `recursive` is set.
+pub fn register(
+    dir: &Path,
+    pattern: &str,
+    recursive: bool,
+) -> notify::Result<UnboundedReceiver<Vec<PathBuf>>> {
+    let mode = if recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+
+    let mut watcher = WATCHER
+        .lock()
+        .map_err(|_| notify::Error::generic("the file watcher is poisoned"))?;
+    let (watcher, watched) = match &mut *watcher {
+        Some(watcher) => watcher,
+        unset => unset.insert((notify::recommended_watcher(dispatch)?, HashMap::new())),
+    };
+
+    let covered = watched.iter().any(|(watched_dir, watched_mode)| {
+        if *watched_mode == RecursiveMode::Recursive {
+            dir.starts_with(watched_dir)
+        } else {
+            watched_dir == dir && mode == RecursiveMode::NonRecursive
+        }
+    });
+    if !covered {
+        watcher.watch(dir, mode)?;
+        watched.insert(dir.to_owned(), mode);
+    }
+
+    let (tx, rx) = unbounded_channel();
+    if let Ok(mut registrations) = REGISTRATIONS.lock() {
+        registrations.push(Registration {
+            dir: dir.to_owned(),
+            pattern: pattern.to_owned(),
+            tx,
+        });
+    }
+
+    Ok(rx)
+}